@@ -9,90 +9,74 @@ use iced::{
     Alignment, Element, Length, Task,
 };
 use iced_aw::TabLabel;
+use openrtx_companion::core::{
+    get_ports, Attached, BackupError, BackupJob, JobHandle, JobState, RadioHW, RadioStatus,
+    RestoreJob, SerialPort,
+};
 use rfd::AsyncFileDialog;
-use serial_enumerator::get_serial_list;
-use std::sync::mpsc::{channel, Receiver};
-
-// Wrapper type for SerialItem to enable trait definition
-#[derive(Clone)]
-pub struct SerialPort {
-    name: String,
-    vendor: String,
-    product: String,
-}
-
-// Display trait for SeriatPortInfo
-impl std::fmt::Display for SerialPort {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.name)
-    }
-}
-
-// Debug trait for SerialPortInfo
-impl std::fmt::Debug for SerialPort {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("SerialPort")
-            .field("name", &self.name)
-            .field("product", &self.product)
-            .field("vendor", &self.vendor)
-            .finish()
-    }
-}
-
-// Unwrap result from serialport library
-fn get_ports() -> Vec<SerialPort> {
-    let ports = get_serial_list();
-    ports
-        .iter()
-        .map(|p| SerialPort {
-            name: p.name.clone(),
-            vendor: p.vendor.clone().unwrap_or(String::from("")),
-            product: p.product.clone().unwrap_or(String::from("")),
-        })
-        .collect()
-}
+use std::path::PathBuf;
 
 #[derive(Clone, Debug)]
 pub enum BackupMessage {
     BackupPressed,
     RestorePressed,
+    CancelPressed,
     OpenRestoreFilePressed,
     RestoreFileSelected(Option<String>),
     StartBackup(Option<String>),
     PortSelected(SerialPort),
     FilePath(Option<String>),
+    PortsChanged(Attached),
+    RefreshInfoPressed,
+    InfoLoaded(Result<RadioStatus, String>),
     Tick,
 }
 
 pub struct BackupTab {
     backup_in_progress: bool,
-    backup_progress: Option<Receiver<(usize, usize)>>,
+    backup_job: Option<JobHandle<Vec<PathBuf>, BackupError>>,
+    restore_in_progress: bool,
+    restore_job: Option<JobHandle<RadioHW, BackupError>>,
     serial_ports: Vec<SerialPort>,
     serial_port: Option<SerialPort>,
+    // The selected port has been unplugged
+    port_gone: bool,
     ports_combo_state: combo_box::State<SerialPort>,
+    // Information read from the radio on the selected port
+    radio_status: Option<Result<RadioStatus, String>>,
+    status_loading: bool,
     progress: f32,
     restore_file: Option<String>,
     status_text: String,
 }
 
+pub fn port_options(mut ports: Vec<SerialPort>) -> Vec<SerialPort> {
+    // Workaround: Iced crashes when rendering empty combo box
+    if ports.is_empty() {
+        ports.push(SerialPort {
+            name: String::from("No serial port found!"),
+            vendor: String::from(""),
+            product: String::from(""),
+        });
+    }
+    ports
+}
+
 impl Default for BackupTab {
     fn default() -> Self {
-        let mut ports = get_ports();
-        // Workaround: Iced crashes when rendering empty combo box
-        if ports.len() == 0 {
-            ports.push(SerialPort {
-                name: String::from("No serial port found!"),
-                vendor: String::from(""),
-                product: String::from(""),
-            });
-        }
+        let ports = port_options(get_ports());
         Self {
             progress: 0.0,
             backup_in_progress: false,
-            backup_progress: None,
+            backup_job: None,
+            restore_in_progress: false,
+            restore_job: None,
             serial_ports: ports.clone(),
             serial_port: None,
+            port_gone: false,
             ports_combo_state: combo_box::State::new(ports),
+            radio_status: None,
+            status_loading: false,
             restore_file: None,
             status_text: String::from("Select an action"),
         }
@@ -120,7 +104,36 @@ impl BackupTab {
                     move |f| Message::StartBackup(f),
                 )
             }
-            BackupMessage::RestorePressed => Task::none(),
+            BackupMessage::RestorePressed => {
+                let port = match &self.serial_port {
+                    Some(p) => p.name.clone(),
+                    None => {
+                        self.status_text = String::from("No serial port selected!");
+                        return Task::none();
+                    }
+                };
+                let file_uri = match &self.restore_file {
+                    Some(f) => f.clone(),
+                    None => {
+                        self.status_text = String::from("No backup image selected!");
+                        return Task::none();
+                    }
+                };
+                self.progress = 0.0;
+                self.restore_in_progress = true;
+                self.status_text = String::from("Checking backup image...");
+                let bare_path = file_uri.strip_prefix("file:///").unwrap_or(&file_uri);
+                self.restore_job = Some(RestoreJob::new(port, bare_path).start());
+                Task::none()
+            }
+            BackupMessage::CancelPressed => {
+                let backup_cancelled = self.backup_job.as_ref().is_some_and(|j| j.cancel());
+                let restore_cancelled = self.restore_job.as_ref().is_some_and(|j| j.cancel());
+                if backup_cancelled || restore_cancelled {
+                    self.status_text = String::from("Cancelling...");
+                }
+                Task::none()
+            }
             BackupMessage::OpenRestoreFilePressed => Task::perform(
                 async {
                     let file = AsyncFileDialog::new().pick_file().await;
@@ -136,50 +149,165 @@ impl BackupTab {
                 move |f| Message::FilePath(f),
             ),
             BackupMessage::RestoreFileSelected(restore_file) => {
+                match &restore_file {
+                    Some(f) => self.status_text = format!("Loaded backup image: {f}"),
+                    None => self.status_text = String::from("Error in reading backup image!"),
+                };
                 self.restore_file = restore_file;
                 Task::none()
             }
+            BackupMessage::FilePath(path) => self.update(BackupMessage::RestoreFileSelected(path)),
             BackupMessage::StartBackup(path) => {
                 // Open link with configured serial port
                 let port = match &self.serial_port {
                     Some(p) => p.name.clone(),
-                    None => panic!("No serial port selected!"),
+                    None => {
+                        self.backup_in_progress = false;
+                        self.status_text = String::from("No serial port selected!");
+                        return Task::none();
+                    }
                 };
-                let (progress_tx, progress_rx) = channel();
-                self.backup_progress = Some(progress_rx);
-                std::thread::spawn(move || {
-                    rtxlink::link::Link::new(&port);
-                    rtxlink::flow::backup(path, Some(&progress_tx));
-                });
+                let dest = match path {
+                    Some(p) => p,
+                    None => {
+                        self.backup_in_progress = false;
+                        self.status_text = String::from("No backup folder selected!");
+                        return Task::none();
+                    }
+                };
+                let bare_path = dest.strip_prefix("file:///").unwrap_or(&dest);
+                self.backup_job = Some(BackupJob::new(port, bare_path).start());
                 Task::none()
             }
             BackupMessage::PortSelected(port) => {
                 self.serial_port = Some(port);
+                self.port_gone = false;
+                self.radio_status = None;
+                self.update(BackupMessage::RefreshInfoPressed)
+            }
+            BackupMessage::RefreshInfoPressed => {
+                let Some(port) = self.serial_port.clone() else {
+                    return Task::none();
+                };
+                // The link is already used by the running job
+                if self.backup_in_progress || self.restore_in_progress || self.status_loading {
+                    return Task::none();
+                }
+                self.status_loading = true;
+                Task::perform(
+                    crate::blocking(move || {
+                        RadioStatus::read(&port.name).map_err(|e| e.to_string())
+                    }),
+                    |res| Message::Backup(BackupMessage::InfoLoaded(res)),
+                )
+            }
+            BackupMessage::InfoLoaded(res) => {
+                self.status_loading = false;
+                self.radio_status = Some(res);
+                Task::none()
+            }
+            BackupMessage::PortsChanged(attached) => {
+                let ports = port_options(attached.ports.clone());
+                if ports != self.serial_ports {
+                    self.serial_ports = ports.clone();
+                    self.ports_combo_state = combo_box::State::new(ports);
+                }
+                if let Some(port) = &self.serial_port {
+                    self.port_gone = !attached.has_port(port);
+                }
                 Task::none()
             }
             BackupMessage::Tick => {
-                if self.backup_in_progress {
-                    if self.backup_progress.is_some() {
-                        let (transferred_bytes, total_bytes) =
-                            match self.backup_progress.as_ref().unwrap().try_iter().last() {
-                                Some(x) => x,
-                                None => {
-                                    self.status_text = String::from("Backup complete!");
-                                    (100, 100)
-                                }
-                            };
-                        self.progress = transferred_bytes as f32 / total_bytes as f32 * 100.0;
-                        if transferred_bytes < total_bytes {
+                if let Some(backup_job) = &mut self.backup_job {
+                    match backup_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text = format!("{}/{}", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(files) => {
                             self.status_text =
-                                String::from(format!("{transferred_bytes}/{total_bytes}"));
+                                format!("Backup complete! Saved {} files", files.len());
+                            self.progress = 100.0;
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Backup failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Cancelled");
+                            self.progress = 0.0;
                         }
                     }
-                };
+                    if !backup_job.is_running() {
+                        self.backup_in_progress = false;
+                        self.backup_job = None;
+                    }
+                }
+                if let Some(restore_job) = &mut self.restore_job {
+                    match restore_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text =
+                                    format!("Restored {}/{}", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(radio) => {
+                            self.status_text = format!("Restore of {radio} complete!");
+                            self.progress = 100.0;
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Restore failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Cancelled");
+                            self.progress = 0.0;
+                        }
+                    }
+                    if !restore_job.is_running() {
+                        self.restore_in_progress = false;
+                        self.restore_job = None;
+                    }
+                }
                 Task::none()
             }
-            _ => Task::none(),
         }
     }
+
+    // A serial port is selected and still attached
+    fn port_ready(&self) -> bool {
+        self.serial_port.is_some() && !self.port_gone
+    }
+
+    // A backup or a restore is using the radio
+    fn job_in_progress(&self) -> bool {
+        self.backup_in_progress || self.restore_in_progress
+    }
+
+    // Information about the radio, two fields per row
+    fn radio_info(&self) -> Element<'_, BackupMessage> {
+        let status = match &self.radio_status {
+            Some(Ok(status)) => status,
+            Some(Err(e)) => {
+                return text(format!("Cannot read radio information: {e}"))
+                    .style(text::danger)
+                    .size(15)
+                    .into()
+            }
+            None => return Column::new().into(),
+        };
+        status
+            .fields()
+            .chunks(2)
+            .fold(Column::new().spacing(5).padding([0, 20]), |column, pair| {
+                column.push(pair.iter().fold(Row::new(), |row, (name, value)| {
+                    row.push(Column::new().width(110).push(text(*name).size(15)))
+                        .push(Column::new().width(180).push(text(value.clone()).size(15)))
+                }))
+            })
+            .into()
+    }
 }
 
 impl Tab for BackupTab {
@@ -213,8 +341,20 @@ impl Tab for BackupTab {
                         Column::new().width(120).push(text("Serial port:").size(15)),
                         port_combo_box,
                     ]
+                    .push_maybe(self.port_gone.then(|| text("Unplugged").size(15)))
+                    .push(
+                        Button::new(Text::new("Refresh info").size(15)).on_press_maybe(
+                            (self.port_ready()
+                                && !self.status_loading
+                                && !self.backup_in_progress
+                                && !self.restore_in_progress)
+                                .then_some(BackupMessage::RefreshInfoPressed),
+                        ),
+                    )
+                    .spacing(10)
                     .padding(20),
                 )
+                .push(self.radio_info())
                 .push(row![Column::new()
                     .width(600)
                     .align_x(Alignment::Center)
@@ -226,12 +366,37 @@ impl Tab for BackupTab {
                         .push(
                             Button::new(Text::new("Backup").align_x(Horizontal::Center))
                                 .width(Length::Fill)
-                                .on_press(BackupMessage::BackupPressed),
+                                .on_press_maybe(
+                                    (self.port_ready() && !self.job_in_progress())
+                                        .then_some(BackupMessage::BackupPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Select Backup").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press(BackupMessage::OpenRestoreFilePressed),
                         )
                         .push(
                             Button::new(Text::new("Restore").align_x(Horizontal::Center))
                                 .width(Length::Fill)
-                                .on_press(BackupMessage::RestorePressed),
+                                .on_press_maybe(
+                                    (self.port_ready()
+                                        && self.restore_file.is_some()
+                                        && !self.job_in_progress())
+                                    .then_some(BackupMessage::RestorePressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Cancel").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (self.backup_job.as_ref().is_some_and(|j| j.can_cancel())
+                                        || self
+                                            .restore_job
+                                            .as_ref()
+                                            .is_some_and(|j| j.can_cancel()))
+                                    .then_some(BackupMessage::CancelPressed),
+                                ),
                         ),
                 ),
         )
@@ -0,0 +1,355 @@
+// show logs when debugging
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use crate::backup::port_options;
+use crate::Message;
+use crate::Tab;
+use iced::{
+    alignment::{Horizontal, Vertical},
+    widget::{
+        checkbox, combo_box, progress_bar, row, scrollable, text, Button, Column, Container, Row,
+        Text,
+    },
+    Alignment, Element, Font, Length, Task,
+};
+use iced_aw::TabLabel;
+use openrtx_companion::core::{
+    calibration::CalibrationIssue, get_ports, Attached, Calibration, CalibrationError,
+    CalibrationReadJob, CalibrationWriteJob, JobHandle, JobState, RadioHW, SerialPort,
+};
+use rfd::AsyncFileDialog;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug)]
+pub enum CalibrationMessage {
+    PortSelected(SerialPort),
+    PortsChanged(Attached),
+    ReadPressed,
+    OpenPressed,
+    Opened(Option<Result<Calibration, String>>),
+    SavePressed,
+    Saved(Option<Result<PathBuf, String>>),
+    WritePressed,
+    OtherUnitToggled(bool),
+    CancelPressed,
+    Tick,
+}
+
+pub struct CalibrationTab {
+    serial_ports: Vec<SerialPort>,
+    serial_port: Option<SerialPort>,
+    // The selected port has been unplugged
+    port_gone: bool,
+    ports_combo_state: combo_box::State<SerialPort>,
+    calibration: Option<Calibration>,
+    // Values of the calibration outside of their sane range
+    issues: Vec<CalibrationIssue>,
+    // Write the calibration on another radio than the one it was read from
+    other_unit: bool,
+    read_job: Option<JobHandle<Calibration, CalibrationError>>,
+    write_job: Option<JobHandle<RadioHW, CalibrationError>>,
+    progress: f32,
+    status_text: String,
+}
+
+impl Default for CalibrationTab {
+    fn default() -> Self {
+        let ports = port_options(get_ports());
+        Self {
+            serial_ports: ports.clone(),
+            serial_port: None,
+            port_gone: false,
+            ports_combo_state: combo_box::State::new(ports),
+            calibration: None,
+            issues: vec![],
+            other_unit: false,
+            read_job: None,
+            write_job: None,
+            progress: 0.0,
+            status_text: String::from("Read the calibration of a radio or open a file"),
+        }
+    }
+}
+
+impl CalibrationTab {
+    pub fn update(&mut self, message: CalibrationMessage) -> Task<Message> {
+        match message {
+            CalibrationMessage::PortSelected(port) => {
+                self.serial_port = Some(port);
+                self.port_gone = false;
+                Task::none()
+            }
+            CalibrationMessage::PortsChanged(attached) => {
+                let ports = port_options(attached.ports.clone());
+                if ports != self.serial_ports {
+                    self.serial_ports = ports.clone();
+                    self.ports_combo_state = combo_box::State::new(ports);
+                }
+                if let Some(port) = &self.serial_port {
+                    self.port_gone = !attached.has_port(port);
+                }
+                Task::none()
+            }
+            CalibrationMessage::ReadPressed => {
+                if let Some(port) = &self.serial_port {
+                    self.progress = 0.0;
+                    self.read_job = Some(CalibrationReadJob::new(port.name.clone()).start());
+                }
+                Task::none()
+            }
+            CalibrationMessage::OpenPressed => Task::perform(
+                async {
+                    let file = AsyncFileDialog::new()
+                        .add_filter("Calibration", &["toml"])
+                        .pick_file()
+                        .await?;
+                    Some(Calibration::read(file.path()).map_err(|e| e.to_string()))
+                },
+                |res| Message::Calibration(CalibrationMessage::Opened(res)),
+            ),
+            CalibrationMessage::Opened(res) => {
+                match res {
+                    Some(Ok(calibration)) => {
+                        self.status_text = format!("Loaded {} calibration", calibration.radio);
+                        self.set_calibration(calibration);
+                    }
+                    Some(Err(e)) => self.status_text = format!("Error in reading calibration: {e}"),
+                    None => (),
+                }
+                Task::none()
+            }
+            CalibrationMessage::SavePressed => {
+                let Some(calibration) = self.calibration.clone() else {
+                    return Task::none();
+                };
+                Task::perform(
+                    async move {
+                        let file = AsyncFileDialog::new()
+                            .add_filter("Calibration", &["toml"])
+                            .set_file_name("calibration.toml")
+                            .save_file()
+                            .await?;
+                        let path = file.path().to_path_buf();
+                        Some(
+                            calibration
+                                .write(&path)
+                                .map(|_| path)
+                                .map_err(|e| e.to_string()),
+                        )
+                    },
+                    |res| Message::Calibration(CalibrationMessage::Saved(res)),
+                )
+            }
+            CalibrationMessage::Saved(res) => {
+                match res {
+                    Some(Ok(path)) => {
+                        self.status_text = format!("Saved calibration to {}", path.display())
+                    }
+                    Some(Err(e)) => self.status_text = format!("Error in saving calibration: {e}"),
+                    None => (),
+                }
+                Task::none()
+            }
+            CalibrationMessage::WritePressed => {
+                if let (Some(port), Some(calibration)) = (&self.serial_port, &self.calibration) {
+                    self.progress = 0.0;
+                    self.write_job = Some(
+                        CalibrationWriteJob::new(port.name.clone(), calibration.clone())
+                            .other_unit(self.other_unit)
+                            .start(),
+                    );
+                }
+                Task::none()
+            }
+            CalibrationMessage::OtherUnitToggled(other_unit) => {
+                self.other_unit = other_unit;
+                Task::none()
+            }
+            CalibrationMessage::CancelPressed => {
+                let read_cancelled = self.read_job.as_ref().is_some_and(|j| j.cancel());
+                let write_cancelled = self.write_job.as_ref().is_some_and(|j| j.cancel());
+                if read_cancelled || write_cancelled {
+                    self.status_text = String::from("Cancelling...");
+                }
+                Task::none()
+            }
+            CalibrationMessage::Tick => {
+                if let Some(read_job) = &mut self.read_job {
+                    let mut calibration = None;
+                    match read_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text = format!("Read {}/{}", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(read) => {
+                            self.status_text = format!("Read {} calibration", read.radio);
+                            self.progress = 100.0;
+                            calibration = Some(read.clone());
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Reading failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Cancelled");
+                            self.progress = 0.0;
+                        }
+                    }
+                    if !read_job.is_running() {
+                        self.read_job = None;
+                    }
+                    if let Some(calibration) = calibration {
+                        self.set_calibration(calibration);
+                    }
+                }
+                if let Some(write_job) = &mut self.write_job {
+                    match write_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text = format!("Wrote {}/{}", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(radio) => {
+                            self.status_text = format!("Calibration of {radio} restored!");
+                            self.progress = 100.0;
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Writing failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Cancelled");
+                            self.progress = 0.0;
+                        }
+                    }
+                    if !write_job.is_running() {
+                        self.write_job = None;
+                    }
+                }
+                Task::none()
+            }
+        }
+    }
+
+    fn set_calibration(&mut self, calibration: Calibration) {
+        self.issues = calibration.check();
+        self.calibration = Some(calibration);
+    }
+
+    fn is_busy(&self) -> bool {
+        self.read_job.is_some() || self.write_job.is_some()
+    }
+
+    // A serial port is selected and still attached
+    fn port_ready(&self) -> bool {
+        self.serial_port.is_some() && !self.port_gone && !self.is_busy()
+    }
+}
+
+impl Tab for CalibrationTab {
+    type Message = Message;
+
+    fn title(&self) -> String {
+        String::from("Calibration")
+    }
+
+    fn tab_label(&self) -> TabLabel {
+        TabLabel::Text(self.title())
+    }
+
+    fn content(&self) -> Element<'_, Self::Message> {
+        let port_combo_box = combo_box(
+            &self.ports_combo_state,
+            "Select a serial port",
+            self.serial_port.as_ref(),
+            CalibrationMessage::PortSelected,
+        )
+        .width(250);
+
+        let content: Element<'_, CalibrationMessage> = Container::new(
+            Column::new()
+                .max_width(800)
+                .push(
+                    row![
+                        Column::new().width(120).push(text("Serial port:").size(15)),
+                        port_combo_box,
+                    ]
+                    .push_maybe(self.port_gone.then(|| text("Unplugged").size(15)))
+                    .spacing(10)
+                    .padding(20),
+                )
+                .push(row![Column::new()
+                    .width(800)
+                    .align_x(Alignment::Center)
+                    .push(text(&self.status_text).size(20)),])
+                .push_maybe(self.calibration.as_ref().map(|calibration| {
+                    scrollable(text(calibration.to_string()).font(Font::MONOSPACE).size(13))
+                        .height(200)
+                }))
+                .push(self.issues.iter().fold(Column::new(), |column, issue| {
+                    column.push(text(issue.to_string()).style(text::danger).size(15))
+                }))
+                .push(row![progress_bar(0.0..=100.0, self.progress),].padding(20))
+                .push(
+                    row![checkbox("Restore on another radio", self.other_unit)
+                        .on_toggle_maybe(
+                            (!self.is_busy()).then_some(CalibrationMessage::OtherUnitToggled)
+                        )
+                        .size(15),]
+                    .padding([0, 20]),
+                )
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .push(
+                            Button::new(Text::new("Read").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    self.port_ready().then_some(CalibrationMessage::ReadPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Open").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (!self.is_busy()).then_some(CalibrationMessage::OpenPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Save").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    self.calibration
+                                        .is_some()
+                                        .then_some(CalibrationMessage::SavePressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Write").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (self.port_ready()
+                                        && self.calibration.is_some()
+                                        && self.issues.is_empty())
+                                    .then_some(CalibrationMessage::WritePressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Cancel").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (self.read_job.as_ref().is_some_and(|j| j.can_cancel())
+                                        || self.write_job.as_ref().is_some_and(|j| j.can_cancel()))
+                                    .then_some(CalibrationMessage::CancelPressed),
+                                ),
+                        ),
+                ),
+        )
+        .align_x(Horizontal::Center)
+        .align_y(Vertical::Center)
+        .into();
+
+        content.map(Message::Calibration)
+    }
+}
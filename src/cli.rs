@@ -0,0 +1,710 @@
+use std::io::{IsTerminal, Write};
+
+use openrtx_companion::core::{
+    catalog::cache_dir,
+    chirp, contacts,
+    files::{self, Entry},
+    flash::{detect_radio, find_target, radio_target, target_radio},
+    get_ports, rdt,
+    stock::{archive_root, needs_archive, NOT_ARCHIVED},
+    telemetry, BackupJob, Calibration, CalibrationReadJob, CalibrationWriteJob, Catalog, Codeplug,
+    FirmwareInfo, FlashJob, FlashOutcome, JobError, JobEvent, JobHandle, JobState, Keyring,
+    RadioHW, RadioStatus, RestoreJob, RigProtocol, RigServer, StockArchive, StockArchiveJob,
+    StockRestoreJob, TelemetryRecorder, Transfer, TransferJob,
+};
+use rtxflash::target;
+
+const USAGE: &str = "\
+Usage: openrtx-companion [COMMAND]
+
+Without a command the graphical interface is started.
+
+Commands:
+  flash --port <port> [--target <target>] [--verify] [--strict] <firmware.bin>
+                       Flash a firmware image on a device in bootloader mode,
+                       the target is detected from the device when omitted.
+                       With --verify, check that the rebooted radio reports
+                       the firmware version of the image over rtxlink. With
+                       --strict, refuse images not signed by a trusted key
+  backup --port <port> <directory>
+                       Save a backup of the radio into a directory
+  restore --port <port> <image.bin>
+                       Write a backup image back to the radio
+  calibration-backup --port <port> <calibration.toml>
+                       Save the RF calibration of an MD-3x0 or MD-UV3x0
+  calibration-show <calibration.toml>
+                       Print the tables of a saved calibration
+  calibration-restore --port <port> [--other-unit] <calibration.toml>
+                       Write a saved calibration back to the radio, after
+                       checking its values. With --other-unit, allow a
+                       calibration read from another radio
+  catalog --source <url or folder> [--target <target>] [--download <version>]
+                       List the releases of a firmware catalog, or download
+                       and check one of them into the firmware cache
+  stock-archive --target <target>
+                       Archive the codeplug of a TYT radio turned on with
+                       its vendor firmware, over USB DFU, before flashing
+                       OpenRTX. The vendor firmware and the calibration
+                       cannot be read out of the radio
+  stock-restore --target <target>
+                       Write the archived codeplug back to a TYT radio
+                       turned on with its vendor firmware, once its vendor
+                       update image is flashed back
+  chirp-import --codeplug <codeplug.rtxc> <channels.csv>
+                       Add the channels of a CHIRP CSV file to a codeplug
+                       file, which is created if missing
+  chirp-export --codeplug <codeplug.rtxc> <channels.csv>
+                       Save the FM channels of a codeplug file as CHIRP CSV
+  rdt-convert [--target <target>] --codeplug <codeplug.rtxc> <stock.rdt>
+                       Convert a stock TYT codeplug, saved by the vendor
+                       CPS or in a backup image, to an OpenRTX codeplug
+  contacts-import --codeplug <codeplug.rtxc> <contacts.csv or .vcf>
+                       Add the M17 contacts of a CSV or vCard file to a
+                       codeplug file, which is created if missing
+  contacts-export --codeplug <codeplug.rtxc> <contacts.csv or .vcf>
+                       Save the M17 contacts of a codeplug file as CSV or
+                       vCard
+  files --port <port> [<directory>]
+                       List a directory of the radio storage, or its memory
+                       areas when it has no file system
+  file-get --port <port> --to <local file> <radio path>
+                       Download a file from the radio
+  file-put --port <port> --to <radio directory> <local file>
+                       Upload a file into a directory of the radio
+  info --port <port>   Print the model, firmware and state of the radio
+  rigctld --port <port> [--listen <tcp port>]
+                       Serve the Hamlib rigctld protocol on a local TCP
+                       port, 4532 by default, until interrupted
+  flrig --port <port> [--listen <tcp port>]
+                       Serve the flrig XML-RPC API on a local TCP port,
+                       12345 by default, until interrupted
+  telemetry --port <port> [--interval <seconds>] <csv file>
+                       Record the frequency, signal strength and battery
+                       voltage of the radio, every second by default, until
+                       interrupted
+  ports                List the available serial ports
+  devices              List the devices available for flashing
+  targets              List the supported flashing targets
+  help                 Print this message";
+
+// Options which do not take a value
+const FLAGS: &[&str] = &["verify", "strict", "other-unit"];
+
+/// Options and positional arguments of a command
+struct Args {
+    options: Vec<(String, String)>,
+    flags: Vec<String>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    /// Parse the arguments of a command, which takes the `accepted` options
+    fn parse(args: &[String], accepted: &[&str]) -> Result<Args, String> {
+        let mut options = vec![];
+        let mut flags = vec![];
+        let mut positional = vec![];
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                if !accepted.contains(&name) {
+                    return Err(format!("Unknown option --{name}"));
+                }
+                if FLAGS.contains(&name) {
+                    flags.push(String::from(name));
+                    continue;
+                }
+                match iter.next() {
+                    Some(value) => options.push((String::from(name), value.clone())),
+                    None => return Err(format!("Missing value for option --{name}")),
+                }
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        Ok(Args {
+            options,
+            flags,
+            positional,
+        })
+    }
+
+    fn option(&self, name: &str) -> Result<&str, String> {
+        self.options
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+            .ok_or(format!("Missing option --{name}"))
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|f| f == name)
+    }
+
+    fn path(&self) -> Result<&str, String> {
+        match self.positional.as_slice() {
+            [path] => Ok(path),
+            [] => Err(String::from("Missing path argument")),
+            _ => Err(String::from("Too many arguments")),
+        }
+    }
+}
+
+type Command = fn(&Args) -> Result<(), String>;
+
+// Headless commands, by name, with the options they take
+const COMMANDS: &[(&str, &[&str], Command)] = &[
+    ("flash", &["port", "target", "verify", "strict"], flash),
+    ("backup", &["port"], backup),
+    ("restore", &["port"], restore),
+    ("calibration-backup", &["port"], calibration_backup),
+    ("calibration-show", &[], calibration_show),
+    (
+        "calibration-restore",
+        &["port", "other-unit"],
+        calibration_restore,
+    ),
+    ("catalog", &["source", "target", "download"], catalog),
+    ("stock-archive", &["target"], stock_archive),
+    ("stock-restore", &["target"], stock_restore),
+    ("chirp-import", &["codeplug"], chirp_import),
+    ("chirp-export", &["codeplug"], chirp_export),
+    ("rdt-convert", &["target", "codeplug"], rdt_convert),
+    ("contacts-import", &["codeplug"], contacts_import),
+    ("contacts-export", &["codeplug"], contacts_export),
+    ("files", &["port"], list_files),
+    ("file-get", &["port", "to"], file_get),
+    ("file-put", &["port", "to"], file_put),
+    ("info", &["port"], info),
+    ("rigctld", &["port", "listen"], |args| {
+        rig_server(args, RigProtocol::Rigctld)
+    }),
+    ("flrig", &["port", "listen"], |args| {
+        rig_server(args, RigProtocol::Flrig)
+    }),
+    ("telemetry", &["port", "interval"], record_telemetry),
+    ("ports", &[], ports),
+    ("devices", &[], devices),
+    ("targets", &[], targets),
+];
+
+const HELP: [&str; 3] = ["help", "--help", "-h"];
+
+/// Check whether the command line asks for a headless command
+///
+/// Only the names of the commands are recognized, other arguments are left
+/// to the GUI.
+pub fn is_command(args: &[String]) -> bool {
+    args.first().is_some_and(|arg| {
+        HELP.contains(&arg.as_str()) || COMMANDS.iter().any(|(name, _, _)| name == arg)
+    })
+}
+
+/// Run a headless command, returning the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => {
+            println!("{USAGE}");
+            return 2;
+        }
+    };
+    if HELP.contains(&command) {
+        println!("{USAGE}");
+        return 0;
+    }
+    let Some((_, accepted, command)) = COMMANDS.iter().find(|(name, _, _)| *name == command) else {
+        eprintln!("Unknown command: {command}\n\n{USAGE}");
+        return 2;
+    };
+    let args = match Args::parse(rest, accepted) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return 2;
+        }
+    };
+    match command(&args) {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            1
+        }
+    }
+}
+
+fn file_get(args: &Args) -> Result<(), String> {
+    let to = args.option("to")?;
+    let transfer = Transfer::Download {
+        remote: String::from(args.path()?),
+        local: to.into(),
+    };
+    copy_file(args, transfer)
+}
+
+fn file_put(args: &Args) -> Result<(), String> {
+    let to = args.option("to")?;
+    let transfer = Transfer::upload(args.path()?.as_ref(), to);
+    copy_file(args, transfer)
+}
+
+fn info(args: &Args) -> Result<(), String> {
+    let status = RadioStatus::read(args.option("port")?).map_err(|e| e.to_string())?;
+    print!("{status}");
+    Ok(())
+}
+
+fn ports(_args: &Args) -> Result<(), String> {
+    for p in get_ports() {
+        println!("{p:?}");
+    }
+    Ok(())
+}
+
+fn devices(_args: &Args) -> Result<(), String> {
+    for d in target::get_devices() {
+        println!("{}\t{d}", d.port);
+    }
+    Ok(())
+}
+
+fn targets(_args: &Args) -> Result<(), String> {
+    for t in target::get_targets() {
+        println!("{t}");
+    }
+    Ok(())
+}
+
+// Print the events of a job on the terminal until it ends
+fn wait_job<T, E>(label: &str, mut job: JobHandle<T, E>) -> Result<T, String>
+where
+    T: Clone + Send + 'static,
+    E: JobError,
+{
+    let mut progress_shown = false;
+    while let Some(event) = job.recv() {
+        match event {
+            JobEvent::Progress(p) => {
+                eprint!(
+                    "\r{label} {}/{} ({:.0}%)",
+                    p.transferred,
+                    p.total,
+                    p.percent()
+                );
+                _ = std::io::stderr().flush();
+                progress_shown = true;
+            }
+            JobEvent::Phase(phase) => {
+                if progress_shown {
+                    eprintln!();
+                    progress_shown = false;
+                }
+                eprintln!("{phase}");
+            }
+            _ => (),
+        }
+    }
+    if progress_shown {
+        eprintln!();
+    }
+    match job.state() {
+        JobState::Completed(v) => Ok(v.clone()),
+        JobState::Failed(e) => Err(e.clone()),
+        JobState::Cancelled => Err(String::from("Cancelled")),
+        JobState::Running { .. } => unreachable!(),
+    }
+}
+
+// Ask a yes or no question on the terminal, no being the default
+fn confirm(question: &str) -> bool {
+    eprint!("{question} [y/N] ");
+    _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    _ = std::io::stdin().read_line(&mut answer);
+    answer.trim().eq_ignore_ascii_case("y")
+}
+
+fn flash(args: &Args) -> Result<(), String> {
+    let port = String::from(args.option("port")?);
+    let firmware = args.path()?;
+    let radio = target::get_devices()
+        .iter()
+        .find(|d| d.port == port)
+        .and_then(detect_radio);
+    let target = match (args.option("target"), radio) {
+        (Ok(name), _) => find_target(name).ok_or(format!("Unknown target: {name}"))?,
+        (Err(_), Some(radio)) => {
+            radio_target(radio).ok_or(format!("No flashing target for detected {radio}"))?
+        }
+        (Err(_), None) => return Err(String::from("Cannot detect the radio, use --target")),
+    };
+    if let Some(radio) = radio.filter(|r| target_radio(&target) != Some(*r)) {
+        eprintln!("WARNING: target {target} is not meant for the detected {radio}!");
+    }
+    if let Some(radio) = target_radio(&target).filter(|r| needs_archive(&archive_root(), *r)) {
+        eprintln!("The {radio} may still run its vendor firmware. {NOT_ARCHIVED}");
+        eprintln!(
+            "Its codeplug can be archived with stock-archive while it runs the vendor firmware"
+        );
+        if std::io::stdin().is_terminal() && !confirm("Flash anyway?") {
+            return Err(String::from("Flashing cancelled"));
+        }
+    }
+    let info = FirmwareInfo::read(firmware).map_err(|e| format!("{firmware}: {e}"))?;
+    eprintln!("{info}");
+    // Strict mode is enforced by the flash job itself
+    match Keyring::load_default().and_then(|k| k.verify(firmware.as_ref())) {
+        Ok(signer) => eprintln!("Signed by {signer}"),
+        Err(e) => eprintln!("{e}"),
+    }
+    // Blocking issues are reported by the flash job itself
+    if let Some(radio) = target_radio(&target) {
+        for issue in info.check(radio).iter().filter(|i| !i.is_blocking()) {
+            eprintln!("Warning: {issue}");
+        }
+    }
+
+    let job = FlashJob::new(target, port, firmware)
+        .verify(args.flag("verify"))
+        .strict(args.flag("strict"))
+        .start();
+    match wait_job("Flashing", job)? {
+        FlashOutcome::Flashed => println!("Flashing complete!"),
+        FlashOutcome::NotVerified(reason) => {
+            eprintln!("Warning: the firmware was written but could not be verified: {reason}")
+        }
+        FlashOutcome::Verified { info, version } => {
+            println!("Flashing complete! {info} runs firmware {version}")
+        }
+    }
+    Ok(())
+}
+
+fn backup(args: &Args) -> Result<(), String> {
+    let port = String::from(args.option("port")?);
+    let dest = args.path()?;
+
+    wait_job("Backup", BackupJob::new(port, dest).start())?;
+    println!("Backup complete!");
+    Ok(())
+}
+
+fn restore(args: &Args) -> Result<(), String> {
+    let port = String::from(args.option("port")?);
+    let image = args.path()?;
+
+    let radio = wait_job("Restore", RestoreJob::new(port, image).start())?;
+    println!("Restore of {radio} complete!");
+    Ok(())
+}
+
+fn calibration_backup(args: &Args) -> Result<(), String> {
+    let port = String::from(args.option("port")?);
+    let dest = args.path()?;
+
+    let calibration = wait_job("Reading", CalibrationReadJob::new(port).start())?;
+    for issue in calibration.check() {
+        eprintln!("Warning: {issue}");
+    }
+    calibration
+        .write(dest.as_ref())
+        .map_err(|e| format!("{dest}: {e}"))?;
+    println!("Saved {} calibration", calibration.radio);
+    Ok(())
+}
+
+fn calibration_show(args: &Args) -> Result<(), String> {
+    let calibration = Calibration::read(args.path()?.as_ref()).map_err(|e| e.to_string())?;
+    print!("{calibration}");
+    for issue in calibration.check() {
+        println!("Invalid: {issue}");
+    }
+    Ok(())
+}
+
+fn calibration_restore(args: &Args) -> Result<(), String> {
+    let port = String::from(args.option("port")?);
+    let calibration = Calibration::read(args.path()?.as_ref()).map_err(|e| e.to_string())?;
+
+    let radio = wait_job(
+        "Writing",
+        CalibrationWriteJob::new(port, calibration)
+            .other_unit(args.flag("other-unit"))
+            .start(),
+    )?;
+    println!("Calibration of {radio} restored!");
+    Ok(())
+}
+
+fn catalog(args: &Args) -> Result<(), String> {
+    let catalog = Catalog::load(args.option("source")?).map_err(|e| e.to_string())?;
+    let target = match args.option("target") {
+        Ok(name) => Some(find_target(name).ok_or(format!("Unknown target: {name}"))?),
+        Err(_) => None,
+    };
+    let releases: Vec<_> = catalog
+        .releases
+        .iter()
+        .filter(|r| target.as_ref().is_none_or(|t| r.is_for(t)))
+        .collect();
+    let Ok(version) = args.option("download") else {
+        for r in releases {
+            println!("{}\t{}\t{}", r.version, r.target, r.url);
+        }
+        return Ok(());
+    };
+    let matching: Vec<_> = releases
+        .into_iter()
+        .filter(|r| r.version == version)
+        .collect();
+    match matching.as_slice() {
+        [release] => {
+            let path = catalog
+                .download(release, &cache_dir())
+                .map_err(|e| e.to_string())?;
+            println!("{}", path.display());
+            Ok(())
+        }
+        [] => Err(format!("No release {version}")),
+        _ => Err(format!(
+            "Several targets have release {version}, use --target"
+        )),
+    }
+}
+
+// Radio model of a stock command, which radios in DFU mode do not report
+fn stock_radio(args: &Args) -> Result<RadioHW, String> {
+    let name = args.option("target")?;
+    let target = find_target(name).ok_or(format!("Unknown target: {name}"))?;
+    target_radio(&target).ok_or(format!("Target {target} is not a radio"))
+}
+
+fn stock_archive(args: &Args) -> Result<(), String> {
+    let radio = stock_radio(args)?;
+
+    let archive = wait_job(
+        "Archiving",
+        StockArchiveJob::new(radio, archive_root()).start(),
+    )?;
+    eprintln!("Archived {}", archive.contents().join(", "));
+    eprintln!("{NOT_ARCHIVED}");
+    println!("{}", archive.dir.display());
+    Ok(())
+}
+
+fn stock_restore(args: &Args) -> Result<(), String> {
+    let radio = stock_radio(args)?;
+    let archive = StockArchive::latest(&archive_root(), radio)
+        .map_err(|e| e.to_string())?
+        .ok_or(format!("No factory data archived for the {radio}"))?;
+    eprintln!(
+        "Restoring {} archived on {}",
+        archive.contents().join(", "),
+        archive.created
+    );
+
+    wait_job("Restoring", StockRestoreJob::new(archive).start())?;
+    println!("Factory data restored!");
+    Ok(())
+}
+
+fn list_files(args: &Args) -> Result<(), String> {
+    let port = args.option("port")?;
+    let dir = match args.positional.as_slice() {
+        [] => "/",
+        _ => args.path()?,
+    };
+    for entry in files::browse(port, dir).map_err(|e| e.to_string())? {
+        match entry {
+            Entry::File { name, size } => println!("{size:>10}  {name}"),
+            Entry::Directory { name } => println!("{:>10}  {name}/", "-"),
+            Entry::Memory { index, name, size } => println!("{size:>10}  {name} (memory {index})"),
+        }
+    }
+    Ok(())
+}
+
+fn copy_file(args: &Args, transfer: Transfer) -> Result<(), String> {
+    let port = String::from(args.option("port")?);
+
+    let transfer = wait_job("Copy", TransferJob::new(port, transfer).start())?;
+    println!("Copied {transfer}");
+    Ok(())
+}
+
+// Print the rows of a CHIRP file which were not fully converted
+fn print_reports(reports: &[chirp::RowReport]) {
+    for report in reports {
+        eprintln!("Warning: {report}");
+    }
+}
+
+fn chirp_import(args: &Args) -> Result<(), String> {
+    let path = std::path::Path::new(args.option("codeplug")?);
+    let csv = args.path()?;
+    let mut codeplug = match path.exists() {
+        true => Codeplug::load(path).map_err(|e| format!("{}: {e}", path.display()))?,
+        false => Codeplug::default(),
+    };
+
+    let import = chirp::import_file(csv.as_ref()).map_err(|e| format!("{csv}: {e}"))?;
+    print_reports(&import.reports);
+    println!("Imported {} channels", import.channels.len());
+    codeplug.channels.extend(import.channels);
+    for issue in codeplug.check() {
+        eprintln!("Invalid: {issue}");
+    }
+    codeplug.save(path).map_err(|e| e.to_string())
+}
+
+fn chirp_export(args: &Args) -> Result<(), String> {
+    let codeplug = Codeplug::load(args.option("codeplug")?.as_ref()).map_err(|e| e.to_string())?;
+    let csv = args.path()?;
+
+    let reports =
+        chirp::export_file(&codeplug.channels, csv.as_ref()).map_err(|e| format!("{csv}: {e}"))?;
+    print_reports(&reports);
+    println!(
+        "Exported {} channels",
+        codeplug.channels.len() - reports.len()
+    );
+    Ok(())
+}
+
+fn rdt_convert(args: &Args) -> Result<(), String> {
+    let radio = match args.option("target") {
+        Ok(name) => {
+            let target = find_target(name).ok_or(format!("Unknown target: {name}"))?;
+            Some(target_radio(&target).ok_or(format!("Target {target} is not a radio"))?)
+        }
+        Err(_) => None,
+    };
+    let stock = args.path()?;
+
+    let conversion =
+        rdt::convert_file(stock.as_ref(), radio).map_err(|e| format!("{stock}: {e}"))?;
+    for warning in &conversion.warnings {
+        eprintln!("Warning: {warning}");
+    }
+    let codeplug = &conversion.codeplug;
+    codeplug
+        .save(args.option("codeplug")?.as_ref())
+        .map_err(|e| e.to_string())?;
+    println!(
+        "Converted {} codeplug: {} channels, {} zones, {} contacts",
+        conversion.radio,
+        codeplug.channels.len(),
+        codeplug.banks.len(),
+        codeplug.contacts.len()
+    );
+    Ok(())
+}
+
+fn contacts_import(args: &Args) -> Result<(), String> {
+    let path = std::path::Path::new(args.option("codeplug")?);
+    let list = args.path()?;
+    let mut codeplug = match path.exists() {
+        true => Codeplug::load(path).map_err(|e| format!("{}: {e}", path.display()))?,
+        false => Codeplug::default(),
+    };
+
+    let import = contacts::import_file(list.as_ref()).map_err(|e| format!("{list}: {e}"))?;
+    for skipped in &import.skipped {
+        eprintln!("Warning: {skipped}");
+    }
+    println!("Imported {} contacts", import.contacts.len());
+    codeplug.contacts.extend(import.contacts);
+    codeplug.save(path).map_err(|e| e.to_string())
+}
+
+fn contacts_export(args: &Args) -> Result<(), String> {
+    let codeplug = Codeplug::load(args.option("codeplug")?.as_ref()).map_err(|e| e.to_string())?;
+    let list = args.path()?;
+
+    let skipped = contacts::export_file(&codeplug.contacts, list.as_ref())
+        .map_err(|e| format!("{list}: {e}"))?;
+    for name in &skipped {
+        eprintln!("Warning: {name} is not an M17 contact, not exported");
+    }
+    println!(
+        "Exported {} contacts",
+        codeplug.contacts.len() - skipped.len()
+    );
+    Ok(())
+}
+
+fn rig_server(args: &Args, protocol: RigProtocol) -> Result<(), String> {
+    let port = args.option("port")?;
+    let listen = match args.option("listen") {
+        Ok(listen) => listen
+            .parse()
+            .map_err(|_| format!("Invalid TCP port: {listen}"))?,
+        Err(_) => protocol.default_port(),
+    };
+
+    let server =
+        RigServer::start(port, protocol, ("127.0.0.1", listen)).map_err(|e| e.to_string())?;
+    println!("Serving {protocol} on {}", server.address());
+    // Print the requests as they are served
+    let mut printed = 0;
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        let status = server.status();
+        let new = (status.requests - printed).min(status.last_requests.len());
+        for request in &status.last_requests[status.last_requests.len() - new..] {
+            println!("{request}");
+        }
+        printed = status.requests;
+        if let Some(e) = status.error {
+            server.stop();
+            return Err(format!("Server stopped on error: {e}"));
+        }
+    }
+}
+
+fn record_telemetry(args: &Args) -> Result<(), String> {
+    let port = args.option("port")?;
+    let output = args.path()?;
+    let interval = match args.option("interval") {
+        Ok(interval) => interval
+            .parse::<f64>()
+            .ok()
+            .filter(|seconds| *seconds >= 0.1)
+            .ok_or(format!("Invalid interval: {interval}"))?,
+        Err(_) => 1.0,
+    };
+
+    let recorder = TelemetryRecorder::start(
+        port,
+        std::time::Duration::from_secs_f64(interval),
+        Some(std::path::Path::new(output)),
+    )
+    .map_err(|e| e.to_string())?;
+    println!("Recording to {output}");
+    let mut last_error = None;
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        for sample in recorder.take_samples() {
+            let rssi = sample.rssi.map_or("-".into(), |r| format!("{r} dBm"));
+            let battery = sample
+                .battery
+                .map_or("-".into(), |b| format!("{:.2} V", b as f64 / 1000.0));
+            println!(
+                "{}  {:.5} MHz  {rssi}  {battery}",
+                telemetry::format_time(sample.time),
+                sample.frequency as f64 / 1e6,
+            );
+        }
+        // Errors are printed once, until sampling recovers
+        let error = recorder.error();
+        if !recorder.is_running() {
+            return Err(error.unwrap_or_default());
+        }
+        if error != last_error {
+            match &error {
+                Some(e) => eprintln!("{e}"),
+                None => eprintln!("Sampling resumed"),
+            }
+            last_error = error;
+        }
+    }
+}
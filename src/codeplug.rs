@@ -0,0 +1,724 @@
+// show logs when debugging
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use crate::backup::port_options;
+use crate::Message;
+use crate::Tab;
+use iced::{
+    alignment::{Horizontal, Vertical},
+    widget::{
+        combo_box, pick_list, progress_bar, row, scrollable, text, text_input, Button, Column,
+        Container, Row, Text,
+    },
+    Alignment, Element, Length, Task,
+};
+use iced_aw::TabLabel;
+use openrtx_companion::core::{
+    chirp::{self, ChirpImport, RowReport},
+    codeplug::{mhz, power_from_watts, watts, Bandwidth, Channel, OpMode, CTCSS_TONES},
+    get_ports,
+    rdt::{self, RdtConversion},
+    Attached, Codeplug, CodeplugError, CodeplugReadJob, CodeplugWriteJob, JobHandle, JobState,
+    SerialPort,
+};
+use rfd::AsyncFileDialog;
+use std::path::PathBuf;
+
+/// CTCSS tone of a channel, for the tone pick list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tone(Option<u16>);
+
+impl std::fmt::Display for Tone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(tone) => write!(f, "{}.{} Hz", tone / 10, tone % 10),
+            None => write!(f, "None"),
+        }
+    }
+}
+
+const MODES: [OpMode; 2] = [OpMode::Fm, OpMode::M17];
+
+#[derive(Clone, Debug)]
+pub enum ChannelField {
+    Name(String),
+    RxFrequency(String),
+    TxFrequency(String),
+    Mode(OpMode),
+    Tone(Tone),
+    Can(String),
+    Power(String),
+    Bandwidth(Bandwidth),
+}
+
+#[derive(Clone, Debug)]
+pub enum CodeplugMessage {
+    PortSelected(SerialPort),
+    PortsChanged(Attached),
+    ReadPressed,
+    OpenPressed,
+    Opened(Option<Result<Codeplug, String>>),
+    SavePressed,
+    Saved(Option<Result<PathBuf, String>>),
+    WritePressed,
+    CancelPressed,
+    ImportPressed,
+    Imported(Option<Result<ChirpImport, String>>),
+    ExportPressed,
+    Exported(Option<Result<(PathBuf, Vec<RowReport>), String>>),
+    ConvertPressed,
+    Converted(Option<Result<RdtConversion, String>>),
+    Edit(usize, ChannelField),
+    AddChannel,
+    RemoveChannel(usize),
+    Tick,
+}
+
+// Text of the numeric fields of a channel, kept as typed while it does not
+// parse
+struct ChannelText {
+    rx_frequency: String,
+    tx_frequency: String,
+    can: String,
+    power: String,
+}
+
+impl ChannelText {
+    fn new(channel: &Channel) -> Self {
+        ChannelText {
+            rx_frequency: mhz(channel.rx_frequency),
+            tx_frequency: mhz(channel.tx_frequency),
+            can: channel.tx_can.to_string(),
+            power: format!("{:.2}", watts(channel.power)),
+        }
+    }
+
+    // Fields which do not parse, for channel `number`
+    fn errors(&self, number: usize, mode: OpMode) -> Vec<String> {
+        let mut errors = vec![];
+        if parse_mhz(&self.rx_frequency).is_none() {
+            errors.push(format!("Channel {number} RX frequency is not a number"));
+        }
+        if parse_mhz(&self.tx_frequency).is_none() {
+            errors.push(format!("Channel {number} TX frequency is not a number"));
+        }
+        if mode == OpMode::M17 && self.can.trim().parse::<u8>().is_err() {
+            errors.push(format!("Channel {number} CAN is not a number"));
+        }
+        if parse_watts(&self.power).is_none() {
+            errors.push(format!("Channel {number} power is not a number"));
+        }
+        errors
+    }
+}
+
+fn parse_mhz(value: &str) -> Option<u32> {
+    let mhz: f64 = value.trim().parse().ok()?;
+    (mhz > 0.0).then(|| (mhz * 1e6).round() as u32)
+}
+
+fn parse_watts(value: &str) -> Option<f32> {
+    value.trim().parse().ok().filter(|w: &f32| *w > 0.0)
+}
+
+pub struct CodeplugTab {
+    serial_ports: Vec<SerialPort>,
+    serial_port: Option<SerialPort>,
+    // The selected port has been unplugged
+    port_gone: bool,
+    ports_combo_state: combo_box::State<SerialPort>,
+    codeplug: Option<Codeplug>,
+    texts: Vec<ChannelText>,
+    // Invalid channel settings and fields which do not parse
+    issues: Vec<String>,
+    // Entries of the last import or export which were not fully converted
+    warnings: Vec<String>,
+    read_job: Option<JobHandle<Codeplug, CodeplugError>>,
+    write_job: Option<JobHandle<(), CodeplugError>>,
+    progress: f32,
+    status_text: String,
+}
+
+impl Default for CodeplugTab {
+    fn default() -> Self {
+        let ports = port_options(get_ports());
+        Self {
+            serial_ports: ports.clone(),
+            serial_port: None,
+            port_gone: false,
+            ports_combo_state: combo_box::State::new(ports),
+            codeplug: None,
+            texts: vec![],
+            issues: vec![],
+            warnings: vec![],
+            read_job: None,
+            write_job: None,
+            progress: 0.0,
+            status_text: String::from("Read the codeplug of a radio or open a file"),
+        }
+    }
+}
+
+impl CodeplugTab {
+    pub fn update(&mut self, message: CodeplugMessage) -> Task<Message> {
+        match message {
+            CodeplugMessage::PortSelected(port) => {
+                self.serial_port = Some(port);
+                self.port_gone = false;
+                Task::none()
+            }
+            CodeplugMessage::PortsChanged(attached) => {
+                let ports = port_options(attached.ports.clone());
+                if ports != self.serial_ports {
+                    self.serial_ports = ports.clone();
+                    self.ports_combo_state = combo_box::State::new(ports);
+                }
+                if let Some(port) = &self.serial_port {
+                    self.port_gone = !attached.has_port(port);
+                }
+                Task::none()
+            }
+            CodeplugMessage::ReadPressed => {
+                if let Some(port) = &self.serial_port {
+                    self.progress = 0.0;
+                    self.read_job = Some(CodeplugReadJob::new(port.name.clone()).start());
+                }
+                Task::none()
+            }
+            CodeplugMessage::OpenPressed => Task::perform(
+                async {
+                    let file = AsyncFileDialog::new()
+                        .add_filter("Codeplug", &["rtxc"])
+                        .pick_file()
+                        .await?;
+                    Some(Codeplug::load(file.path()).map_err(|e| e.to_string()))
+                },
+                |res| Message::Codeplug(CodeplugMessage::Opened(res)),
+            ),
+            CodeplugMessage::Opened(res) => {
+                match res {
+                    Some(Ok(codeplug)) => {
+                        self.status_text =
+                            format!("Loaded codeplug with {} channels", codeplug.channels.len());
+                        self.set_codeplug(codeplug);
+                    }
+                    Some(Err(e)) => self.status_text = format!("Error in reading codeplug: {e}"),
+                    None => (),
+                }
+                Task::none()
+            }
+            CodeplugMessage::SavePressed => {
+                let Some(codeplug) = self.codeplug.clone() else {
+                    return Task::none();
+                };
+                Task::perform(
+                    async move {
+                        let file = AsyncFileDialog::new()
+                            .add_filter("Codeplug", &["rtxc"])
+                            .set_file_name("codeplug.rtxc")
+                            .save_file()
+                            .await?;
+                        let path = file.path().to_path_buf();
+                        Some(
+                            codeplug
+                                .save(&path)
+                                .map(|_| path)
+                                .map_err(|e| e.to_string()),
+                        )
+                    },
+                    |res| Message::Codeplug(CodeplugMessage::Saved(res)),
+                )
+            }
+            CodeplugMessage::Saved(res) => {
+                match res {
+                    Some(Ok(path)) => {
+                        self.status_text = format!("Saved codeplug to {}", path.display())
+                    }
+                    Some(Err(e)) => self.status_text = format!("Error in saving codeplug: {e}"),
+                    None => (),
+                }
+                Task::none()
+            }
+            CodeplugMessage::WritePressed => {
+                if let (Some(port), Some(codeplug)) = (&self.serial_port, &self.codeplug) {
+                    self.progress = 0.0;
+                    self.write_job =
+                        Some(CodeplugWriteJob::new(port.name.clone(), codeplug.clone()).start());
+                }
+                Task::none()
+            }
+            CodeplugMessage::CancelPressed => {
+                let read_cancelled = self.read_job.as_ref().is_some_and(|j| j.cancel());
+                let write_cancelled = self.write_job.as_ref().is_some_and(|j| j.cancel());
+                if read_cancelled || write_cancelled {
+                    self.status_text = String::from("Cancelling...");
+                }
+                Task::none()
+            }
+            CodeplugMessage::ImportPressed => Task::perform(
+                async {
+                    let file = AsyncFileDialog::new()
+                        .set_title("Import CHIRP channels")
+                        .add_filter("CHIRP CSV", &["csv"])
+                        .pick_file()
+                        .await?;
+                    Some(chirp::import_file(file.path()).map_err(|e| e.to_string()))
+                },
+                |res| Message::Codeplug(CodeplugMessage::Imported(res)),
+            ),
+            CodeplugMessage::Imported(res) => {
+                match res {
+                    Some(Ok(import)) => {
+                        self.status_text = format!(
+                            "Imported {} channels, {} rows not fully converted",
+                            import.channels.len(),
+                            import.reports.len()
+                        );
+                        let mut codeplug = self.codeplug.take().unwrap_or_default();
+                        codeplug.channels.extend(import.channels);
+                        self.set_codeplug(codeplug);
+                        self.warnings = import.reports.iter().map(|r| r.to_string()).collect();
+                    }
+                    Some(Err(e)) => self.status_text = format!("Error in importing channels: {e}"),
+                    None => (),
+                }
+                Task::none()
+            }
+            CodeplugMessage::ExportPressed => {
+                let Some(codeplug) = self.codeplug.clone() else {
+                    return Task::none();
+                };
+                Task::perform(
+                    async move {
+                        let file = AsyncFileDialog::new()
+                            .set_title("Export CHIRP channels")
+                            .add_filter("CHIRP CSV", &["csv"])
+                            .set_file_name("channels.csv")
+                            .save_file()
+                            .await?;
+                        let path = file.path().to_path_buf();
+                        Some(
+                            chirp::export_file(&codeplug.channels, &path)
+                                .map(|reports| (path, reports))
+                                .map_err(|e| e.to_string()),
+                        )
+                    },
+                    |res| Message::Codeplug(CodeplugMessage::Exported(res)),
+                )
+            }
+            CodeplugMessage::Exported(res) => {
+                match res {
+                    Some(Ok((path, reports))) => {
+                        self.status_text = format!("Exported channels to {}", path.display());
+                        self.warnings = reports.iter().map(|r| r.to_string()).collect();
+                    }
+                    Some(Err(e)) => self.status_text = format!("Error in exporting channels: {e}"),
+                    None => (),
+                }
+                Task::none()
+            }
+            CodeplugMessage::ConvertPressed => Task::perform(
+                async {
+                    let file = AsyncFileDialog::new()
+                        .set_title("Convert a stock TYT codeplug")
+                        .add_filter("Stock codeplug", &["rdt", "bin"])
+                        .pick_file()
+                        .await?;
+                    Some(rdt::convert_file(file.path(), None).map_err(|e| e.to_string()))
+                },
+                |res| Message::Codeplug(CodeplugMessage::Converted(res)),
+            ),
+            CodeplugMessage::Converted(res) => {
+                match res {
+                    Some(Ok(conversion)) => {
+                        self.status_text = format!(
+                            "Converted {} codeplug: {} channels, {} zones, {} contacts",
+                            conversion.radio,
+                            conversion.codeplug.channels.len(),
+                            conversion.codeplug.banks.len(),
+                            conversion.codeplug.contacts.len()
+                        );
+                        self.set_codeplug(conversion.codeplug);
+                        self.warnings = conversion.warnings.iter().map(|w| w.to_string()).collect();
+                    }
+                    Some(Err(e)) => self.status_text = format!("Error in converting codeplug: {e}"),
+                    None => (),
+                }
+                Task::none()
+            }
+            CodeplugMessage::Edit(index, field) => {
+                self.edit(index, field);
+                self.check();
+                Task::none()
+            }
+            CodeplugMessage::AddChannel => {
+                let codeplug = self.codeplug.get_or_insert_with(Codeplug::default);
+                let channel = Channel::new(format!("Channel {}", codeplug.channels.len() + 1));
+                self.texts.push(ChannelText::new(&channel));
+                codeplug.channels.push(channel);
+                self.check();
+                Task::none()
+            }
+            CodeplugMessage::RemoveChannel(index) => {
+                if let Some(codeplug) = &mut self.codeplug {
+                    codeplug.remove_channel(index);
+                    self.texts.remove(index);
+                    self.check();
+                }
+                Task::none()
+            }
+            CodeplugMessage::Tick => {
+                if let Some(read_job) = &mut self.read_job {
+                    let mut codeplug = None;
+                    match read_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text = format!("Read {}/{}", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(read) => {
+                            self.status_text =
+                                format!("Read codeplug with {} channels", read.channels.len());
+                            self.progress = 100.0;
+                            codeplug = Some(read.clone());
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Reading failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Cancelled");
+                            self.progress = 0.0;
+                        }
+                    }
+                    if !read_job.is_running() {
+                        self.read_job = None;
+                    }
+                    if let Some(codeplug) = codeplug {
+                        self.set_codeplug(codeplug);
+                    }
+                }
+                if let Some(write_job) = &mut self.write_job {
+                    match write_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text = format!("Wrote {}/{}", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(()) => {
+                            self.status_text = String::from("Codeplug written to the radio!");
+                            self.progress = 100.0;
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Writing failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Cancelled");
+                            self.progress = 0.0;
+                        }
+                    }
+                    if !write_job.is_running() {
+                        self.write_job = None;
+                    }
+                }
+                Task::none()
+            }
+        }
+    }
+
+    fn edit(&mut self, index: usize, field: ChannelField) {
+        let (Some(channel), Some(text)) = (
+            self.codeplug
+                .as_mut()
+                .and_then(|c| c.channels.get_mut(index)),
+            self.texts.get_mut(index),
+        ) else {
+            return;
+        };
+        match field {
+            ChannelField::Name(name) => channel.name = name,
+            ChannelField::RxFrequency(value) => {
+                if let Some(frequency) = parse_mhz(&value) {
+                    channel.rx_frequency = frequency;
+                }
+                text.rx_frequency = value;
+            }
+            ChannelField::TxFrequency(value) => {
+                if let Some(frequency) = parse_mhz(&value) {
+                    channel.tx_frequency = frequency;
+                }
+                text.tx_frequency = value;
+            }
+            ChannelField::Mode(mode) => channel.mode = mode,
+            ChannelField::Tone(Tone(tone)) => {
+                channel.rx_tone = tone;
+                channel.tx_tone = tone;
+            }
+            ChannelField::Can(value) => {
+                if let Ok(can) = value.trim().parse() {
+                    channel.rx_can = can;
+                    channel.tx_can = can;
+                }
+                text.can = value;
+            }
+            ChannelField::Power(value) => {
+                if let Some(w) = parse_watts(&value) {
+                    channel.power = power_from_watts(w);
+                }
+                text.power = value;
+            }
+            ChannelField::Bandwidth(bandwidth) => channel.bandwidth = bandwidth,
+        }
+    }
+
+    fn set_codeplug(&mut self, codeplug: Codeplug) {
+        self.texts = codeplug.channels.iter().map(ChannelText::new).collect();
+        self.codeplug = Some(codeplug);
+        self.warnings = vec![];
+        self.check();
+    }
+
+    fn check(&mut self) {
+        let Some(codeplug) = &self.codeplug else {
+            self.issues = vec![];
+            return;
+        };
+        let mut issues: Vec<String> = codeplug.check().iter().map(|i| i.to_string()).collect();
+        for (index, (text, channel)) in self.texts.iter().zip(&codeplug.channels).enumerate() {
+            issues.extend(text.errors(index + 1, channel.mode));
+        }
+        self.issues = issues;
+    }
+
+    fn is_busy(&self) -> bool {
+        self.read_job.is_some() || self.write_job.is_some()
+    }
+
+    // A serial port is selected and still attached
+    fn port_ready(&self) -> bool {
+        self.serial_port.is_some() && !self.port_gone && !self.is_busy()
+    }
+
+    fn channel_row<'a>(
+        &'a self,
+        index: usize,
+        channel: &'a Channel,
+        fields: &'a ChannelText,
+    ) -> Element<'a, CodeplugMessage> {
+        let edit = move |field: fn(String) -> ChannelField| {
+            move |value: String| CodeplugMessage::Edit(index, field(value))
+        };
+        let tones: Vec<Tone> = std::iter::once(Tone(None))
+            .chain(CTCSS_TONES.iter().map(|t| Tone(Some(*t))))
+            .collect();
+        row![
+            text!("{}", index + 1).size(13).width(30),
+            text_input("Name", &channel.name)
+                .on_input(edit(ChannelField::Name))
+                .size(13)
+                .width(150),
+            text_input("MHz", &fields.rx_frequency)
+                .on_input(edit(ChannelField::RxFrequency))
+                .size(13)
+                .width(90),
+            text_input("MHz", &fields.tx_frequency)
+                .on_input(edit(ChannelField::TxFrequency))
+                .size(13)
+                .width(90),
+            pick_list(MODES, Some(channel.mode), move |mode| {
+                CodeplugMessage::Edit(index, ChannelField::Mode(mode))
+            })
+            .text_size(13)
+            .width(80),
+        ]
+        .push(match channel.mode {
+            OpMode::Fm => Element::from(
+                pick_list(tones, Some(Tone(channel.tx_tone)), move |tone| {
+                    CodeplugMessage::Edit(index, ChannelField::Tone(tone))
+                })
+                .text_size(13)
+                .width(100),
+            ),
+            OpMode::M17 => text_input("CAN", &fields.can)
+                .on_input(edit(ChannelField::Can))
+                .size(13)
+                .width(100)
+                .into(),
+            _ => Column::new().width(100).into(),
+        })
+        .push(
+            text_input("W", &fields.power)
+                .on_input(edit(ChannelField::Power))
+                .size(13)
+                .width(60),
+        )
+        .push(
+            pick_list(Bandwidth::ALL, Some(channel.bandwidth), move |bandwidth| {
+                CodeplugMessage::Edit(index, ChannelField::Bandwidth(bandwidth))
+            })
+            .text_size(13)
+            .width(100),
+        )
+        .push(
+            Button::new(Text::new("Remove").size(13))
+                .on_press_maybe((!self.is_busy()).then_some(CodeplugMessage::RemoveChannel(index))),
+        )
+        .spacing(5)
+        .align_y(Alignment::Center)
+        .into()
+    }
+}
+
+impl Tab for CodeplugTab {
+    type Message = Message;
+
+    fn title(&self) -> String {
+        String::from("Codeplug")
+    }
+
+    fn tab_label(&self) -> TabLabel {
+        TabLabel::Text(self.title())
+    }
+
+    fn content(&self) -> Element<'_, Self::Message> {
+        let port_combo_box = combo_box(
+            &self.ports_combo_state,
+            "Select a serial port",
+            self.serial_port.as_ref(),
+            CodeplugMessage::PortSelected,
+        )
+        .width(250);
+        let header = [
+            ("#", 30),
+            ("Name", 150),
+            ("RX (MHz)", 90),
+            ("TX (MHz)", 90),
+            ("Mode", 80),
+            ("CTCSS / CAN", 100),
+            ("Power (W)", 60),
+            ("Bandwidth", 100),
+        ]
+        .into_iter()
+        .fold(Row::new().spacing(5), |row, (name, width)| {
+            row.push(text(name).size(13).width(width))
+        });
+        let channels = self.codeplug.iter().flat_map(|c| c.channels.iter());
+
+        let content: Element<'_, CodeplugMessage> = Container::new(
+            Column::new()
+                .max_width(900)
+                .push(
+                    row![
+                        Column::new().width(120).push(text("Serial port:").size(15)),
+                        port_combo_box,
+                    ]
+                    .push_maybe(self.port_gone.then(|| text("Unplugged").size(15)))
+                    .spacing(10)
+                    .padding(20),
+                )
+                .push(row![Column::new()
+                    .width(900)
+                    .align_x(Alignment::Center)
+                    .push(text(&self.status_text).size(20)),])
+                .push(header)
+                .push(
+                    scrollable(
+                        channels
+                            .zip(&self.texts)
+                            .enumerate()
+                            .fold(Column::new().spacing(5), |column, (index, (c, t))| {
+                                column.push(self.channel_row(index, c, t))
+                            })
+                            .push(
+                                Button::new(Text::new("Add channel").size(13)).on_press_maybe(
+                                    (!self.is_busy()).then_some(CodeplugMessage::AddChannel),
+                                ),
+                            ),
+                    )
+                    .height(250),
+                )
+                .push(self.issues.iter().fold(Column::new(), |column, issue| {
+                    column.push(text(issue).style(text::danger).size(15))
+                }))
+                .push(self.warnings.iter().fold(Column::new(), |column, warning| {
+                    column.push(text(warning).size(13))
+                }))
+                .push(row![progress_bar(0.0..=100.0, self.progress),].padding(20))
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .push(
+                            Button::new(Text::new("Read").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    self.port_ready().then_some(CodeplugMessage::ReadPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Open").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (!self.is_busy()).then_some(CodeplugMessage::OpenPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Save").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    self.codeplug
+                                        .is_some()
+                                        .then_some(CodeplugMessage::SavePressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Convert stock").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (!self.is_busy()).then_some(CodeplugMessage::ConvertPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Import CHIRP").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (!self.is_busy()).then_some(CodeplugMessage::ImportPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Export CHIRP").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    self.codeplug
+                                        .is_some()
+                                        .then_some(CodeplugMessage::ExportPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Write").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (self.port_ready()
+                                        && self.codeplug.is_some()
+                                        && self.issues.is_empty())
+                                    .then_some(CodeplugMessage::WritePressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Cancel").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (self.read_job.as_ref().is_some_and(|j| j.can_cancel())
+                                        || self.write_job.as_ref().is_some_and(|j| j.can_cancel()))
+                                    .then_some(CodeplugMessage::CancelPressed),
+                                ),
+                        ),
+                ),
+        )
+        .align_x(Horizontal::Center)
+        .align_y(Vertical::Center)
+        .into();
+
+        content.map(Message::Codeplug)
+    }
+}
@@ -0,0 +1,497 @@
+// show logs when debugging
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use crate::backup::port_options;
+use crate::Message;
+use crate::Tab;
+use iced::{
+    alignment::{Horizontal, Vertical},
+    widget::{
+        combo_box, progress_bar, row, scrollable, text, text_input, Button, Column, Container, Row,
+        Text,
+    },
+    Alignment, Element, Length, Task,
+};
+use iced_aw::TabLabel;
+use openrtx_companion::core::{
+    codeplug::{Contact, OpMode},
+    contacts::{self, encode_callsign, ContactIssue, ContactsImport},
+    get_ports, Attached, Codeplug, CodeplugError, CodeplugReadJob, ContactsWriteJob, JobHandle,
+    JobState, SerialPort,
+};
+use rfd::AsyncFileDialog;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug)]
+pub enum ContactsMessage {
+    PortSelected(SerialPort),
+    PortsChanged(Attached),
+    ReadPressed,
+    WritePressed,
+    CancelPressed,
+    ImportPressed,
+    Imported(Option<Result<ContactsImport, String>>),
+    ExportPressed,
+    Exported(Option<Result<(PathBuf, Vec<String>), String>>),
+    EditName(usize, String),
+    EditCallsign(usize, String),
+    AddContact,
+    RemoveContact(usize),
+    Tick,
+}
+
+pub struct ContactsTab {
+    serial_ports: Vec<SerialPort>,
+    serial_port: Option<SerialPort>,
+    // The selected port has been unplugged
+    port_gone: bool,
+    ports_combo_state: combo_box::State<SerialPort>,
+    contacts: Option<Vec<Contact>>,
+    // Callsigns of the contacts, kept as typed while they are not valid
+    callsigns: Vec<String>,
+    issues: Vec<String>,
+    // Entries of the last import or export which were left out
+    warnings: Vec<String>,
+    read_job: Option<JobHandle<Codeplug, CodeplugError>>,
+    write_job: Option<JobHandle<(), CodeplugError>>,
+    progress: f32,
+    status_text: String,
+}
+
+impl Default for ContactsTab {
+    fn default() -> Self {
+        let ports = port_options(get_ports());
+        Self {
+            serial_ports: ports.clone(),
+            serial_port: None,
+            port_gone: false,
+            ports_combo_state: combo_box::State::new(ports),
+            contacts: None,
+            callsigns: vec![],
+            issues: vec![],
+            warnings: vec![],
+            read_job: None,
+            write_job: None,
+            progress: 0.0,
+            status_text: String::from("Read the contacts of a radio or import a contact list"),
+        }
+    }
+}
+
+// Callsign of an M17 contact or ID of a DMR one, for display
+fn address(contact: &Contact) -> String {
+    match (contact.m17_callsign(), contact.dmr_id()) {
+        (Some(callsign), _) => callsign,
+        (None, Some(id)) => id.to_string(),
+        (None, None) => String::new(),
+    }
+}
+
+impl ContactsTab {
+    pub fn update(&mut self, message: ContactsMessage) -> Task<Message> {
+        match message {
+            ContactsMessage::PortSelected(port) => {
+                self.serial_port = Some(port);
+                self.port_gone = false;
+                Task::none()
+            }
+            ContactsMessage::PortsChanged(attached) => {
+                let ports = port_options(attached.ports.clone());
+                if ports != self.serial_ports {
+                    self.serial_ports = ports.clone();
+                    self.ports_combo_state = combo_box::State::new(ports);
+                }
+                if let Some(port) = &self.serial_port {
+                    self.port_gone = !attached.has_port(port);
+                }
+                Task::none()
+            }
+            ContactsMessage::ReadPressed => {
+                if let Some(port) = &self.serial_port {
+                    self.progress = 0.0;
+                    self.read_job = Some(CodeplugReadJob::new(port.name.clone()).start());
+                }
+                Task::none()
+            }
+            ContactsMessage::WritePressed => {
+                if let (Some(port), Some(contacts)) = (&self.serial_port, &self.contacts) {
+                    self.progress = 0.0;
+                    self.write_job =
+                        Some(ContactsWriteJob::new(port.name.clone(), contacts.clone()).start());
+                }
+                Task::none()
+            }
+            ContactsMessage::CancelPressed => {
+                let read_cancelled = self.read_job.as_ref().is_some_and(|j| j.cancel());
+                let write_cancelled = self.write_job.as_ref().is_some_and(|j| j.cancel());
+                if read_cancelled || write_cancelled {
+                    self.status_text = String::from("Cancelling...");
+                }
+                Task::none()
+            }
+            ContactsMessage::ImportPressed => Task::perform(
+                async {
+                    let file = AsyncFileDialog::new()
+                        .set_title("Import contacts")
+                        .add_filter("Contact list", &["csv", "vcf"])
+                        .pick_file()
+                        .await?;
+                    Some(contacts::import_file(file.path()).map_err(|e| e.to_string()))
+                },
+                |res| Message::Contacts(ContactsMessage::Imported(res)),
+            ),
+            ContactsMessage::Imported(res) => {
+                match res {
+                    Some(Ok(import)) => {
+                        self.status_text = format!(
+                            "Imported {} contacts, {} skipped",
+                            import.contacts.len(),
+                            import.skipped.len()
+                        );
+                        let mut contacts = self.contacts.take().unwrap_or_default();
+                        contacts.extend(import.contacts);
+                        self.set_contacts(contacts);
+                        self.warnings = import.skipped.iter().map(|s| s.to_string()).collect();
+                    }
+                    Some(Err(e)) => self.status_text = format!("Error in importing contacts: {e}"),
+                    None => (),
+                }
+                Task::none()
+            }
+            ContactsMessage::ExportPressed => {
+                let Some(list) = self.contacts.clone() else {
+                    return Task::none();
+                };
+                Task::perform(
+                    async move {
+                        let file = AsyncFileDialog::new()
+                            .set_title("Export contacts")
+                            .add_filter("CSV", &["csv"])
+                            .add_filter("vCard", &["vcf"])
+                            .set_file_name("contacts.csv")
+                            .save_file()
+                            .await?;
+                        let path = file.path().to_path_buf();
+                        Some(
+                            contacts::export_file(&list, &path)
+                                .map(|skipped| (path, skipped))
+                                .map_err(|e| e.to_string()),
+                        )
+                    },
+                    |res| Message::Contacts(ContactsMessage::Exported(res)),
+                )
+            }
+            ContactsMessage::Exported(res) => {
+                match res {
+                    Some(Ok((path, skipped))) => {
+                        self.status_text = format!("Exported contacts to {}", path.display());
+                        self.warnings = skipped
+                            .iter()
+                            .map(|name| format!("{name} is not an M17 contact, not exported"))
+                            .collect();
+                    }
+                    Some(Err(e)) => self.status_text = format!("Error in exporting contacts: {e}"),
+                    None => (),
+                }
+                Task::none()
+            }
+            ContactsMessage::EditName(index, name) => {
+                if let Some(contact) = self.contacts.as_mut().and_then(|c| c.get_mut(index)) {
+                    contact.name = name;
+                }
+                self.check();
+                Task::none()
+            }
+            ContactsMessage::EditCallsign(index, callsign) => {
+                let callsign = callsign.to_ascii_uppercase();
+                if let (Some(contact), Some(text)) = (
+                    self.contacts.as_mut().and_then(|c| c.get_mut(index)),
+                    self.callsigns.get_mut(index),
+                ) {
+                    if let Ok(updated) = Contact::m17(contact.name.clone(), &callsign) {
+                        *contact = updated;
+                    }
+                    *text = callsign;
+                }
+                self.check();
+                Task::none()
+            }
+            ContactsMessage::AddContact => {
+                let contacts = self.contacts.get_or_insert_with(Vec::new);
+                let name = format!("Contact {}", contacts.len() + 1);
+                // Placeholder callsign, replaced when a valid one is typed
+                contacts.extend(Contact::m17(name, "@ALL").ok());
+                self.callsigns.push(String::new());
+                self.check();
+                Task::none()
+            }
+            ContactsMessage::RemoveContact(index) => {
+                if let Some(contacts) = &mut self.contacts {
+                    if index < contacts.len() {
+                        contacts.remove(index);
+                        self.callsigns.remove(index);
+                        self.check();
+                    }
+                }
+                Task::none()
+            }
+            ContactsMessage::Tick => {
+                if let Some(read_job) = &mut self.read_job {
+                    let mut contacts = None;
+                    match read_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text = format!("Read {}/{}", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(codeplug) => {
+                            self.status_text = format!("Read {} contacts", codeplug.contacts.len());
+                            self.progress = 100.0;
+                            contacts = Some(codeplug.contacts.clone());
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Reading failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Cancelled");
+                            self.progress = 0.0;
+                        }
+                    }
+                    if !read_job.is_running() {
+                        self.read_job = None;
+                    }
+                    if let Some(contacts) = contacts {
+                        self.set_contacts(contacts);
+                    }
+                }
+                if let Some(write_job) = &mut self.write_job {
+                    match write_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text =
+                                    format!("Transferred {}/{}", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(()) => {
+                            self.status_text = String::from("Contacts written to the radio!");
+                            self.progress = 100.0;
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Writing failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Cancelled");
+                            self.progress = 0.0;
+                        }
+                    }
+                    if !write_job.is_running() {
+                        self.write_job = None;
+                    }
+                }
+                Task::none()
+            }
+        }
+    }
+
+    fn set_contacts(&mut self, contacts: Vec<Contact>) {
+        self.callsigns = contacts.iter().map(address).collect();
+        self.contacts = Some(contacts);
+        self.warnings = vec![];
+        self.check();
+    }
+
+    fn check(&mut self) {
+        let Some(contacts) = &self.contacts else {
+            self.issues = vec![];
+            return;
+        };
+        let mut issues: Vec<ContactIssue> = contacts::check(contacts)
+            .into_iter()
+            .filter(|i| !matches!(i, ContactIssue::Address { .. }))
+            .collect();
+        // The callsign typed is checked instead of the last valid one
+        for (index, (callsign, contact)) in self.callsigns.iter().zip(contacts).enumerate() {
+            if contact.mode != OpMode::M17 {
+                continue;
+            }
+            if let Err(error) = encode_callsign(callsign) {
+                issues.push(ContactIssue::Callsign {
+                    contact: index,
+                    error,
+                });
+            }
+        }
+        issues.sort_by_key(|i| i.contact());
+        self.issues = issues.iter().map(|i| i.to_string()).collect();
+    }
+
+    fn is_busy(&self) -> bool {
+        self.read_job.is_some() || self.write_job.is_some()
+    }
+
+    // A serial port is selected and still attached
+    fn port_ready(&self) -> bool {
+        self.serial_port.is_some() && !self.port_gone && !self.is_busy()
+    }
+
+    fn contact_row<'a>(
+        &'a self,
+        index: usize,
+        contact: &'a Contact,
+        callsign: &'a str,
+    ) -> Element<'a, ContactsMessage> {
+        row![
+            text!("{}", index + 1).size(13).width(30),
+            text_input("Name", &contact.name)
+                .on_input(move |name| ContactsMessage::EditName(index, name))
+                .size(13)
+                .width(200),
+            text(contact.mode.to_string()).size(13).width(60),
+        ]
+        .push(match contact.mode {
+            // Only M17 addresses can be edited
+            OpMode::M17 => Element::from(
+                text_input("Callsign", callsign)
+                    .on_input(move |callsign| ContactsMessage::EditCallsign(index, callsign))
+                    .size(13)
+                    .width(150),
+            ),
+            _ => text(callsign).size(13).width(150).into(),
+        })
+        .push(
+            Button::new(Text::new("Remove").size(13))
+                .on_press_maybe((!self.is_busy()).then_some(ContactsMessage::RemoveContact(index))),
+        )
+        .spacing(5)
+        .align_y(Alignment::Center)
+        .into()
+    }
+}
+
+impl Tab for ContactsTab {
+    type Message = Message;
+
+    fn title(&self) -> String {
+        String::from("Contacts")
+    }
+
+    fn tab_label(&self) -> TabLabel {
+        TabLabel::Text(self.title())
+    }
+
+    fn content(&self) -> Element<'_, Self::Message> {
+        let port_combo_box = combo_box(
+            &self.ports_combo_state,
+            "Select a serial port",
+            self.serial_port.as_ref(),
+            ContactsMessage::PortSelected,
+        )
+        .width(250);
+        let header = [
+            ("#", 30),
+            ("Name", 200),
+            ("Mode", 60),
+            ("Callsign / ID", 150),
+        ]
+        .into_iter()
+        .fold(Row::new().spacing(5), |row, (name, width)| {
+            row.push(text(name).size(13).width(width))
+        });
+        let contacts = self.contacts.iter().flatten();
+
+        let content: Element<'_, ContactsMessage> = Container::new(
+            Column::new()
+                .max_width(600)
+                .push(
+                    row![
+                        Column::new().width(120).push(text("Serial port:").size(15)),
+                        port_combo_box,
+                    ]
+                    .push_maybe(self.port_gone.then(|| text("Unplugged").size(15)))
+                    .spacing(10)
+                    .padding(20),
+                )
+                .push(row![Column::new()
+                    .width(600)
+                    .align_x(Alignment::Center)
+                    .push(text(&self.status_text).size(20)),])
+                .push(header)
+                .push(
+                    scrollable(
+                        contacts
+                            .zip(&self.callsigns)
+                            .enumerate()
+                            .fold(Column::new().spacing(5), |column, (index, (c, s))| {
+                                column.push(self.contact_row(index, c, s))
+                            })
+                            .push(
+                                Button::new(Text::new("Add contact").size(13)).on_press_maybe(
+                                    (!self.is_busy()).then_some(ContactsMessage::AddContact),
+                                ),
+                            ),
+                    )
+                    .height(250),
+                )
+                .push(self.issues.iter().fold(Column::new(), |column, issue| {
+                    column.push(text(issue).style(text::danger).size(15))
+                }))
+                .push(self.warnings.iter().fold(Column::new(), |column, warning| {
+                    column.push(text(warning).size(13))
+                }))
+                .push(row![progress_bar(0.0..=100.0, self.progress),].padding(20))
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .push(
+                            Button::new(Text::new("Read").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    self.port_ready().then_some(ContactsMessage::ReadPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Import").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (!self.is_busy()).then_some(ContactsMessage::ImportPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Export").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    self.contacts
+                                        .is_some()
+                                        .then_some(ContactsMessage::ExportPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Write").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (self.port_ready()
+                                        && self.contacts.is_some()
+                                        && self.issues.is_empty())
+                                    .then_some(ContactsMessage::WritePressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Cancel").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (self.read_job.as_ref().is_some_and(|j| j.can_cancel())
+                                        || self.write_job.as_ref().is_some_and(|j| j.can_cancel()))
+                                    .then_some(ContactsMessage::CancelPressed),
+                                ),
+                        ),
+                ),
+        )
+        .align_x(Horizontal::Center)
+        .align_y(Vertical::Center)
+        .into();
+
+        content.map(Message::Contacts)
+    }
+}
@@ -0,0 +1,303 @@
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+use super::fmp::{self, MemInfo};
+use super::manifest::{BackupManifest, ManifestError};
+use super::{
+    cat, CancelToken, JobError, JobEvent, JobHandle, LinkError, Phase, Progress, RadioHW, RadioLink,
+};
+
+/// Events reported by a [`BackupJob`]
+pub type BackupEvent = JobEvent<Vec<PathBuf>, BackupError>;
+
+/// Events reported by a [`RestoreJob`]
+pub type RestoreEvent = JobEvent<RadioHW, BackupError>;
+
+#[derive(Debug)]
+pub enum BackupError {
+    UnsupportedRadio(String),
+    ImageSizeMismatch {
+        radio: RadioHW,
+        size: usize,
+    },
+    /// The radio has no memory of the name recorded in the manifest
+    MissingMemory {
+        radio: RadioHW,
+        memory: String,
+    },
+    /// The image was taken from a different radio model
+    RadioMismatch {
+        image: RadioHW,
+        radio: RadioHW,
+    },
+    /// The image does not match the checksum of its manifest
+    Corrupted,
+    Manifest(ManifestError),
+    Link(LinkError),
+    Io(std::io::Error),
+    Cancelled,
+}
+
+impl std::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupError::UnsupportedRadio(info) => write!(f, "Unsupported radio: {info}"),
+            BackupError::ImageSizeMismatch { radio, size } => write!(
+                f,
+                "Backup image size ({size} bytes) does not match any memory of {radio}"
+            ),
+            BackupError::MissingMemory { radio, memory } => {
+                write!(
+                    f,
+                    "Backup image is of a memory {radio} does not have: {memory}"
+                )
+            }
+            BackupError::RadioMismatch { image, radio } => write!(
+                f,
+                "Backup image was taken from a {image}, cannot restore it on a {radio}"
+            ),
+            BackupError::Corrupted => write!(f, "Backup image does not match its checksum"),
+            BackupError::Manifest(e) => write!(f, "{e}"),
+            BackupError::Link(e) => write!(f, "{e}"),
+            BackupError::Io(e) => write!(f, "{e}"),
+            BackupError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl JobError for BackupError {
+    fn is_cancelled(&self) -> bool {
+        matches!(self, BackupError::Cancelled)
+    }
+}
+
+impl From<std::io::Error> for BackupError {
+    fn from(e: std::io::Error) -> Self {
+        BackupError::Io(e)
+    }
+}
+
+impl From<ManifestError> for BackupError {
+    fn from(e: ManifestError) -> Self {
+        BackupError::Manifest(e)
+    }
+}
+
+impl From<LinkError> for BackupError {
+    fn from(e: LinkError) -> Self {
+        match e {
+            LinkError::Cancelled => BackupError::Cancelled,
+            e => BackupError::Link(e),
+        }
+    }
+}
+
+/// Check that a backup image can be written back to the connected radio,
+/// returning the radio model and the index of the memory to write
+///
+/// The memory is the one of name `memory`, as recorded in the manifest of
+/// the image. Images without a manifest go to the first memory of their
+/// size.
+pub fn check_restore_image(
+    image: &[u8],
+    memory: Option<&str>,
+    info: &str,
+    memories: &[MemInfo],
+) -> Result<(RadioHW, usize), BackupError> {
+    let radio =
+        RadioHW::from_info(info).ok_or(BackupError::UnsupportedRadio(String::from(info)))?;
+    let index = match memory {
+        Some(name) => memories
+            .iter()
+            .position(|m| m.name == name)
+            .ok_or_else(|| BackupError::MissingMemory {
+                radio,
+                memory: String::from(name),
+            })?,
+        None => memories
+            .iter()
+            .position(|m| m.size == image.len())
+            .unwrap_or(memories.len()),
+    };
+    if memories.get(index).map(|m| m.size) != Some(image.len()) {
+        return Err(BackupError::ImageSizeMismatch {
+            radio,
+            size: image.len(),
+        });
+    }
+    Ok((radio, index))
+}
+
+// Name of the file holding the backup of a memory area
+fn backup_file_name(info: &str, memory: &str, now: time::OffsetDateTime) -> String {
+    let sanitize = |s: &str| -> String {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+    format!(
+        "{}_{}_{:04}{:02}{:02}-{:02}{:02}{:02}.bin",
+        sanitize(info),
+        sanitize(memory),
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    )
+}
+
+/// Save the memory of a radio running OpenRTX into a folder
+#[derive(Clone, Debug)]
+pub struct BackupJob {
+    pub port: String,
+    pub dest: PathBuf,
+}
+
+impl BackupJob {
+    pub fn new(port: String, dest: impl Into<PathBuf>) -> Self {
+        BackupJob {
+            port,
+            dest: dest.into(),
+        }
+    }
+
+    /// Run the job on the calling thread, returning the saved images
+    ///
+    /// Each image is saved along with its [`BackupManifest`].
+    pub fn run(
+        self,
+        events: &Sender<BackupEvent>,
+        cancel: &CancelToken,
+    ) -> Result<Vec<PathBuf>, BackupError> {
+        if !self.dest.is_dir() {
+            return Err(BackupError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} is not a folder", self.dest.display()),
+            )));
+        }
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut link = RadioLink::open(&self.port)?;
+        let info = cat::info(&mut link)?;
+        // Firmwares without the FW identifier leave the version out
+        let version = match cat::version(&mut link) {
+            Ok(version) => Some(version),
+            Err(LinkError::Status(cat::ENOTSUP)) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let memories = fmp::meminfo(&mut link)?;
+
+        _ = events.send(JobEvent::Phase(Phase::Reading));
+        let now = time::OffsetDateTime::now_local().unwrap_or(time::OffsetDateTime::now_utc());
+        let total = memories.iter().map(|m| m.size).sum();
+        let mut done = 0;
+        let mut files = vec![];
+        for (i, memory) in memories.iter().enumerate() {
+            let data = fmp::dump(&mut link, i, cancel, |p| {
+                _ = events.send(JobEvent::Progress(Progress {
+                    transferred: done + p.transferred,
+                    total,
+                }));
+            })?;
+            let path = self.dest.join(backup_file_name(&info, &memory.name, now));
+            std::fs::write(&path, &data)?;
+            BackupManifest::new(
+                &info,
+                version.as_deref(),
+                &self.port,
+                &memory.name,
+                now,
+                &data,
+            )
+            .write(&path)?;
+            files.push(path);
+            done += memory.size;
+        }
+        Ok(files)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<Vec<PathBuf>, BackupError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
+
+/// Write a backup image back to a radio running OpenRTX
+#[derive(Clone, Debug)]
+pub struct RestoreJob {
+    pub port: String,
+    pub image: PathBuf,
+}
+
+impl RestoreJob {
+    pub fn new(port: String, image: impl Into<PathBuf>) -> Self {
+        RestoreJob {
+            port,
+            image: image.into(),
+        }
+    }
+
+    /// Run the job on the calling thread, returning the restored radio model
+    ///
+    /// Cancellation is only possible until the radio memory starts being
+    /// written.
+    pub fn run(
+        self,
+        events: &Sender<RestoreEvent>,
+        cancel: &CancelToken,
+    ) -> Result<RadioHW, BackupError> {
+        let image = std::fs::read(&self.image)?;
+        let manifest = BackupManifest::read(&self.image)?;
+        if manifest.as_ref().is_some_and(|m| !m.matches(&image)) {
+            return Err(BackupError::Corrupted);
+        }
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut link = RadioLink::open(&self.port)?;
+
+        _ = events.send(JobEvent::Phase(Phase::Checking));
+        let info = cat::info(&mut link)?;
+        let memories = fmp::meminfo(&mut link)?;
+        // Images without a manifest predate them and can only be checked by size
+        if let (Some(image_radio), Some(radio)) = (
+            manifest.as_ref().and_then(|m| m.radio),
+            RadioHW::from_info(&info),
+        ) {
+            if image_radio != radio {
+                return Err(BackupError::RadioMismatch {
+                    image: image_radio,
+                    radio,
+                });
+            }
+        }
+        let (radio, memory) = check_restore_image(
+            &image,
+            manifest.as_ref().map(|m| m.memory.as_str()),
+            &info,
+            &memories,
+        )?;
+
+        _ = events.send(JobEvent::Phase(Phase::Writing));
+        cancel
+            .uninterruptible(|| {
+                fmp::flash(&mut link, memory, &image, |p| {
+                    _ = events.send(JobEvent::Progress(p));
+                })
+            })
+            .ok_or(BackupError::Cancelled)??;
+        Ok(radio)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<RadioHW, BackupError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
@@ -0,0 +1,739 @@
+//! Factory RF calibration of the MD-3x0 family
+//!
+//! OpenRTX exposes the calibration of TYT radios as a memory area named
+//! [`MEMORY_NAME`]. It is made of one block of 0x200 bytes per band: the
+//! calibration tables are rows of 16 bytes holding one value per calibration
+//! point, followed by the RX and TX frequencies of the points, stored as
+//! little-endian BCD in units of 10 Hz. The MD-3x0 has a single band with 9
+//! points, the MD-UV3x0 a UHF band with 9 points followed by a VHF band
+//! with 5 points.
+//!
+//! The calibration is saved as a TOML file holding the raw memory, so that
+//! bytes not decoded here are preserved, and the decoded tables. Tables
+//! edited in the file take precedence over the raw memory.
+//!
+//! The radios report no serial number over rtxlink: the raw memory, as read
+//! from the radio, also identifies the unit a calibration belongs to.
+
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+
+use time::OffsetDateTime;
+use toml::{Table, Value};
+
+use super::fmp;
+use super::manifest::timestamp;
+use super::{
+    cat, CancelToken, JobError, JobEvent, JobHandle, LinkError, Phase, RadioHW, RadioLink,
+};
+
+/// Name of the memory area holding the calibration
+pub const MEMORY_NAME: &str = "Calibration";
+
+/// Events reported by a [`CalibrationReadJob`]
+pub type CalibrationReadEvent = JobEvent<Calibration, CalibrationError>;
+
+/// Events reported by a [`CalibrationWriteJob`]
+pub type CalibrationWriteEvent = JobEvent<RadioHW, CalibrationError>;
+
+const BAND_SIZE: usize = 0x200;
+const RX_FREQ_OFFSET: usize = 0x180;
+const TX_FREQ_OFFSET: usize = 0x1a4;
+
+const UHF: RangeInclusive<u32> = 400_000_000..=480_000_000;
+const VHF: RangeInclusive<u32> = 136_000_000..=174_000_000;
+
+// Calibration table, one value per point
+struct TableLayout {
+    name: &'static str,
+    offset: usize,
+    range: RangeInclusive<u8>,
+}
+
+// Sane ranges of the tables, wide enough for the spread between units. Values
+// outside of them are only found in corrupted calibrations.
+const TABLES: &[TableLayout] = &[
+    // DAC setting of the power amplifier, 0xff is the value of erased flash
+    TableLayout {
+        name: "TX high power",
+        offset: 0x10,
+        range: 0x20..=0xfa,
+    },
+    TableLayout {
+        name: "TX low power",
+        offset: 0x20,
+        range: 0x10..=0xfa,
+    },
+    // Tuning voltage of the receiver front end, the ends of the DAC range
+    // detune it
+    TableLayout {
+        name: "RX sensitivity",
+        offset: 0x30,
+        range: 0x08..=0xf8,
+    },
+    // RSSI levels, a threshold of 0 keeps the squelch open
+    TableLayout {
+        name: "Open squelch 9",
+        offset: 0x40,
+        range: 0x01..=0xf0,
+    },
+    TableLayout {
+        name: "Close squelch 9",
+        offset: 0x50,
+        range: 0x01..=0xf0,
+    },
+    TableLayout {
+        name: "Open squelch 1",
+        offset: 0x60,
+        range: 0x01..=0xf0,
+    },
+    TableLayout {
+        name: "Close squelch 1",
+        offset: 0x70,
+        range: 0x01..=0xf0,
+    },
+    // Deviation of the sub-audio tones, a value of 0 mutes them and the
+    // highest ones overdeviate
+    TableLayout {
+        name: "CTCSS 67 Hz",
+        offset: 0x80,
+        range: 0x01..=0xc0,
+    },
+    TableLayout {
+        name: "CTCSS 151 Hz",
+        offset: 0x90,
+        range: 0x01..=0xc0,
+    },
+    TableLayout {
+        name: "CTCSS 254 Hz",
+        offset: 0xa0,
+        range: 0x01..=0xc0,
+    },
+    TableLayout {
+        name: "DCS modulation 2",
+        offset: 0xb0,
+        range: 0x01..=0xc0,
+    },
+    TableLayout {
+        name: "DCS modulation 1",
+        offset: 0xc0,
+        range: 0x01..=0xc0,
+    },
+    // Bias of the modulator, the ends of the DAC range distort the audio
+    TableLayout {
+        name: "Modulation point",
+        offset: 0xd0,
+        range: 0x10..=0xf0,
+    },
+];
+
+struct BandLayout {
+    name: &'static str,
+    points: usize,
+    /// Frequency ranges the points of the band can be in
+    frequencies: &'static [RangeInclusive<u32>],
+}
+
+fn bands(radio: RadioHW) -> Option<&'static [BandLayout]> {
+    match radio {
+        // The MD-3x0 comes in a UHF and a VHF version
+        RadioHW::Md3x0 => Some(&[BandLayout {
+            name: "RF",
+            points: 9,
+            frequencies: &[UHF, VHF],
+        }]),
+        RadioHW::Mduv3x0 => Some(&[
+            BandLayout {
+                name: "UHF",
+                points: 9,
+                frequencies: &[UHF],
+            },
+            BandLayout {
+                name: "VHF",
+                points: 5,
+                frequencies: &[VHF],
+            },
+        ]),
+        _ => None,
+    }
+}
+
+/// Check whether the calibration of a radio can be decoded
+pub fn is_supported(radio: RadioHW) -> bool {
+    bands(radio).is_some()
+}
+
+#[derive(Debug)]
+pub enum CalibrationError {
+    UnsupportedRadio(String),
+    /// The radio does not expose its calibration memory
+    NoCalibration,
+    SizeMismatch {
+        radio: RadioHW,
+        size: usize,
+    },
+    /// The calibration was read from a different radio model
+    RadioMismatch {
+        calibration: RadioHW,
+        radio: RadioHW,
+    },
+    /// The radio holds another calibration than the one the file was read
+    /// from, it is another unit or its calibration changed since
+    OtherUnit,
+    /// The calibration has values outside of their sane range
+    OutOfRange(Vec<CalibrationIssue>),
+    Parse(String),
+    Link(LinkError),
+    Io(std::io::Error),
+    Cancelled,
+}
+
+impl std::fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CalibrationError::UnsupportedRadio(info) => {
+                write!(f, "Calibration of {info} is not supported")
+            }
+            CalibrationError::NoCalibration => {
+                write!(f, "Radio firmware does not give access to the calibration")
+            }
+            CalibrationError::SizeMismatch { radio, size } => write!(
+                f,
+                "Calibration size ({size} bytes) does not match the {radio}"
+            ),
+            CalibrationError::RadioMismatch { calibration, radio } => write!(
+                f,
+                "Calibration was read from a {calibration}, cannot write it on a {radio}"
+            ),
+            CalibrationError::OtherUnit => write!(
+                f,
+                "Calibration was not read from this radio, or the radio was \
+                 calibrated since"
+            ),
+            CalibrationError::OutOfRange(issues) => {
+                write!(f, "Calibration has {} invalid values", issues.len())?;
+                match issues.first() {
+                    Some(issue) => write!(f, ", e.g. {issue}"),
+                    None => Ok(()),
+                }
+            }
+            CalibrationError::Parse(e) => write!(f, "Invalid calibration file: {e}"),
+            CalibrationError::Link(e) => write!(f, "{e}"),
+            CalibrationError::Io(e) => write!(f, "{e}"),
+            CalibrationError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for CalibrationError {}
+
+impl JobError for CalibrationError {
+    fn is_cancelled(&self) -> bool {
+        matches!(self, CalibrationError::Cancelled)
+    }
+}
+
+impl From<std::io::Error> for CalibrationError {
+    fn from(e: std::io::Error) -> Self {
+        CalibrationError::Io(e)
+    }
+}
+
+impl From<LinkError> for CalibrationError {
+    fn from(e: LinkError) -> Self {
+        match e {
+            LinkError::Cancelled => CalibrationError::Cancelled,
+            e => CalibrationError::Link(e),
+        }
+    }
+}
+
+/// Calibration value which is not safe to write to a radio
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CalibrationIssue {
+    OutOfRange {
+        band: &'static str,
+        table: &'static str,
+        point: usize,
+        value: u8,
+    },
+    Frequency {
+        band: &'static str,
+        point: usize,
+        frequency: u32,
+    },
+    /// The points of a band are not sorted by frequency
+    FrequencyOrder { band: &'static str },
+    /// Low power is set above high power
+    PowerOrder { band: &'static str, point: usize },
+}
+
+impl std::fmt::Display for CalibrationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CalibrationIssue::OutOfRange {
+                band,
+                table,
+                point,
+                value,
+            } => write!(f, "{band} {table} point {point} is out of range ({value})"),
+            CalibrationIssue::Frequency {
+                band,
+                point,
+                frequency,
+            } => write!(
+                f,
+                "{band} point {point} has an invalid frequency ({frequency} Hz)"
+            ),
+            CalibrationIssue::FrequencyOrder { band } => {
+                write!(f, "{band} points are not sorted by frequency")
+            }
+            CalibrationIssue::PowerOrder { band, point } => {
+                write!(f, "{band} point {point} has a low power above high power")
+            }
+        }
+    }
+}
+
+/// Values of a calibration table, one per point
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CalibrationTable {
+    pub name: &'static str,
+    pub values: Vec<u8>,
+}
+
+impl CalibrationTable {
+    /// Name of the table in calibration files
+    pub fn key(&self) -> String {
+        self.name.to_ascii_lowercase().replace(' ', "_")
+    }
+}
+
+/// Calibration points of a band
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CalibrationBand {
+    pub name: &'static str,
+    /// Frequencies of the points, in Hz
+    pub rx_frequencies: Vec<u32>,
+    pub tx_frequencies: Vec<u32>,
+    pub tables: Vec<CalibrationTable>,
+}
+
+/// Decoded calibration memory of a radio
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Calibration {
+    pub radio: RadioHW,
+    pub bands: Vec<CalibrationBand>,
+    // Memory the calibration was decoded from
+    raw: Vec<u8>,
+}
+
+pub(crate) fn bcd_decode(data: &[u8]) -> u32 {
+    let bcd = u32::from_le_bytes(data.try_into().unwrap_or_default());
+    let mut value = 0;
+    for i in (0..8).rev() {
+        let digit = (bcd >> (i * 4)) & 0xf;
+        if digit > 9 {
+            return 0;
+        }
+        value = value * 10 + digit;
+    }
+    value * 10
+}
+
+fn bcd_encode(frequency: u32) -> [u8; 4] {
+    let mut value = frequency / 10;
+    let mut bcd = 0;
+    for i in 0..8 {
+        bcd |= (value % 10) << (i * 4);
+        value /= 10;
+    }
+    bcd.to_le_bytes()
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl Calibration {
+    /// Decode the calibration memory of a radio
+    pub fn decode(radio: RadioHW, data: &[u8]) -> Result<Calibration, CalibrationError> {
+        let layout = bands(radio).ok_or(CalibrationError::UnsupportedRadio(String::from(radio)))?;
+        if data.len() != layout.len() * BAND_SIZE {
+            return Err(CalibrationError::SizeMismatch {
+                radio,
+                size: data.len(),
+            });
+        }
+        let bands = layout
+            .iter()
+            .zip(data.chunks(BAND_SIZE))
+            .map(|(band, data)| {
+                let frequencies = |offset: usize| {
+                    data[offset..offset + band.points * 4]
+                        .chunks(4)
+                        .map(bcd_decode)
+                        .collect()
+                };
+                CalibrationBand {
+                    name: band.name,
+                    rx_frequencies: frequencies(RX_FREQ_OFFSET),
+                    tx_frequencies: frequencies(TX_FREQ_OFFSET),
+                    tables: TABLES
+                        .iter()
+                        .map(|t| CalibrationTable {
+                            name: t.name,
+                            values: data[t.offset..t.offset + band.points].to_vec(),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        Ok(Calibration {
+            radio,
+            bands,
+            raw: data.to_vec(),
+        })
+    }
+
+    /// Whether the calibration was read from a radio holding `data` as its
+    /// calibration memory
+    pub fn is_from(&self, data: &[u8]) -> bool {
+        self.raw == data
+    }
+
+    /// Calibration memory, with the values of the tables
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = self.raw.clone();
+        for (band, block) in self.bands.iter().zip(data.chunks_mut(BAND_SIZE)) {
+            for (i, f) in band.rx_frequencies.iter().enumerate() {
+                let offset = RX_FREQ_OFFSET + i * 4;
+                block[offset..offset + 4].copy_from_slice(&bcd_encode(*f));
+            }
+            for (i, f) in band.tx_frequencies.iter().enumerate() {
+                let offset = TX_FREQ_OFFSET + i * 4;
+                block[offset..offset + 4].copy_from_slice(&bcd_encode(*f));
+            }
+            for (table, layout) in band.tables.iter().zip(TABLES) {
+                block[layout.offset..layout.offset + table.values.len()]
+                    .copy_from_slice(&table.values);
+            }
+        }
+        data
+    }
+
+    /// Compare the calibration against the sane range of its values
+    pub fn check(&self) -> Vec<CalibrationIssue> {
+        let mut issues = vec![];
+        let layout = bands(self.radio).unwrap_or_default();
+        for (band, layout) in self.bands.iter().zip(layout) {
+            for frequencies in [&band.rx_frequencies, &band.tx_frequencies] {
+                for (point, &frequency) in frequencies.iter().enumerate() {
+                    if !layout.frequencies.iter().any(|r| r.contains(&frequency)) {
+                        issues.push(CalibrationIssue::Frequency {
+                            band: band.name,
+                            point,
+                            frequency,
+                        });
+                    }
+                }
+                if !frequencies.is_sorted() {
+                    issues.push(CalibrationIssue::FrequencyOrder { band: band.name });
+                }
+            }
+            for (table, layout) in band.tables.iter().zip(TABLES) {
+                for (point, &value) in table.values.iter().enumerate() {
+                    if !layout.range.contains(&value) {
+                        issues.push(CalibrationIssue::OutOfRange {
+                            band: band.name,
+                            table: table.name,
+                            point,
+                            value,
+                        });
+                    }
+                }
+            }
+            // Power tables come first in the layout
+            let (high, low) = (&band.tables[0].values, &band.tables[1].values);
+            for (point, (high, low)) in high.iter().zip(low).enumerate() {
+                if low > high {
+                    issues.push(CalibrationIssue::PowerOrder {
+                        band: band.name,
+                        point,
+                    });
+                }
+            }
+        }
+        issues
+    }
+
+    /// Save the calibration as a TOML file
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let mut bands = Table::new();
+        for band in &self.bands {
+            let list = |values: &mut dyn Iterator<Item = i64>| {
+                Value::Array(values.map(Value::Integer).collect())
+            };
+            let mut table = Table::new();
+            table.insert(
+                "rx_frequencies".into(),
+                list(&mut band.rx_frequencies.iter().map(|&f| f.into())),
+            );
+            table.insert(
+                "tx_frequencies".into(),
+                list(&mut band.tx_frequencies.iter().map(|&f| f.into())),
+            );
+            for t in &band.tables {
+                table.insert(t.key(), list(&mut t.values.iter().map(|&v| v.into())));
+            }
+            bands.insert(band.name.into(), Value::Table(table));
+        }
+        let mut file = Table::new();
+        file.insert("radio".into(), Value::String(self.radio.into()));
+        file.insert(
+            "timestamp".into(),
+            Value::String(timestamp(OffsetDateTime::now_utc())),
+        );
+        // The memory is kept as read, to recognize the radio it belongs to
+        file.insert("memory".into(), Value::String(hex(&self.raw)));
+        file.insert("bands".into(), Value::Table(bands));
+        std::fs::write(path, file.to_string())
+    }
+
+    /// Read a calibration file
+    pub fn read(path: &Path) -> Result<Calibration, CalibrationError> {
+        let text = std::fs::read_to_string(path)?;
+        let file: Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| CalibrationError::Parse(e.message().to_string()))?;
+        let string = |key: &str| {
+            file.get(key)
+                .and_then(Value::as_str)
+                .ok_or(CalibrationError::Parse(format!("no {key}")))
+        };
+        let radio = RadioHW::from_info(string("radio")?)
+            .ok_or(CalibrationError::Parse(String::from("unknown radio")))?;
+        let raw = unhex(string("memory")?)
+            .ok_or(CalibrationError::Parse(String::from("invalid memory")))?;
+        let mut calibration = Calibration::decode(radio, &raw)?;
+
+        let bands = file.get("bands").and_then(Value::as_table);
+        for band in &mut calibration.bands {
+            let Some(values) = bands
+                .and_then(|b| b.get(band.name))
+                .and_then(Value::as_table)
+            else {
+                continue;
+            };
+            let points = band.rx_frequencies.len();
+            let list = |key: &str| -> Result<Option<Vec<i64>>, CalibrationError> {
+                let Some(list) = values.get(key) else {
+                    return Ok(None);
+                };
+                let list = list
+                    .as_array()
+                    .and_then(|l| l.iter().map(Value::as_integer).collect::<Option<Vec<_>>>())
+                    .filter(|l| l.len() == points)
+                    .ok_or(CalibrationError::Parse(format!(
+                        "{} {key} must be a list of {points} integers",
+                        band.name
+                    )))?;
+                Ok(Some(list))
+            };
+            let invalid = |key: &str| CalibrationError::Parse(format!("{} {key}", band.name));
+            if let Some(list) = list("rx_frequencies")? {
+                band.rx_frequencies = list
+                    .into_iter()
+                    .map(|f| u32::try_from(f).map_err(|_| invalid("rx_frequencies")))
+                    .collect::<Result<_, _>>()?;
+            }
+            if let Some(list) = list("tx_frequencies")? {
+                band.tx_frequencies = list
+                    .into_iter()
+                    .map(|f| u32::try_from(f).map_err(|_| invalid("tx_frequencies")))
+                    .collect::<Result<_, _>>()?;
+            }
+            for table in &mut band.tables {
+                let key = table.key();
+                if let Some(list) = list(&key)? {
+                    table.values = list
+                        .into_iter()
+                        .map(|v| u8::try_from(v).map_err(|_| invalid(&key)))
+                        .collect::<Result<_, _>>()?;
+                }
+            }
+        }
+        Ok(calibration)
+    }
+}
+
+impl std::fmt::Display for Calibration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mhz = |list: &[u32]| -> String {
+            list.iter()
+                .map(|f| format!("{:>8.3}", *f as f64 / 1e6))
+                .collect()
+        };
+        writeln!(f, "{} calibration", self.radio)?;
+        for band in &self.bands {
+            writeln!(f, "\n{} band", band.name)?;
+            writeln!(
+                f,
+                "{:<18}{}",
+                "RX frequency (MHz)",
+                mhz(&band.rx_frequencies)
+            )?;
+            writeln!(
+                f,
+                "{:<18}{}",
+                "TX frequency (MHz)",
+                mhz(&band.tx_frequencies)
+            )?;
+            for table in &band.tables {
+                let values: String = table.values.iter().map(|v| format!("{v:>8}")).collect();
+                writeln!(f, "{:<18}{values}", table.name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Index of the calibration memory area
+fn find_memory(memories: &[fmp::MemInfo]) -> Result<usize, CalibrationError> {
+    memories
+        .iter()
+        .position(|m| m.name.eq_ignore_ascii_case(MEMORY_NAME))
+        .ok_or(CalibrationError::NoCalibration)
+}
+
+/// Read the calibration of a radio running OpenRTX
+#[derive(Clone, Debug)]
+pub struct CalibrationReadJob {
+    pub port: String,
+}
+
+impl CalibrationReadJob {
+    pub fn new(port: String) -> Self {
+        CalibrationReadJob { port }
+    }
+
+    /// Run the job on the calling thread
+    pub fn run(
+        self,
+        events: &Sender<CalibrationReadEvent>,
+        cancel: &CancelToken,
+    ) -> Result<Calibration, CalibrationError> {
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut link = RadioLink::open(&self.port)?;
+        let info = cat::info(&mut link)?;
+        let radio = RadioHW::from_info(&info)
+            .filter(|r| is_supported(*r))
+            .ok_or(CalibrationError::UnsupportedRadio(info))?;
+        let memory = find_memory(&fmp::meminfo(&mut link)?)?;
+
+        _ = events.send(JobEvent::Phase(Phase::Reading));
+        let data = fmp::dump(&mut link, memory, cancel, |p| {
+            _ = events.send(JobEvent::Progress(p));
+        })?;
+        Calibration::decode(radio, &data)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<Calibration, CalibrationError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
+
+/// Write a calibration back to a radio running OpenRTX
+///
+/// The calibration is only written if all its values are in their sane
+/// range and it was read from the same radio model. Unless allowed, it must
+/// also have been read from the same unit, which still holds it.
+#[derive(Clone, Debug)]
+pub struct CalibrationWriteJob {
+    pub port: String,
+    pub calibration: Calibration,
+    pub other_unit: bool,
+}
+
+impl CalibrationWriteJob {
+    pub fn new(port: String, calibration: Calibration) -> Self {
+        CalibrationWriteJob {
+            port,
+            calibration,
+            other_unit: false,
+        }
+    }
+
+    /// Write the calibration even if it was read from another unit
+    pub fn other_unit(mut self, other_unit: bool) -> Self {
+        self.other_unit = other_unit;
+        self
+    }
+
+    /// Run the job on the calling thread, returning the calibrated radio
+    /// model
+    pub fn run(
+        self,
+        events: &Sender<CalibrationWriteEvent>,
+        cancel: &CancelToken,
+    ) -> Result<RadioHW, CalibrationError> {
+        let issues = self.calibration.check();
+        if !issues.is_empty() {
+            return Err(CalibrationError::OutOfRange(issues));
+        }
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut link = RadioLink::open(&self.port)?;
+
+        _ = events.send(JobEvent::Phase(Phase::Checking));
+        let info = cat::info(&mut link)?;
+        let radio = RadioHW::from_info(&info).ok_or(CalibrationError::UnsupportedRadio(info))?;
+        if radio != self.calibration.radio {
+            return Err(CalibrationError::RadioMismatch {
+                calibration: self.calibration.radio,
+                radio,
+            });
+        }
+        let memories = fmp::meminfo(&mut link)?;
+        let memory = find_memory(&memories)?;
+        let data = self.calibration.encode();
+        if memories[memory].size != data.len() {
+            return Err(CalibrationError::SizeMismatch {
+                radio,
+                size: data.len(),
+            });
+        }
+        if !self.other_unit {
+            let current = fmp::dump(&mut link, memory, cancel, |_| ())?;
+            if !self.calibration.is_from(&current) {
+                return Err(CalibrationError::OtherUnit);
+            }
+        }
+
+        _ = events.send(JobEvent::Phase(Phase::Writing));
+        cancel
+            .uninterruptible(|| {
+                fmp::flash(&mut link, memory, &data, |p| {
+                    _ = events.send(JobEvent::Progress(p));
+                })
+            })
+            .ok_or(CalibrationError::Cancelled)??;
+        Ok(radio)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<RadioHW, CalibrationError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
@@ -0,0 +1,198 @@
+//! rtxlink CAT (Computer Aided Transceiver) protocol
+//!
+//! A CAT request is made of an opcode followed by a two characters command
+//! identifier, plus the value to set for SET requests. The radio answers GET
+//! requests with a DATA frame and SET requests with an ACK frame carrying an
+//! errno-like status, zero on success.
+
+use rtxlink::link::Protocol;
+
+use super::codeplug::OpMode;
+use super::link::{LinkError, RadioLink};
+
+/// CAT frame opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Get = 0x47,
+    Set = 0x53,
+    Data = 0x44,
+    Ack = 0x41,
+}
+
+impl Opcode {
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        match byte {
+            0x47 => Some(Opcode::Get),
+            0x53 => Some(Opcode::Set),
+            0x44 => Some(Opcode::Data),
+            0x41 => Some(Opcode::Ack),
+            _ => None,
+        }
+    }
+}
+
+/// CAT command identifiers
+///
+/// This is the whole CAT surface used by the companion. Firmwares which do
+/// not implement an identifier answer with [`ENOTSUP`]: values read with
+/// [`get_optional`] are then left empty, and commands setting them fail with
+/// that status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CatId {
+    /// Radio hardware name
+    Info,
+    /// Firmware version, including the hash of the build
+    Version,
+    /// Receive frequency in Hz, u32 little endian
+    RxFreq,
+    /// Transmit frequency in Hz, u32 little endian
+    TxFreq,
+    /// Hardware revision of the radio
+    HwVersion,
+    /// Callsign configured in the radio settings
+    Callsign,
+    /// Operating mode, one byte
+    OpMode,
+    /// Battery voltage in mV, u16 little endian
+    Battery,
+    /// Push to talk state, one byte, non-zero while transmitting
+    Ptt,
+    /// Received signal strength in dBm, i16 little endian
+    Rssi,
+    /// Squelch level, one byte from 0 (open) to 15
+    Squelch,
+    /// Squelch state, one byte, non-zero while a signal opens the squelch
+    SquelchOpen,
+    /// Volume, one byte from 0 to 255
+    Volume,
+    /// Monitor, one byte, non-zero to keep the squelch open
+    Monitor,
+    /// GPS receiver, one byte, non-zero when enabled
+    Gps,
+}
+
+impl CatId {
+    pub fn code(&self) -> [u8; 2] {
+        *match self {
+            CatId::Info => b"IN",
+            CatId::Version => b"FW",
+            CatId::RxFreq => b"RF",
+            CatId::TxFreq => b"TF",
+            CatId::HwVersion => b"HV",
+            CatId::Callsign => b"CS",
+            CatId::OpMode => b"OM",
+            CatId::Battery => b"BV",
+            CatId::Ptt => b"PT",
+            CatId::Rssi => b"RS",
+            CatId::Squelch => b"SQ",
+            CatId::SquelchOpen => b"SO",
+            CatId::Volume => b"VO",
+            CatId::Monitor => b"MO",
+            CatId::Gps => b"GP",
+        }
+    }
+}
+
+/// ACK status for an unknown command identifier
+pub const ENOTSUP: u8 = 95;
+
+/// Read the value of a CAT command
+pub fn get(link: &mut RadioLink, id: CatId) -> Result<Vec<u8>, LinkError> {
+    let mut request = vec![Opcode::Get as u8];
+    request.extend_from_slice(&id.code());
+    let reply = link.request(Protocol::CAT, request)?;
+    match reply.split_first() {
+        Some((&op, data)) if op == Opcode::Data as u8 => Ok(data.to_vec()),
+        Some((&op, [status])) if op == Opcode::Ack as u8 => Err(LinkError::Status(*status)),
+        _ => Err(LinkError::Protocol(format!("{reply:02x?}"))),
+    }
+}
+
+/// Set the value of a CAT command
+pub fn set(link: &mut RadioLink, id: CatId, value: &[u8]) -> Result<(), LinkError> {
+    let mut request = vec![Opcode::Set as u8];
+    request.extend_from_slice(&id.code());
+    request.extend_from_slice(value);
+    let reply = link.request(Protocol::CAT, request)?;
+    match reply.as_slice() {
+        [op, 0] if *op == Opcode::Ack as u8 => Ok(()),
+        [op, status] if *op == Opcode::Ack as u8 => Err(LinkError::Status(*status)),
+        _ => Err(LinkError::Protocol(format!("{reply:02x?}"))),
+    }
+}
+
+/// Read the value of a CAT command, None if the radio does not support it
+pub fn get_optional(link: &mut RadioLink, id: CatId) -> Result<Option<Vec<u8>>, LinkError> {
+    match get(link, id) {
+        Ok(value) => Ok(Some(value)),
+        Err(LinkError::Status(ENOTSUP)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Read a string value, which may be NUL terminated
+fn get_string(link: &mut RadioLink, id: CatId) -> Result<String, LinkError> {
+    let value = get(link, id)?;
+    Ok(String::from_utf8_lossy(&value)
+        .trim_end_matches('\0')
+        .to_string())
+}
+
+/// Hardware name of the radio
+pub fn info(link: &mut RadioLink) -> Result<String, LinkError> {
+    get_string(link, CatId::Info)
+}
+
+/// Version of the firmware running on the radio
+pub fn version(link: &mut RadioLink) -> Result<String, LinkError> {
+    get_string(link, CatId::Version)
+}
+
+// Read a value of fixed size
+fn get_array<const N: usize>(link: &mut RadioLink, id: CatId) -> Result<[u8; N], LinkError> {
+    let value = get(link, id)?;
+    value
+        .as_slice()
+        .try_into()
+        .map_err(|_| LinkError::Protocol(format!("{value:02x?}")))
+}
+
+/// Receive frequency in Hz
+pub fn rx_frequency(link: &mut RadioLink) -> Result<u32, LinkError> {
+    get_array(link, CatId::RxFreq).map(u32::from_le_bytes)
+}
+
+/// Tune the radio to a simplex frequency in Hz
+pub fn set_frequency(link: &mut RadioLink, frequency: u32) -> Result<(), LinkError> {
+    set(link, CatId::RxFreq, &frequency.to_le_bytes())?;
+    set_tx_frequency(link, frequency)
+}
+
+/// Set the transmit frequency in Hz, leaving the receive frequency as is
+pub fn set_tx_frequency(link: &mut RadioLink, frequency: u32) -> Result<(), LinkError> {
+    set(link, CatId::TxFreq, &frequency.to_le_bytes())
+}
+
+/// Operating mode
+pub fn op_mode(link: &mut RadioLink) -> Result<OpMode, LinkError> {
+    get_array::<1>(link, CatId::OpMode).map(|[mode]| OpMode::from(mode))
+}
+
+pub fn set_op_mode(link: &mut RadioLink, mode: OpMode) -> Result<(), LinkError> {
+    set(link, CatId::OpMode, &[mode.into()])
+}
+
+/// Whether the radio is transmitting
+pub fn ptt(link: &mut RadioLink) -> Result<bool, LinkError> {
+    get_array::<1>(link, CatId::Ptt).map(|[ptt]| ptt != 0)
+}
+
+/// Start or stop transmitting
+pub fn set_ptt(link: &mut RadioLink, ptt: bool) -> Result<(), LinkError> {
+    set(link, CatId::Ptt, &[ptt as u8])
+}
+
+/// Received signal strength in dBm
+pub fn rssi(link: &mut RadioLink) -> Result<i16, LinkError> {
+    get_array(link, CatId::Rssi).map(i16::from_le_bytes)
+}
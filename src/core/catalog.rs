@@ -0,0 +1,220 @@
+//! Catalog of firmware releases
+//!
+//! A catalog is a JSON manifest listing the available firmware images:
+//!
+//! ```json
+//! {
+//!   "releases": [
+//!     {
+//!       "version": "v0.3.6",
+//!       "target": "MD3x0",
+//!       "url": "openrtx_md3x0_v0.3.6.bin",
+//!       "sha256": "…"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! The manifest is read from an HTTP(S) URL, a local file or a local
+//! directory holding a `manifest.json`. Image URLs relative to the manifest
+//! are resolved against its location, so a release folder can be copied
+//! as-is to an offline machine or served by any HTTP server.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use rtxflash::target::Target;
+use serde_json::Value;
+
+use super::dirs;
+use super::manifest::sha256_hex;
+use super::signature::{signature_path, SIGNATURE_EXTENSION};
+
+/// Name of the manifest when the catalog source is a directory
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+#[derive(Debug)]
+pub enum CatalogError {
+    Io(std::io::Error),
+    Http(String),
+    Parse(String),
+    HashMismatch { expected: String, actual: String },
+}
+
+impl std::fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CatalogError::Io(e) => write!(f, "{e}"),
+            CatalogError::Http(e) => write!(f, "Download failed: {e}"),
+            CatalogError::Parse(e) => write!(f, "Invalid firmware catalog: {e}"),
+            CatalogError::HashMismatch { expected, actual } => write!(
+                f,
+                "Downloaded image has SHA-256 {actual}, the catalog expects {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {}
+
+impl From<std::io::Error> for CatalogError {
+    fn from(e: std::io::Error) -> Self {
+        CatalogError::Io(e)
+    }
+}
+
+/// Firmware image listed in a catalog
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Release {
+    pub version: String,
+    /// Name of the flashing target, as displayed by rtxflash
+    pub target: String,
+    /// Location of the image, absolute or relative to the manifest
+    pub url: String,
+    /// SHA-256 of the image, 64 lowercase hex digits
+    pub sha256: String,
+}
+
+impl Release {
+    pub fn is_for(&self, target: &Target) -> bool {
+        target.to_string().eq_ignore_ascii_case(&self.target)
+    }
+
+    // Name of the image in the download cache, unique per content
+    fn cache_name(&self) -> String {
+        let name = self.url.rsplit('/').next().unwrap_or_default();
+        let hash = self.sha256.get(..16).unwrap_or(&self.sha256);
+        format!("{hash}-{name}")
+    }
+}
+
+impl std::fmt::Display for Release {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.version, self.target)
+    }
+}
+
+/// Releases read from a manifest
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Catalog {
+    /// Location of the manifest, used to resolve relative image URLs
+    pub manifest: String,
+    pub releases: Vec<Release>,
+}
+
+fn is_http(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+// Read a local file or download it
+fn fetch(location: &str) -> Result<Vec<u8>, CatalogError> {
+    if is_http(location) {
+        let response = ureq::get(location)
+            .call()
+            .map_err(|e| CatalogError::Http(e.to_string()))?;
+        let mut data = vec![];
+        response.into_reader().read_to_end(&mut data)?;
+        Ok(data)
+    } else {
+        let path = location.strip_prefix("file://").unwrap_or(location);
+        Ok(std::fs::read(path)?)
+    }
+}
+
+impl Catalog {
+    /// Read the catalog from a URL, a manifest file or a directory
+    pub fn load(source: &str) -> Result<Catalog, CatalogError> {
+        let source = source.trim();
+        let local = source.strip_prefix("file://").unwrap_or(source);
+        let manifest = if !is_http(source) && Path::new(local).is_dir() {
+            Path::new(local).join(MANIFEST_NAME).display().to_string()
+        } else {
+            String::from(source)
+        };
+        let data = fetch(&manifest)?;
+        let json = serde_json::from_slice::<Value>(&data)
+            .map_err(|e| CatalogError::Parse(e.to_string()))?;
+        let releases = json
+            .get("releases")
+            .and_then(Value::as_array)
+            .ok_or(CatalogError::Parse(String::from("no releases")))?
+            .iter()
+            .map(|r| {
+                let field = |key: &str| -> Result<String, CatalogError> {
+                    r.get(key)
+                        .and_then(Value::as_str)
+                        .map(String::from)
+                        .ok_or(CatalogError::Parse(format!("release without {key}")))
+                };
+                let sha256 = field("sha256")?.to_ascii_lowercase();
+                if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(CatalogError::Parse(format!("invalid sha256 {sha256}")));
+                }
+                Ok(Release {
+                    version: field("version")?,
+                    target: field("target")?,
+                    url: field("url")?,
+                    sha256,
+                })
+            })
+            .collect::<Result<_, CatalogError>>()?;
+        Ok(Catalog { manifest, releases })
+    }
+
+    /// Releases which can be flashed with a target
+    pub fn for_target<'a>(&'a self, target: &'a Target) -> impl Iterator<Item = &'a Release> {
+        self.releases.iter().filter(move |r| r.is_for(target))
+    }
+
+    // Location of an image, relative URLs being resolved against the manifest
+    fn resolve(&self, url: &str) -> String {
+        if is_http(url) || url.starts_with("file://") || Path::new(url).is_absolute() {
+            return String::from(url);
+        }
+        match self.manifest.rfind(['/', '\\']) {
+            Some(i) => format!("{}/{url}", &self.manifest[..i]),
+            None => String::from(url),
+        }
+    }
+
+    /// Download the image of a release into a cache directory, returning
+    /// its path
+    ///
+    /// Images already in the cache are not downloaded again. The image is
+    /// only saved if its hash matches the catalog, along with its signature
+    /// when the release has one.
+    pub fn download(&self, release: &Release, cache: &Path) -> Result<PathBuf, CatalogError> {
+        let path = cache.join(release.cache_name());
+        if let Ok(data) = std::fs::read(&path) {
+            if sha256_hex(&data) == release.sha256 {
+                return Ok(path);
+            }
+        }
+        let url = self.resolve(&release.url);
+        let data = fetch(&url)?;
+        let actual = sha256_hex(&data);
+        if actual != release.sha256 {
+            return Err(CatalogError::HashMismatch {
+                expected: release.sha256.clone(),
+                actual,
+            });
+        }
+        std::fs::create_dir_all(cache)?;
+        std::fs::write(&path, data)?;
+        if let Ok(signature) = fetch(&format!("{url}.{SIGNATURE_EXTENSION}")) {
+            std::fs::write(signature_path(&path), signature)?;
+        }
+        Ok(path)
+    }
+}
+
+/// Directory where downloaded firmware images are kept
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir().join("firmware")
+}
+
+/// Catalog source configured through the `OPENRTX_CATALOG` environment
+/// variable
+pub fn default_source() -> String {
+    std::env::var("OPENRTX_CATALOG").unwrap_or_default()
+}
@@ -0,0 +1,350 @@
+//! Channel lists in the CSV layout of CHIRP
+//!
+//! CHIRP stores a channel as one frequency and a duplex setting: `+` and
+//! `-` shift the TX frequency by the offset, `split` uses the offset as TX
+//! frequency and `off` disables TX. OpenRTX stores both frequencies, so
+//! duplex settings are converted on import and recomputed on export.
+//!
+//! Settings OpenRTX has no equivalent for, like DCS or modes other than FM,
+//! are reported row by row instead of being silently dropped.
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use super::codeplug::{power_from_watts, watts, Bandwidth, Channel, OpMode, BANDS, CTCSS_TONES};
+
+/// Columns written on export, in the order of CHIRP
+const COLUMNS: [&str; 21] = [
+    "Location",
+    "Name",
+    "Frequency",
+    "Duplex",
+    "Offset",
+    "Tone",
+    "rToneFreq",
+    "cToneFreq",
+    "DtcsCode",
+    "DtcsPolarity",
+    "RxDtcsCode",
+    "CrossMode",
+    "Mode",
+    "TStep",
+    "Skip",
+    "Power",
+    "Comment",
+    "URCALL",
+    "RPT1CALL",
+    "RPT2CALL",
+    "DVCODE",
+];
+
+/// Tone CHIRP writes in unused tone columns
+const DEFAULT_TONE: &str = "88.5";
+
+#[derive(Debug)]
+pub enum ChirpError {
+    /// The file has no column of this name
+    MissingColumn(&'static str),
+    Csv(csv::Error),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for ChirpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChirpError::MissingColumn(column) => {
+                write!(f, "Not a CHIRP channel list, no {column} column")
+            }
+            ChirpError::Csv(e) => write!(f, "{e}"),
+            ChirpError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ChirpError {}
+
+impl From<csv::Error> for ChirpError {
+    fn from(e: csv::Error) -> Self {
+        ChirpError::Csv(e)
+    }
+}
+
+impl From<std::io::Error> for ChirpError {
+    fn from(e: std::io::Error) -> Self {
+        ChirpError::Io(e)
+    }
+}
+
+/// Setting of a channel which could not be converted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Unmapped {
+    /// DCS tone mode, including its reverse and cross variants
+    Dcs(String),
+    /// Tone mode without equivalent, e.g. reverse tone squelch
+    ToneMode(String),
+    /// Tone which is not one of the CTCSS tones of OpenRTX
+    Tone(String),
+    Mode(String),
+    Duplex(String),
+    Frequency(String),
+    Power(String),
+}
+
+impl std::fmt::Display for Unmapped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unmapped::Dcs(mode) => write!(f, "DCS is not supported ({mode})"),
+            Unmapped::ToneMode(mode) => write!(f, "tone mode {mode} is not supported"),
+            Unmapped::Tone(tone) => write!(f, "tone {tone} is not a CTCSS tone"),
+            Unmapped::Mode(mode) => write!(f, "mode {mode} is not supported"),
+            Unmapped::Duplex(duplex) => write!(f, "duplex {duplex} is not supported"),
+            Unmapped::Frequency(frequency) => write!(f, "invalid frequency {frequency}"),
+            Unmapped::Power(power) => write!(f, "invalid power {power}"),
+        }
+    }
+}
+
+/// Settings of a row which could not be converted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowReport {
+    /// CHIRP location of the row
+    pub location: String,
+    pub name: String,
+    /// The row was left out, as its channel could not be converted at all
+    pub skipped: bool,
+    pub unmapped: Vec<Unmapped>,
+}
+
+impl std::fmt::Display for RowReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.location, self.name)?;
+        if self.skipped {
+            write!(f, " skipped")?;
+        }
+        let unmapped: Vec<String> = self.unmapped.iter().map(|u| u.to_string()).collect();
+        write!(f, ": {}", unmapped.join(", "))
+    }
+}
+
+/// Channels read from a CHIRP file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChirpImport {
+    pub channels: Vec<Channel>,
+    /// Rows which were not fully converted
+    pub reports: Vec<RowReport>,
+}
+
+fn parse_mhz(value: &str) -> Option<u32> {
+    let mhz: f64 = value.trim().parse().ok()?;
+    (mhz >= 0.0).then(|| (mhz * 1e6).round() as u32)
+}
+
+fn parse_tone(value: &str) -> Result<u16, Unmapped> {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .map(|hz| (hz * 10.0).round() as u16)
+        .filter(|tone| CTCSS_TONES.contains(tone))
+        .ok_or(Unmapped::Tone(String::from(value)))
+}
+
+fn tone_text(tone: u16) -> String {
+    format!("{}.{}", tone / 10, tone % 10)
+}
+
+// Columns of the file by name, as CHIRP versions add and remove some
+struct Row<'a> {
+    headers: &'a csv::StringRecord,
+    record: &'a csv::StringRecord,
+}
+
+impl Row<'_> {
+    fn get(&self, column: &str) -> &str {
+        self.headers
+            .iter()
+            .position(|h| h == column)
+            .and_then(|i| self.record.get(i))
+            .unwrap_or_default()
+            .trim()
+    }
+
+    // Convert the row, along with the settings which could not be converted
+    fn channel(&self) -> (Option<Channel>, Vec<Unmapped>) {
+        let mut unmapped = vec![];
+        let mut channel = Channel::new(String::from(self.get("Name")));
+        channel.description = String::from(self.get("Comment"));
+        channel.bandwidth = match self.get("Mode") {
+            "FM" => Bandwidth::B25,
+            "NFM" | "" => Bandwidth::B12_5,
+            mode => return (None, vec![Unmapped::Mode(String::from(mode))]),
+        };
+        let Some(frequency) = parse_mhz(self.get("Frequency")) else {
+            let frequency = String::from(self.get("Frequency"));
+            return (None, vec![Unmapped::Frequency(frequency)]);
+        };
+        channel.rx_frequency = frequency;
+        let offset = self.get("Offset");
+        channel.tx_frequency = match (self.get("Duplex"), parse_mhz(offset)) {
+            ("", _) => frequency,
+            ("+", Some(offset)) => frequency.saturating_add(offset),
+            ("-", Some(offset)) => frequency.saturating_sub(offset),
+            ("split", Some(tx)) => tx,
+            ("off", _) => {
+                channel.rx_only = true;
+                frequency
+            }
+            ("+" | "-" | "split", None) => {
+                unmapped.push(Unmapped::Frequency(String::from(offset)));
+                frequency
+            }
+            (duplex, _) => {
+                unmapped.push(Unmapped::Duplex(String::from(duplex)));
+                frequency
+            }
+        };
+
+        let r_tone = || parse_tone(self.get("rToneFreq"));
+        let c_tone = || parse_tone(self.get("cToneFreq"));
+        let tones = match self.get("Tone") {
+            "" => Ok((None, None)),
+            "Tone" => r_tone().map(|t| (None, Some(t))),
+            "TSQL" => c_tone().map(|t| (Some(t), Some(t))),
+            "Cross" => match self.get("CrossMode") {
+                "Tone->Tone" => r_tone().and_then(|tx| Ok((Some(c_tone()?), Some(tx)))),
+                "Tone->" => r_tone().map(|tx| (None, Some(tx))),
+                "->Tone" => c_tone().map(|rx| (Some(rx), None)),
+                mode => Err(Unmapped::Dcs(format!("Cross {mode}"))),
+            },
+            mode @ ("DTCS" | "DTCS-R") => Err(Unmapped::Dcs(String::from(mode))),
+            mode => Err(Unmapped::ToneMode(String::from(mode))),
+        };
+        match tones {
+            Ok((rx, tx)) => (channel.rx_tone, channel.tx_tone) = (rx, tx),
+            Err(e) => unmapped.push(e),
+        }
+
+        match self.get("Power") {
+            "" => (),
+            power => match power.trim_end_matches('W').parse::<f32>() {
+                Ok(w) if w > 0.0 => channel.power = power_from_watts(w),
+                _ => unmapped.push(Unmapped::Power(String::from(power))),
+            },
+        }
+        (Some(channel), unmapped)
+    }
+}
+
+/// Read the channels of a CHIRP CSV file
+///
+/// Rows with settings which could not be converted are reported, rows
+/// whose mode or frequency could not be converted are left out.
+pub fn import(reader: impl Read) -> Result<ChirpImport, ChirpError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers = reader.headers()?.clone();
+    for column in ["Name", "Frequency"] {
+        if !headers.iter().any(|h| h == column) {
+            return Err(ChirpError::MissingColumn(column));
+        }
+    }
+    let mut channels = vec![];
+    let mut reports = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let row = Row {
+            headers: &headers,
+            record: &record,
+        };
+        let (channel, unmapped) = row.channel();
+        if !unmapped.is_empty() {
+            reports.push(RowReport {
+                location: String::from(row.get("Location")),
+                name: String::from(row.get("Name")),
+                skipped: channel.is_none(),
+                unmapped,
+            });
+        }
+        channels.extend(channel);
+    }
+    Ok(ChirpImport { channels, reports })
+}
+
+/// Write channels as a CHIRP CSV file
+///
+/// Only FM channels can be represented, the others are left out and
+/// reported.
+pub fn export(channels: &[Channel], writer: impl Write) -> Result<Vec<RowReport>, ChirpError> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(COLUMNS)?;
+    let mut reports = vec![];
+    let mut location = 0;
+    for channel in channels {
+        if channel.mode != OpMode::Fm {
+            reports.push(RowReport {
+                location: String::from("-"),
+                name: channel.name.clone(),
+                skipped: true,
+                unmapped: vec![Unmapped::Mode(channel.mode.to_string())],
+            });
+            continue;
+        }
+        let same_band = BANDS
+            .iter()
+            .any(|b| b.contains(&channel.rx_frequency) && b.contains(&channel.tx_frequency));
+        let mhz = |f: u32| format!("{:.6}", f as f64 / 1e6);
+        let (duplex, offset) = match channel.tx_frequency {
+            _ if channel.rx_only => ("off", 0),
+            tx if tx == channel.rx_frequency => ("", 0),
+            tx if !same_band => ("split", tx),
+            tx if tx > channel.rx_frequency => ("+", tx - channel.rx_frequency),
+            tx => ("-", channel.rx_frequency - tx),
+        };
+        let (tone, cross_mode, r_tone, c_tone) = match (channel.rx_tone, channel.tx_tone) {
+            (None, None) => ("", "Tone->Tone", None, None),
+            (None, Some(tx)) => ("Tone", "Tone->Tone", Some(tx), None),
+            (Some(rx), Some(tx)) if rx == tx => ("TSQL", "Tone->Tone", Some(tx), Some(rx)),
+            (Some(rx), None) => ("Cross", "->Tone", None, Some(rx)),
+            (Some(rx), Some(tx)) => ("Cross", "Tone->Tone", Some(tx), Some(rx)),
+        };
+        let tone_column = |t: Option<u16>| t.map(tone_text).unwrap_or(String::from(DEFAULT_TONE));
+        writer.write_record([
+            location.to_string(),
+            channel.name.clone(),
+            mhz(channel.rx_frequency),
+            String::from(duplex),
+            mhz(offset),
+            String::from(tone),
+            tone_column(r_tone),
+            tone_column(c_tone),
+            String::from("023"),
+            String::from("NN"),
+            String::from("023"),
+            String::from(cross_mode),
+            String::from(match channel.bandwidth {
+                Bandwidth::B12_5 => "NFM",
+                Bandwidth::B20 | Bandwidth::B25 => "FM",
+            }),
+            String::from("5.00"),
+            String::new(),
+            format!("{:.1}W", watts(channel.power)),
+            channel.description.clone(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+        ])?;
+        location += 1;
+    }
+    writer.flush()?;
+    Ok(reports)
+}
+
+/// Read the channels of a CHIRP CSV file on disk
+pub fn import_file(path: &Path) -> Result<ChirpImport, ChirpError> {
+    import(std::fs::File::open(path)?)
+}
+
+/// Save channels as a CHIRP CSV file on disk
+pub fn export_file(channels: &[Channel], path: &Path) -> Result<Vec<RowReport>, ChirpError> {
+    export(channels, std::fs::File::create(path)?)
+}
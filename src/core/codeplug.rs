@@ -0,0 +1,880 @@
+//! OpenRTX codeplug
+//!
+//! OpenRTX keeps its codeplug in the file [`CODEPLUG_PATH`] of the radio
+//! storage, using the layout of its `cps.h` header, all values little
+//! endian:
+//!
+//! - a header of 88 bytes: the magic number, the version, the author and
+//!   the description (32 bytes each), the timestamp and the number of
+//!   contacts, channels and banks;
+//! - the contacts, [`CONTACT_SIZE`] bytes each;
+//! - the channels, [`CHANNEL_SIZE`] bytes each;
+//! - a table of u32 offsets of the banks, relative to the end of the table,
+//!   followed by the banks: a name of 32 bytes, the number of channels as
+//!   u16 and the u16 indices of the channels.
+//!
+//! Only the FM and M17 settings of the channels are decoded, the bytes of
+//! other modes and the position of the channels are kept as they were read.
+
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+
+use time::OffsetDateTime;
+
+use super::contacts::{decode_callsign, encode_callsign, CallsignError, ContactIssue};
+use super::files::has_file_access;
+use super::fmp;
+use super::{CancelToken, JobError, JobEvent, JobHandle, LinkError, Phase, Progress, RadioLink};
+
+/// Path of the codeplug in the radio storage
+pub const CODEPLUG_PATH: &str = "/default.rtxc";
+
+/// Events reported by a [`CodeplugReadJob`]
+pub type CodeplugReadEvent = JobEvent<Codeplug, CodeplugError>;
+
+/// Events reported by a [`CodeplugWriteJob`]
+pub type CodeplugWriteEvent = JobEvent<(), CodeplugError>;
+
+const MAGIC: u64 = 0x4358_5452;
+/// Major version of the supported layout, in the high byte of the version
+const VERSION_MAJOR: u8 = 0;
+const VERSION: u16 = 0x0001;
+const HEADER_SIZE: usize = 88;
+pub const CONTACT_SIZE: usize = 39;
+pub const CHANNEL_SIZE: usize = 75;
+/// Size of the names, which are NUL terminated UTF-8
+pub const NAME_SIZE: usize = 32;
+const CHANNEL_DESCR_SIZE: usize = 16;
+
+/// Frequency bands of the radios supported by OpenRTX
+pub const BANDS: &[RangeInclusive<u32>] = &[136_000_000..=174_000_000, 400_000_000..=480_000_000];
+
+/// CTCSS tones supported by OpenRTX, in 0.1 Hz
+pub const CTCSS_TONES: [u16; 50] = [
+    670, 693, 719, 744, 770, 797, 825, 854, 885, 915, 948, 974, 1000, 1035, 1072, 1109, 1148, 1188,
+    1230, 1273, 1318, 1365, 1413, 1462, 1514, 1567, 1598, 1622, 1655, 1679, 1713, 1738, 1773, 1799,
+    1835, 1862, 1899, 1928, 1966, 1995, 2035, 2065, 2107, 2181, 2257, 2291, 2336, 2418, 2503, 2541,
+];
+
+/// Highest M17 channel access number
+pub const MAX_CAN: u8 = 15;
+
+/// Highest transmit power, in steps of 0.2 dBm above 10 dBm (10 W)
+pub const MAX_POWER: u8 = 150;
+
+#[derive(Debug)]
+pub enum CodeplugError {
+    /// The data does not start with the codeplug magic number
+    NotCodeplug,
+    UnsupportedVersion(u16),
+    /// The data ends before the end of the codeplug
+    Truncated,
+    /// The codeplug has channels which are not safe to write to a radio
+    Invalid(Vec<ChannelIssue>),
+    /// The contacts to write are not valid
+    InvalidContacts(Vec<ContactIssue>),
+    /// The firmware does not support the FMP file requests
+    NoFileAccess,
+    Link(LinkError),
+    Io(std::io::Error),
+    Cancelled,
+}
+
+impl std::fmt::Display for CodeplugError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeplugError::NotCodeplug => write!(f, "Not an OpenRTX codeplug"),
+            CodeplugError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported codeplug version {}.{}", v >> 8, v & 0xff)
+            }
+            CodeplugError::Truncated => write!(f, "Codeplug is truncated"),
+            CodeplugError::Invalid(issues) => {
+                write!(f, "Codeplug has {} invalid values", issues.len())?;
+                match issues.first() {
+                    Some(issue) => write!(f, ", e.g. {issue}"),
+                    None => Ok(()),
+                }
+            }
+            CodeplugError::InvalidContacts(issues) => {
+                write!(f, "Codeplug has {} invalid contacts", issues.len())?;
+                match issues.first() {
+                    Some(issue) => write!(f, ", e.g. {issue}"),
+                    None => Ok(()),
+                }
+            }
+            CodeplugError::NoFileAccess => {
+                write!(f, "The firmware does not support file access")
+            }
+            CodeplugError::Link(e) => write!(f, "{e}"),
+            CodeplugError::Io(e) => write!(f, "{e}"),
+            CodeplugError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for CodeplugError {}
+
+impl JobError for CodeplugError {
+    fn is_cancelled(&self) -> bool {
+        matches!(self, CodeplugError::Cancelled)
+    }
+}
+
+impl From<std::io::Error> for CodeplugError {
+    fn from(e: std::io::Error) -> Self {
+        CodeplugError::Io(e)
+    }
+}
+
+impl From<LinkError> for CodeplugError {
+    fn from(e: LinkError) -> Self {
+        match e {
+            LinkError::Cancelled => CodeplugError::Cancelled,
+            e => CodeplugError::Link(e),
+        }
+    }
+}
+
+/// Channel setting which is not valid for OpenRTX
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChannelIssue {
+    /// The name is empty or longer than 31 bytes
+    Name {
+        channel: usize,
+    },
+    RxFrequency {
+        channel: usize,
+        frequency: u32,
+    },
+    TxFrequency {
+        channel: usize,
+        frequency: u32,
+    },
+    /// The tone is not one of [`CTCSS_TONES`]
+    Tone {
+        channel: usize,
+        tone: u16,
+    },
+    Can {
+        channel: usize,
+        can: u8,
+    },
+    Power {
+        channel: usize,
+        power: u8,
+    },
+}
+
+impl ChannelIssue {
+    /// Index of the channel with the issue
+    pub fn channel(&self) -> usize {
+        match self {
+            ChannelIssue::Name { channel }
+            | ChannelIssue::RxFrequency { channel, .. }
+            | ChannelIssue::TxFrequency { channel, .. }
+            | ChannelIssue::Tone { channel, .. }
+            | ChannelIssue::Can { channel, .. }
+            | ChannelIssue::Power { channel, .. } => *channel,
+        }
+    }
+}
+
+impl std::fmt::Display for ChannelIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Channels are numbered from 1 on the radio
+        let number = self.channel() + 1;
+        match self {
+            ChannelIssue::Name { .. } => {
+                write!(f, "Channel {number} name must have 1 to 31 characters")
+            }
+            ChannelIssue::RxFrequency { frequency, .. } => write!(
+                f,
+                "Channel {number} RX frequency {} MHz is out of band",
+                mhz(*frequency)
+            ),
+            ChannelIssue::TxFrequency { frequency, .. } => write!(
+                f,
+                "Channel {number} TX frequency {} MHz is out of band",
+                mhz(*frequency)
+            ),
+            ChannelIssue::Tone { tone, .. } => write!(
+                f,
+                "Channel {number} tone {}.{} Hz is not a CTCSS tone",
+                tone / 10,
+                tone % 10
+            ),
+            ChannelIssue::Can { can, .. } => {
+                write!(f, "Channel {number} CAN {can} is above {MAX_CAN}")
+            }
+            ChannelIssue::Power { power, .. } => write!(
+                f,
+                "Channel {number} power {:.2} W is above {:.0} W",
+                watts(*power),
+                watts(MAX_POWER)
+            ),
+        }
+    }
+}
+
+/// Frequency in MHz, for display
+pub fn mhz(frequency: u32) -> String {
+    format!("{:.5}", frequency as f64 / 1e6)
+}
+
+/// Transmit power in W of a codeplug power value
+pub fn watts(power: u8) -> f32 {
+    10f32.powf((10.0 + 0.2 * power as f32) / 10.0) / 1000.0
+}
+
+/// Codeplug power value closest to a transmit power in W
+pub fn power_from_watts(watts: f32) -> u8 {
+    let dbm = 10.0 * (watts * 1000.0).log10();
+    ((dbm - 10.0) / 0.2).round().clamp(0.0, 255.0) as u8
+}
+
+/// Operating mode of a channel or contact
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpMode {
+    None,
+    Fm,
+    Dmr,
+    M17,
+    Unknown(u8),
+}
+
+impl From<u8> for OpMode {
+    fn from(mode: u8) -> Self {
+        match mode {
+            0 => OpMode::None,
+            1 => OpMode::Fm,
+            2 => OpMode::Dmr,
+            3 => OpMode::M17,
+            m => OpMode::Unknown(m),
+        }
+    }
+}
+
+impl From<OpMode> for u8 {
+    fn from(mode: OpMode) -> Self {
+        match mode {
+            OpMode::None => 0,
+            OpMode::Fm => 1,
+            OpMode::Dmr => 2,
+            OpMode::M17 => 3,
+            OpMode::Unknown(m) => m,
+        }
+    }
+}
+
+impl std::fmt::Display for OpMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpMode::None => write!(f, "None"),
+            OpMode::Fm => write!(f, "FM"),
+            OpMode::Dmr => write!(f, "DMR"),
+            OpMode::M17 => write!(f, "M17"),
+            OpMode::Unknown(m) => write!(f, "Unknown ({m})"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Bandwidth {
+    #[default]
+    B12_5,
+    B20,
+    B25,
+}
+
+impl Bandwidth {
+    pub const ALL: [Bandwidth; 3] = [Bandwidth::B12_5, Bandwidth::B20, Bandwidth::B25];
+}
+
+impl std::fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bandwidth::B12_5 => write!(f, "12.5 kHz"),
+            Bandwidth::B20 => write!(f, "20 kHz"),
+            Bandwidth::B25 => write!(f, "25 kHz"),
+        }
+    }
+}
+
+/// Channel of the codeplug
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Channel {
+    pub name: String,
+    pub description: String,
+    pub mode: OpMode,
+    pub bandwidth: Bandwidth,
+    pub rx_only: bool,
+    /// Transmit power, in steps of 0.2 dBm above 10 dBm
+    pub power: u8,
+    /// Frequencies in Hz
+    pub rx_frequency: u32,
+    pub tx_frequency: u32,
+    pub scan_list: u8,
+    pub group_list: u8,
+    /// FM CTCSS tones in 0.1 Hz, None when disabled
+    pub rx_tone: Option<u16>,
+    pub tx_tone: Option<u16>,
+    /// M17 channel access numbers
+    pub rx_can: u8,
+    pub tx_can: u8,
+    // Position of the channel
+    location: [u8; 9],
+    // Mode settings as read, reused when the mode is unchanged
+    read_mode: OpMode,
+    info: [u8; 5],
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Channel {
+            name: String::new(),
+            description: String::new(),
+            mode: OpMode::Fm,
+            bandwidth: Bandwidth::B12_5,
+            rx_only: false,
+            // 1 W
+            power: 100,
+            rx_frequency: 430_000_000,
+            tx_frequency: 430_000_000,
+            scan_list: 0,
+            group_list: 0,
+            rx_tone: None,
+            tx_tone: None,
+            rx_can: 0,
+            tx_can: 0,
+            location: [0; 9],
+            read_mode: OpMode::None,
+            info: [0; 5],
+        }
+    }
+}
+
+// Tone byte: enable bit followed by the index in the CTCSS table
+fn tone_decode(byte: u8) -> Option<u16> {
+    (byte & 1 == 1).then(|| CTCSS_TONES.get((byte >> 1) as usize).copied().unwrap_or(0))
+}
+
+fn tone_encode(tone: Option<u16>) -> u8 {
+    match tone.and_then(|t| CTCSS_TONES.iter().position(|c| *c == t)) {
+        Some(index) => ((index as u8) << 1) | 1,
+        None => 0,
+    }
+}
+
+impl Channel {
+    /// FM channel on 430 MHz, without tone, at 1 W
+    pub fn new(name: String) -> Self {
+        Channel {
+            name,
+            ..Default::default()
+        }
+    }
+
+    fn decode(data: &[u8]) -> Channel {
+        let mode = OpMode::from(data[0]);
+        let info: [u8; 5] = data[70..75].try_into().unwrap_or_default();
+        let mut channel = Channel {
+            name: string(&data[13..45]),
+            description: string(&data[45..61]),
+            mode,
+            bandwidth: match data[1] & 0x3 {
+                1 => Bandwidth::B20,
+                2 => Bandwidth::B25,
+                _ => Bandwidth::B12_5,
+            },
+            rx_only: data[1] & 0x4 != 0,
+            power: data[2],
+            rx_frequency: u32::from_le_bytes(data[3..7].try_into().unwrap_or_default()),
+            tx_frequency: u32::from_le_bytes(data[7..11].try_into().unwrap_or_default()),
+            scan_list: data[11],
+            group_list: data[12],
+            location: data[61..70].try_into().unwrap_or_default(),
+            read_mode: mode,
+            info,
+            ..Default::default()
+        };
+        match mode {
+            OpMode::Fm => {
+                channel.rx_tone = tone_decode(info[0]);
+                channel.tx_tone = tone_decode(info[1]);
+            }
+            OpMode::M17 => {
+                channel.rx_can = info[0] & 0xf;
+                channel.tx_can = info[0] >> 4;
+            }
+            _ => (),
+        }
+        channel
+    }
+
+    fn encode(&self, data: &mut Vec<u8>) {
+        data.push(self.mode.into());
+        data.push(self.bandwidth as u8 | (self.rx_only as u8) << 2);
+        data.push(self.power);
+        data.extend_from_slice(&self.rx_frequency.to_le_bytes());
+        data.extend_from_slice(&self.tx_frequency.to_le_bytes());
+        data.push(self.scan_list);
+        data.push(self.group_list);
+        data.extend_from_slice(&fixed::<NAME_SIZE>(&self.name));
+        data.extend_from_slice(&fixed::<CHANNEL_DESCR_SIZE>(&self.description));
+        data.extend_from_slice(&self.location);
+        let mut info = if self.mode == self.read_mode {
+            self.info
+        } else {
+            [0; 5]
+        };
+        match self.mode {
+            OpMode::Fm => {
+                info = [
+                    tone_encode(self.rx_tone),
+                    tone_encode(self.tx_tone),
+                    0,
+                    0,
+                    0,
+                ];
+            }
+            OpMode::M17 => info[0] = (self.rx_can & 0xf) | self.tx_can << 4,
+            _ => (),
+        }
+        data.extend_from_slice(&info);
+    }
+
+    /// Settings of the channel which are not valid, `index` being its
+    /// position in the codeplug
+    pub fn check(&self, index: usize) -> Vec<ChannelIssue> {
+        let mut issues = vec![];
+        let in_band = |f: u32| BANDS.iter().any(|b| b.contains(&f));
+        if self.name.is_empty() || self.name.len() >= NAME_SIZE {
+            issues.push(ChannelIssue::Name { channel: index });
+        }
+        if !in_band(self.rx_frequency) {
+            issues.push(ChannelIssue::RxFrequency {
+                channel: index,
+                frequency: self.rx_frequency,
+            });
+        }
+        if !self.rx_only && !in_band(self.tx_frequency) {
+            issues.push(ChannelIssue::TxFrequency {
+                channel: index,
+                frequency: self.tx_frequency,
+            });
+        }
+        if self.power > MAX_POWER {
+            issues.push(ChannelIssue::Power {
+                channel: index,
+                power: self.power,
+            });
+        }
+        match self.mode {
+            OpMode::Fm => {
+                for tone in [self.rx_tone, self.tx_tone].into_iter().flatten() {
+                    if !CTCSS_TONES.contains(&tone) {
+                        issues.push(ChannelIssue::Tone {
+                            channel: index,
+                            tone,
+                        });
+                    }
+                }
+            }
+            OpMode::M17 => {
+                for can in [self.rx_can, self.tx_can] {
+                    if can > MAX_CAN {
+                        issues.push(ChannelIssue::Can {
+                            channel: index,
+                            can,
+                        });
+                    }
+                }
+            }
+            _ => (),
+        }
+        issues
+    }
+}
+
+/// Kind of DMR call made to a contact
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallType {
+    Group,
+    Private,
+    All,
+}
+
+/// Contact of the codeplug
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Contact {
+    pub name: String,
+    pub mode: OpMode,
+    // DMR ID or M17 address
+    info: [u8; 6],
+}
+
+impl Contact {
+    /// DMR contact
+    pub fn dmr(name: String, id: u32, call_type: CallType) -> Contact {
+        let mut info = [0; 6];
+        info[..4].copy_from_slice(&id.to_le_bytes());
+        info[4] = (call_type as u8) << 1;
+        Contact {
+            name,
+            mode: OpMode::Dmr,
+            info,
+        }
+    }
+
+    /// M17 contact
+    pub fn m17(name: String, callsign: &str) -> Result<Contact, CallsignError> {
+        Ok(Contact {
+            name,
+            mode: OpMode::M17,
+            info: encode_callsign(callsign)?,
+        })
+    }
+
+    /// Callsign of an M17 contact, if its address is a valid callsign
+    pub fn m17_callsign(&self) -> Option<String> {
+        (self.mode == OpMode::M17)
+            .then(|| decode_callsign(&self.info))
+            .flatten()
+    }
+
+    /// ID of a DMR contact
+    pub fn dmr_id(&self) -> Option<u32> {
+        (self.mode == OpMode::Dmr)
+            .then(|| u32::from_le_bytes(self.info[..4].try_into().unwrap_or_default()))
+    }
+
+    fn decode(data: &[u8]) -> Contact {
+        Contact {
+            name: string(&data[..NAME_SIZE]),
+            mode: OpMode::from(data[NAME_SIZE]),
+            info: data[NAME_SIZE + 1..CONTACT_SIZE]
+                .try_into()
+                .unwrap_or_default(),
+        }
+    }
+
+    fn encode(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(&fixed::<NAME_SIZE>(&self.name));
+        data.push(self.mode.into());
+        data.extend_from_slice(&self.info);
+    }
+}
+
+/// Bank of channels
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bank {
+    pub name: String,
+    /// Indices of the channels in the codeplug
+    pub channels: Vec<u16>,
+}
+
+/// Decoded OpenRTX codeplug
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Codeplug {
+    pub author: String,
+    pub description: String,
+    /// Time of the last change, in seconds since the Unix epoch
+    pub timestamp: u64,
+    pub contacts: Vec<Contact>,
+    pub channels: Vec<Channel>,
+    pub banks: Vec<Bank>,
+}
+
+impl Default for Codeplug {
+    fn default() -> Self {
+        Codeplug {
+            author: String::from("OpenRTX Companion"),
+            description: String::new(),
+            timestamp: 0,
+            contacts: vec![],
+            channels: vec![],
+            banks: vec![],
+        }
+    }
+}
+
+// String stored in a NUL padded array
+fn string(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).to_string()
+}
+
+fn fixed<const N: usize>(s: &str) -> [u8; N] {
+    let mut data = [0; N];
+    let len = s.len().min(N);
+    data[..len].copy_from_slice(&s.as_bytes()[..len]);
+    data
+}
+
+// Cursor over the codeplug being decoded
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], CodeplugError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + size)
+            .ok_or(CodeplugError::Truncated)?;
+        self.offset += size;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, CodeplugError> {
+        Ok(u16::from_le_bytes(
+            self.take(2)?.try_into().unwrap_or_default(),
+        ))
+    }
+
+    fn u32(&mut self) -> Result<u32, CodeplugError> {
+        Ok(u32::from_le_bytes(
+            self.take(4)?.try_into().unwrap_or_default(),
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64, CodeplugError> {
+        Ok(u64::from_le_bytes(
+            self.take(8)?.try_into().unwrap_or_default(),
+        ))
+    }
+}
+
+impl Codeplug {
+    pub fn decode(data: &[u8]) -> Result<Codeplug, CodeplugError> {
+        let mut reader = Reader { data, offset: 0 };
+        if reader.u64().ok() != Some(MAGIC) {
+            return Err(CodeplugError::NotCodeplug);
+        }
+        let version = reader.u16()?;
+        if (version >> 8) as u8 != VERSION_MAJOR {
+            return Err(CodeplugError::UnsupportedVersion(version));
+        }
+        let author = string(reader.take(NAME_SIZE)?);
+        let description = string(reader.take(NAME_SIZE)?);
+        let timestamp = reader.u64()?;
+        let contacts = reader.u16()? as usize;
+        let channels = reader.u16()? as usize;
+        let banks = reader.u16()? as usize;
+
+        let contacts = (0..contacts)
+            .map(|_| Ok(Contact::decode(reader.take(CONTACT_SIZE)?)))
+            .collect::<Result<_, CodeplugError>>()?;
+        let channels = (0..channels)
+            .map(|_| Ok(Channel::decode(reader.take(CHANNEL_SIZE)?)))
+            .collect::<Result<_, CodeplugError>>()?;
+        let offsets = (0..banks)
+            .map(|_| reader.u32())
+            .collect::<Result<Vec<_>, _>>()?;
+        let banks_start = reader.offset;
+        let banks = offsets
+            .into_iter()
+            .map(|offset| {
+                reader.offset = banks_start + offset as usize;
+                let name = string(reader.take(NAME_SIZE)?);
+                let count = reader.u16()?;
+                let channels = (0..count).map(|_| reader.u16()).collect::<Result<_, _>>()?;
+                Ok(Bank { name, channels })
+            })
+            .collect::<Result<_, CodeplugError>>()?;
+        Ok(Codeplug {
+            author,
+            description,
+            timestamp,
+            contacts,
+            channels,
+            banks,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(
+            HEADER_SIZE + self.contacts.len() * CONTACT_SIZE + self.channels.len() * CHANNEL_SIZE,
+        );
+        data.extend_from_slice(&MAGIC.to_le_bytes());
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&fixed::<NAME_SIZE>(&self.author));
+        data.extend_from_slice(&fixed::<NAME_SIZE>(&self.description));
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data.extend_from_slice(&(self.contacts.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(self.channels.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(self.banks.len() as u16).to_le_bytes());
+        for contact in &self.contacts {
+            contact.encode(&mut data);
+        }
+        for channel in &self.channels {
+            channel.encode(&mut data);
+        }
+        let mut banks = vec![];
+        for bank in &self.banks {
+            data.extend_from_slice(&(banks.len() as u32).to_le_bytes());
+            banks.extend_from_slice(&fixed::<NAME_SIZE>(&bank.name));
+            banks.extend_from_slice(&(bank.channels.len() as u16).to_le_bytes());
+            for channel in &bank.channels {
+                banks.extend_from_slice(&channel.to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&banks);
+        data
+    }
+
+    /// Settings of the channels which are not valid
+    pub fn check(&self) -> Vec<ChannelIssue> {
+        self.channels
+            .iter()
+            .enumerate()
+            .flat_map(|(index, channel)| channel.check(index))
+            .collect()
+    }
+
+    /// Remove a channel, along with its entries in the banks
+    pub fn remove_channel(&mut self, index: usize) {
+        if index >= self.channels.len() {
+            return;
+        }
+        self.channels.remove(index);
+        let index = index as u16;
+        for bank in &mut self.banks {
+            bank.channels.retain(|c| *c != index);
+            for channel in &mut bank.channels {
+                if *channel > index {
+                    *channel -= 1;
+                }
+            }
+        }
+    }
+
+    /// Load a codeplug file
+    pub fn load(path: &Path) -> Result<Codeplug, CodeplugError> {
+        Codeplug::decode(&std::fs::read(path)?)
+    }
+
+    /// Save the codeplug in the format used by the radio
+    pub fn save(&self, path: &Path) -> Result<(), CodeplugError> {
+        Ok(std::fs::write(path, self.encode())?)
+    }
+}
+
+/// Read the codeplug of a radio running OpenRTX
+#[derive(Clone, Debug)]
+pub struct CodeplugReadJob {
+    pub port: String,
+}
+
+impl CodeplugReadJob {
+    pub fn new(port: String) -> Self {
+        CodeplugReadJob { port }
+    }
+
+    /// Run the job on the calling thread
+    pub fn run(
+        self,
+        events: &Sender<CodeplugReadEvent>,
+        cancel: &CancelToken,
+    ) -> Result<Codeplug, CodeplugError> {
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut link = RadioLink::open(&self.port)?;
+
+        _ = events.send(JobEvent::Phase(Phase::Reading));
+        let data = read(&mut link, cancel, |p| {
+            _ = events.send(JobEvent::Progress(p));
+        })?;
+        Codeplug::decode(&data)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<Codeplug, CodeplugError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
+
+// Error of a file request, which firmwares without file access answer with
+// an error status as for a missing file
+fn file_error(link: &mut RadioLink, e: LinkError) -> CodeplugError {
+    match e {
+        LinkError::Status(_) if matches!(has_file_access(link), Ok(false)) => {
+            CodeplugError::NoFileAccess
+        }
+        e => e.into(),
+    }
+}
+
+// Read the codeplug file of the radio
+pub(crate) fn read(
+    link: &mut RadioLink,
+    cancel: &CancelToken,
+    progress: impl FnMut(Progress),
+) -> Result<Vec<u8>, CodeplugError> {
+    fmp::read(link, CODEPLUG_PATH, cancel, progress).map_err(|e| file_error(link, e))
+}
+
+// Replace the codeplug of the radio, once uploaded next to the current one
+pub(crate) fn write(
+    link: &mut RadioLink,
+    codeplug: &mut Codeplug,
+    events: &Sender<CodeplugWriteEvent>,
+    cancel: &CancelToken,
+) -> Result<(), CodeplugError> {
+    codeplug.timestamp = OffsetDateTime::now_utc().unix_timestamp() as u64;
+    _ = events.send(JobEvent::Phase(Phase::Writing));
+    let upload = format!("{CODEPLUG_PATH}.new");
+    fmp::write(link, &upload, &codeplug.encode(), cancel, |p| {
+        _ = events.send(JobEvent::Progress(p));
+    })
+    .map_err(|e| file_error(link, e))?;
+    cancel
+        .uninterruptible(|| {
+            match fmp::remove(link, CODEPLUG_PATH) {
+                Ok(()) | Err(LinkError::Status(_)) => (),
+                Err(e) => return Err(e),
+            }
+            fmp::rename(link, &upload, CODEPLUG_PATH)
+        })
+        .ok_or(CodeplugError::Cancelled)??;
+    Ok(())
+}
+
+/// Write a codeplug to a radio running OpenRTX
+///
+/// The codeplug is only written if all its channels are valid. It is
+/// uploaded next to the current one, which is only replaced once the
+/// transfer is complete.
+#[derive(Clone, Debug)]
+pub struct CodeplugWriteJob {
+    pub port: String,
+    pub codeplug: Codeplug,
+}
+
+impl CodeplugWriteJob {
+    pub fn new(port: String, codeplug: Codeplug) -> Self {
+        CodeplugWriteJob { port, codeplug }
+    }
+
+    /// Run the job on the calling thread
+    pub fn run(
+        mut self,
+        events: &Sender<CodeplugWriteEvent>,
+        cancel: &CancelToken,
+    ) -> Result<(), CodeplugError> {
+        let issues = self.codeplug.check();
+        if !issues.is_empty() {
+            return Err(CodeplugError::Invalid(issues));
+        }
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut link = RadioLink::open(&self.port)?;
+
+        write(&mut link, &mut self.codeplug, events, cancel)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<(), CodeplugError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
@@ -0,0 +1,440 @@
+//! M17 contacts of the OpenRTX codeplug
+//!
+//! M17 contacts store their callsign as a 48 bit big-endian address,
+//! encoded in base 40 from the last character to the first: space (the
+//! padding), `A` to `Z`, `0` to `9`, `-`, `/` and `.`. Callsigns have 1 to 9
+//! of these characters, and `@ALL` is the broadcast address.
+//!
+//! Contact lists are exchanged as CSV files with `Name` and `Callsign`
+//! columns, or as vCards with the callsign in an `X-M17-CALLSIGN` property.
+//! Only M17 contacts are exported, the others are reported.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+
+use super::codeplug::{self, CodeplugWriteEvent, Contact, OpMode};
+use super::{CancelToken, Codeplug, CodeplugError, JobEvent, JobHandle, Phase, RadioLink};
+
+const CHARSET: &[u8; 40] = b" ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-/.";
+const MAX_CALLSIGN: usize = 9;
+/// Address of the broadcast callsign
+const BROADCAST: u64 = 0xffff_ffff_ffff;
+const BROADCAST_CALLSIGN: &str = "@ALL";
+// Addresses from 40^9 are not callsigns
+const MAX_ADDRESS: u64 = 40u64.pow(MAX_CALLSIGN as u32);
+/// Longest contact name, the codeplug keeps a NUL terminator
+const MAX_NAME: usize = 31;
+const VCARD_CALLSIGN: &str = "X-M17-CALLSIGN";
+
+/// Reason a callsign can not be encoded as an M17 address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CallsignError {
+    Empty,
+    /// The callsign has more than 9 characters
+    TooLong(usize),
+    /// Character outside of the base 40 character set
+    Character(char),
+}
+
+impl std::fmt::Display for CallsignError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallsignError::Empty => write!(f, "callsign is empty"),
+            CallsignError::TooLong(len) => {
+                write!(f, "callsign has {len} characters, at most {MAX_CALLSIGN}")
+            }
+            CallsignError::Character(c) => write!(f, "'{c}' can not be used in a callsign"),
+        }
+    }
+}
+
+impl std::error::Error for CallsignError {}
+
+/// Encode a callsign as an M17 address
+///
+/// Lowercase letters are converted to uppercase, surrounding spaces are
+/// ignored.
+pub fn encode_callsign(callsign: &str) -> Result<[u8; 6], CallsignError> {
+    let callsign = callsign.trim().to_ascii_uppercase();
+    if callsign == BROADCAST_CALLSIGN {
+        return Ok(BROADCAST.to_be_bytes()[2..].try_into().unwrap_or_default());
+    }
+    let len = callsign.chars().count();
+    if len == 0 {
+        return Err(CallsignError::Empty);
+    }
+    if len > MAX_CALLSIGN {
+        return Err(CallsignError::TooLong(len));
+    }
+    let mut address = 0u64;
+    for c in callsign.chars().rev() {
+        let value = CHARSET
+            .iter()
+            .skip(1)
+            .position(|b| *b as char == c)
+            .ok_or(CallsignError::Character(c))?;
+        address = address * 40 + value as u64 + 1;
+    }
+    Ok(address.to_be_bytes()[2..].try_into().unwrap_or_default())
+}
+
+/// Decode an M17 address, None if it is not a callsign
+pub fn decode_callsign(address: &[u8; 6]) -> Option<String> {
+    let mut bytes = [0; 8];
+    bytes[2..].copy_from_slice(address);
+    let mut address = u64::from_be_bytes(bytes);
+    if address == BROADCAST {
+        return Some(String::from(BROADCAST_CALLSIGN));
+    }
+    if address == 0 || address >= MAX_ADDRESS {
+        return None;
+    }
+    let mut callsign = String::new();
+    while address > 0 {
+        callsign.push(CHARSET[(address % 40) as usize] as char);
+        address /= 40;
+    }
+    Some(callsign)
+}
+
+/// Contact setting which is not valid for OpenRTX
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContactIssue {
+    /// The name is empty or longer than 31 bytes
+    Name { contact: usize },
+    Callsign {
+        contact: usize,
+        error: CallsignError,
+    },
+    /// The address of an M17 contact is not a callsign
+    Address { contact: usize },
+}
+
+impl ContactIssue {
+    /// Index of the contact with the issue
+    pub fn contact(&self) -> usize {
+        match self {
+            ContactIssue::Name { contact }
+            | ContactIssue::Callsign { contact, .. }
+            | ContactIssue::Address { contact } => *contact,
+        }
+    }
+}
+
+impl std::fmt::Display for ContactIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let number = self.contact() + 1;
+        match self {
+            ContactIssue::Name { .. } => {
+                write!(
+                    f,
+                    "Contact {number} name must have 1 to {MAX_NAME} characters"
+                )
+            }
+            ContactIssue::Callsign { error, .. } => write!(f, "Contact {number} {error}"),
+            ContactIssue::Address { .. } => {
+                write!(f, "Contact {number} address is not a callsign")
+            }
+        }
+    }
+}
+
+/// Settings of the contacts which are not valid
+pub fn check(contacts: &[Contact]) -> Vec<ContactIssue> {
+    let mut issues = vec![];
+    for (contact, c) in contacts.iter().enumerate() {
+        if c.name.is_empty() || c.name.len() > MAX_NAME {
+            issues.push(ContactIssue::Name { contact });
+        }
+        if c.mode == OpMode::M17 && c.m17_callsign().is_none() {
+            issues.push(ContactIssue::Address { contact });
+        }
+    }
+    issues
+}
+
+#[derive(Debug)]
+pub enum ContactsError {
+    /// The CSV file has no column of this name
+    MissingColumn(&'static str),
+    Csv(csv::Error),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for ContactsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContactsError::MissingColumn(column) => {
+                write!(f, "Not a contact list, no {column} column")
+            }
+            ContactsError::Csv(e) => write!(f, "{e}"),
+            ContactsError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ContactsError {}
+
+impl From<csv::Error> for ContactsError {
+    fn from(e: csv::Error) -> Self {
+        ContactsError::Csv(e)
+    }
+}
+
+impl From<std::io::Error> for ContactsError {
+    fn from(e: std::io::Error) -> Self {
+        ContactsError::Io(e)
+    }
+}
+
+/// File format of a contact list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    VCard,
+}
+
+impl Format {
+    /// Format of a file from its extension, CSV unless it is a vCard
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("vcf") || ext.eq_ignore_ascii_case("vcard") => {
+                Format::VCard
+            }
+            _ => Format::Csv,
+        }
+    }
+}
+
+/// Entry of a contact list which could not be imported
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedContact {
+    pub name: String,
+    pub callsign: String,
+    pub error: CallsignError,
+}
+
+impl std::fmt::Display for SkippedContact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) skipped: {}",
+            self.name, self.callsign, self.error
+        )
+    }
+}
+
+/// Contacts read from a contact list
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContactsImport {
+    pub contacts: Vec<Contact>,
+    /// Entries whose callsign could not be encoded
+    pub skipped: Vec<SkippedContact>,
+}
+
+impl ContactsImport {
+    fn push(&mut self, name: String, callsign: String) {
+        match Contact::m17(name.clone(), &callsign) {
+            Ok(contact) => self.contacts.push(contact),
+            Err(error) => self.skipped.push(SkippedContact {
+                name,
+                callsign,
+                error,
+            }),
+        }
+    }
+}
+
+fn import_csv(reader: impl Read) -> Result<ContactsImport, ContactsError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let column = |name: &'static str| {
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .ok_or(ContactsError::MissingColumn(name))
+    };
+    let (name, callsign) = (column("Name")?, column("Callsign")?);
+    let mut import = ContactsImport {
+        contacts: vec![],
+        skipped: vec![],
+    };
+    for record in reader.records() {
+        let record = record?;
+        let get = |i| String::from(record.get(i).unwrap_or_default().trim());
+        import.push(get(name), get(callsign));
+    }
+    Ok(import)
+}
+
+// Value of a vCard property, without its escapes
+fn vcard_unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => (),
+        }
+    }
+    unescaped
+}
+
+fn vcard_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+fn import_vcard(mut reader: impl Read) -> Result<ContactsImport, ContactsError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    // Long lines are folded by starting the next ones with a space or a tab
+    let text = text
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut import = ContactsImport {
+        contacts: vec![],
+        skipped: vec![],
+    };
+    let (mut name, mut callsign) = (String::new(), String::new());
+    for line in text.lines() {
+        let Some((property, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Parameters follow the property name, e.g. FN;CHARSET=UTF-8
+        let property = property.split(';').next().unwrap_or_default();
+        let property = property.rsplit('.').next().unwrap_or_default();
+        match property.to_ascii_uppercase().as_str() {
+            "BEGIN" => (name, callsign) = (String::new(), String::new()),
+            "FN" => name = vcard_unescape(value.trim()),
+            VCARD_CALLSIGN => callsign = vcard_unescape(value.trim()),
+            "END" => import.push(std::mem::take(&mut name), std::mem::take(&mut callsign)),
+            _ => (),
+        }
+    }
+    Ok(import)
+}
+
+/// Read the M17 contacts of a contact list
+///
+/// Entries whose callsign can not be encoded are left out and reported.
+pub fn import(reader: impl Read, format: Format) -> Result<ContactsImport, ContactsError> {
+    match format {
+        Format::Csv => import_csv(reader),
+        Format::VCard => import_vcard(reader),
+    }
+}
+
+/// Write the M17 contacts as a contact list
+///
+/// Returns the names of the other contacts, which are left out.
+pub fn export(
+    contacts: &[Contact],
+    mut writer: impl Write,
+    format: Format,
+) -> Result<Vec<String>, ContactsError> {
+    let mut skipped = vec![];
+    let mut entries = vec![];
+    for contact in contacts {
+        match contact.m17_callsign() {
+            Some(callsign) => entries.push((contact.name.as_str(), callsign)),
+            None => skipped.push(contact.name.clone()),
+        }
+    }
+    match format {
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            csv.write_record(["Name", "Callsign"])?;
+            for (name, callsign) in &entries {
+                csv.write_record([*name, callsign.as_str()])?;
+            }
+            csv.flush()?;
+        }
+        Format::VCard => {
+            for (name, callsign) in &entries {
+                write!(
+                    writer,
+                    "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:{name}\r\nN:{name};;;;\r\n\
+                     {VCARD_CALLSIGN}:{callsign}\r\nEND:VCARD\r\n",
+                    name = vcard_escape(name)
+                )?;
+            }
+        }
+    }
+    Ok(skipped)
+}
+
+/// Read the M17 contacts of a contact list on disk, in the format of its
+/// extension
+pub fn import_file(path: &Path) -> Result<ContactsImport, ContactsError> {
+    import(std::fs::File::open(path)?, Format::from_path(path))
+}
+
+/// Save the M17 contacts as a contact list on disk, in the format of its
+/// extension
+pub fn export_file(contacts: &[Contact], path: &Path) -> Result<Vec<String>, ContactsError> {
+    export(
+        contacts,
+        std::fs::File::create(path)?,
+        Format::from_path(path),
+    )
+}
+
+/// Replace the contacts of the codeplug of a radio running OpenRTX
+///
+/// The codeplug is read from the radio first, so that only its contacts
+/// change. It is written back like by a
+/// [`CodeplugWriteJob`](super::CodeplugWriteJob).
+#[derive(Clone, Debug)]
+pub struct ContactsWriteJob {
+    pub port: String,
+    pub contacts: Vec<Contact>,
+}
+
+impl ContactsWriteJob {
+    pub fn new(port: String, contacts: Vec<Contact>) -> Self {
+        ContactsWriteJob { port, contacts }
+    }
+
+    /// Run the job on the calling thread
+    pub fn run(
+        self,
+        events: &Sender<CodeplugWriteEvent>,
+        cancel: &CancelToken,
+    ) -> Result<(), CodeplugError> {
+        let issues = check(&self.contacts);
+        if !issues.is_empty() {
+            return Err(CodeplugError::InvalidContacts(issues));
+        }
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut link = RadioLink::open(&self.port)?;
+
+        _ = events.send(JobEvent::Phase(Phase::Reading));
+        let data = codeplug::read(&mut link, cancel, |p| {
+            _ = events.send(JobEvent::Progress(p));
+        })?;
+        let mut codeplug = Codeplug::decode(&data)?;
+        let issues = codeplug.check();
+        if !issues.is_empty() {
+            return Err(CodeplugError::Invalid(issues));
+        }
+        codeplug.contacts = self.contacts;
+        codeplug::write(&mut link, &mut codeplug, events, cancel)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<(), CodeplugError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
@@ -0,0 +1,337 @@
+//! USB DFU access to TYT radios running their vendor firmware
+//!
+//! Turned on normally and attached over USB, the vendor firmware of the
+//! MD-3x0 and MD-UV3x0 answers as a DFU device, as the TYT programming
+//! software expects. TYT commands are sent as DNLOAD requests of block 0:
+//! entering the programming mode, setting the address of the following
+//! transfers, erasing a 64 KiB sector of the external flash and rebooting.
+//! The external flash is then transferred [`BLOCK_SIZE`] bytes at a time,
+//! block 2 being at the set address.
+//!
+//! The TYT bootloader answers with the same USB ID, but only allows writing
+//! the firmware: the firmware itself cannot be read out of the radio.
+
+use std::time::Duration;
+
+use rusb::UsbContext;
+
+use super::{CancelToken, Progress, RadioHW};
+
+/// USB vendor and product ID of the radios in DFU mode
+pub const USB_ID: (u16, u16) = (0x0483, 0xdf11);
+
+/// Size of the transferred blocks
+pub const BLOCK_SIZE: usize = 1024;
+
+/// Size of the external flash sectors erased before writing
+pub const SECTOR_SIZE: usize = 0x10000;
+
+// Block at the address set by the last TYT command
+const FIRST_BLOCK: u16 = 2;
+// Blocks transferred after setting an address, as the programming software
+// does for the 256 KiB codeplug of the MD-3x0
+const SEGMENT_BLOCKS: usize = 256;
+
+// DFU requests
+const DFU_DNLOAD: u8 = 1;
+const DFU_UPLOAD: u8 = 2;
+const DFU_GETSTATUS: u8 = 3;
+const DFU_CLRSTATUS: u8 = 4;
+const DFU_GETSTATE: u8 = 5;
+const DFU_ABORT: u8 = 6;
+
+// DFU states
+pub const STATE_IDLE: u8 = 2;
+pub const STATE_DNLOAD_SYNC: u8 = 3;
+pub const STATE_DNBUSY: u8 = 4;
+pub const STATE_DNLOAD_IDLE: u8 = 5;
+pub const STATE_MANIFEST_SYNC: u8 = 6;
+pub const STATE_MANIFEST: u8 = 7;
+pub const STATE_MANIFEST_WAIT_RESET: u8 = 8;
+pub const STATE_UPLOAD_IDLE: u8 = 9;
+pub const STATE_ERROR: u8 = 10;
+
+// Time given to the radio to answer a request
+const USB_TIMEOUT: Duration = Duration::from_secs(5);
+// Requests sent before giving up on reaching a state
+const MAX_RETRIES: usize = 50;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug)]
+pub enum DfuError {
+    /// No radio is attached in DFU mode
+    NotFound,
+    Usb(String),
+    /// The radio is in an unexpected DFU state
+    State(u8),
+    /// The radio answered with an unexpected amount of data
+    Protocol(String),
+    /// The transfer was aborted on request
+    Cancelled,
+}
+
+impl std::fmt::Display for DfuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DfuError::NotFound => write!(
+                f,
+                "No radio found in DFU mode, turn it on normally with its vendor firmware"
+            ),
+            DfuError::Usb(e) => write!(f, "USB error: {e}"),
+            DfuError::State(state) => write!(f, "The radio is in unexpected DFU state {state}"),
+            DfuError::Protocol(e) => write!(f, "Unexpected answer from radio: {e}"),
+            DfuError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for DfuError {}
+
+impl From<rusb::Error> for DfuError {
+    fn from(e: rusb::Error) -> Self {
+        DfuError::Usb(e.to_string())
+    }
+}
+
+/// Answer to a DFU GETSTATUS request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DfuStatus {
+    pub status: u8,
+    /// Time to wait before the next request
+    pub poll_timeout: Duration,
+    pub state: u8,
+}
+
+/// DFU requests to a radio, attached over USB or simulated
+pub trait DfuDevice {
+    fn dnload(&mut self, block: u16, data: &[u8]) -> Result<(), DfuError>;
+    fn upload(&mut self, block: u16, length: usize) -> Result<Vec<u8>, DfuError>;
+    /// Status of the radio, which moves on to the next state after a DNLOAD
+    fn get_status(&mut self) -> Result<DfuStatus, DfuError>;
+    fn get_state(&mut self) -> Result<u8, DfuError>;
+    fn clear_status(&mut self) -> Result<(), DfuError>;
+    fn abort(&mut self) -> Result<(), DfuError>;
+}
+
+struct UsbDfu {
+    handle: rusb::DeviceHandle<rusb::Context>,
+}
+
+impl DfuDevice for UsbDfu {
+    fn dnload(&mut self, block: u16, data: &[u8]) -> Result<(), DfuError> {
+        self.handle
+            .write_control(0x21, DFU_DNLOAD, block, 0, data, USB_TIMEOUT)?;
+        Ok(())
+    }
+
+    fn upload(&mut self, block: u16, length: usize) -> Result<Vec<u8>, DfuError> {
+        let mut data = vec![0; length];
+        let len = self
+            .handle
+            .read_control(0xa1, DFU_UPLOAD, block, 0, &mut data, USB_TIMEOUT)?;
+        data.truncate(len);
+        Ok(data)
+    }
+
+    fn get_status(&mut self) -> Result<DfuStatus, DfuError> {
+        let mut status = [0; 6];
+        let len = self
+            .handle
+            .read_control(0xa1, DFU_GETSTATUS, 0, 0, &mut status, USB_TIMEOUT)?;
+        if len != status.len() {
+            return Err(DfuError::Protocol(format!("{:02x?}", &status[..len])));
+        }
+        let poll_timeout = u32::from_le_bytes([status[1], status[2], status[3], 0]);
+        Ok(DfuStatus {
+            status: status[0],
+            poll_timeout: Duration::from_millis(poll_timeout as u64),
+            state: status[4],
+        })
+    }
+
+    fn get_state(&mut self) -> Result<u8, DfuError> {
+        let mut state = [0; 1];
+        self.handle
+            .read_control(0xa1, DFU_GETSTATE, 0, 0, &mut state, USB_TIMEOUT)?;
+        Ok(state[0])
+    }
+
+    fn clear_status(&mut self) -> Result<(), DfuError> {
+        self.handle
+            .write_control(0x21, DFU_CLRSTATUS, 0, 0, &[], USB_TIMEOUT)?;
+        Ok(())
+    }
+
+    fn abort(&mut self) -> Result<(), DfuError> {
+        self.handle
+            .write_control(0x21, DFU_ABORT, 0, 0, &[], USB_TIMEOUT)?;
+        Ok(())
+    }
+}
+
+/// Open the radio attached in DFU mode
+pub fn open() -> Result<Box<dyn DfuDevice>, DfuError> {
+    #[cfg(all(unix, feature = "sim"))]
+    if let Some(device) = crate::sim::virtual_dfu() {
+        return Ok(device);
+    }
+    let handle = rusb::Context::new()?
+        .open_device_with_vid_pid(USB_ID.0, USB_ID.1)
+        .ok_or(DfuError::NotFound)?;
+    handle.claim_interface(0)?;
+    Ok(Box::new(UsbDfu { handle }))
+}
+
+/// Region of the external flash holding the codeplug of the vendor
+/// firmware, as its address and size
+pub fn codeplug_region(radio: RadioHW) -> Option<(u32, usize)> {
+    match radio {
+        RadioHW::Md3x0 => Some((0, 0x40000)),
+        RadioHW::Mduv3x0 => Some((0, 0xd0000)),
+        _ => None,
+    }
+}
+
+// Bring the radio back to the idle state, ready for the next request
+fn enter_idle(device: &mut dyn DfuDevice) -> Result<(), DfuError> {
+    for _ in 0..MAX_RETRIES {
+        match device.get_state()? {
+            STATE_IDLE => return Ok(()),
+            STATE_DNLOAD_SYNC | STATE_DNLOAD_IDLE | STATE_MANIFEST_SYNC | STATE_MANIFEST
+            | STATE_UPLOAD_IDLE => device.abort()?,
+            STATE_ERROR => device.clear_status()?,
+            STATE_DNBUSY | STATE_MANIFEST_WAIT_RESET => std::thread::sleep(POLL_INTERVAL),
+            state => return Err(DfuError::State(state)),
+        }
+    }
+    Err(DfuError::State(device.get_state()?))
+}
+
+// Wait for the radio to take the data of the last DNLOAD request
+fn wait_dnload(device: &mut dyn DfuDevice) -> Result<(), DfuError> {
+    for _ in 0..MAX_RETRIES {
+        let status = device.get_status()?;
+        match status.state {
+            STATE_DNLOAD_IDLE => return Ok(()),
+            STATE_DNLOAD_SYNC | STATE_DNBUSY => std::thread::sleep(status.poll_timeout),
+            state => return Err(DfuError::State(state)),
+        }
+    }
+    Err(DfuError::State(STATE_DNBUSY))
+}
+
+// Send a TYT command, leaving the radio idle once it is done
+fn command(device: &mut dyn DfuDevice, command: &[u8]) -> Result<(), DfuError> {
+    enter_idle(device)?;
+    device.dnload(0, command)?;
+    wait_dnload(device)?;
+    enter_idle(device)
+}
+
+fn set_address(device: &mut dyn DfuDevice, address: u32) -> Result<(), DfuError> {
+    let mut request = vec![0x21];
+    request.extend_from_slice(&address.to_le_bytes());
+    command(device, &request)
+}
+
+fn erase_sector(device: &mut dyn DfuDevice, address: u32) -> Result<(), DfuError> {
+    let mut request = vec![0x41];
+    request.extend_from_slice(&address.to_le_bytes());
+    command(device, &request)
+}
+
+/// Enter the programming mode of the vendor firmware, as the TYT
+/// programming software does before reading or writing the codeplug
+pub fn enter_programming_mode(device: &mut dyn DfuDevice) -> Result<(), DfuError> {
+    for request in [
+        [0x91, 0x01],
+        [0xa2, 0x02],
+        [0xa2, 0x02],
+        [0xa2, 0x03],
+        [0xa2, 0x04],
+        [0xa2, 0x07],
+    ] {
+        command(device, &request)?;
+    }
+    Ok(())
+}
+
+/// Reboot the radio, leaving the programming mode
+pub fn reboot(device: &mut dyn DfuDevice) -> Result<(), DfuError> {
+    enter_idle(device)?;
+    device.dnload(0, &[0x91, 0x05])?;
+    // The radio resets without answering further
+    _ = device.get_status();
+    Ok(())
+}
+
+// Check that a region can be transferred in blocks
+fn check_region(address: u32, size: usize, unit: usize) -> Result<(), DfuError> {
+    if !(address as usize).is_multiple_of(unit) || !size.is_multiple_of(unit) {
+        return Err(DfuError::Protocol(format!(
+            "Region of {size} bytes at {address:#x} is not aligned on {unit} bytes"
+        )));
+    }
+    Ok(())
+}
+
+/// Read a region of the external flash, in programming mode
+pub fn read(
+    device: &mut dyn DfuDevice,
+    address: u32,
+    size: usize,
+    cancel: &CancelToken,
+    mut progress: impl FnMut(Progress),
+) -> Result<Vec<u8>, DfuError> {
+    check_region(address, size, BLOCK_SIZE)?;
+    let mut data = Vec::with_capacity(size);
+    for i in 0..size / BLOCK_SIZE {
+        if cancel.is_cancelled() {
+            return Err(DfuError::Cancelled);
+        }
+        if i % SEGMENT_BLOCKS == 0 {
+            set_address(device, address + (i * BLOCK_SIZE) as u32)?;
+        }
+        let block = device.upload(FIRST_BLOCK + (i % SEGMENT_BLOCKS) as u16, BLOCK_SIZE)?;
+        if block.len() != BLOCK_SIZE {
+            return Err(DfuError::Protocol(format!(
+                "Block of {} bytes instead of {BLOCK_SIZE}",
+                block.len()
+            )));
+        }
+        data.extend_from_slice(&block);
+        progress(Progress {
+            transferred: data.len(),
+            total: size,
+        });
+    }
+    Ok(data)
+}
+
+/// Erase and write a region of the external flash, in programming mode
+///
+/// The region must be made of whole sectors. Stopping in between would leave
+/// erased sectors, so the write cannot be cancelled.
+pub fn write(
+    device: &mut dyn DfuDevice,
+    address: u32,
+    data: &[u8],
+    mut progress: impl FnMut(Progress),
+) -> Result<(), DfuError> {
+    check_region(address, data.len(), SECTOR_SIZE)?;
+    for sector in (0..data.len()).step_by(SECTOR_SIZE) {
+        erase_sector(device, address + sector as u32)?;
+    }
+    for (i, block) in data.chunks(BLOCK_SIZE).enumerate() {
+        if i % SEGMENT_BLOCKS == 0 {
+            set_address(device, address + (i * BLOCK_SIZE) as u32)?;
+        }
+        device.dnload(FIRST_BLOCK + (i % SEGMENT_BLOCKS) as u16, block)?;
+        wait_dnload(device)?;
+        progress(Progress {
+            transferred: (i + 1) * BLOCK_SIZE,
+            total: data.len(),
+        });
+    }
+    enter_idle(device)
+}
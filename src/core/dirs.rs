@@ -0,0 +1,33 @@
+//! Per-user folders of the application
+//!
+//! The XDG base directories are used when set, falling back to the Windows
+//! application data folders and then to the usual locations in the home
+//! folder.
+
+use std::path::PathBuf;
+
+const APP_DIR: &str = "openrtx-companion";
+
+fn user_dir(xdg_var: &str, windows_var: &str, home_fallback: &str) -> PathBuf {
+    std::env::var_os(xdg_var)
+        .or_else(|| std::env::var_os(windows_var))
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(home_fallback)))
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR)
+}
+
+/// Files which can be downloaded again, such as firmware images
+pub fn cache_dir() -> PathBuf {
+    user_dir("XDG_CACHE_HOME", "LOCALAPPDATA", ".cache")
+}
+
+/// User settings, such as the trusted keys
+pub fn config_dir() -> PathBuf {
+    user_dir("XDG_CONFIG_HOME", "APPDATA", ".config")
+}
+
+/// Files which cannot be recreated, such as the stock firmware archives
+pub fn data_dir() -> PathBuf {
+    user_dir("XDG_DATA_HOME", "APPDATA", ".local/share")
+}
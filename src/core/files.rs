@@ -0,0 +1,244 @@
+//! Files of the radio storage
+//!
+//! Radios without a file system, or running a firmware which does not
+//! support the FMP file requests, show their memory areas instead, which can
+//! only be downloaded.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use super::fmp::{self, EntryKind};
+use super::{CancelToken, JobError, JobEvent, JobHandle, LinkError, Phase, RadioLink};
+
+/// Events reported by a [`TransferJob`]
+pub type TransferEvent = JobEvent<Transfer, FileError>;
+
+#[derive(Debug)]
+pub enum FileError {
+    Link(LinkError),
+    Io(std::io::Error),
+    Cancelled,
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileError::Link(e) => write!(f, "{e}"),
+            FileError::Io(e) => write!(f, "{e}"),
+            FileError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+impl JobError for FileError {
+    fn is_cancelled(&self) -> bool {
+        matches!(self, FileError::Cancelled)
+    }
+}
+
+impl From<std::io::Error> for FileError {
+    fn from(e: std::io::Error) -> Self {
+        FileError::Io(e)
+    }
+}
+
+impl From<LinkError> for FileError {
+    fn from(e: LinkError) -> Self {
+        match e {
+            LinkError::Cancelled => FileError::Cancelled,
+            e => FileError::Link(e),
+        }
+    }
+}
+
+/// Entry shown when browsing the radio
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Entry {
+    File {
+        name: String,
+        size: usize,
+    },
+    Directory {
+        name: String,
+    },
+    /// Memory area, by index, of a radio without file system
+    Memory {
+        index: usize,
+        name: String,
+        size: usize,
+    },
+}
+
+impl Entry {
+    pub fn name(&self) -> &str {
+        match self {
+            Entry::File { name, .. } | Entry::Directory { name } | Entry::Memory { name, .. } => {
+                name
+            }
+        }
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            Entry::File { size, .. } | Entry::Memory { size, .. } => Some(*size),
+            Entry::Directory { .. } => None,
+        }
+    }
+}
+
+/// Path of an entry of a directory of the radio
+pub fn join(dir: &str, name: &str) -> String {
+    format!("{}/{name}", dir.trim_end_matches('/'))
+}
+
+/// Parent of a directory of the radio
+pub fn parent(dir: &str) -> String {
+    match dir.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => String::from("/"),
+        Some((parent, _)) => String::from(parent),
+    }
+}
+
+/// Whether the firmware of the radio supports the FMP file requests
+///
+/// Firmwares without file system answer the listing of the root directory
+/// with an error status.
+pub fn has_file_access(link: &mut RadioLink) -> Result<bool, LinkError> {
+    match fmp::list(link, "/") {
+        Ok(_) => Ok(true),
+        Err(LinkError::Status(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// List a directory of the radio, directories first
+///
+/// Listing the root directory of a radio without file system returns its
+/// memory areas, see [`has_file_access`].
+pub fn browse(port: &str, dir: &str) -> Result<Vec<Entry>, LinkError> {
+    let mut link = RadioLink::open(port)?;
+    let files = match fmp::list(&mut link, dir) {
+        Ok(files) => files,
+        Err(LinkError::Status(_)) if dir == "/" => {
+            return Ok(fmp::meminfo(&mut link)?
+                .into_iter()
+                .enumerate()
+                .map(|(index, m)| Entry::Memory {
+                    index,
+                    name: m.name,
+                    size: m.size,
+                })
+                .collect())
+        }
+        Err(e) => return Err(e),
+    };
+    let mut entries: Vec<Entry> = files
+        .into_iter()
+        .map(|f| match f.kind {
+            EntryKind::Directory => Entry::Directory { name: f.name },
+            EntryKind::File => Entry::File {
+                name: f.name,
+                size: f.size,
+            },
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        let is_file = |e: &Entry| !matches!(e, Entry::Directory { .. });
+        (is_file(a), a.name()).cmp(&(is_file(b), b.name()))
+    });
+    Ok(entries)
+}
+
+pub fn rename(port: &str, from: &str, to: &str) -> Result<(), LinkError> {
+    fmp::rename(&mut RadioLink::open(port)?, from, to)
+}
+
+pub fn remove(port: &str, path: &str) -> Result<(), LinkError> {
+    fmp::remove(&mut RadioLink::open(port)?, path)
+}
+
+/// Copy between the radio and the computer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transfer {
+    Download { remote: String, local: PathBuf },
+    DumpMemory { memory: usize, local: PathBuf },
+    Upload { local: PathBuf, remote: String },
+}
+
+impl Transfer {
+    /// Upload a local file into a directory of the radio, keeping its name
+    pub fn upload(local: &Path, dir: &str) -> Transfer {
+        let name = local.file_name().unwrap_or_default().to_string_lossy();
+        Transfer::Upload {
+            local: local.to_path_buf(),
+            remote: join(dir, &name),
+        }
+    }
+}
+
+impl std::fmt::Display for Transfer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transfer::Download { remote, local } => {
+                write!(f, "{remote} to {}", local.display())
+            }
+            Transfer::DumpMemory { memory, local } => {
+                write!(f, "memory {memory} to {}", local.display())
+            }
+            Transfer::Upload { local, remote } => write!(f, "{} to {remote}", local.display()),
+        }
+    }
+}
+
+/// Copy a file between the radio and the computer
+#[derive(Clone, Debug)]
+pub struct TransferJob {
+    pub port: String,
+    pub transfer: Transfer,
+}
+
+impl TransferJob {
+    pub fn new(port: String, transfer: Transfer) -> Self {
+        TransferJob { port, transfer }
+    }
+
+    /// Run the job on the calling thread, returning the transfer done
+    ///
+    /// Cancelled uploads do not leave partial files on the radio.
+    pub fn run(
+        self,
+        events: &Sender<TransferEvent>,
+        cancel: &CancelToken,
+    ) -> Result<Transfer, FileError> {
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut link = RadioLink::open(&self.port)?;
+        let progress = |p| {
+            _ = events.send(JobEvent::Progress(p));
+        };
+        match &self.transfer {
+            Transfer::Download { remote, local } => {
+                _ = events.send(JobEvent::Phase(Phase::Reading));
+                let data = fmp::read(&mut link, remote, cancel, progress)?;
+                std::fs::write(local, data)?;
+            }
+            Transfer::DumpMemory { memory, local } => {
+                _ = events.send(JobEvent::Phase(Phase::Reading));
+                let data = fmp::dump(&mut link, *memory, cancel, progress)?;
+                std::fs::write(local, data)?;
+            }
+            Transfer::Upload { local, remote } => {
+                let data = std::fs::read(local)?;
+                _ = events.send(JobEvent::Phase(Phase::Writing));
+                fmp::write(&mut link, remote, &data, cancel, progress)?;
+            }
+        }
+        Ok(self.transfer)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<Transfer, FileError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
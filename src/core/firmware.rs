@@ -0,0 +1,200 @@
+use std::path::Path;
+
+use super::RadioHW;
+
+// Header of the firmware images wrapped for the TYT bootloader
+const TYT_WRAP_MAGIC: &[u8] = b"OutSecurityBin";
+const TYT_WRAP_HEADER_SIZE: usize = 0x100;
+// Address the TYT bootloader loads the firmware at
+const TYT_LOAD_ADDRESS: u32 = 0x0800_c000;
+const STM32_FLASH_END: u32 = 0x0810_0000;
+
+const ESP_IMAGE_MAGIC: u8 = 0xe9;
+const ESP_IMAGE_HEADER_SIZE: usize = 24;
+const ESP_SEGMENT_HEADER_SIZE: usize = 8;
+const ESP_CHIP_ID_ESP32S3: u16 = 0x0009;
+
+/// Layout of a firmware image, as found in its first bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Plain STM32 image, starting with the vector table
+    Stm32 {
+        stack: u32,
+        reset: u32,
+    },
+    /// STM32 image wrapped for the TYT bootloader
+    TytWrapped,
+    /// ESP-IDF application image
+    Esp32 {
+        chip_id: u16,
+        segments: u8,
+    },
+    Unknown,
+}
+
+/// What could be learnt from a firmware image without flashing it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    pub size: usize,
+    pub format: ImageFormat,
+    /// The image contains the OpenRTX name, vendor images do not
+    pub openrtx: bool,
+    /// The image ends before the data announced by its header
+    pub truncated: bool,
+}
+
+/// Problem found checking an image against the radio it is flashed on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageIssue {
+    TooLarge { size: usize, max: usize },
+    WrongFormat(RadioHW),
+    WrongChip(u16),
+    WrongLoadAddress(u32),
+    UnknownFormat,
+    VendorImage,
+    Truncated,
+}
+
+impl ImageIssue {
+    /// Whether flashing the image would leave the radio unusable
+    pub fn is_blocking(&self) -> bool {
+        !matches!(self, ImageIssue::UnknownFormat | ImageIssue::VendorImage)
+    }
+}
+
+impl std::fmt::Display for ImageIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageIssue::TooLarge { size, max } => write!(
+                f,
+                "Image is too large ({size} bytes), the radio holds at most {max} bytes"
+            ),
+            ImageIssue::WrongFormat(radio) => write!(f, "Image is not meant for the {radio}"),
+            ImageIssue::WrongChip(id) => write!(f, "Image is built for ESP chip id {id}"),
+            ImageIssue::WrongLoadAddress(reset) => write!(
+                f,
+                "Image entry point {reset:#010x} is outside of the firmware area"
+            ),
+            ImageIssue::UnknownFormat => write!(f, "Image format not recognized"),
+            ImageIssue::VendorImage => write!(f, "Image does not look like an OpenRTX build"),
+            ImageIssue::Truncated => write!(f, "Image is truncated"),
+        }
+    }
+}
+
+fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+// Whether the segments announced by an ESP-IDF image header are all there
+fn esp_segments_complete(data: &[u8], segments: u8) -> bool {
+    let mut offset = ESP_IMAGE_HEADER_SIZE;
+    for _ in 0..segments {
+        let Some(len) = le_u32(data, offset + 4) else {
+            return false;
+        };
+        offset += ESP_SEGMENT_HEADER_SIZE + len as usize;
+    }
+    offset <= data.len()
+}
+
+impl FirmwareInfo {
+    pub fn parse(data: &[u8]) -> Self {
+        let format = if data.starts_with(TYT_WRAP_MAGIC) {
+            ImageFormat::TytWrapped
+        } else if data.first() == Some(&ESP_IMAGE_MAGIC) && data.len() >= ESP_IMAGE_HEADER_SIZE {
+            ImageFormat::Esp32 {
+                segments: data[1],
+                chip_id: u16::from_le_bytes([data[12], data[13]]),
+            }
+        } else {
+            match (le_u32(data, 0), le_u32(data, 4)) {
+                // Stack in SRAM or CCM, Thumb reset handler in flash
+                (Some(stack), Some(reset))
+                    if (stack >> 24 == 0x20 || stack >> 24 == 0x10)
+                        && reset >> 24 == 0x08
+                        && reset & 1 == 1 =>
+                {
+                    ImageFormat::Stm32 { stack, reset }
+                }
+                _ => ImageFormat::Unknown,
+            }
+        };
+        let truncated = match format {
+            ImageFormat::TytWrapped => data.len() <= TYT_WRAP_HEADER_SIZE,
+            ImageFormat::Esp32 { segments, .. } => !esp_segments_complete(data, segments),
+            _ => false,
+        };
+        FirmwareInfo {
+            size: data.len(),
+            format,
+            openrtx: data.windows(7).any(|w| w == b"OpenRTX"),
+            truncated,
+        }
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(FirmwareInfo::parse(&std::fs::read(path)?))
+    }
+
+    /// Check whether the image can be flashed on a radio
+    pub fn check(&self, radio: RadioHW) -> Vec<ImageIssue> {
+        let mut issues = vec![];
+        if self.truncated {
+            issues.push(ImageIssue::Truncated);
+        }
+        let payload = match self.format {
+            ImageFormat::TytWrapped => self.size.saturating_sub(TYT_WRAP_HEADER_SIZE),
+            _ => self.size,
+        };
+        if payload > radio.firmware_size() {
+            issues.push(ImageIssue::TooLarge {
+                size: payload,
+                max: radio.firmware_size(),
+            });
+        }
+        match (&self.format, radio) {
+            (ImageFormat::Unknown, _) => issues.push(ImageIssue::UnknownFormat),
+            (ImageFormat::Stm32 { reset, .. }, RadioHW::Md3x0 | RadioHW::Mduv3x0) => {
+                if !(TYT_LOAD_ADDRESS..STM32_FLASH_END).contains(reset) {
+                    issues.push(ImageIssue::WrongLoadAddress(*reset));
+                }
+            }
+            (ImageFormat::TytWrapped, RadioHW::Md3x0 | RadioHW::Mduv3x0) => (),
+            (ImageFormat::Esp32 { chip_id, .. }, RadioHW::Twrplus) => {
+                if *chip_id != ESP_CHIP_ID_ESP32S3 {
+                    issues.push(ImageIssue::WrongChip(*chip_id));
+                }
+            }
+            (_, radio) => issues.push(ImageIssue::WrongFormat(radio)),
+        }
+        // The content of wrapped images is encrypted and cannot be searched
+        if !self.openrtx && self.format != ImageFormat::TytWrapped {
+            issues.push(ImageIssue::VendorImage);
+        }
+        issues
+    }
+}
+
+impl std::fmt::Display for FirmwareInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.format {
+            ImageFormat::Stm32 { reset, .. } => write!(f, "STM32 image, entry {reset:#010x}")?,
+            ImageFormat::TytWrapped => write!(f, "TYT wrapped image")?,
+            ImageFormat::Esp32 { chip_id, segments } => {
+                write!(f, "ESP32 image, chip id {chip_id}, {segments} segments")?
+            }
+            ImageFormat::Unknown => write!(f, "Unknown image")?,
+        }
+        write!(f, ", {} KiB", self.size.div_ceil(1024))?;
+        if self.truncated {
+            write!(f, ", truncated")?;
+        }
+        if self.openrtx {
+            write!(f, ", OpenRTX build")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use rtxflash::{flash, target};
+
+use super::firmware::{FirmwareInfo, ImageFormat, ImageIssue};
+use super::job::relay_progress;
+use super::ports::usb_id;
+use super::signature::{Keyring, SignatureError};
+use super::{
+    cat, get_ports, CancelToken, JobError, JobEvent, JobHandle, LinkError, Phase, RadioHW,
+    RadioLink, SerialPort,
+};
+
+// Time given to the radio to reboot into the new firmware
+const REBOOT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Events reported by a [`FlashJob`]
+pub type FlashEvent = JobEvent<FlashOutcome, FlashError>;
+
+/// Result of a successful flash
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FlashOutcome {
+    /// The firmware was written, the verification was not requested
+    Flashed,
+    /// The firmware was written, but could not be verified for this reason
+    ///
+    /// This is a warning: nothing tells that the radio runs the image.
+    NotVerified(String),
+    /// The rebooted radio, whose hardware name is given, runs the firmware
+    /// version found in the flashed image
+    Verified { info: String, version: String },
+}
+
+#[derive(Debug)]
+pub enum FlashError {
+    FirmwareNotFound(PathBuf),
+    /// The image would leave the radio unusable
+    InvalidImage(ImageIssue),
+    Signature(SignatureError),
+    Flash(std::io::Error),
+    /// The rebooted radio reports another model than the flashed target
+    WrongRadio(String),
+    /// The radio did not answer over rtxlink after flashing
+    NoAnswer,
+    /// The rebooted radio runs this firmware version, which is not found in
+    /// the image
+    VerifyFailed(String),
+    Link(LinkError),
+    Cancelled,
+}
+
+impl std::fmt::Display for FlashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlashError::FirmwareNotFound(path) => {
+                write!(f, "Firmware not found: {}", path.display())
+            }
+            FlashError::InvalidImage(issue) => write!(f, "{issue}"),
+            FlashError::Signature(e) => write!(f, "{e}"),
+            FlashError::Flash(e) => write!(f, "{e}"),
+            FlashError::WrongRadio(info) => write!(
+                f,
+                "The radio reports itself as {info}, which is not the flashed target"
+            ),
+            FlashError::NoAnswer => {
+                write!(f, "The radio did not answer over rtxlink after flashing")
+            }
+            FlashError::VerifyFailed(version) => write!(
+                f,
+                "The radio runs firmware {version}, which is not the flashed image"
+            ),
+            FlashError::Link(e) => write!(f, "{e}"),
+            FlashError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for FlashError {}
+
+impl JobError for FlashError {
+    fn is_cancelled(&self) -> bool {
+        matches!(self, FlashError::Cancelled)
+    }
+}
+
+impl From<LinkError> for FlashError {
+    fn from(e: LinkError) -> Self {
+        match e {
+            LinkError::Cancelled => FlashError::Cancelled,
+            e => FlashError::Link(e),
+        }
+    }
+}
+
+/// Find a flashing target by its name, ignoring case
+pub fn find_target(name: &str) -> Option<target::Target> {
+    target::get_targets()
+        .into_iter()
+        .find(|t| t.to_string().eq_ignore_ascii_case(name))
+}
+
+/// Identify the radio behind a device in bootloader mode
+///
+/// The identification string of the bootloader is preferred, as the USB ID
+/// is shared by several radios.
+pub fn detect_radio(device: &target::DeviceInfo) -> Option<RadioHW> {
+    RadioHW::from_info(&device.to_string()).or_else(|| {
+        let (vid, pid) = usb_id(&device.port)?;
+        RadioHW::from_usb_id(vid, pid)
+    })
+}
+
+/// Radio model a flashing target is meant for
+pub fn target_radio(target: &target::Target) -> Option<RadioHW> {
+    RadioHW::from_info(&target.to_string())
+}
+
+/// Flashing target matching a radio model
+pub fn radio_target(radio: RadioHW) -> Option<target::Target> {
+    target::get_targets()
+        .into_iter()
+        .find(|t| target_radio(t) == Some(radio))
+}
+
+/// Write a firmware image on a device in bootloader mode
+#[derive(Clone, Debug)]
+pub struct FlashJob {
+    pub target: target::Target,
+    /// Port of the device, as reported by `rtxflash::target::get_devices()`
+    pub port: String,
+    pub firmware: PathBuf,
+    /// Check that the rebooted radio runs the firmware version found in the
+    /// image
+    pub verify: bool,
+    /// Refuse images not signed by a key of the default [`Keyring`]
+    pub strict: bool,
+}
+
+impl FlashJob {
+    pub fn new(target: target::Target, port: String, firmware: impl Into<PathBuf>) -> Self {
+        FlashJob {
+            target,
+            port,
+            firmware: firmware.into(),
+            verify: false,
+            strict: false,
+        }
+    }
+
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Run the job on the calling thread
+    ///
+    /// Cancellation is possible until the firmware starts being written,
+    /// so that the bootloader is never left with a partial write, and again
+    /// while waiting for the rebooted radio.
+    pub fn run(
+        self,
+        events: &Sender<FlashEvent>,
+        cancel: &CancelToken,
+    ) -> Result<FlashOutcome, FlashError> {
+        if !self.firmware.is_file() {
+            return Err(FlashError::FirmwareNotFound(self.firmware));
+        }
+        if self.strict {
+            _ = events.send(JobEvent::Phase(Phase::Checking));
+            Keyring::load_default()
+                .and_then(|keyring| keyring.verify(&self.firmware))
+                .map_err(FlashError::Signature)?;
+        }
+        let image = std::fs::read(&self.firmware).map_err(FlashError::Flash)?;
+        let info = FirmwareInfo::parse(&image);
+        // The target is given to rtxflash, which takes it by value
+        let radio = target_radio(&self.target);
+        let target_name = self.target.to_string();
+        if let Some(radio) = radio {
+            _ = events.send(JobEvent::Phase(Phase::Checking));
+            if let Some(issue) = info.check(radio).into_iter().find(ImageIssue::is_blocking) {
+                return Err(FlashError::InvalidImage(issue));
+            }
+        }
+        let ports = get_ports();
+        // rtxflash expects base path, not URI
+        let path = self.firmware.to_string_lossy().to_string();
+        _ = events.send(JobEvent::Phase(Phase::Writing));
+        let (raw_tx, relay) = relay_progress(events);
+        let res = cancel
+            .uninterruptible(|| flash::flash(self.target, self.port.clone(), path, Some(&raw_tx)));
+        drop(raw_tx);
+        _ = relay.join();
+        res.ok_or(FlashError::Cancelled)?
+            .map_err(FlashError::Flash)?;
+
+        if !self.verify {
+            return Ok(FlashOutcome::Flashed);
+        }
+        // The content of wrapped images is encrypted and cannot be searched,
+        // it may as well be a vendor firmware which does not speak rtxlink
+        if info.format == ImageFormat::TytWrapped {
+            return Ok(FlashOutcome::NotVerified(String::from(
+                "TYT wrapped images are encrypted",
+            )));
+        }
+        let Some(radio) = radio else {
+            return Ok(FlashOutcome::NotVerified(format!(
+                "{target_name} is not a known radio"
+            )));
+        };
+        _ = events.send(JobEvent::Phase(Phase::Rebooting));
+        let (mut link, radio_info) = wait_for_radio(radio, &ports, &self.port, cancel)?;
+        _ = events.send(JobEvent::Phase(Phase::Checking));
+        Ok(match verify_firmware(&mut link, &image)? {
+            Some(version) => FlashOutcome::Verified {
+                info: radio_info,
+                version,
+            },
+            None => FlashOutcome::NotVerified(format!(
+                "OpenRTX answers on {radio_info}, but does not report its firmware version"
+            )),
+        })
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<FlashOutcome, FlashError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
+
+/// Check that the firmware version reported by a radio is found in the
+/// image flashed on it
+///
+/// The bootloader does not allow reading the firmware back, the version
+/// string is built into the image instead. Returns the version, or None when
+/// the firmware does not report it.
+pub fn verify_firmware(link: &mut RadioLink, image: &[u8]) -> Result<Option<String>, FlashError> {
+    let version = match cat::version(link) {
+        Ok(version) => version,
+        Err(LinkError::Status(cat::ENOTSUP)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let found = !version.is_empty()
+        && image
+            .windows(version.len())
+            .any(|w| w == version.as_bytes());
+    match found {
+        true => Ok(Some(version)),
+        false => Err(FlashError::VerifyFailed(version)),
+    }
+}
+
+// Wait for the radio to come back with its new firmware, on the port used to
+// flash it or on a new one with the USB ID of the radios running OpenRTX, and
+// check that it reports the flashed model over rtxlink, returning the link to
+// it and the hardware name it reports
+fn wait_for_radio(
+    radio: RadioHW,
+    before: &[SerialPort],
+    port: &str,
+    cancel: &CancelToken,
+) -> Result<(RadioLink, String), FlashError> {
+    let deadline = Instant::now() + REBOOT_TIMEOUT;
+    while Instant::now() < deadline {
+        std::thread::sleep(Duration::from_secs(1));
+        if cancel.is_cancelled() {
+            return Err(FlashError::Cancelled);
+        }
+        // Other devices are left alone, the link times out if the radio
+        // does not answer. Where USB IDs can not be read, every new port is
+        // tried.
+        let candidates = get_ports().into_iter().filter(|p| {
+            let expected = match usb_id(&p.name) {
+                Some(id) => id == radio.rtxlink_usb_id(),
+                None => true,
+            };
+            (p.name == port || !before.contains(p)) && expected
+        });
+        for candidate in candidates {
+            let Ok(mut link) = RadioLink::open(&candidate.name) else {
+                continue;
+            };
+            if let Ok(info) = cat::info(&mut link) {
+                if RadioHW::from_info(&info) != Some(radio) {
+                    return Err(FlashError::WrongRadio(info));
+                }
+                return Ok((link, info));
+            }
+        }
+    }
+    Err(FlashError::NoAnswer)
+}
@@ -0,0 +1,368 @@
+//! flrig compatible XML-RPC API
+//!
+//! flrig is controlled with XML-RPC calls over HTTP, like `rig.get_vfo` or
+//! `rig.set_ptt`. The calls supported here are answered with CAT requests
+//! to the radio, through a [`super::RigServer`] serving
+//! [`super::RigProtocol::Flrig`]. Frequencies are set as simplex, on both RX
+//! and TX, and the S-meter is scaled from 0 (S0) to 100 (S9+60 dB), with S9
+//! at 50, as flrig does.
+
+use super::cat;
+use super::codeplug::OpMode;
+use super::rigctl::{Exchange, S9};
+use super::RadioLink;
+
+/// Default TCP port of flrig
+pub const DEFAULT_PORT: u16 = 12345;
+
+/// Version of flrig reported to the clients
+const VERSION: &str = "2.0.0";
+
+// Largest HTTP header and body accepted, XML-RPC calls being much smaller
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+const METHODS: [&str; 19] = [
+    "main.get_version",
+    "rig.get_AB",
+    "rig.get_info",
+    "rig.get_mode",
+    "rig.get_modeA",
+    "rig.get_modes",
+    "rig.get_ptt",
+    "rig.get_smeter",
+    "rig.get_vfo",
+    "rig.get_vfoA",
+    "rig.get_xcvr",
+    "rig.set_frequency",
+    "rig.set_mode",
+    "rig.set_modeA",
+    "rig.set_ptt",
+    "rig.set_vfo",
+    "rig.set_vfoA",
+    "system.listMethods",
+    "system.methodHelp",
+];
+
+/// XML-RPC value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Double(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Empty value, returned by the calls setting the radio
+    Nil,
+}
+
+impl Value {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(value) => Some(*value as f64),
+            Value::Double(value) => Some(*value),
+            Value::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn to_xml(&self) -> String {
+        match self {
+            Value::Int(value) => format!("<value><i4>{value}</i4></value>"),
+            Value::Double(value) => format!("<value><double>{value}</double></value>"),
+            Value::String(value) => format!("<value>{}</value>", escape(value)),
+            Value::Array(values) => {
+                let values: String = values.iter().map(Value::to_xml).collect();
+                format!("<value><array><data>{values}</data></array></value>")
+            }
+            Value::Nil => String::from("<value></value>"),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{value}"),
+            Value::Double(value) => write!(f, "{value}"),
+            Value::String(value) => write!(f, "{value:?}"),
+            Value::Array(values) => {
+                let values: Vec<String> = values.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            Value::Nil => write!(f, "ok"),
+        }
+    }
+}
+
+/// XML-RPC method call
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub method: String,
+    pub params: Vec<Value>,
+}
+
+impl std::fmt::Display for Call {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params: Vec<String> = self.params.iter().map(Value::to_string).collect();
+        write!(f, "{}({})", self.method, params.join(", "))
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// Content of the first `tag` element of `xml`, and the text following it
+fn element<'a>(xml: &'a str, tag: &str) -> Option<(&'a str, &'a str)> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some((&xml[start..end], &xml[end + close.len()..]))
+}
+
+// Scalar value, from the content of a `value` element
+fn parse_value(xml: &str) -> Option<Value> {
+    let trimmed = xml.trim();
+    let Some(tag) = trimmed.strip_prefix('<') else {
+        // Untyped values are strings
+        return Some(Value::String(unescape(xml)));
+    };
+    let name = &tag[..tag.find('>')?];
+    if let Some(name) = name.strip_suffix('/') {
+        return (name == "string").then(|| Value::String(String::new()));
+    }
+    let (content, _) = element(trimmed, name)?;
+    match name {
+        "i4" | "int" | "boolean" => content.trim().parse().ok().map(Value::Int),
+        "double" => content.trim().parse().ok().map(Value::Double),
+        "string" => Some(Value::String(unescape(content))),
+        _ => None,
+    }
+}
+
+/// Parse the body of an XML-RPC request
+pub fn parse_call(xml: &str) -> Option<Call> {
+    let (method, _) = element(xml, "methodName")?;
+    let mut params = vec![];
+    let mut rest = element(xml, "params").map_or("", |(params, _)| params);
+    while let Some((param, next)) = element(rest, "param") {
+        let (value, _) = element(param, "value")?;
+        params.push(parse_value(value)?);
+        rest = next;
+    }
+    Some(Call {
+        method: method.trim().to_string(),
+        params,
+    })
+}
+
+/// Body of the XML-RPC response to a call
+pub fn response(result: &Result<Value, String>) -> String {
+    let body = match result {
+        Ok(value) => format!("<params><param>{}</param></params>", value.to_xml()),
+        Err(fault) => format!(
+            "<fault><value><struct>\
+             <member><name>faultCode</name><value><i4>-1</i4></value></member>\
+             <member><name>faultString</name>{}</member>\
+             </struct></value></fault>",
+            Value::String(fault.clone()).to_xml()
+        ),
+    };
+    format!("<?xml version=\"1.0\"?>\r\n<methodResponse>{body}</methodResponse>\r\n")
+}
+
+fn mode_name(mode: OpMode) -> Option<&'static str> {
+    match mode {
+        OpMode::Fm => Some("FM"),
+        OpMode::M17 => Some("M17"),
+        _ => None,
+    }
+}
+
+fn parse_mode(name: &str) -> Option<OpMode> {
+    match name {
+        "FM" | "FMN" => Some(OpMode::Fm),
+        "M17" => Some(OpMode::M17),
+        _ => None,
+    }
+}
+
+/// S-meter reading of flrig for a signal strength in dBm
+pub fn smeter(rssi: i16) -> i32 {
+    let rssi = i32::from(rssi);
+    let s9 = i32::from(S9);
+    // 6 dB per S unit below S9, 60 dB above S9 for the other half
+    let value = match rssi <= s9 {
+        true => 50 - (s9 - rssi) * 50 / 54,
+        false => 50 + (rssi - s9) * 50 / 60,
+    };
+    value.clamp(0, 100)
+}
+
+/// Answer a call with CAT requests to the radio
+pub fn execute(link: &mut RadioLink, call: &Call) -> Result<Value, String> {
+    let param = |index: usize| {
+        call.params
+            .get(index)
+            .ok_or(format!("{} expects {} parameters", call.method, index + 1))
+    };
+    let value = match call.method.as_str() {
+        "main.get_version" => Value::String(String::from(VERSION)),
+        "system.listMethods" => Value::Array(
+            METHODS
+                .iter()
+                .map(|method| Value::String(String::from(*method)))
+                .collect(),
+        ),
+        "system.methodHelp" => Value::String(String::new()),
+        "rig.get_xcvr" | "rig.get_info" => {
+            Value::String(cat::info(link).map_err(|e| e.to_string())?)
+        }
+        "rig.get_AB" => Value::String(String::from("A")),
+        "rig.get_vfo" | "rig.get_vfoA" => {
+            let frequency = cat::rx_frequency(link).map_err(|e| e.to_string())?;
+            Value::String(frequency.to_string())
+        }
+        "rig.set_vfo" | "rig.set_vfoA" | "rig.set_frequency" => {
+            let frequency = param(0)?
+                .as_f64()
+                .filter(|frequency| *frequency > 0.0)
+                .ok_or("Invalid frequency")?;
+            cat::set_frequency(link, frequency.round() as u32).map_err(|e| e.to_string())?;
+            Value::Nil
+        }
+        "rig.get_mode" | "rig.get_modeA" => {
+            let mode = cat::op_mode(link).map_err(|e| e.to_string())?;
+            let name = mode_name(mode).ok_or(format!("Unsupported mode {mode:?}"))?;
+            Value::String(String::from(name))
+        }
+        "rig.set_mode" | "rig.set_modeA" => {
+            let name = param(0)?.as_str().ok_or("Invalid mode")?;
+            let mode = parse_mode(name).ok_or(format!("Unsupported mode {name}"))?;
+            cat::set_op_mode(link, mode).map_err(|e| e.to_string())?;
+            Value::Nil
+        }
+        "rig.get_modes" => Value::Array(vec![
+            Value::String(String::from("FM")),
+            Value::String(String::from("M17")),
+        ]),
+        "rig.get_ptt" => Value::Int(cat::ptt(link).map_err(|e| e.to_string())? as i32),
+        "rig.set_ptt" => {
+            let ptt = param(0)?.as_f64().ok_or("Invalid PTT state")?;
+            cat::set_ptt(link, ptt != 0.0).map_err(|e| e.to_string())?;
+            Value::Nil
+        }
+        "rig.get_smeter" => Value::Int(smeter(cat::rssi(link).map_err(|e| e.to_string())?)),
+        method => return Err(format!("Unknown method {method}")),
+    };
+    Ok(value)
+}
+
+// End of the HTTP header, including the empty line
+fn header_end(data: &[u8]) -> Option<usize> {
+    data.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|end| end + 4)
+}
+
+// Answer a request which is not served with an HTTP error, and close the
+// connection
+fn reject(status: &str, request: &str, pending: &mut Vec<u8>) -> Exchange {
+    pending.clear();
+    Exchange {
+        request: String::from(request),
+        reply: String::from(status),
+        data: format!(
+            "HTTP/1.1 {status}\r\nServer: openrtx-companion\r\n\
+             Content-Length: 0\r\nConnection: close\r\n\r\n"
+        )
+        .into_bytes(),
+        close: true,
+    }
+}
+
+// Answer the next complete HTTP request
+pub(super) fn serve(link: &mut RadioLink, pending: &mut Vec<u8>) -> Option<Exchange> {
+    let Some(body_start) = header_end(pending).filter(|end| *end <= MAX_HEADER_SIZE) else {
+        return (pending.len() > MAX_HEADER_SIZE).then(|| {
+            reject(
+                "431 Request Header Fields Too Large",
+                "Oversized header",
+                pending,
+            )
+        });
+    };
+    let header = String::from_utf8_lossy(&pending[..body_start]).to_string();
+    let request_line = header.lines().next().unwrap_or_default().to_string();
+    match request_line.split(' ').collect::<Vec<_>>()[..] {
+        ["POST", _, version] if version.starts_with("HTTP/1.") => (),
+        [_, _, version] if version.starts_with("HTTP/1.") => {
+            return Some(reject("405 Method Not Allowed", &request_line, pending))
+        }
+        _ => return Some(reject("400 Bad Request", &request_line, pending)),
+    }
+    let field = |name: &str| {
+        header.lines().find_map(|line| {
+            let (field, value) = line.split_once(':')?;
+            field
+                .trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+    let length: usize = match field("Content-Length").map(|length| length.parse()) {
+        Some(Ok(length)) if length <= MAX_BODY_SIZE => length,
+        Some(Ok(_)) => return Some(reject("413 Content Too Large", &request_line, pending)),
+        Some(Err(_)) => return Some(reject("400 Bad Request", &request_line, pending)),
+        None => return Some(reject("411 Length Required", &request_line, pending)),
+    };
+    if pending.len() < body_start + length {
+        return None;
+    }
+    let request: Vec<u8> = pending.drain(..body_start + length).collect();
+    let body = String::from_utf8_lossy(&request[body_start..]);
+
+    let Some(call) = parse_call(&body) else {
+        return Some(reject(
+            "400 Bad Request",
+            "Invalid XML-RPC request",
+            pending,
+        ));
+    };
+    let result = execute(link, &call);
+    let reply = match &result {
+        Ok(value) => value.to_string(),
+        Err(fault) => format!("fault: {fault}"),
+    };
+    let body = response(&result);
+    let data = format!(
+        "HTTP/1.1 200 OK\r\nServer: openrtx-companion\r\n\
+         Content-Type: text/xml\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let close = field("Connection").is_some_and(|c| c.eq_ignore_ascii_case("close"));
+    Some(Exchange {
+        request: call.to_string(),
+        reply,
+        data: data.into_bytes(),
+        close,
+    })
+}
@@ -0,0 +1,384 @@
+//! rtxlink File and Memory management Protocol (FMP)
+//!
+//! An FMP frame is made of an opcode, a status byte and a list of up to 255
+//! parameters, each prefixed by its length. Bulk data for DUMP and FLASH
+//! requests is moved with the DAT protocol: every DAT frame carries a block
+//! index followed by up to [`DAT_BLOCK_SIZE`] bytes and is acknowledged by
+//! the receiver with a DAT frame carrying the same index.
+//!
+//! File requests take absolute paths on the radio storage. READ answers with
+//! the file size and sends its content like DUMP, WRITE takes the path and
+//! size of the file and receives its content like FLASH. LIST answers with
+//! one [`FileEntry`] per parameter.
+
+use rtxlink::link::Protocol;
+
+use super::link::{LinkError, RadioLink};
+use super::{CancelToken, Progress};
+
+/// Maximum payload of a DAT frame
+pub const DAT_BLOCK_SIZE: usize = 1024;
+
+/// FMP frame opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    MemInfo = 0x01,
+    Dump = 0x02,
+    Flash = 0x03,
+    Read = 0x04,
+    Write = 0x05,
+    List = 0x06,
+    Move = 0x07,
+    Copy = 0x08,
+    Mkdir = 0x09,
+    Rm = 0x0a,
+    Reset = 0x0b,
+}
+
+impl Opcode {
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        [
+            Opcode::MemInfo,
+            Opcode::Dump,
+            Opcode::Flash,
+            Opcode::Read,
+            Opcode::Write,
+            Opcode::List,
+            Opcode::Move,
+            Opcode::Copy,
+            Opcode::Mkdir,
+            Opcode::Rm,
+            Opcode::Reset,
+        ]
+        .into_iter()
+        .find(|o| *o as u8 == byte)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FmpFrame {
+    pub opcode: Opcode,
+    /// errno-like status, zero on success
+    pub status: u8,
+    pub params: Vec<Vec<u8>>,
+}
+
+impl FmpFrame {
+    pub fn new(opcode: Opcode, params: Vec<Vec<u8>>) -> Self {
+        FmpFrame {
+            opcode,
+            status: 0,
+            params,
+        }
+    }
+
+    /// Encode the frame, failing when the parameters do not fit in it
+    pub fn encode(&self) -> Result<Vec<u8>, LinkError> {
+        let count = u8::try_from(self.params.len()).map_err(|_| {
+            LinkError::Protocol(format!("Too many parameters: {}", self.params.len()))
+        })?;
+        let mut data = vec![self.opcode as u8, self.status, count];
+        for p in &self.params {
+            let len = u8::try_from(p.len())
+                .map_err(|_| LinkError::Protocol(format!("Parameter too long: {}", p.len())))?;
+            data.push(len);
+            data.extend_from_slice(p);
+        }
+        Ok(data)
+    }
+
+    pub fn decode(data: &[u8]) -> Option<FmpFrame> {
+        let (header, mut rest) = data.split_at_checked(3)?;
+        let opcode = Opcode::from_byte(header[0])?;
+        let mut params = vec![];
+        for _ in 0..header[2] {
+            let (len, tail) = rest.split_first()?;
+            let (param, tail) = tail.split_at_checked(*len as usize)?;
+            params.push(param.to_vec());
+            rest = tail;
+        }
+        Some(FmpFrame {
+            opcode,
+            status: header[1],
+            params,
+        })
+    }
+}
+
+/// Memory area description returned by MEMINFO
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemInfo {
+    pub name: String,
+    pub size: usize,
+}
+
+impl MemInfo {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = (self.size as u32).to_le_bytes().to_vec();
+        data.extend_from_slice(self.name.as_bytes());
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Option<MemInfo> {
+        let (size, name) = data.split_at_checked(4)?;
+        Some(MemInfo {
+            name: String::from_utf8_lossy(name).to_string(),
+            size: u32::from_le_bytes(size.try_into().ok()?) as usize,
+        })
+    }
+}
+
+/// Kind of a file system entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File = 0x00,
+    Directory = 0x01,
+}
+
+/// File system entry returned by LIST
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub size: usize,
+}
+
+impl FileEntry {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![self.kind as u8];
+        data.extend_from_slice(&(self.size as u32).to_le_bytes());
+        data.extend_from_slice(self.name.as_bytes());
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Option<FileEntry> {
+        let (&kind, rest) = data.split_first()?;
+        let (size, name) = rest.split_at_checked(4)?;
+        Some(FileEntry {
+            name: String::from_utf8_lossy(name).to_string(),
+            kind: match kind {
+                0x00 => EntryKind::File,
+                0x01 => EntryKind::Directory,
+                _ => return None,
+            },
+            size: u32::from_le_bytes(size.try_into().ok()?) as usize,
+        })
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+}
+
+/// Send an FMP request and check the status of the answer
+pub fn request(link: &mut RadioLink, request: FmpFrame) -> Result<FmpFrame, LinkError> {
+    let reply = link.request(Protocol::FMP, request.encode()?)?;
+    let reply = FmpFrame::decode(&reply).ok_or(LinkError::Protocol(format!("{reply:02x?}")))?;
+    if reply.opcode != request.opcode {
+        return Err(LinkError::Protocol(format!("{:?}", reply.opcode)));
+    }
+    if reply.status != 0 {
+        return Err(LinkError::Status(reply.status));
+    }
+    Ok(reply)
+}
+
+/// List the memory areas of the radio
+pub fn meminfo(link: &mut RadioLink) -> Result<Vec<MemInfo>, LinkError> {
+    let reply = request(link, FmpFrame::new(Opcode::MemInfo, vec![]))?;
+    reply
+        .params
+        .iter()
+        .map(|p| MemInfo::decode(p).ok_or(LinkError::Protocol(format!("{p:02x?}"))))
+        .collect()
+}
+
+/// Read the whole content of a memory area
+///
+/// When `cancel` is set, the transfer is aborted on the radio side before
+/// returning [`LinkError::Cancelled`].
+pub fn dump(
+    link: &mut RadioLink,
+    memory: usize,
+    cancel: &CancelToken,
+    progress: impl FnMut(Progress),
+) -> Result<Vec<u8>, LinkError> {
+    let reply = request(link, FmpFrame::new(Opcode::Dump, vec![vec![memory as u8]]))?;
+    receive_data(link, &reply, cancel, progress)
+}
+
+// Receive the DAT blocks following a DUMP or READ answer, which holds the
+// size of the data
+fn receive_data(
+    link: &mut RadioLink,
+    reply: &FmpFrame,
+    cancel: &CancelToken,
+    mut progress: impl FnMut(Progress),
+) -> Result<Vec<u8>, LinkError> {
+    let total = reply
+        .params
+        .first()
+        .and_then(|s| <[u8; 4]>::try_from(s.as_slice()).ok())
+        .map(|s| u32::from_le_bytes(s) as usize)
+        .ok_or(LinkError::Protocol(String::from("Missing transfer size")))?;
+
+    let mut data = Vec::with_capacity(total);
+    // Blocks are numbered from 0, the index wrapping around after 255
+    let mut expected = 0u8;
+    while data.len() < total {
+        let block = link.receive(Protocol::DAT)?;
+        let (&index, payload) = block
+            .split_first()
+            .ok_or(LinkError::Protocol(String::from("Empty data block")))?;
+        // Do not acknowledge the block, so that the radio stops sending
+        if cancel.is_cancelled() {
+            reset(link)?;
+            return Err(LinkError::Cancelled);
+        }
+        // The previous block sent again, as its acknowledge was lost
+        if index == expected.wrapping_sub(1) && !data.is_empty() {
+            link.send(Protocol::DAT, vec![index]);
+            continue;
+        }
+        if index != expected {
+            reset(link)?;
+            return Err(LinkError::Protocol(format!(
+                "Data block {index} received instead of {expected}"
+            )));
+        }
+        expected = expected.wrapping_add(1);
+        data.extend_from_slice(payload);
+        link.send(Protocol::DAT, vec![index]);
+        progress(Progress {
+            transferred: data.len().min(total),
+            total,
+        });
+    }
+    data.truncate(total);
+    Ok(data)
+}
+
+/// Write a memory area, from its beginning
+pub fn flash(
+    link: &mut RadioLink,
+    memory: usize,
+    data: &[u8],
+    progress: impl FnMut(Progress),
+) -> Result<(), LinkError> {
+    let size = (data.len() as u32).to_le_bytes().to_vec();
+    request(
+        link,
+        FmpFrame::new(Opcode::Flash, vec![vec![memory as u8], size]),
+    )?;
+    send_data(link, data, &CancelToken::new(), progress)
+}
+
+// Send data as DAT blocks after a FLASH or WRITE request
+fn send_data(
+    link: &mut RadioLink,
+    data: &[u8],
+    cancel: &CancelToken,
+    mut progress: impl FnMut(Progress),
+) -> Result<(), LinkError> {
+    let mut transferred = 0;
+    for (i, chunk) in data.chunks(DAT_BLOCK_SIZE).enumerate() {
+        if cancel.is_cancelled() {
+            reset(link)?;
+            return Err(LinkError::Cancelled);
+        }
+        // The radio expects the index to wrap around after 255, which
+        // the truncation does
+        let index = i as u8;
+        let mut block = vec![index];
+        block.extend_from_slice(chunk);
+        let ack = link.request(Protocol::DAT, block)?;
+        if ack.first() != Some(&index) {
+            return Err(LinkError::Protocol(format!("Bad acknowledge {ack:02x?}")));
+        }
+        transferred += chunk.len();
+        progress(Progress {
+            transferred,
+            total: data.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Abort any transfer in progress on the radio
+pub fn reset(link: &mut RadioLink) -> Result<(), LinkError> {
+    request(link, FmpFrame::new(Opcode::Reset, vec![]))?;
+    Ok(())
+}
+
+fn path_param(path: &str) -> Vec<u8> {
+    path.as_bytes().to_vec()
+}
+
+/// List the entries of a directory of the radio storage
+pub fn list(link: &mut RadioLink, path: &str) -> Result<Vec<FileEntry>, LinkError> {
+    let reply = request(link, FmpFrame::new(Opcode::List, vec![path_param(path)]))?;
+    reply
+        .params
+        .iter()
+        .map(|p| FileEntry::decode(p).ok_or(LinkError::Protocol(format!("{p:02x?}"))))
+        .collect()
+}
+
+/// Read a file of the radio storage
+///
+/// Like [`dump`], the transfer is aborted on the radio side when `cancel`
+/// is set.
+pub fn read(
+    link: &mut RadioLink,
+    path: &str,
+    cancel: &CancelToken,
+    progress: impl FnMut(Progress),
+) -> Result<Vec<u8>, LinkError> {
+    let reply = request(link, FmpFrame::new(Opcode::Read, vec![path_param(path)]))?;
+    receive_data(link, &reply, cancel, progress)
+}
+
+/// Create or replace a file of the radio storage
+///
+/// When `cancel` is set, the transfer is aborted and the partial file is
+/// removed before returning [`LinkError::Cancelled`].
+pub fn write(
+    link: &mut RadioLink,
+    path: &str,
+    data: &[u8],
+    cancel: &CancelToken,
+    progress: impl FnMut(Progress),
+) -> Result<(), LinkError> {
+    let size = (data.len() as u32).to_le_bytes().to_vec();
+    request(
+        link,
+        FmpFrame::new(Opcode::Write, vec![path_param(path), size]),
+    )?;
+    match send_data(link, data, cancel, progress) {
+        Err(LinkError::Cancelled) => {
+            remove(link, path)?;
+            Err(LinkError::Cancelled)
+        }
+        res => res,
+    }
+}
+
+/// Rename or move a file or directory
+pub fn rename(link: &mut RadioLink, from: &str, to: &str) -> Result<(), LinkError> {
+    request(
+        link,
+        FmpFrame::new(Opcode::Move, vec![path_param(from), path_param(to)]),
+    )?;
+    Ok(())
+}
+
+/// Remove a file or an empty directory
+pub fn remove(link: &mut RadioLink, path: &str) -> Result<(), LinkError> {
+    request(link, FmpFrame::new(Opcode::Rm, vec![path_param(path)]))?;
+    Ok(())
+}
+
+pub fn mkdir(link: &mut RadioLink, path: &str) -> Result<(), LinkError> {
+    request(link, FmpFrame::new(Opcode::Mkdir, vec![path_param(path)]))?;
+    Ok(())
+}
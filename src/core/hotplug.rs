@@ -0,0 +1,44 @@
+use rtxflash::target::{self, DeviceInfo};
+
+use super::{get_ports, SerialPort};
+
+/// Serial ports and flashable devices attached to the host at a given time
+///
+/// Comparing two scans tells which radios were plugged in or removed in
+/// between.
+#[derive(Clone, Debug)]
+pub struct Attached {
+    pub ports: Vec<SerialPort>,
+    pub devices: Vec<DeviceInfo>,
+}
+
+impl Attached {
+    /// Enumerate the ports and devices currently attached
+    pub fn scan() -> Self {
+        Attached {
+            ports: get_ports(),
+            devices: target::get_devices(),
+        }
+    }
+
+    /// Check whether a device of a previous scan is still attached
+    pub fn has_device(&self, device: &DeviceInfo) -> bool {
+        self.devices.iter().any(|d| same_device(d, device))
+    }
+
+    /// Check whether a port of a previous scan is still attached
+    pub fn has_port(&self, port: &SerialPort) -> bool {
+        self.ports.iter().any(|p| p.name == port.name)
+    }
+}
+
+// Devices are identified by their port and description, as the same port
+// can be reused by a different device after a replug
+fn same_device(a: &DeviceInfo, b: &DeviceInfo) -> bool {
+    a.port == b.port && a.to_string() == b.to_string()
+}
+
+/// Check whether two device lists describe the same devices
+pub fn same_devices(a: &[DeviceInfo], b: &[DeviceInfo]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_device(a, b))
+}
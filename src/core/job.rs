@@ -0,0 +1,301 @@
+//! Jobs run against the radio in a worker thread
+//!
+//! A [`JobHandle`] owns the worker thread of a job and follows it through the
+//! [`JobEvent`]s it sends: `Started`, then phases and progress, then exactly
+//! one of `Completed`, `Failed` or `Cancelled`, from which the handle derives
+//! the [`JobState`] shown to the user.
+//!
+//! Cancellation goes through a shared [`CancelToken`], which jobs check
+//! between steps. Steps which must not be stopped halfway, such as writing a
+//! firmware, are run uninterruptible: a cancellation requested meanwhile is
+//! deferred until the step is done, and [`JobHandle::can_cancel`] is false
+//! while they run. A job stopping on request ends with `Cancelled`, any other
+//! error with `Failed`.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+// Cancellation token states
+const RUNNING: u8 = 0;
+const CANCELLED: u8 = 1;
+const UNINTERRUPTIBLE: u8 = 2;
+// Cancelled during an uninterruptible step, which is left to complete
+const DEFERRED: u8 = 3;
+
+/// Cancellation request shared between a job and its handle
+///
+/// Jobs check the token between steps and leave the radio in a defined
+/// state before giving up. Steps which must not be stopped halfway, such as
+/// writing a firmware, are run through [`CancelToken::uninterruptible`]: a
+/// cancellation requested while they run takes effect once they are done.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicU8>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation, deferred until the end of the uninterruptible
+    /// step in progress if any
+    pub fn cancel(&self) {
+        _ = self
+            .0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |state| match state {
+                RUNNING => Some(CANCELLED),
+                UNINTERRUPTIBLE => Some(DEFERRED),
+                _ => None,
+            });
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == CANCELLED
+    }
+
+    pub fn is_interruptible(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == RUNNING
+    }
+
+    /// Run `f` to completion, cancellation requests made meanwhile taking
+    /// effect after it, returns None without running it if cancellation was
+    /// already requested
+    pub fn uninterruptible<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+        self.0
+            .compare_exchange(RUNNING, UNINTERRUPTIBLE, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()?;
+        let res = f();
+        _ = self
+            .0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |state| match state {
+                DEFERRED => Some(CANCELLED),
+                _ => Some(RUNNING),
+            });
+        Some(res)
+    }
+}
+
+/// Progress of a running job
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub transferred: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    /// Completion percentage, between 0 and 100
+    pub fn percent(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.transferred as f32 / self.total as f32 * 100.0
+    }
+}
+
+/// Step of a job, reported when the job moves from one to the next
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Connecting,
+    Checking,
+    Reading,
+    Writing,
+    Rebooting,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Phase::Connecting => "Connecting to radio...",
+                Phase::Checking => "Checking image...",
+                Phase::Reading => "Reading memory...",
+                Phase::Writing => "Writing memory...",
+                Phase::Rebooting => "Waiting for the radio to reboot...",
+            }
+        )
+    }
+}
+
+/// Event reported by a job
+///
+/// A job always reports `Started` first and ends with exactly one of
+/// `Completed`, `Failed` or `Cancelled`.
+#[derive(Debug)]
+pub enum JobEvent<T, E> {
+    Started,
+    Progress(Progress),
+    Phase(Phase),
+    Completed(T),
+    Failed(E),
+    Cancelled,
+}
+
+impl<T, E> JobEvent<T, E> {
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            JobEvent::Completed(_) | JobEvent::Failed(_) | JobEvent::Cancelled
+        )
+    }
+}
+
+/// Error ending a job, which tells a stop on request apart from a failure
+pub trait JobError: std::fmt::Display + Send + 'static {
+    /// Whether the job gave up because its cancellation was requested
+    fn is_cancelled(&self) -> bool;
+}
+
+/// State of a job, as derived from its events
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobState<T> {
+    Running {
+        phase: Option<Phase>,
+        progress: Option<Progress>,
+    },
+    Completed(T),
+    Failed(String),
+    Cancelled,
+}
+
+/// Handle to a job running in a worker thread
+pub struct JobHandle<T, E> {
+    events: Receiver<JobEvent<T, E>>,
+    _thread: JoinHandle<()>,
+    state: JobState<T>,
+    cancel: CancelToken,
+}
+
+impl<T, E> JobHandle<T, E>
+where
+    T: Clone + Send + 'static,
+    E: JobError,
+{
+    pub(crate) fn spawn<F>(job: F) -> Self
+    where
+        F: FnOnce(&Sender<JobEvent<T, E>>, &CancelToken) -> Result<T, E> + Send + 'static,
+    {
+        let (events_tx, events_rx) = channel();
+        let cancel = CancelToken::new();
+        let thread = {
+            let cancel = cancel.clone();
+            std::thread::spawn(move || {
+                _ = events_tx.send(JobEvent::Started);
+                let event = match job(&events_tx, &cancel) {
+                    Ok(v) => JobEvent::Completed(v),
+                    Err(e) if e.is_cancelled() => JobEvent::Cancelled,
+                    Err(e) => JobEvent::Failed(e),
+                };
+                _ = events_tx.send(event);
+            })
+        };
+        JobHandle {
+            events: events_rx,
+            _thread: thread,
+            state: JobState::Running {
+                phase: None,
+                progress: None,
+            },
+            cancel,
+        }
+    }
+
+    /// Ask the job to stop, returns false if it already ended
+    pub fn cancel(&self) -> bool {
+        if self.is_running() {
+            self.cancel.cancel();
+        }
+        self.is_running()
+    }
+
+    pub fn can_cancel(&self) -> bool {
+        self.is_running() && self.cancel.is_interruptible()
+    }
+
+    pub fn state(&self) -> &JobState<T> {
+        &self.state
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, JobState::Running { .. })
+    }
+
+    /// Wait for the next event, returns None once the job ended
+    pub fn recv(&mut self) -> Option<JobEvent<T, E>> {
+        if !self.is_running() {
+            return None;
+        }
+        match self.events.recv() {
+            Ok(event) => {
+                self.apply(&event);
+                Some(event)
+            }
+            Err(_) => {
+                self.lost();
+                None
+            }
+        }
+    }
+
+    /// Apply the pending events without blocking and return the job state
+    pub fn poll(&mut self) -> &JobState<T> {
+        while self.is_running() {
+            match self.events.try_recv() {
+                Ok(event) => self.apply(&event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.lost(),
+            }
+        }
+        &self.state
+    }
+
+    fn apply(&mut self, event: &JobEvent<T, E>) {
+        match event {
+            JobEvent::Started => (),
+            JobEvent::Progress(p) => {
+                if let JobState::Running { progress, .. } = &mut self.state {
+                    *progress = Some(*p);
+                }
+            }
+            JobEvent::Phase(p) => {
+                self.state = JobState::Running {
+                    phase: Some(*p),
+                    progress: None,
+                }
+            }
+            JobEvent::Completed(v) => self.state = JobState::Completed(v.clone()),
+            JobEvent::Failed(e) => self.state = JobState::Failed(e.to_string()),
+            JobEvent::Cancelled => self.state = JobState::Cancelled,
+        }
+    }
+
+    // The worker ended without reporting an outcome, e.g. it panicked
+    fn lost(&mut self) {
+        self.state = JobState::Failed(String::from("Job ended unexpectedly"));
+    }
+}
+
+// Adapt the (transferred, total) progress channel used by rtxflash and
+// rtxlink to job progress events
+pub(crate) fn relay_progress<T, E>(
+    events: &Sender<JobEvent<T, E>>,
+) -> (Sender<(usize, usize)>, JoinHandle<()>)
+where
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    let (raw_tx, raw_rx) = channel::<(usize, usize)>();
+    let events = events.clone();
+    let relay = std::thread::spawn(move || {
+        for (transferred, total) in raw_rx {
+            // (0, 0) is used as an error marker
+            if total == 0 {
+                continue;
+            }
+            _ = events.send(JobEvent::Progress(Progress { transferred, total }));
+        }
+    });
+    (raw_tx, relay)
+}
@@ -0,0 +1,126 @@
+//! rtxlink connection to a radio running OpenRTX
+//!
+//! A serial port carries a single link at a time: every radio user of the
+//! companion (jobs, servers, recorders and pollers) goes through
+//! [`RadioLink::open`], which claims the port until the link is dropped.
+
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rtxlink::link::{Frame, Link, Protocol};
+
+// Time waited for a port used by another link, long enough for a short
+// exchange such as a status poll to end
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Serial ports with a link open in this process
+static CLAIMED_PORTS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+#[derive(Debug)]
+pub enum LinkError {
+    Io(std::io::Error),
+    /// errno-like status returned by the radio
+    Status(u8),
+    /// The radio answered with an unexpected frame
+    Protocol(String),
+    /// The serial port is used by another link
+    Busy(String),
+    /// The transfer was aborted on request
+    Cancelled,
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::Io(e) => write!(f, "{e}"),
+            LinkError::Status(s) => write!(f, "Radio returned error {s}"),
+            LinkError::Protocol(e) => write!(f, "Unexpected answer from radio: {e}"),
+            LinkError::Busy(port) => write!(f, "{port} is in use by another operation"),
+            LinkError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl From<std::io::Error> for LinkError {
+    fn from(e: std::io::Error) -> Self {
+        LinkError::Io(e)
+    }
+}
+
+/// Whether a link is open on a serial port, e.g. by a running job
+pub fn is_port_busy(port: &str) -> bool {
+    CLAIMED_PORTS.lock().unwrap().contains(port)
+}
+
+// Exclusive use of a serial port, until dropped
+struct PortClaim(String);
+
+impl PortClaim {
+    fn acquire(port: &str) -> Result<PortClaim, LinkError> {
+        let deadline = Instant::now() + CLAIM_TIMEOUT;
+        while !CLAIMED_PORTS.lock().unwrap().insert(String::from(port)) {
+            if Instant::now() >= deadline {
+                return Err(LinkError::Busy(String::from(port)));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        Ok(PortClaim(String::from(port)))
+    }
+}
+
+impl Drop for PortClaim {
+    fn drop(&mut self) {
+        CLAIMED_PORTS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// rtxlink connection to a radio running OpenRTX
+///
+/// The serial port is closed and released when the link is dropped.
+pub struct RadioLink {
+    link: Link,
+    _claim: PortClaim,
+}
+
+impl RadioLink {
+    /// Open the serial port of a radio, waiting briefly if another link
+    /// uses it
+    pub fn open(port: &str) -> Result<RadioLink, LinkError> {
+        let claim = PortClaim::acquire(port)?;
+        // rtxlink does not report a port which can not be opened
+        if !super::get_ports().iter().any(|p| p.name == port) {
+            return Err(LinkError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{port} not found"),
+            )));
+        }
+        Ok(RadioLink {
+            link: Link::new(port),
+            _claim: claim,
+        })
+    }
+
+    pub fn send(&mut self, proto: Protocol, data: Vec<u8>) {
+        self.link.send(Frame { proto, data });
+    }
+
+    /// Wait for the next frame of a protocol, dropping frames of the others
+    pub fn receive(&mut self, proto: Protocol) -> Result<Vec<u8>, LinkError> {
+        loop {
+            let frame = self.link.receive()?;
+            if frame.proto == proto {
+                return Ok(frame.data);
+            }
+        }
+    }
+
+    /// Send a request and wait for the answer on the same protocol
+    pub fn request(&mut self, proto: Protocol, data: Vec<u8>) -> Result<Vec<u8>, LinkError> {
+        self.send(proto, data);
+        self.receive(proto)
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use toml::{Table, Value};
+
+use super::RadioHW;
+
+/// Description of a backup image, saved in a TOML file next to it
+///
+/// The manifest records where the image comes from, so that it is not
+/// restored on a different radio model, and lets other tools index backups.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupManifest {
+    pub radio: Option<RadioHW>,
+    /// Hardware name reported by the radio
+    pub info: String,
+    /// Version of the firmware the image was read with, when reported
+    pub firmware_version: Option<String>,
+    pub port: String,
+    pub memory: String,
+    /// Time of the backup, in UTC
+    pub timestamp: String,
+    pub size: usize,
+    /// SHA-256 of the image, as lowercase hex
+    pub sha256: String,
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(std::io::Error),
+    Parse(String),
+    Missing(&'static str),
+}
+
+impl std::fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::Io(e) => write!(f, "{e}"),
+            ManifestError::Parse(e) => write!(f, "Invalid backup manifest: {e}"),
+            ManifestError::Missing(key) => write!(f, "Backup manifest has no {key}"),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl From<std::io::Error> for ManifestError {
+    fn from(e: std::io::Error) -> Self {
+        ManifestError::Io(e)
+    }
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+// Format a time as RFC 3339 in UTC, with or without milliseconds
+fn format_utc(time: OffsetDateTime, millis: bool) -> String {
+    let t = time.to_offset(time::UtcOffset::UTC);
+    let fraction = match millis {
+        true => format!(".{:03}", t.millisecond()),
+        false => String::new(),
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{fraction}Z",
+        t.year(),
+        t.month() as u8,
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second()
+    )
+}
+
+/// Format a time as RFC 3339, in UTC
+pub fn timestamp(time: OffsetDateTime) -> String {
+    format_utc(time, false)
+}
+
+/// Format a time as RFC 3339 in UTC, with milliseconds
+pub fn timestamp_millis(time: OffsetDateTime) -> String {
+    format_utc(time, true)
+}
+
+impl BackupManifest {
+    /// Describe an image read from a radio
+    pub fn new(
+        info: &str,
+        firmware_version: Option<&str>,
+        port: &str,
+        memory: &str,
+        time: OffsetDateTime,
+        data: &[u8],
+    ) -> Self {
+        BackupManifest {
+            radio: RadioHW::from_info(info),
+            info: String::from(info),
+            firmware_version: firmware_version.map(String::from),
+            port: String::from(port),
+            memory: String::from(memory),
+            timestamp: timestamp(time),
+            size: data.len(),
+            sha256: sha256_hex(data),
+        }
+    }
+
+    /// Path of the manifest of a backup image
+    pub fn path(image: &Path) -> PathBuf {
+        image.with_extension("toml")
+    }
+
+    /// Check whether an image is the one described by the manifest
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() == self.size && sha256_hex(data) == self.sha256
+    }
+
+    pub fn write(&self, image: &Path) -> std::io::Result<()> {
+        let mut table = Table::new();
+        if let Some(radio) = self.radio {
+            table.insert("radio".into(), Value::String(radio.into()));
+        }
+        table.insert("info".into(), Value::String(self.info.clone()));
+        if let Some(version) = &self.firmware_version {
+            table.insert("firmware_version".into(), Value::String(version.clone()));
+        }
+        table.insert("port".into(), Value::String(self.port.clone()));
+        table.insert("memory".into(), Value::String(self.memory.clone()));
+        table.insert("timestamp".into(), Value::String(self.timestamp.clone()));
+        table.insert("size".into(), Value::Integer(self.size as i64));
+        table.insert("sha256".into(), Value::String(self.sha256.clone()));
+        std::fs::write(Self::path(image), table.to_string())
+    }
+
+    /// Read the manifest of a backup image, if it has one
+    pub fn read(image: &Path) -> Result<Option<Self>, ManifestError> {
+        let text = match std::fs::read_to_string(Self::path(image)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let table: Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| ManifestError::Parse(e.message().to_string()))?;
+        let string = |key: &'static str| -> Result<String, ManifestError> {
+            table
+                .get(key)
+                .and_then(Value::as_str)
+                .map(String::from)
+                .ok_or(ManifestError::Missing(key))
+        };
+        Ok(Some(BackupManifest {
+            radio: table
+                .get("radio")
+                .and_then(Value::as_str)
+                .and_then(RadioHW::from_info),
+            info: string("info")?,
+            firmware_version: string("firmware_version").ok(),
+            port: string("port")?,
+            memory: string("memory")?,
+            timestamp: string("timestamp")?,
+            size: table
+                .get("size")
+                .and_then(Value::as_integer)
+                .and_then(|s| usize::try_from(s).ok())
+                .ok_or(ManifestError::Missing("size"))?,
+            sha256: string("sha256")?,
+        }))
+    }
+}
@@ -0,0 +1,54 @@
+//! Radio workflows shared by the graphical and command line interfaces
+//!
+//! Every workflow is described by a job ([`FlashJob`], [`BackupJob`],
+//! [`RestoreJob`]) which can either be run on the calling thread or started
+//! in a worker thread, returning a [`JobHandle`] to follow its progress
+//! through [`JobEvent`]s.
+
+pub mod backup;
+pub mod calibration;
+pub mod cat;
+pub mod catalog;
+pub mod chirp;
+pub mod codeplug;
+pub mod contacts;
+pub mod dfu;
+pub mod dirs;
+pub mod files;
+pub mod firmware;
+pub mod flash;
+pub mod flrig;
+pub mod fmp;
+pub mod hotplug;
+pub mod job;
+pub mod link;
+pub mod manifest;
+pub mod ports;
+pub mod radio;
+pub mod rdt;
+pub mod remote;
+pub mod rigctl;
+pub mod signature;
+pub mod status;
+pub mod stock;
+pub mod telemetry;
+
+pub use backup::{BackupError, BackupJob, RestoreJob};
+pub use calibration::{Calibration, CalibrationError, CalibrationReadJob, CalibrationWriteJob};
+pub use catalog::{Catalog, CatalogError, Release};
+pub use codeplug::{Codeplug, CodeplugError, CodeplugReadJob, CodeplugWriteJob};
+pub use contacts::ContactsWriteJob;
+pub use files::{FileError, Transfer, TransferJob};
+pub use firmware::{FirmwareInfo, ImageIssue};
+pub use flash::{FlashError, FlashJob, FlashOutcome};
+pub use hotplug::Attached;
+pub use job::{CancelToken, JobError, JobEvent, JobHandle, JobState, Phase, Progress};
+pub use link::{is_port_busy, LinkError, RadioLink};
+pub use manifest::BackupManifest;
+pub use ports::{get_ports, SerialPort};
+pub use radio::RadioHW;
+pub use rigctl::{RigProtocol, RigServer};
+pub use signature::{Keyring, SignatureError, Signer};
+pub use status::RadioStatus;
+pub use stock::{StockArchive, StockArchiveJob, StockError, StockRestoreJob};
+pub use telemetry::{TelemetryError, TelemetryRecorder};
@@ -0,0 +1,67 @@
+use serial_enumerator::get_serial_list;
+
+// Wrapper type for SerialItem to enable trait definition
+#[derive(Clone, PartialEq, Eq)]
+pub struct SerialPort {
+    pub name: String,
+    pub vendor: String,
+    pub product: String,
+}
+
+// Display trait for SeriatPortInfo
+impl std::fmt::Display for SerialPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// Debug trait for SerialPortInfo
+impl std::fmt::Debug for SerialPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SerialPort")
+            .field("name", &self.name)
+            .field("product", &self.product)
+            .field("vendor", &self.vendor)
+            .finish()
+    }
+}
+
+// Unwrap result from serialport library
+pub fn get_ports() -> Vec<SerialPort> {
+    #[allow(unused_mut)]
+    let mut ports: Vec<SerialPort> = get_serial_list()
+        .iter()
+        .map(|p| SerialPort {
+            name: p.name.clone(),
+            vendor: p.vendor.clone().unwrap_or(String::from("")),
+            product: p.product.clone().unwrap_or(String::from("")),
+        })
+        .collect();
+    #[cfg(all(unix, feature = "sim"))]
+    ports.extend(crate::sim::virtual_ports());
+    ports
+}
+
+/// USB vendor and product ID of the device behind a port, when known
+#[cfg(target_os = "linux")]
+pub fn usb_id(port: &str) -> Option<(u16, u16)> {
+    let name = std::path::Path::new(port).file_name()?;
+    let device = std::path::Path::new("/sys/class/tty")
+        .join(name)
+        .join("device")
+        .canonicalize()
+        .ok()?;
+    // The IDs are attributes of the USB device, a parent of the interface
+    let read_id = |dir: &std::path::Path, attr: &str| -> Option<u16> {
+        let id = std::fs::read_to_string(dir.join(attr)).ok()?;
+        u16::from_str_radix(id.trim(), 16).ok()
+    };
+    device
+        .ancestors()
+        .find_map(|dir| Some((read_id(dir, "idVendor")?, read_id(dir, "idProduct")?)))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn usb_id(_port: &str) -> Option<(u16, u16)> {
+    None
+}
@@ -0,0 +1,83 @@
+// USB IDs of the supported radios in bootloader mode, with the radios using
+// each of them. The MD-3x0 family all use the generic STM32 DFU ID, so they
+// can only be told apart by the identification string of the bootloader.
+const USB_IDS: &[(u16, u16, &[RadioHW])] = &[
+    (0x0483, 0xdf11, &[RadioHW::Md3x0, RadioHW::Mduv3x0]),
+    (0x303a, 0x1001, &[RadioHW::Twrplus]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadioHW {
+    Mduv3x0,
+    Twrplus,
+    Md3x0,
+}
+
+impl RadioHW {
+    pub fn all() -> [RadioHW; 3] {
+        [RadioHW::Md3x0, RadioHW::Mduv3x0, RadioHW::Twrplus]
+    }
+
+    /// Match the hardware name reported by the radio over rtxlink
+    pub fn from_info(info: &str) -> Option<RadioHW> {
+        let normalized: String = info
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        // Check MD-UV3x0 first, as its name contains the MD-3x0 one
+        if normalized.contains("mduv3") {
+            Some(RadioHW::Mduv3x0)
+        } else if normalized.contains("md3") {
+            Some(RadioHW::Md3x0)
+        } else if normalized.contains("ttwrplus") {
+            Some(RadioHW::Twrplus)
+        } else {
+            None
+        }
+    }
+
+    /// Space available for the firmware in the radio flash
+    pub fn firmware_size(&self) -> usize {
+        match self {
+            // 1 MiB of internal flash, minus the 48 KiB of the bootloader
+            RadioHW::Md3x0 | RadioHW::Mduv3x0 => 976 * 1024,
+            RadioHW::Twrplus => 16 * 1024 * 1024,
+        }
+    }
+
+    /// USB vendor and product ID of the serial port of the radio running
+    /// OpenRTX: the STM32 virtual COM port of the MD-3x0 family, and the USB
+    /// Serial/JTAG controller of the ESP32-S3 for the T-TWR Plus
+    pub fn rtxlink_usb_id(&self) -> (u16, u16) {
+        match self {
+            RadioHW::Md3x0 | RadioHW::Mduv3x0 => (0x0483, 0x5740),
+            RadioHW::Twrplus => (0x303a, 0x1001),
+        }
+    }
+
+    /// Match the USB vendor and product ID of a device, None when the ID is
+    /// unknown or shared by several radios
+    pub fn from_usb_id(vid: u16, pid: u16) -> Option<RadioHW> {
+        match USB_IDS.iter().find(|(v, p, _)| *v == vid && *p == pid) {
+            Some((_, _, [radio])) => Some(*radio),
+            _ => None,
+        }
+    }
+}
+
+impl From<RadioHW> for String {
+    fn from(radio: RadioHW) -> String {
+        String::from(match radio {
+            RadioHW::Md3x0 => "MD3x0",
+            RadioHW::Mduv3x0 => "MD-UV3x0",
+            RadioHW::Twrplus => "T-TWR Plus",
+        })
+    }
+}
+
+impl std::fmt::Display for RadioHW {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from(*self))
+    }
+}
@@ -0,0 +1,373 @@
+//! Conversion of stock TYT codeplugs to OpenRTX
+//!
+//! The vendor CPS saves codeplugs as `.rdt` files: a header of
+//! [`RDT_HEADER`] bytes, the codeplug and a footer of 16 bytes. The radios
+//! keep the same codeplug at the start of their SPI flash, so it can also be
+//! read from the images saved by the Backup tab.
+//!
+//! The MD-380 codeplug is 256 KiB. The MD-UV380 extends it with a second
+//! area at 0x110000 of the flash holding its 3000 channels and 10000
+//! contacts, which follows the first 256 KiB in `.rdt` files. Names are
+//! UTF-16 and frequencies little-endian BCD in units of 10 Hz. Only the
+//! first 16 channels of each zone are read.
+//!
+//! Analog channels, zones and contacts are converted. DMR channels are not
+//! converted and are reported as warnings, as are the DCS tones of analog
+//! channels, which are converted without them. Names of 16 UTF-16 characters
+//! may not fit in [`NAME_SIZE`] bytes of UTF-8, they are cut on a character
+//! boundary with a warning.
+
+use std::path::Path;
+
+use super::calibration::bcd_decode;
+use super::codeplug::{
+    power_from_watts, Bandwidth, Bank, CallType, Channel, Codeplug, Contact, NAME_SIZE,
+};
+use super::manifest::{BackupManifest, ManifestError};
+use super::RadioHW;
+
+/// Size of the header of `.rdt` files
+pub const RDT_HEADER: usize = 549;
+const RDT_FOOTER: usize = 16;
+
+// Area of the codeplug holding a table of fixed size entries
+struct Table {
+    offset: usize,
+    count: usize,
+    size: usize,
+}
+
+struct Layout {
+    /// Size of the flash holding the codeplug
+    flash_size: usize,
+    /// Areas of the flash stored in `.rdt` files, as offset and size
+    rdt_areas: &'static [(usize, usize)],
+    contacts: Table,
+    zones: Table,
+    channels: Table,
+}
+
+const MD380: Layout = Layout {
+    flash_size: 0x40000,
+    rdt_areas: &[(0, 0x40000)],
+    contacts: Table {
+        offset: 0x5f80,
+        count: 1000,
+        size: 36,
+    },
+    zones: Table {
+        offset: 0x149e0,
+        count: 250,
+        size: 64,
+    },
+    channels: Table {
+        offset: 0x1ee00,
+        count: 1000,
+        size: 64,
+    },
+};
+
+const MDUV380: Layout = Layout {
+    flash_size: 0x1a0000,
+    rdt_areas: &[(0, 0x40000), (0x110000, 0x90000)],
+    contacts: Table {
+        offset: 0x140000,
+        count: 10000,
+        size: 36,
+    },
+    zones: Table {
+        offset: 0x149e0,
+        count: 250,
+        size: 64,
+    },
+    channels: Table {
+        offset: 0x110000,
+        count: 3000,
+        size: 64,
+    },
+};
+
+impl Layout {
+    fn rdt_size(&self) -> usize {
+        RDT_HEADER + self.rdt_areas.iter().map(|(_, size)| size).sum::<usize>() + RDT_FOOTER
+    }
+
+    // Flash image holding the codeplug of a `.rdt` file
+    fn flash_image(&self, rdt: &[u8]) -> Vec<u8> {
+        let mut image = vec![0xff; self.flash_size];
+        let mut offset = RDT_HEADER;
+        for (address, size) in self.rdt_areas {
+            image[*address..address + size].copy_from_slice(&rdt[offset..offset + size]);
+            offset += size;
+        }
+        image
+    }
+}
+
+fn layout(radio: RadioHW) -> Option<&'static Layout> {
+    match radio {
+        RadioHW::Md3x0 => Some(&MD380),
+        RadioHW::Mduv3x0 => Some(&MDUV380),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum RdtError {
+    UnsupportedRadio(RadioHW),
+    /// The size of the image matches no codeplug, and the radio it was read
+    /// from is unknown
+    UnknownImage(usize),
+    /// The image is too small to hold the codeplug of the radio
+    Truncated {
+        radio: RadioHW,
+        size: usize,
+    },
+    Manifest(ManifestError),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for RdtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RdtError::UnsupportedRadio(radio) => {
+                write!(f, "Stock codeplugs of the {radio} are not supported")
+            }
+            RdtError::UnknownImage(size) => write!(
+                f,
+                "Image of {size} bytes is not a known codeplug, and it has no backup manifest"
+            ),
+            RdtError::Truncated { radio, size } => write!(
+                f,
+                "Image of {size} bytes is too small for a {radio} codeplug"
+            ),
+            RdtError::Manifest(e) => write!(f, "{e}"),
+            RdtError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RdtError {}
+
+impl From<std::io::Error> for RdtError {
+    fn from(e: std::io::Error) -> Self {
+        RdtError::Io(e)
+    }
+}
+
+impl From<ManifestError> for RdtError {
+    fn from(e: ManifestError) -> Self {
+        RdtError::Manifest(e)
+    }
+}
+
+/// Entry of the stock codeplug which was not converted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RdtWarning {
+    /// Digital channel, by its number in the stock codeplug
+    DigitalChannel { number: usize, name: String },
+    /// DCS tone of an analog channel, which was converted without it
+    Dcs { number: usize, name: String },
+    /// Zone member which is not a converted channel
+    ZoneMember { zone: String, number: usize },
+    /// Name too long for the OpenRTX codeplug, converted as `short`
+    LongName { name: String, short: String },
+}
+
+impl std::fmt::Display for RdtWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RdtWarning::DigitalChannel { number, name } => {
+                write!(f, "Channel {number} ({name}) is digital, not converted")
+            }
+            RdtWarning::Dcs { number, name } => {
+                write!(
+                    f,
+                    "Channel {number} ({name}) uses DCS, converted without tone"
+                )
+            }
+            RdtWarning::ZoneMember { zone, number } => {
+                write!(f, "Zone {zone} member {number} is not an analog channel")
+            }
+            RdtWarning::LongName { name, short } => {
+                write!(f, "Name {name} is too long, converted as {short}")
+            }
+        }
+    }
+}
+
+/// OpenRTX codeplug converted from a stock one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RdtConversion {
+    pub radio: RadioHW,
+    pub codeplug: Codeplug,
+    pub warnings: Vec<RdtWarning>,
+}
+
+// UTF-16 name, ended by NUL or erased flash
+fn utf16(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|u| *u != 0 && *u != 0xffff)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+// Name cut to fit in the OpenRTX codeplug with its NUL, on a character
+// boundary
+fn fit_name(name: String, warnings: &mut Vec<RdtWarning>) -> String {
+    if name.len() < NAME_SIZE {
+        return name;
+    }
+    let end = (0..NAME_SIZE)
+        .rev()
+        .find(|i| name.is_char_boundary(*i))
+        .unwrap_or(0);
+    let short = name[..end].to_string();
+    warnings.push(RdtWarning::LongName {
+        name,
+        short: short.clone(),
+    });
+    short
+}
+
+// CTCSS tone of a channel in 0.1 Hz, stored as BCD, or Err for DCS codes
+fn tone(data: &[u8]) -> Result<Option<u16>, ()> {
+    let value = u16::from_le_bytes([data[0], data[1]]);
+    if value == 0xffff {
+        return Ok(None);
+    }
+    if value & 0x8000 != 0 {
+        return Err(());
+    }
+    let tone = (0..4)
+        .rev()
+        .fold(0, |tone, i| tone * 10 + ((value >> (i * 4)) & 0xf));
+    Ok(Some(tone))
+}
+
+fn entries<'a>(image: &'a [u8], table: &Table) -> impl Iterator<Item = (usize, &'a [u8])> {
+    image[table.offset..table.offset + table.count * table.size]
+        .chunks_exact(table.size)
+        .enumerate()
+        .map(|(i, entry)| (i + 1, entry))
+}
+
+fn convert_image(radio: RadioHW, layout: &Layout, image: &[u8]) -> RdtConversion {
+    let mut codeplug = Codeplug::default();
+    let mut warnings = vec![];
+
+    // Index in the OpenRTX codeplug of the converted channels, by number
+    let mut converted = vec![None; layout.channels.count + 1];
+    for (number, entry) in entries(image, &layout.channels) {
+        let name = utf16(&entry[32..64]);
+        if name.is_empty() || entry[16..20] == [0xff; 4] {
+            continue;
+        }
+        // Mode: 1 analog, 2 digital
+        if entry[0] & 0x3 != 1 {
+            warnings.push(RdtWarning::DigitalChannel { number, name });
+            continue;
+        }
+        let mut channel = Channel::new(fit_name(name.clone(), &mut warnings));
+        channel.bandwidth = match entry[0] & 0x8 {
+            0 => Bandwidth::B12_5,
+            _ => Bandwidth::B25,
+        };
+        channel.power = power_from_watts(if entry[4] & 0x20 != 0 { 5.0 } else { 1.0 });
+        channel.rx_frequency = bcd_decode(&entry[16..20]);
+        channel.tx_frequency = bcd_decode(&entry[20..24]);
+        match (tone(&entry[24..26]), tone(&entry[26..28])) {
+            (Ok(rx), Ok(tx)) => (channel.rx_tone, channel.tx_tone) = (rx, tx),
+            _ => warnings.push(RdtWarning::Dcs { number, name }),
+        }
+        converted[number] = Some(codeplug.channels.len() as u16);
+        codeplug.channels.push(channel);
+    }
+
+    for (_, entry) in entries(image, &layout.zones) {
+        let name = utf16(&entry[..32]);
+        if name.is_empty() {
+            continue;
+        }
+        let mut bank = Bank {
+            name: fit_name(name.clone(), &mut warnings),
+            channels: vec![],
+        };
+        for member in entry[32..64].chunks_exact(2) {
+            let number = u16::from_le_bytes([member[0], member[1]]) as usize;
+            if number == 0 || number == 0xffff {
+                continue;
+            }
+            match converted.get(number).copied().flatten() {
+                Some(index) => bank.channels.push(index),
+                None => warnings.push(RdtWarning::ZoneMember {
+                    zone: name.clone(),
+                    number,
+                }),
+            }
+        }
+        codeplug.banks.push(bank);
+    }
+
+    for (_, entry) in entries(image, &layout.contacts) {
+        let id = u32::from_le_bytes([entry[0], entry[1], entry[2], 0]);
+        let name = utf16(&entry[4..36]);
+        if id == 0 || id == 0xffffff || name.is_empty() {
+            continue;
+        }
+        let call_type = match entry[3] & 0x3 {
+            2 => CallType::Private,
+            3 => CallType::All,
+            _ => CallType::Group,
+        };
+        let name = fit_name(name, &mut warnings);
+        codeplug.contacts.push(Contact::dmr(name, id, call_type));
+    }
+
+    codeplug.description = format!("Converted from {radio}");
+    RdtConversion {
+        radio,
+        codeplug,
+        warnings,
+    }
+}
+
+/// Convert a `.rdt` file or a flash image holding a stock codeplug
+///
+/// The radio is detected from the size of `.rdt` files and MD-380 images,
+/// other images need the radio they were read from.
+pub fn convert(data: &[u8], radio: Option<RadioHW>) -> Result<RdtConversion, RdtError> {
+    for (rdt_radio, layout) in [(RadioHW::Md3x0, &MD380), (RadioHW::Mduv3x0, &MDUV380)] {
+        if data.len() == layout.rdt_size() && radio.is_none_or(|r| r == rdt_radio) {
+            let image = layout.flash_image(data);
+            return Ok(convert_image(rdt_radio, layout, &image));
+        }
+    }
+    let radio = match radio {
+        Some(radio) => radio,
+        None if data.len() == MD380.flash_size => RadioHW::Md3x0,
+        None => return Err(RdtError::UnknownImage(data.len())),
+    };
+    let layout = layout(radio).ok_or(RdtError::UnsupportedRadio(radio))?;
+    if data.len() < layout.flash_size {
+        return Err(RdtError::Truncated {
+            radio,
+            size: data.len(),
+        });
+    }
+    Ok(convert_image(radio, layout, data))
+}
+
+/// Convert a stock codeplug file
+///
+/// The radio of backup images is read from their manifest when `radio` is
+/// not given.
+pub fn convert_file(path: &Path, radio: Option<RadioHW>) -> Result<RdtConversion, RdtError> {
+    let radio = match radio {
+        Some(radio) => Some(radio),
+        None => BackupManifest::read(path)?.and_then(|m| m.radio),
+    };
+    convert(&std::fs::read(path)?, radio)
+}
@@ -0,0 +1,240 @@
+//! Remote control of a radio from its front panel state
+//!
+//! [`PanelState`] gathers what the radio display shows, read through CAT
+//! requests, and [`PanelCommand`]s are the actions of its keys and knobs.
+//! Values the firmware does not support are left empty, and the matching
+//! controls can be hidden. A [`RemoteSession`] keeps the link to the radio
+//! open while it is controlled.
+
+use std::ops::RangeInclusive;
+
+use super::cat::{self, CatId};
+use super::codeplug::{OpMode, BANDS};
+use super::{LinkError, RadioHW, RadioLink};
+
+/// Settings which can be turned on and off remotely
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    /// Keep the squelch open
+    Monitor,
+    Gps,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 2] = [Setting::Monitor, Setting::Gps];
+
+    pub fn id(&self) -> CatId {
+        match self {
+            Setting::Monitor => CatId::Monitor,
+            Setting::Gps => CatId::Gps,
+        }
+    }
+}
+
+impl std::fmt::Display for Setting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Setting::Monitor => write!(f, "Monitor"),
+            Setting::Gps => write!(f, "GPS"),
+        }
+    }
+}
+
+/// State shown on the front panel of the radio
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PanelState {
+    /// Hardware name reported by the radio
+    pub info: String,
+    /// Frequencies in Hz
+    pub rx_frequency: u32,
+    pub tx_frequency: Option<u32>,
+    pub mode: Option<OpMode>,
+    /// Received signal strength in dBm
+    pub rssi: Option<i16>,
+    /// Squelch level, from 0 to 15
+    pub squelch: Option<u8>,
+    pub squelch_open: Option<bool>,
+    /// Battery voltage in mV
+    pub battery: Option<u16>,
+    /// Volume, from 0 to 255
+    pub volume: Option<u8>,
+    /// Settings supported by the radio, with their state
+    pub settings: Vec<(Setting, bool)>,
+}
+
+fn byte(value: Vec<u8>) -> Option<u8> {
+    match value.as_slice() {
+        [byte] => Some(*byte),
+        _ => None,
+    }
+}
+
+impl PanelState {
+    /// Query the radio on an open link
+    pub fn query(link: &mut RadioLink) -> Result<PanelState, LinkError> {
+        let mut settings = vec![];
+        for setting in Setting::ALL {
+            if let Some(state) = cat::get_optional(link, setting.id())?.and_then(byte) {
+                settings.push((setting, state != 0));
+            }
+        }
+        Ok(PanelState {
+            info: cat::info(link)?,
+            rx_frequency: cat::rx_frequency(link)?,
+            tx_frequency: cat::get_optional(link, CatId::TxFreq)?
+                .and_then(|f| Some(u32::from_le_bytes(f.try_into().ok()?))),
+            mode: cat::get_optional(link, CatId::OpMode)?
+                .and_then(byte)
+                .map(OpMode::from),
+            rssi: cat::get_optional(link, CatId::Rssi)?
+                .and_then(|r| Some(i16::from_le_bytes(r.try_into().ok()?))),
+            squelch: cat::get_optional(link, CatId::Squelch)?.and_then(byte),
+            squelch_open: cat::get_optional(link, CatId::SquelchOpen)?
+                .and_then(byte)
+                .map(|open| open != 0),
+            battery: cat::get_optional(link, CatId::Battery)?
+                .and_then(|b| Some(u16::from_le_bytes(b.try_into().ok()?))),
+            volume: cat::get_optional(link, CatId::Volume)?.and_then(byte),
+            settings,
+        })
+    }
+
+    /// Connect to the radio on a serial port and query it
+    pub fn read(port: &str) -> Result<PanelState, LinkError> {
+        PanelState::query(&mut RadioLink::open(port)?)
+    }
+
+    /// State of a setting, None when the radio does not support it
+    pub fn setting(&self, setting: Setting) -> Option<bool> {
+        self.settings
+            .iter()
+            .find(|(s, _)| *s == setting)
+            .map(|(_, state)| *state)
+    }
+
+    /// Show the effect of a command before the radio reports it
+    pub fn apply(&mut self, command: &PanelCommand) {
+        match *command {
+            PanelCommand::Tune(frequency) => {
+                self.rx_frequency = frequency;
+                self.tx_frequency = Some(frequency);
+            }
+            PanelCommand::TuneTx(frequency) => self.tx_frequency = Some(frequency),
+            PanelCommand::Mode(mode) => self.mode = Some(mode),
+            PanelCommand::Squelch(level) => self.squelch = Some(level),
+            PanelCommand::Volume(volume) => self.volume = Some(volume),
+            PanelCommand::Set(setting, on) => {
+                for (s, state) in &mut self.settings {
+                    if *s == setting {
+                        *state = on;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Action on the front panel of the radio
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanelCommand {
+    /// Tune to a simplex frequency in Hz
+    Tune(u32),
+    /// Set the TX frequency in Hz, for split operation
+    TuneTx(u32),
+    Mode(OpMode),
+    Squelch(u8),
+    Volume(u8),
+    Set(Setting, bool),
+}
+
+impl PanelCommand {
+    /// Whether this command supersedes `other`, acting on the same control,
+    /// tuning also setting the TX frequency
+    pub fn replaces(&self, other: &PanelCommand) -> bool {
+        match (self, other) {
+            (PanelCommand::Tune(_), PanelCommand::TuneTx(_)) => true,
+            (PanelCommand::Set(a, _), PanelCommand::Set(b, _)) => a == b,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+
+    /// Send the command to the radio
+    pub fn send(&self, link: &mut RadioLink) -> Result<(), LinkError> {
+        match *self {
+            PanelCommand::Tune(frequency) => cat::set_frequency(link, frequency),
+            PanelCommand::TuneTx(frequency) => cat::set_tx_frequency(link, frequency),
+            PanelCommand::Mode(mode) => cat::set_op_mode(link, mode),
+            PanelCommand::Squelch(level) => cat::set(link, CatId::Squelch, &[level]),
+            PanelCommand::Volume(volume) => cat::set(link, CatId::Volume, &[volume]),
+            PanelCommand::Set(setting, on) => cat::set(link, setting.id(), &[on as u8]),
+        }
+    }
+}
+
+/// Frequency bands in Hz a radio can be tuned in, knowing the frequency it
+/// is tuned to
+///
+/// The MD-3x0 and the T-TWR Plus are built for either VHF or UHF, which the
+/// firmware does not report, so their band is the one holding the current
+/// frequency. All the bands are allowed for unknown radios, and when the
+/// current frequency is in none of them.
+pub fn radio_bands(radio: Option<RadioHW>, frequency: u32) -> Vec<RangeInclusive<u32>> {
+    let bands: Vec<_> = match radio {
+        Some(RadioHW::Md3x0 | RadioHW::Twrplus) => BANDS
+            .iter()
+            .filter(|band| band.contains(&frequency))
+            .cloned()
+            .collect(),
+        Some(RadioHW::Mduv3x0) | None => vec![],
+    };
+    match bands.is_empty() {
+        true => BANDS.to_vec(),
+        false => bands,
+    }
+}
+
+/// Whether a frequency in Hz is in one of the bands
+pub fn in_band(bands: &[RangeInclusive<u32>], frequency: u32) -> bool {
+    bands.iter().any(|band| band.contains(&frequency))
+}
+
+/// Frequency `steps` tuning steps away, None when out of band
+pub fn step_frequency(
+    bands: &[RangeInclusive<u32>],
+    frequency: u32,
+    step: u32,
+    steps: i32,
+) -> Option<u32> {
+    let frequency = i64::from(frequency) + i64::from(step) * i64::from(steps);
+    u32::try_from(frequency).ok().filter(|f| in_band(bands, *f))
+}
+
+/// Link to a radio tuned remotely
+///
+/// The serial port is kept until the session is dropped, so that polling
+/// does not reopen it on each request.
+pub struct RemoteSession {
+    link: RadioLink,
+    /// Model of the radio, if known
+    pub radio: Option<RadioHW>,
+    /// Bands the radio can be tuned in
+    pub bands: Vec<RangeInclusive<u32>>,
+}
+
+impl RemoteSession {
+    /// Connect to the radio on a serial port
+    pub fn open(port: &str) -> Result<RemoteSession, LinkError> {
+        let mut link = RadioLink::open(port)?;
+        let radio = RadioHW::from_info(&cat::info(&mut link)?);
+        let bands = radio_bands(radio, cat::rx_frequency(&mut link)?);
+        Ok(RemoteSession { link, radio, bands })
+    }
+
+    /// Send commands to the radio, then query its state
+    pub fn send(&mut self, commands: &[PanelCommand]) -> Result<PanelState, LinkError> {
+        for command in commands {
+            command.send(&mut self.link)?;
+        }
+        PanelState::query(&mut self.link)
+    }
+}
@@ -0,0 +1,435 @@
+//! Hamlib rigctld compatible server
+//!
+//! [`RigServer`] listens on a local TCP port and answers the text protocol
+//! of `rigctld`, one command per line, or the flrig XML-RPC API (see
+//! [`super::flrig`]), by sending CAT requests to the radio over a single
+//! rtxlink connection. Applications using the Hamlib "NET rigctl" model (2)
+//! can then control the radio without a native driver.
+//!
+//! The supported commands are `f`/`F` (frequency), `m`/`M` (mode), `t`/`T`
+//! (PTT), `l STRENGTH` (signal strength over S9), `_` (radio information),
+//! `v`, `\chk_vfo` and `\dump_state`, along with their long forms. Hamlib
+//! has no M17 mode, M17 is reported as `PKTFM`. Frequencies are set as
+//! simplex, on both RX and TX. Clients sending a line longer than
+//! [`MAX_LINE_SIZE`] are disconnected.
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use super::cat;
+use super::codeplug::{OpMode, BANDS};
+use super::flrig;
+use super::{LinkError, RadioLink};
+
+/// Default TCP port of rigctld
+pub const DEFAULT_PORT: u16 = 4532;
+
+/// Protocols served by a [`RigServer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RigProtocol {
+    Rigctld,
+    Flrig,
+}
+
+impl RigProtocol {
+    pub const ALL: [RigProtocol; 2] = [RigProtocol::Rigctld, RigProtocol::Flrig];
+
+    /// TCP port applications connect to by default
+    pub fn default_port(&self) -> u16 {
+        match self {
+            RigProtocol::Rigctld => DEFAULT_PORT,
+            RigProtocol::Flrig => flrig::DEFAULT_PORT,
+        }
+    }
+
+    // Take the next complete request out of the received bytes and answer it
+    fn serve(&self, link: &mut RadioLink, pending: &mut Vec<u8>) -> Option<Exchange> {
+        match self {
+            RigProtocol::Rigctld => serve_line(link, pending),
+            RigProtocol::Flrig => flrig::serve(link, pending),
+        }
+    }
+}
+
+impl std::fmt::Display for RigProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RigProtocol::Rigctld => write!(f, "Hamlib rigctld"),
+            RigProtocol::Flrig => write!(f, "flrig XML-RPC"),
+        }
+    }
+}
+
+/// A request answered by a [`RigServer`]
+pub(super) struct Exchange {
+    /// Request, as shown in the log, empty for requests not worth logging
+    pub request: String,
+    /// Reply, as shown in the log
+    pub reply: String,
+    /// Bytes sent back to the client
+    pub data: Vec<u8>,
+    /// Close the connection once the reply is sent
+    pub close: bool,
+}
+
+/// Number of requests kept for display
+const LOG_SIZE: usize = 20;
+/// Longest command line accepted, rigctld commands being much shorter
+pub const MAX_LINE_SIZE: usize = 4 * 1024;
+// Interval at which the server polls its clients
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Hamlib error codes, as returned in RPRT lines
+const RIG_EINVAL: i32 = -1;
+const RIG_ENIMPL: i32 = -4;
+const RIG_ETIMEOUT: i32 = -5;
+const RIG_EIO: i32 = -6;
+const RIG_EPROTO: i32 = -8;
+const RIG_ERJCTED: i32 = -9;
+const RIG_ENAVAIL: i32 = -11;
+
+// PTT set over CAT
+const RIG_PTT_RIG: u32 = 0x1;
+
+// Hamlib mode, level and VFO bits, for the rig capabilities
+const RIG_MODE_FM: u32 = 0x20;
+const RIG_MODE_PKTFM: u32 = 0x1000;
+const RIG_LEVEL_STRENGTH: u32 = 0x4000_0000;
+const RIG_VFO_A: u32 = 0x1;
+
+/// Signal strength of S9, in dBm
+pub(super) const S9: i16 = -73;
+
+// Hamlib name of an operating mode
+fn mode_name(mode: OpMode) -> Option<&'static str> {
+    match mode {
+        OpMode::Fm => Some("FM"),
+        OpMode::M17 => Some("PKTFM"),
+        _ => None,
+    }
+}
+
+fn parse_mode(name: &str) -> Option<OpMode> {
+    match name {
+        "FM" | "FMN" => Some(OpMode::Fm),
+        "PKTFM" => Some(OpMode::M17),
+        _ => None,
+    }
+}
+
+fn error_code(e: &LinkError) -> i32 {
+    match e {
+        LinkError::Io(e) if e.kind() == std::io::ErrorKind::TimedOut => RIG_ETIMEOUT,
+        LinkError::Io(_) | LinkError::Busy(_) | LinkError::Cancelled => RIG_EIO,
+        LinkError::Status(_) => RIG_ERJCTED,
+        LinkError::Protocol(_) => RIG_EPROTO,
+    }
+}
+
+// Capabilities of the radio, in the layout of Hamlib 4 `dump_state`
+fn dump_state() -> String {
+    let modes = RIG_MODE_FM | RIG_MODE_PKTFM;
+    let mut state = String::from("1\n2\n2\n");
+    for band in BANDS {
+        state += &format!(
+            "{} {} {modes:#x} -1 -1 {RIG_VFO_A:#x} 0x0\n",
+            band.start(),
+            band.end()
+        );
+    }
+    state += "0 0 0 0 0 0 0\n";
+    // TX ranges, with the power in mW
+    for band in BANDS {
+        state += &format!(
+            "{} {} {modes:#x} 100 5000 {RIG_VFO_A:#x} 0x0\n",
+            band.start(),
+            band.end()
+        );
+    }
+    state += "0 0 0 0 0 0 0\n";
+    // Tuning steps and filters
+    state += &format!("{modes:#x} 12500\n0 0\n{modes:#x} 12500\n{modes:#x} 25000\n0 0\n");
+    // RIT, XIT, IF shift, announces, preamps and attenuators
+    state += "0\n0\n0\n0\n0\n0\n";
+    // Functions, levels and parameters which can be read and set
+    state += &format!("0x0\n0x0\n{RIG_LEVEL_STRENGTH:#x}\n0x0\n0x0\n0x0\n");
+    // Settings added by Hamlib 4
+    state += &format!("vfo_ops=0x0\nptt_type={RIG_PTT_RIG:#x}\ndone\n");
+    state
+}
+
+// Answer a command line, returns None when the client asks to close the
+// connection
+fn execute(link: &mut RadioLink, line: &str) -> Option<String> {
+    let report = |code: i32| format!("RPRT {code}\n");
+    let result = |res: Result<(), LinkError>| match res {
+        Ok(()) => report(0),
+        Err(e) => report(error_code(&e)),
+    };
+    let value = |res: Result<String, LinkError>| match res {
+        Ok(value) => format!("{value}\n"),
+        Err(e) => report(error_code(&e)),
+    };
+    let mut args = line.split_whitespace();
+    let reply = match args.next().unwrap_or_default() {
+        "q" | "Q" | "\\quit" => return None,
+        "f" | "\\get_freq" => value(cat::rx_frequency(link).map(|f| f.to_string())),
+        "F" | "\\set_freq" => match args.next().and_then(|f| f.parse::<f64>().ok()) {
+            Some(frequency) if frequency > 0.0 => {
+                result(cat::set_frequency(link, frequency.round() as u32))
+            }
+            _ => report(RIG_EINVAL),
+        },
+        "m" | "\\get_mode" => match cat::op_mode(link) {
+            Ok(mode) => match mode_name(mode) {
+                Some(name) => format!("{name}\n0\n"),
+                None => report(RIG_ENAVAIL),
+            },
+            Err(e) => report(error_code(&e)),
+        },
+        "M" | "\\set_mode" => match args.next().and_then(parse_mode) {
+            Some(mode) => result(cat::set_op_mode(link, mode)),
+            None => report(RIG_EINVAL),
+        },
+        "t" | "\\get_ptt" => value(cat::ptt(link).map(|ptt| (ptt as u8).to_string())),
+        "T" | "\\set_ptt" => match args.next().and_then(|p| p.parse::<u8>().ok()) {
+            Some(ptt @ 0..=3) => result(cat::set_ptt(link, ptt != 0)),
+            _ => report(RIG_EINVAL),
+        },
+        "l" | "\\get_level" => match args.next() {
+            Some("STRENGTH") => {
+                value(cat::rssi(link).map(|rssi| rssi.saturating_sub(S9).to_string()))
+            }
+            _ => report(RIG_EINVAL),
+        },
+        "_" | "\\get_info" => value(cat::info(link)),
+        "v" | "\\get_vfo" => String::from("VFOA\n"),
+        "\\chk_vfo" => String::from("0\n"),
+        "\\dump_state" => dump_state(),
+        "" => String::new(),
+        _ => report(RIG_ENIMPL),
+    };
+    Some(reply)
+}
+
+fn serve_line(link: &mut RadioLink, pending: &mut Vec<u8>) -> Option<Exchange> {
+    let newline = pending.iter().position(|b| *b == b'\n');
+    let Some(end) = newline.filter(|end| *end < MAX_LINE_SIZE) else {
+        // Drop the client rather than buffering an endless line
+        return (pending.len() >= MAX_LINE_SIZE).then(|| {
+            pending.clear();
+            let reply = format!("RPRT {RIG_EINVAL}\n");
+            Exchange {
+                request: String::from("Oversized line"),
+                data: reply.clone().into_bytes(),
+                reply,
+                close: true,
+            }
+        });
+    };
+    let line: Vec<u8> = pending.drain(..=end).collect();
+    let request = String::from_utf8_lossy(&line).trim().to_string();
+    Some(match execute(link, &request) {
+        Some(reply) => Exchange {
+            request,
+            data: reply.clone().into_bytes(),
+            reply,
+            close: false,
+        },
+        None => Exchange {
+            request: String::new(),
+            reply: String::new(),
+            data: vec![],
+            close: true,
+        },
+    })
+}
+
+/// Connections and recent requests of a [`RigServer`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerStatus {
+    /// Clients currently connected
+    pub clients: usize,
+    /// Requests served since the server started
+    pub requests: usize,
+    /// Last requests with their reply, oldest first
+    pub last_requests: Vec<String>,
+    /// Why the server stopped, e.g. the radio could not be opened
+    pub error: Option<String>,
+}
+
+// State shared by the server thread and its handle
+#[derive(Default)]
+struct Shared {
+    stop: AtomicBool,
+    clients: AtomicUsize,
+    requests: AtomicUsize,
+    log: Mutex<VecDeque<String>>,
+    error: Mutex<Option<String>>,
+}
+
+impl Shared {
+    fn log(&self, client: SocketAddr, request: &str, reply: &str) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let mut log = self.log.lock().unwrap();
+        if log.len() == LOG_SIZE {
+            log.pop_front();
+        }
+        let reply = reply.lines().next().unwrap_or_default();
+        log.push_back(format!("{client}: {request} -> {reply}"));
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    address: SocketAddr,
+    protocol: RigProtocol,
+    // Received bytes of an incomplete line
+    pending: Vec<u8>,
+}
+
+impl Client {
+    // Answer the complete requests received, returns false once the
+    // connection is closed
+    fn serve(&mut self, link: &mut RadioLink, shared: &Shared) -> bool {
+        let mut buffer = [0; 512];
+        let open = loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => break false,
+                Ok(n) => self.pending.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break true,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break false,
+            }
+        };
+        while let Some(exchange) = self.protocol.serve(link, &mut self.pending) {
+            if !exchange.request.is_empty() {
+                shared.log(self.address, &exchange.request, &exchange.reply);
+            }
+            if self.write(&exchange.data).is_err() || exchange.close {
+                return false;
+            }
+        }
+        open
+    }
+
+    fn write(&mut self, mut data: &[u8]) -> std::io::Result<()> {
+        while !data.is_empty() {
+            match self.stream.write(data) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => data = &data[n..],
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+// Serve all the clients from a single thread, which owns the link to the
+// radio
+fn serve(shared: Arc<Shared>, listener: TcpListener, port: String, protocol: RigProtocol) {
+    let mut link = match RadioLink::open(&port) {
+        Ok(link) => link,
+        Err(e) => {
+            *shared.error.lock().unwrap() = Some(e.to_string());
+            return;
+        }
+    };
+    let mut clients: Vec<Client> = vec![];
+    while !shared.stop.load(Ordering::SeqCst) {
+        while let Ok((stream, address)) = listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                clients.push(Client {
+                    stream,
+                    address,
+                    protocol,
+                    pending: vec![],
+                });
+            }
+        }
+        clients.retain_mut(|client| client.serve(&mut link, &shared));
+        shared.clients.store(clients.len(), Ordering::SeqCst);
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// rigctld or flrig compatible server controlling a radio running OpenRTX
+///
+/// The server keeps the rtxlink connection to the radio, and so the serial
+/// port, until it is stopped or dropped. Failing to open the radio stops the
+/// server, which is reported by [`RigServer::status`].
+pub struct RigServer {
+    address: SocketAddr,
+    protocol: RigProtocol,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RigServer {
+    /// Listen on `address` and serve the radio on serial port `port`
+    pub fn start(
+        port: &str,
+        protocol: RigProtocol,
+        address: impl ToSocketAddrs,
+    ) -> std::io::Result<RigServer> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let shared = Arc::new(Shared::default());
+        let thread = {
+            let shared = shared.clone();
+            let port = String::from(port);
+            std::thread::spawn(move || serve(shared, listener, port, protocol))
+        };
+        Ok(RigServer {
+            address,
+            protocol,
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Address the server listens on
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn protocol(&self) -> RigProtocol {
+        self.protocol
+    }
+
+    pub fn status(&self) -> ServerStatus {
+        ServerStatus {
+            clients: self.shared.clients.load(Ordering::SeqCst),
+            requests: self.shared.requests.load(Ordering::SeqCst),
+            last_requests: self.shared.log.lock().unwrap().iter().cloned().collect(),
+            error: self.shared.error.lock().unwrap().clone(),
+        }
+    }
+
+    /// Disconnect the clients and close the connection to the radio
+    ///
+    /// This waits for the server thread, which ends once the request in
+    /// progress is answered or times out, so that the serial port is free
+    /// again when it returns.
+    pub fn stop(mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+impl Drop for RigServer {
+    // Unlike stop, leave the server thread to end on its own
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+    }
+}
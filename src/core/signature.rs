@@ -0,0 +1,137 @@
+//! Minisign signatures of firmware images
+//!
+//! An image is signed by a detached `<image>.minisig` file, made with
+//! `minisign -S -m <image>`. Trusted public keys are the `.pub` files of the
+//! keyring folder, the file name identifying the signer.
+
+use std::path::{Path, PathBuf};
+
+use minisign_verify::{PublicKey, Signature};
+
+use super::dirs;
+
+/// Extension of the signature files, appended to the image name
+pub const SIGNATURE_EXTENSION: &str = "minisig";
+
+#[derive(Debug)]
+pub enum SignatureError {
+    Unsigned,
+    Invalid(String),
+    /// The signature was not made by any of the trusted keys
+    Untrusted,
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::Unsigned => write!(f, "Image is not signed"),
+            SignatureError::Invalid(e) => write!(f, "Invalid signature: {e}"),
+            SignatureError::Untrusted => write!(f, "Image is not signed by a trusted key"),
+            SignatureError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+impl From<std::io::Error> for SignatureError {
+    fn from(e: std::io::Error) -> Self {
+        SignatureError::Io(e)
+    }
+}
+
+/// Trusted key which signed an image
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signer {
+    /// Name of the key in the keyring
+    pub key: String,
+    /// Trusted comment of the signature, e.g. the build it comes from
+    pub comment: String,
+}
+
+impl std::fmt::Display for Signer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.comment.is_empty() {
+            true => write!(f, "{}", self.key),
+            false => write!(f, "{} ({})", self.key, self.comment),
+        }
+    }
+}
+
+/// Path of the signature of an image
+pub fn signature_path(image: &Path) -> PathBuf {
+    let mut name = image.as_os_str().to_owned();
+    name.push(".");
+    name.push(SIGNATURE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Public keys trusted to sign firmware images
+#[derive(Default)]
+pub struct Keyring {
+    keys: Vec<(String, PublicKey)>,
+}
+
+impl Keyring {
+    /// Folder of the trusted keys, set by the `OPENRTX_TRUSTED_KEYS`
+    /// environment variable or in the user configuration folder
+    pub fn default_dir() -> PathBuf {
+        if let Some(dir) = std::env::var_os("OPENRTX_TRUSTED_KEYS") {
+            return PathBuf::from(dir);
+        }
+        dirs::config_dir().join("trusted-keys")
+    }
+
+    /// Read the `.pub` files of a folder, which may not exist
+    pub fn load(dir: &Path) -> Result<Keyring, SignatureError> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Keyring::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut keys = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "pub") {
+                continue;
+            }
+            let key = PublicKey::decode(&std::fs::read_to_string(&path)?)
+                .map_err(|e| SignatureError::Invalid(format!("{}: {e}", path.display())))?;
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            keys.push((name.to_string(), key));
+        }
+        keys.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(Keyring { keys })
+    }
+
+    pub fn load_default() -> Result<Keyring, SignatureError> {
+        Keyring::load(&Keyring::default_dir())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Check the detached signature of an image
+    pub fn verify(&self, image: &Path) -> Result<Signer, SignatureError> {
+        let signature = match std::fs::read_to_string(signature_path(image)) {
+            Ok(signature) => signature,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(SignatureError::Unsigned)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let signature =
+            Signature::decode(&signature).map_err(|e| SignatureError::Invalid(e.to_string()))?;
+        let data = std::fs::read(image)?;
+        self.keys
+            .iter()
+            .find(|(_, key)| key.verify(&data, &signature, false).is_ok())
+            .map(|(name, _)| Signer {
+                key: name.clone(),
+                comment: String::from(signature.trusted_comment()),
+            })
+            .ok_or(SignatureError::Untrusted)
+    }
+}
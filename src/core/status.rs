@@ -0,0 +1,113 @@
+//! Identity and state of a radio, read through CAT requests
+
+use super::cat::{self, CatId};
+use super::codeplug::OpMode;
+use super::{LinkError, RadioHW, RadioLink};
+
+/// Information reported by a radio running OpenRTX
+///
+/// Values the firmware does not support are left empty, so that older
+/// firmwares can still be inspected.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RadioStatus {
+    /// Hardware name, as reported by the radio
+    pub info: String,
+    pub firmware: Option<String>,
+    /// Commit the firmware was built from
+    pub commit: Option<String>,
+    pub hardware: Option<String>,
+    pub callsign: Option<String>,
+    /// Frequencies in Hz
+    pub rx_frequency: Option<u32>,
+    pub tx_frequency: Option<u32>,
+    pub mode: Option<OpMode>,
+    /// Battery voltage in mV
+    pub battery: Option<u16>,
+}
+
+fn string(value: Vec<u8>) -> String {
+    String::from_utf8_lossy(&value)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+// Commit hash of a `git describe` version, e.g. v0.3.6-12-g1a2b3c4
+fn commit_hash(version: &str) -> Option<String> {
+    let (_, hash) = version.rsplit_once("-g")?;
+    (hash.len() >= 7 && hash.chars().all(|c| c.is_ascii_hexdigit())).then(|| String::from(hash))
+}
+
+impl RadioStatus {
+    /// Query the radio on an open link
+    pub fn query(link: &mut RadioLink) -> Result<RadioStatus, LinkError> {
+        let info = cat::info(link)?;
+        let firmware = cat::get_optional(link, CatId::Version)?.map(string);
+        let frequency = |value: Vec<u8>| Some(u32::from_le_bytes(value.try_into().ok()?));
+        Ok(RadioStatus {
+            info,
+            commit: firmware.as_deref().and_then(commit_hash),
+            firmware,
+            hardware: cat::get_optional(link, CatId::HwVersion)?.map(string),
+            callsign: cat::get_optional(link, CatId::Callsign)?.map(string),
+            rx_frequency: cat::get_optional(link, CatId::RxFreq)?.and_then(frequency),
+            tx_frequency: cat::get_optional(link, CatId::TxFreq)?.and_then(frequency),
+            mode: cat::get_optional(link, CatId::OpMode)?
+                .and_then(|m| m.first().copied())
+                .map(OpMode::from),
+            battery: cat::get_optional(link, CatId::Battery)?
+                .and_then(|b| Some(u16::from_le_bytes(b.try_into().ok()?))),
+        })
+    }
+
+    /// Connect to the radio on a serial port and query it
+    pub fn read(port: &str) -> Result<RadioStatus, LinkError> {
+        RadioStatus::query(&mut RadioLink::open(port)?)
+    }
+
+    pub fn radio(&self) -> Option<RadioHW> {
+        RadioHW::from_info(&self.info)
+    }
+
+    /// Names and values of the fields, for display
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let unknown = || String::from("-");
+        let mhz = |f: Option<u32>| {
+            f.map(|f| format!("{:.5} MHz", f as f64 / 1e6))
+                .unwrap_or_else(unknown)
+        };
+        vec![
+            (
+                "Model",
+                match self.radio() {
+                    Some(radio) => radio.to_string(),
+                    None => self.info.clone(),
+                },
+            ),
+            ("Firmware", self.firmware.clone().unwrap_or_else(unknown)),
+            ("Commit", self.commit.clone().unwrap_or_else(unknown)),
+            ("Hardware", self.hardware.clone().unwrap_or_else(unknown)),
+            ("Callsign", self.callsign.clone().unwrap_or_else(unknown)),
+            ("RX frequency", mhz(self.rx_frequency)),
+            ("TX frequency", mhz(self.tx_frequency)),
+            (
+                "Mode",
+                self.mode.map(|m| m.to_string()).unwrap_or_else(unknown),
+            ),
+            (
+                "Battery",
+                self.battery
+                    .map(|b| format!("{:.2} V", b as f32 / 1000.0))
+                    .unwrap_or_else(unknown),
+            ),
+        ]
+    }
+}
+
+impl std::fmt::Display for RadioStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in self.fields() {
+            writeln!(f, "{name:<14}{value}")?;
+        }
+        Ok(())
+    }
+}
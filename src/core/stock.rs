@@ -0,0 +1,463 @@
+//! Archives of the factory data of TYT radios
+//!
+//! Going back to the vendor firmware of an MD-3x0 or MD-UV3x0 takes its
+//! update image, as distributed by TYT, and the data the vendor firmware
+//! keeps in the radio. Before OpenRTX is flashed, an archive is read out of
+//! the radio turned on normally with its vendor firmware, over USB DFU (see
+//! [`dfu`](super::dfu)):
+//!
+//! - the firmware cannot be read: the TYT bootloader only allows writing it;
+//! - the factory calibration cannot be read either, the vendor firmware only
+//!   exposes the codeplug region of the external flash. OpenRTX leaves the
+//!   calibration in place, it can be saved with
+//!   [`CalibrationReadJob`](super::CalibrationReadJob) once OpenRTX runs;
+//! - the codeplug region of the external flash is read as the TYT
+//!   programming software does.
+//!
+//! Archives are kept in folders holding the region images and an
+//! `archive.toml` with their address and SHA-256. Once the vendor update
+//! image is flashed back, restoring an archive writes these regions, and
+//! only them, back to the radio.
+//!
+//! Whether a radio still runs the vendor firmware can only be told when it
+//! answers over rtxlink, see [`find_openrtx`].
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use time::OffsetDateTime;
+use toml::{Table, Value};
+
+use super::dfu::{self, DfuError};
+use super::dirs;
+use super::manifest::{sha256_hex, timestamp};
+use super::ports::usb_id;
+use super::{
+    cat, is_port_busy, CancelToken, JobError, JobEvent, JobHandle, Phase, Progress, RadioHW,
+    RadioLink, SerialPort,
+};
+
+const ARCHIVE_MANIFEST: &str = "archive.toml";
+
+/// Name of the archived codeplug region
+pub const CODEPLUG_REGION: &str = "Codeplug";
+
+/// What an archive can not hold, and why
+pub const NOT_ARCHIVED: &str = "The vendor firmware and the factory calibration cannot be \
+    read out of the radio: the TYT bootloader only allows writing the firmware, and the \
+    vendor firmware only exposes its codeplug. Keep the update image from TYT to go back \
+    to the vendor firmware.";
+
+/// Events reported by a [`StockArchiveJob`]
+pub type StockArchiveEvent = JobEvent<StockArchive, StockError>;
+
+/// Events reported by a [`StockRestoreJob`]
+pub type StockRestoreEvent = JobEvent<RadioHW, StockError>;
+
+#[derive(Debug)]
+pub enum StockError {
+    Io(std::io::Error),
+    /// Only radios with the TYT bootloader have factory data to archive
+    Unsupported(RadioHW),
+    /// An archived region is not part of the codeplug of the radio
+    InvalidRegion(String),
+    Manifest(String),
+    /// An archived file does not match its checksum
+    Corrupted(PathBuf),
+    Dfu(DfuError),
+    Cancelled,
+}
+
+impl std::fmt::Display for StockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StockError::Io(e) => write!(f, "{e}"),
+            StockError::Unsupported(radio) => {
+                write!(f, "Factory data archives are not supported for the {radio}")
+            }
+            StockError::InvalidRegion(name) => write!(
+                f,
+                "The archived region {name} is not the codeplug of the radio"
+            ),
+            StockError::Manifest(e) => write!(f, "Invalid factory data archive: {e}"),
+            StockError::Corrupted(path) => {
+                write!(f, "{} does not match its checksum", path.display())
+            }
+            StockError::Dfu(e) => write!(f, "{e}"),
+            StockError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for StockError {}
+
+impl JobError for StockError {
+    fn is_cancelled(&self) -> bool {
+        matches!(self, StockError::Cancelled)
+    }
+}
+
+impl From<std::io::Error> for StockError {
+    fn from(e: std::io::Error) -> Self {
+        StockError::Io(e)
+    }
+}
+
+impl From<DfuError> for StockError {
+    fn from(e: DfuError) -> Self {
+        match e {
+            DfuError::Cancelled => StockError::Cancelled,
+            e => StockError::Dfu(e),
+        }
+    }
+}
+
+/// Check whether a radio comes with a vendor firmware whose data is worth
+/// archiving
+pub fn is_supported(radio: RadioHW) -> bool {
+    matches!(radio, RadioHW::Md3x0 | RadioHW::Mduv3x0)
+}
+
+/// Folder holding the factory data archives
+pub fn archive_root() -> PathBuf {
+    dirs::data_dir().join("stock")
+}
+
+/// Whether no factory data was archived for a radio model with a vendor
+/// firmware
+///
+/// The TYT bootloader does not tell which firmware the radio runs, so a radio
+/// is assumed to run the vendor firmware until its data was archived, unless
+/// it was seen answering over rtxlink.
+pub fn needs_archive(root: &Path, radio: RadioHW) -> bool {
+    is_supported(radio) && matches!(StockArchive::latest(root, radio), Ok(None))
+}
+
+/// Models of the TYT radios answering over rtxlink, which run OpenRTX instead
+/// of their vendor firmware
+///
+/// Only the idle ports with the USB ID of these radios running OpenRTX are
+/// probed, so that other devices are left alone. Where USB IDs can not be
+/// read, nothing is found.
+pub fn find_openrtx(ports: &[SerialPort]) -> Vec<RadioHW> {
+    ports
+        .iter()
+        .filter(|p| usb_id(&p.name) == Some(RadioHW::Md3x0.rtxlink_usb_id()))
+        .filter(|p| !is_port_busy(&p.name))
+        .filter_map(|p| {
+            let mut link = RadioLink::open(&p.name).ok()?;
+            RadioHW::from_info(&cat::info(&mut link).ok()?)
+        })
+        .filter(|radio| is_supported(*radio))
+        .collect()
+}
+
+/// Region of the external flash saved in an archive
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchivedRegion {
+    pub name: String,
+    pub address: u32,
+    /// Name of the file in the archive
+    pub file: String,
+    pub sha256: String,
+}
+
+/// Factory data read out of a radio
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StockArchive {
+    pub dir: PathBuf,
+    pub radio: RadioHW,
+    /// Creation time, in UTC
+    pub created: String,
+    pub regions: Vec<ArchivedRegion>,
+}
+
+// Name of the file holding the image of a region
+fn region_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.bin", name.to_lowercase())
+}
+
+impl StockArchive {
+    fn write_manifest(&self) -> std::io::Result<()> {
+        let mut table = Table::new();
+        table.insert("radio".into(), Value::String(self.radio.into()));
+        table.insert("created".into(), Value::String(self.created.clone()));
+        let regions = self
+            .regions
+            .iter()
+            .map(|region| {
+                let mut entry = Table::new();
+                entry.insert("name".into(), Value::String(region.name.clone()));
+                entry.insert("address".into(), Value::Integer(region.address as i64));
+                entry.insert("file".into(), Value::String(region.file.clone()));
+                entry.insert("sha256".into(), Value::String(region.sha256.clone()));
+                Value::Table(entry)
+            })
+            .collect();
+        table.insert("regions".into(), Value::Array(regions));
+        std::fs::write(self.dir.join(ARCHIVE_MANIFEST), table.to_string())
+    }
+
+    /// Read an archive folder
+    pub fn load(dir: &Path) -> Result<StockArchive, StockError> {
+        let text = std::fs::read_to_string(dir.join(ARCHIVE_MANIFEST))?;
+        let table: Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| StockError::Manifest(e.message().to_string()))?;
+        let string = |table: &Table, key: &str| -> Result<String, StockError> {
+            table
+                .get(key)
+                .and_then(Value::as_str)
+                .map(String::from)
+                .ok_or(StockError::Manifest(format!("no {key}")))
+        };
+        let radio = RadioHW::from_info(&string(&table, "radio")?)
+            .ok_or(StockError::Manifest(String::from("unknown radio")))?;
+        let regions = table
+            .get("regions")
+            .and_then(Value::as_array)
+            .ok_or(StockError::Manifest(String::from("no regions")))?
+            .iter()
+            .map(|entry| {
+                let entry = entry
+                    .as_table()
+                    .ok_or(StockError::Manifest(String::from("invalid region")))?;
+                let region = ArchivedRegion {
+                    name: string(entry, "name")?,
+                    address: entry
+                        .get("address")
+                        .and_then(Value::as_integer)
+                        .and_then(|a| u32::try_from(a).ok())
+                        .ok_or(StockError::Manifest(String::from("no address")))?,
+                    file: string(entry, "file")?,
+                    sha256: string(entry, "sha256")?,
+                };
+                // Files are only looked up in the archive folder
+                if region.file != region_file_name(&region.name) {
+                    return Err(StockError::Manifest(format!(
+                        "invalid file {}",
+                        region.file
+                    )));
+                }
+                Ok(region)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(StockArchive {
+            dir: dir.to_path_buf(),
+            radio,
+            created: string(&table, "created")?,
+            regions,
+        })
+    }
+
+    /// Most recent archive of a radio model
+    pub fn latest(root: &Path, radio: RadioHW) -> Result<Option<StockArchive>, StockError> {
+        let entries = match std::fs::read_dir(root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(entries
+            .filter_map(|e| StockArchive::load(&e.ok()?.path()).ok())
+            .filter(|a| a.radio == radio)
+            // Folders created in the same second are named in order
+            .max_by(|a, b| (&a.created, &a.dir).cmp(&(&b.created, &b.dir))))
+    }
+
+    /// Path of the image of a region
+    pub fn region(&self, region: &ArchivedRegion) -> PathBuf {
+        self.dir.join(&region.file)
+    }
+
+    /// Names of the archived regions
+    pub fn contents(&self) -> Vec<&str> {
+        self.regions.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    /// Check the archived files against their checksums
+    pub fn verify(&self) -> Result<(), StockError> {
+        for region in &self.regions {
+            let path = self.region(region);
+            if sha256_hex(&std::fs::read(&path)?) != region.sha256 {
+                return Err(StockError::Corrupted(path));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Create the folder of a new archive, named after the radio and the time with
+// a counter for archives created in the same second
+fn create_dir(root: &Path, radio: RadioHW, now: OffsetDateTime) -> std::io::Result<PathBuf> {
+    let name = format!(
+        "{}_{:04}{:02}{:02}-{:02}{:02}{:02}",
+        String::from(radio).replace(' ', "_"),
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+    std::fs::create_dir_all(root)?;
+    let mut dir = root.join(&name);
+    let mut n = 1;
+    loop {
+        match std::fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                n += 1;
+                dir = root.join(format!("{name}-{n:03}"));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Read the factory data of a TYT radio running its vendor firmware into a
+/// new archive, before OpenRTX is flashed
+#[derive(Clone, Debug)]
+pub struct StockArchiveJob {
+    /// Model of the attached radio, which it does not report over DFU
+    pub radio: RadioHW,
+    /// Folder holding the archives
+    pub root: PathBuf,
+}
+
+impl StockArchiveJob {
+    pub fn new(radio: RadioHW, root: impl Into<PathBuf>) -> Self {
+        StockArchiveJob {
+            radio,
+            root: root.into(),
+        }
+    }
+
+    /// Run the job on the calling thread, returning the new archive
+    ///
+    /// Nothing is left in the archive folder if the job fails.
+    pub fn run(
+        self,
+        events: &Sender<StockArchiveEvent>,
+        cancel: &CancelToken,
+    ) -> Result<StockArchive, StockError> {
+        let radio = self.radio;
+        let (address, size) = dfu::codeplug_region(radio)
+            .filter(|_| is_supported(radio))
+            .ok_or(StockError::Unsupported(radio))?;
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut device = dfu::open()?;
+        dfu::enter_programming_mode(device.as_mut())?;
+
+        _ = events.send(JobEvent::Phase(Phase::Reading));
+        let data = dfu::read(device.as_mut(), address, size, cancel, |p| {
+            _ = events.send(JobEvent::Progress(p));
+        })?;
+        dfu::reboot(device.as_mut())?;
+
+        let now = OffsetDateTime::now_utc();
+        let file = region_file_name(CODEPLUG_REGION);
+        let archive = StockArchive {
+            dir: create_dir(&self.root, radio, now)?,
+            radio,
+            created: timestamp(now),
+            regions: vec![ArchivedRegion {
+                name: String::from(CODEPLUG_REGION),
+                address,
+                file: file.clone(),
+                sha256: sha256_hex(&data),
+            }],
+        };
+        let res =
+            std::fs::write(archive.dir.join(&file), &data).and_then(|()| archive.write_manifest());
+        if let Err(e) = res {
+            _ = std::fs::remove_dir_all(&archive.dir);
+            return Err(e.into());
+        }
+        Ok(archive)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<StockArchive, StockError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
+
+/// Write the regions of an archive back to a TYT radio, once it runs its
+/// vendor firmware again
+///
+/// Only the archived regions are written, and only when they are the
+/// codeplug region of the radio model of the archive.
+#[derive(Clone, Debug)]
+pub struct StockRestoreJob {
+    pub archive: StockArchive,
+}
+
+impl StockRestoreJob {
+    pub fn new(archive: StockArchive) -> Self {
+        StockRestoreJob { archive }
+    }
+
+    /// Run the job on the calling thread, returning the restored radio model
+    ///
+    /// Cancellation stops the job before the radio is written, a region
+    /// being written completely.
+    pub fn run(
+        self,
+        events: &Sender<StockRestoreEvent>,
+        cancel: &CancelToken,
+    ) -> Result<RadioHW, StockError> {
+        _ = events.send(JobEvent::Phase(Phase::Checking));
+        self.archive.verify()?;
+        let radio = self.archive.radio;
+        let codeplug = dfu::codeplug_region(radio).ok_or(StockError::Unsupported(radio))?;
+        let mut images = vec![];
+        for region in &self.archive.regions {
+            let data = std::fs::read(self.archive.region(region))?;
+            if (region.address, data.len()) != codeplug {
+                return Err(StockError::InvalidRegion(region.name.clone()));
+            }
+            images.push((region.address, data));
+        }
+
+        _ = events.send(JobEvent::Phase(Phase::Connecting));
+        let mut device = dfu::open()?;
+        dfu::enter_programming_mode(device.as_mut())?;
+
+        _ = events.send(JobEvent::Phase(Phase::Writing));
+        let total = images.iter().map(|(_, data)| data.len()).sum();
+        let mut done = 0;
+        for (address, data) in images {
+            if cancel.is_cancelled() {
+                return Err(StockError::Cancelled);
+            }
+            cancel
+                .uninterruptible(|| {
+                    dfu::write(device.as_mut(), address, &data, |p| {
+                        _ = events.send(JobEvent::Progress(Progress {
+                            transferred: done + p.transferred,
+                            total,
+                        }));
+                    })
+                })
+                .ok_or(StockError::Cancelled)??;
+            done += data.len();
+        }
+        dfu::reboot(device.as_mut())?;
+        Ok(radio)
+    }
+
+    /// Run the job in a worker thread
+    pub fn start(self) -> JobHandle<RadioHW, StockError> {
+        JobHandle::spawn(move |events, cancel| self.run(events, cancel))
+    }
+}
@@ -0,0 +1,330 @@
+//! Recording of the frequency, signal strength and battery voltage over time
+//!
+//! A [`TelemetryRecorder`] samples the radio over rtxlink CAT at a fixed
+//! interval. Samples are written to a CSV file as they are taken, with the
+//! `Time`, `Frequency`, `RSSI` and `Battery` columns, so that a recording
+//! survives the companion being closed. Times are in UTC with millisecond
+//! resolution, RSSI in dBm and battery voltage in mV; values the radio does
+//! not report are left empty.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+use super::cat::{self, CatId};
+use super::manifest::timestamp_millis;
+use super::{LinkError, RadioLink};
+
+const COLUMNS: [&str; 4] = ["Time", "Frequency", "RSSI", "Battery"];
+// Columns missing from older recordings, read as empty
+const OPTIONAL_COLUMNS: usize = 2;
+
+// Interval at which a running recorder checks whether it is stopped
+const STOP_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Telemetry of the radio at a point in time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sample {
+    pub time: OffsetDateTime,
+    /// RX frequency in Hz
+    pub frequency: u32,
+    /// Received signal strength in dBm
+    pub rssi: Option<i16>,
+    /// Battery voltage in mV
+    pub battery: Option<u16>,
+}
+
+impl Sample {
+    /// Sample the radio on an open link
+    ///
+    /// The time is truncated to the millisecond, as written to recordings.
+    pub fn query(link: &mut RadioLink) -> Result<Sample, LinkError> {
+        let now = OffsetDateTime::now_utc();
+        Ok(Sample {
+            time: now.replace_millisecond(now.millisecond()).unwrap_or(now),
+            frequency: cat::rx_frequency(link)?,
+            rssi: cat::get_optional(link, CatId::Rssi)?
+                .and_then(|r| Some(i16::from_le_bytes(r.try_into().ok()?))),
+            battery: cat::get_optional(link, CatId::Battery)?
+                .and_then(|b| Some(u16::from_le_bytes(b.try_into().ok()?))),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum TelemetryError {
+    /// The CSV file has no column of this name
+    MissingColumn(&'static str),
+    /// A row of the CSV file has an invalid value
+    InvalidRow(u64),
+    Csv(csv::Error),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for TelemetryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TelemetryError::MissingColumn(column) => {
+                write!(f, "Not a telemetry recording, no {column} column")
+            }
+            TelemetryError::InvalidRow(line) => write!(f, "Invalid value on line {line}"),
+            TelemetryError::Csv(e) => write!(f, "{e}"),
+            TelemetryError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for TelemetryError {}
+
+impl From<csv::Error> for TelemetryError {
+    fn from(e: csv::Error) -> Self {
+        TelemetryError::Csv(e)
+    }
+}
+
+impl From<std::io::Error> for TelemetryError {
+    fn from(e: std::io::Error) -> Self {
+        TelemetryError::Io(e)
+    }
+}
+
+/// Format a time as RFC 3339 in UTC, with milliseconds
+pub fn format_time(time: OffsetDateTime) -> String {
+    timestamp_millis(time)
+}
+
+// Milliseconds of the fraction of a second, as written after the dot
+fn parse_millis(fraction: &str) -> Option<u16> {
+    if fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // ".25" is 250 ms, digits past the millisecond are dropped
+    let digits = &fraction[..fraction.len().min(3)];
+    format!("{digits:0<3}").parse().ok()
+}
+
+/// Parse a time written by [`format_time`], the fraction of a second being
+/// optional and of any precision
+pub fn parse_time(text: &str) -> Option<OffsetDateTime> {
+    let (date, time) = text.trim().strip_suffix('Z')?.split_once('T')?;
+    let mut date = date.splitn(3, '-');
+    let year = date.next()?.parse().ok()?;
+    let month: u8 = date.next()?.parse().ok()?;
+    let day = date.next()?.parse().ok()?;
+    let (time, millis) = match time.split_once('.') {
+        Some((time, fraction)) => (time, parse_millis(fraction)?),
+        None => (time, 0),
+    };
+    let mut time = time.splitn(3, ':');
+    let hour = time.next()?.parse().ok()?;
+    let minute = time.next()?.parse().ok()?;
+    let second = time.next()?.parse().ok()?;
+    let date = Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()?;
+    let time = Time::from_hms_milli(hour, minute, second, millis).ok()?;
+    Some(PrimitiveDateTime::new(date, time).assume_utc())
+}
+
+fn write_sample<W: Write>(writer: &mut csv::Writer<W>, sample: &Sample) -> csv::Result<()> {
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    writer.write_record([
+        format_time(sample.time),
+        sample.frequency.to_string(),
+        optional(sample.rssi.map(|r| r.to_string())),
+        optional(sample.battery.map(|b| b.to_string())),
+    ])?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write samples as CSV
+pub fn save(samples: &[Sample], writer: impl Write) -> Result<(), TelemetryError> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(COLUMNS)?;
+    for sample in samples {
+        write_sample(&mut writer, sample)?;
+    }
+    Ok(())
+}
+
+// Value of an optional column, None when empty
+fn parse_optional<T: std::str::FromStr>(field: &str) -> Result<Option<T>, T::Err> {
+    match field {
+        "" => Ok(None),
+        field => field.parse().map(Some),
+    }
+}
+
+/// Read the samples of a CSV recording, other columns being ignored
+///
+/// Recordings without the `RSSI` and `Battery` columns are read with these
+/// values left empty.
+pub fn load(reader: impl Read) -> Result<Vec<Sample>, TelemetryError> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let mut columns = [None; COLUMNS.len()];
+    for (index, column) in COLUMNS.iter().enumerate() {
+        columns[index] = headers.iter().position(|h| h == *column);
+        if columns[index].is_none() && index < COLUMNS.len() - OPTIONAL_COLUMNS {
+            return Err(TelemetryError::MissingColumn(column));
+        }
+    }
+    let mut samples = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line());
+        let invalid = || TelemetryError::InvalidRow(line);
+        let field = |column: usize| {
+            columns[column]
+                .and_then(|index| record.get(index))
+                .unwrap_or_default()
+                .trim()
+        };
+        samples.push(Sample {
+            time: parse_time(field(0)).ok_or_else(invalid)?,
+            frequency: field(1).parse().map_err(|_| invalid())?,
+            rssi: parse_optional(field(2)).map_err(|_| invalid())?,
+            battery: parse_optional(field(3)).map_err(|_| invalid())?,
+        });
+    }
+    Ok(samples)
+}
+
+pub fn load_file(path: &Path) -> Result<Vec<Sample>, TelemetryError> {
+    load(File::open(path)?)
+}
+
+pub fn save_file(samples: &[Sample], path: &Path) -> Result<(), TelemetryError> {
+    save(samples, File::create(path)?)
+}
+
+// State shared by the recorder thread and its handle
+#[derive(Default)]
+struct Shared {
+    stop: AtomicBool,
+    // Samples not yet taken by the handle
+    samples: Mutex<Vec<Sample>>,
+    error: Mutex<Option<String>>,
+}
+
+fn record(
+    shared: Arc<Shared>,
+    port: String,
+    interval: Duration,
+    mut output: Option<csv::Writer<File>>,
+) {
+    let mut link = match RadioLink::open(&port) {
+        Ok(link) => link,
+        Err(e) => {
+            *shared.error.lock().unwrap() = Some(e.to_string());
+            return;
+        }
+    };
+    let mut next = Instant::now();
+    while !shared.stop.load(Ordering::SeqCst) {
+        if Instant::now() < next {
+            std::thread::sleep(STOP_INTERVAL.min(next - Instant::now()));
+            continue;
+        }
+        // Late samples delay the next ones rather than being caught up
+        next = Instant::now() + interval;
+        // Errors are reported and sampling goes on, the radio may come back
+        let result = Sample::query(&mut link)
+            .map_err(|e| e.to_string())
+            .and_then(|sample| {
+                if let Some(writer) = &mut output {
+                    write_sample(writer, &sample).map_err(|e| e.to_string())?;
+                }
+                shared.samples.lock().unwrap().push(sample);
+                Ok(())
+            });
+        *shared.error.lock().unwrap() = result.err();
+    }
+}
+
+/// Sampling of a radio running OpenRTX, until stopped or dropped
+///
+/// The recorder keeps the serial port while it runs. It ends on its own
+/// when the radio can not be opened, with [`TelemetryRecorder::error`]
+/// telling why.
+pub struct TelemetryRecorder {
+    output: Option<PathBuf>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TelemetryRecorder {
+    /// Sample the radio on serial port `port` every `interval`, writing the
+    /// samples to the CSV file `output` if there is one
+    pub fn start(
+        port: &str,
+        interval: Duration,
+        output: Option<&Path>,
+    ) -> Result<TelemetryRecorder, TelemetryError> {
+        let writer = match output {
+            Some(path) => {
+                let mut writer = csv::Writer::from_path(path)?;
+                writer.write_record(COLUMNS)?;
+                writer.flush()?;
+                Some(writer)
+            }
+            None => None,
+        };
+        let shared = Arc::new(Shared::default());
+        let thread = {
+            let shared = shared.clone();
+            let port = String::from(port);
+            std::thread::spawn(move || record(shared, port, interval, writer))
+        };
+        Ok(TelemetryRecorder {
+            output: output.map(Path::to_path_buf),
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// File the samples are written to
+    pub fn output(&self) -> Option<&Path> {
+        self.output.as_deref()
+    }
+
+    /// Samples taken since the last call
+    pub fn take_samples(&self) -> Vec<Sample> {
+        std::mem::take(&mut *self.shared.samples.lock().unwrap())
+    }
+
+    /// Error of the last sample, if it failed, or why the radio could not
+    /// be opened
+    pub fn error(&self) -> Option<String> {
+        self.shared.error.lock().unwrap().clone()
+    }
+
+    /// Whether the radio is still sampled
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Stop sampling and close the connection to the radio, waiting for
+    /// the sample in progress to be taken or to time out
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+impl Drop for TelemetryRecorder {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
@@ -0,0 +1,440 @@
+// show logs when debugging
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use crate::backup::port_options;
+use crate::Message;
+use crate::Tab;
+use iced::{
+    alignment::{Horizontal, Vertical},
+    widget::{
+        combo_box, progress_bar, row, scrollable, text, text_input, Button, Column, Container, Row,
+        Text,
+    },
+    Alignment, Element, Length, Task,
+};
+use iced_aw::TabLabel;
+use openrtx_companion::core::{
+    files::{self, Entry},
+    get_ports, Attached, FileError, JobHandle, JobState, SerialPort, Transfer, TransferJob,
+};
+use rfd::AsyncFileDialog;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug)]
+pub enum FilesMessage {
+    PortSelected(SerialPort),
+    PortsChanged(Attached),
+    RefreshPressed,
+    Listed(Result<Vec<Entry>, String>),
+    EntryPressed(Entry),
+    UpPressed,
+    DownloadPressed(Entry),
+    DownloadTo(Transfer),
+    UploadPressed,
+    // Local files to upload into the current directory, picked or dropped
+    Upload(Vec<PathBuf>),
+    DeletePressed(Entry),
+    RenamePressed(Entry),
+    RenameChanged(String),
+    RenameSubmitted,
+    Done(Result<(), String>),
+    CancelPressed,
+    Tick,
+}
+
+pub struct FilesTab {
+    serial_ports: Vec<SerialPort>,
+    serial_port: Option<SerialPort>,
+    // The selected port has been unplugged
+    port_gone: bool,
+    ports_combo_state: combo_box::State<SerialPort>,
+    dir: String,
+    entries: Vec<Entry>,
+    // Entry being renamed and its new name
+    renaming: Option<(Entry, String)>,
+    transfer_job: Option<JobHandle<Transfer, FileError>>,
+    // Files left to upload after the running transfer
+    upload_queue: Vec<PathBuf>,
+    progress: f32,
+    status_text: String,
+}
+
+impl Default for FilesTab {
+    fn default() -> Self {
+        let ports = port_options(get_ports());
+        Self {
+            serial_ports: ports.clone(),
+            serial_port: None,
+            port_gone: false,
+            ports_combo_state: combo_box::State::new(ports),
+            dir: String::from("/"),
+            entries: vec![],
+            renaming: None,
+            transfer_job: None,
+            upload_queue: vec![],
+            progress: 0.0,
+            status_text: String::from("Select a serial port"),
+        }
+    }
+}
+
+fn size_text(size: usize) -> String {
+    match size {
+        s if s >= 1024 * 1024 => format!("{:.1} MiB", s as f32 / (1024.0 * 1024.0)),
+        s if s >= 1024 => format!("{:.1} KiB", s as f32 / 1024.0),
+        s => format!("{s} B"),
+    }
+}
+
+impl FilesTab {
+    pub fn update(&mut self, message: FilesMessage) -> Task<Message> {
+        match message {
+            FilesMessage::PortSelected(port) => {
+                self.serial_port = Some(port);
+                self.port_gone = false;
+                self.dir = String::from("/");
+                self.update(FilesMessage::RefreshPressed)
+            }
+            FilesMessage::PortsChanged(attached) => {
+                let ports = port_options(attached.ports.clone());
+                if ports != self.serial_ports {
+                    self.serial_ports = ports.clone();
+                    self.ports_combo_state = combo_box::State::new(ports);
+                }
+                if let Some(port) = &self.serial_port {
+                    self.port_gone = !attached.has_port(port);
+                }
+                Task::none()
+            }
+            FilesMessage::RefreshPressed => {
+                let Some(port) = self.port() else {
+                    return Task::none();
+                };
+                let dir = self.dir.clone();
+                self.renaming = None;
+                Task::perform(
+                    crate::blocking(move || files::browse(&port, &dir).map_err(|e| e.to_string())),
+                    |res| Message::Files(FilesMessage::Listed(res)),
+                )
+            }
+            FilesMessage::Listed(res) => {
+                match res {
+                    Ok(entries) => {
+                        self.status_text = format!("{}: {} entries", self.dir, entries.len());
+                        self.entries = entries;
+                    }
+                    Err(e) => {
+                        self.status_text = format!("Error in listing {}: {e}", self.dir);
+                        self.entries = vec![];
+                    }
+                }
+                Task::none()
+            }
+            FilesMessage::EntryPressed(Entry::Directory { name }) => {
+                self.dir = files::join(&self.dir, &name);
+                self.update(FilesMessage::RefreshPressed)
+            }
+            FilesMessage::EntryPressed(_) => Task::none(),
+            FilesMessage::UpPressed => {
+                self.dir = files::parent(&self.dir);
+                self.update(FilesMessage::RefreshPressed)
+            }
+            FilesMessage::DownloadPressed(entry) => {
+                let dir = self.dir.clone();
+                Task::perform(
+                    async move {
+                        let file = AsyncFileDialog::new()
+                            .set_file_name(entry.name())
+                            .save_file()
+                            .await?;
+                        let local = file.path().to_path_buf();
+                        Some(match entry {
+                            Entry::Memory { index, .. } => Transfer::DumpMemory {
+                                memory: index,
+                                local,
+                            },
+                            entry => Transfer::Download {
+                                remote: files::join(&dir, entry.name()),
+                                local,
+                            },
+                        })
+                    },
+                    |transfer| match transfer {
+                        Some(transfer) => Message::Files(FilesMessage::DownloadTo(transfer)),
+                        None => Message::Files(FilesMessage::Done(Ok(()))),
+                    },
+                )
+            }
+            FilesMessage::DownloadTo(transfer) => {
+                self.start(transfer);
+                Task::none()
+            }
+            FilesMessage::UploadPressed => Task::perform(
+                async {
+                    AsyncFileDialog::new()
+                        .pick_files()
+                        .await
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|f| f.path().to_path_buf())
+                        .collect()
+                },
+                |files| Message::Files(FilesMessage::Upload(files)),
+            ),
+            FilesMessage::Upload(files) => {
+                if self.port().is_none() {
+                    self.status_text = String::from("No serial port selected!");
+                    return Task::none();
+                }
+                self.upload_queue.extend(files);
+                if self.transfer_job.is_none() && !self.upload_queue.is_empty() {
+                    let local = self.upload_queue.remove(0);
+                    self.start(Transfer::upload(&local, &self.dir));
+                }
+                Task::none()
+            }
+            FilesMessage::DeletePressed(entry) => {
+                let Some(port) = self.port() else {
+                    return Task::none();
+                };
+                let path = files::join(&self.dir, entry.name());
+                self.status_text = format!("Deleting {path}...");
+                Task::perform(
+                    crate::blocking(move || files::remove(&port, &path).map_err(|e| e.to_string())),
+                    |res| Message::Files(FilesMessage::Done(res)),
+                )
+            }
+            FilesMessage::RenamePressed(entry) => {
+                let name = String::from(entry.name());
+                self.renaming = Some((entry, name));
+                Task::none()
+            }
+            FilesMessage::RenameChanged(name) => {
+                if let Some((_, new_name)) = &mut self.renaming {
+                    *new_name = name;
+                }
+                Task::none()
+            }
+            FilesMessage::RenameSubmitted => {
+                let (Some(port), Some((entry, name))) = (self.port(), self.renaming.take()) else {
+                    return Task::none();
+                };
+                if name.is_empty() || name.contains('/') || name == entry.name() {
+                    return Task::none();
+                }
+                let from = files::join(&self.dir, entry.name());
+                let to = files::join(&self.dir, &name);
+                Task::perform(
+                    crate::blocking(move || {
+                        files::rename(&port, &from, &to).map_err(|e| e.to_string())
+                    }),
+                    |res| Message::Files(FilesMessage::Done(res)),
+                )
+            }
+            FilesMessage::Done(res) => match res {
+                Ok(()) => self.update(FilesMessage::RefreshPressed),
+                Err(e) => {
+                    self.status_text = format!("Error: {e}");
+                    Task::none()
+                }
+            },
+            FilesMessage::CancelPressed => {
+                self.upload_queue.clear();
+                if self.transfer_job.as_ref().is_some_and(|j| j.cancel()) {
+                    self.status_text = String::from("Cancelling...");
+                }
+                Task::none()
+            }
+            FilesMessage::Tick => {
+                let Some(transfer_job) = &mut self.transfer_job else {
+                    return Task::none();
+                };
+                match transfer_job.poll() {
+                    JobState::Running { phase, progress } => match (phase, progress) {
+                        (_, Some(p)) => {
+                            self.progress = p.percent();
+                            self.status_text = format!("{}/{}", p.transferred, p.total);
+                        }
+                        (Some(phase), None) => self.status_text = phase.to_string(),
+                        (None, None) => (),
+                    },
+                    JobState::Completed(transfer) => {
+                        self.status_text = format!("Copied {transfer}");
+                        self.progress = 100.0;
+                    }
+                    JobState::Failed(e) => self.status_text = format!("Transfer failed: {e}"),
+                    JobState::Cancelled => {
+                        self.status_text = String::from("Cancelled");
+                        self.progress = 0.0;
+                    }
+                }
+                if transfer_job.is_running() {
+                    return Task::none();
+                }
+                self.transfer_job = None;
+                if !self.upload_queue.is_empty() {
+                    let local = self.upload_queue.remove(0);
+                    self.start(Transfer::upload(&local, &self.dir));
+                    return Task::none();
+                }
+                self.update(FilesMessage::RefreshPressed)
+            }
+        }
+    }
+
+    // Selected serial port, if still attached
+    fn port(&self) -> Option<String> {
+        self.serial_port
+            .as_ref()
+            .filter(|_| !self.port_gone)
+            .map(|p| p.name.clone())
+    }
+
+    fn start(&mut self, transfer: Transfer) {
+        let Some(port) = self.port() else {
+            return;
+        };
+        self.progress = 0.0;
+        self.status_text = format!("Copying {transfer}...");
+        self.transfer_job = Some(TransferJob::new(port, transfer).start());
+    }
+
+    fn entry_row<'a>(&'a self, entry: &'a Entry) -> Element<'a, FilesMessage> {
+        let busy = self.transfer_job.is_some();
+        let name: Element<'_, FilesMessage> = match &self.renaming {
+            Some((renamed, name)) if renamed == entry => text_input("New name", name)
+                .on_input(FilesMessage::RenameChanged)
+                .on_submit(FilesMessage::RenameSubmitted)
+                .size(15)
+                .into(),
+            _ => match entry {
+                Entry::Directory { name } => Button::new(Text::new(format!("{name}/")).size(15))
+                    .style(iced::widget::button::text)
+                    .on_press(FilesMessage::EntryPressed(entry.clone()))
+                    .into(),
+                entry => text(entry.name()).size(15).into(),
+            },
+        };
+        let is_memory = matches!(entry, Entry::Memory { .. });
+        row![
+            Column::new().width(Length::Fill).push(name),
+            Column::new()
+                .width(80)
+                .push(text(entry.size().map(size_text).unwrap_or_default()).size(15)),
+        ]
+        .push_maybe((!entry_is_dir(entry)).then(|| {
+            Button::new(Text::new("Download").size(15))
+                .on_press_maybe((!busy).then_some(FilesMessage::DownloadPressed(entry.clone())))
+        }))
+        .push_maybe((!is_memory).then(|| {
+            Button::new(Text::new("Rename").size(15))
+                .on_press_maybe((!busy).then_some(FilesMessage::RenamePressed(entry.clone())))
+        }))
+        .push_maybe((!is_memory).then(|| {
+            Button::new(Text::new("Delete").size(15))
+                .on_press_maybe((!busy).then_some(FilesMessage::DeletePressed(entry.clone())))
+        }))
+        .spacing(10)
+        .align_y(Alignment::Center)
+        .into()
+    }
+}
+
+fn entry_is_dir(entry: &Entry) -> bool {
+    matches!(entry, Entry::Directory { .. })
+}
+
+impl Tab for FilesTab {
+    type Message = Message;
+
+    fn title(&self) -> String {
+        String::from("Files")
+    }
+
+    fn tab_label(&self) -> TabLabel {
+        TabLabel::Text(self.title())
+    }
+
+    fn content(&self) -> Element<'_, Self::Message> {
+        let port_combo_box = combo_box(
+            &self.ports_combo_state,
+            "Select a serial port",
+            self.serial_port.as_ref(),
+            FilesMessage::PortSelected,
+        )
+        .width(250);
+        let connected = self.port().is_some();
+        let busy = self.transfer_job.is_some();
+
+        let content: Element<'_, FilesMessage> = Container::new(
+            Column::new()
+                .max_width(700)
+                .push(
+                    row![
+                        Column::new().width(120).push(text("Serial port:").size(15)),
+                        port_combo_box,
+                    ]
+                    .push_maybe(self.port_gone.then(|| text("Unplugged").size(15)))
+                    .spacing(10)
+                    .padding(20),
+                )
+                .push(
+                    row![
+                        Button::new(Text::new("Up").size(15)).on_press_maybe(
+                            (connected && self.dir != "/").then_some(FilesMessage::UpPressed)
+                        ),
+                        Column::new()
+                            .width(Length::Fill)
+                            .push(text(&self.dir).size(15)),
+                        Button::new(Text::new("Refresh").size(15))
+                            .on_press_maybe(connected.then_some(FilesMessage::RefreshPressed)),
+                    ]
+                    .spacing(10)
+                    .padding([0, 20])
+                    .align_y(Alignment::Center),
+                )
+                .push(
+                    scrollable(
+                        self.entries
+                            .iter()
+                            .fold(Column::new().spacing(5).padding(20), |column, entry| {
+                                column.push(self.entry_row(entry))
+                            }),
+                    )
+                    .height(200),
+                )
+                .push(row![Column::new()
+                    .width(700)
+                    .align_x(Alignment::Center)
+                    .push(text(&self.status_text).size(20)),])
+                .push(row![progress_bar(0.0..=100.0, self.progress),].padding(20))
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .push(
+                            Button::new(Text::new("Upload").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (connected && !busy).then_some(FilesMessage::UploadPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Cancel").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    self.transfer_job
+                                        .as_ref()
+                                        .is_some_and(|j| j.can_cancel())
+                                        .then_some(FilesMessage::CancelPressed),
+                                ),
+                        ),
+                )
+                .push(text("Drop files on the window to upload them here").size(13)),
+        )
+        .align_x(Horizontal::Center)
+        .align_y(Vertical::Center)
+        .into();
+
+        content.map(Message::Files)
+    }
+}
@@ -3,41 +3,28 @@
 
 use iced::{
     alignment::{Horizontal, Vertical},
-    widget::{combo_box, progress_bar, row, text, Button, Column, Container, Row, Text},
+    widget::{
+        checkbox, combo_box, progress_bar, row, text, text_input, Button, Column, Container, Row,
+        Text,
+    },
     Alignment, Element, Length, Task,
 };
 use iced_aw::TabLabel;
+use openrtx_companion::core::{
+    catalog::{cache_dir, default_source},
+    flash::{detect_radio, radio_target, target_radio},
+    hotplug::same_devices,
+    stock::{archive_root, find_openrtx, is_supported, NOT_ARCHIVED},
+    Attached, Catalog, FirmwareInfo, FlashError, FlashJob, FlashOutcome, ImageIssue, JobHandle,
+    JobState, Keyring, RadioHW, Release, SerialPort, Signer, StockArchive, StockArchiveJob,
+    StockError, StockRestoreJob,
+};
 use rfd::AsyncFileDialog;
-use rtxflash::{flash, target};
-use std::io::Error;
-use std::sync::mpsc::{channel, Receiver};
-use std::thread::JoinHandle;
+use rtxflash::target;
+use std::path::PathBuf;
 
 use crate::{Message, Tab};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum RadioHW {
-    Mduv3x0,
-    Twrplus,
-    Md3x0,
-}
-
-impl RadioHW {
-    fn all() -> [RadioHW; 3] {
-        [RadioHW::Md3x0, RadioHW::Mduv3x0, RadioHW::Twrplus]
-    }
-}
-
-impl From<RadioHW> for String {
-    fn from(radio: RadioHW) -> String {
-        String::from(match radio {
-            RadioHW::Md3x0 => "MD3x0",
-            RadioHW::Mduv3x0 => "MD-UV3x0",
-            RadioHW::Twrplus => "T-TWR Plus",
-        })
-    }
-}
-
 #[derive(Clone, Debug)]
 pub enum FlashMessage {
     DeviceSelected(rtxflash::target::DeviceInfo),
@@ -45,22 +32,59 @@ pub enum FlashMessage {
     OpenFWPressed,
     OpenFile(Option<String>),
     FlashPressed,
+    CancelPressed,
+    VerifyToggled(bool),
+    StrictToggled(bool),
     FilePath(Option<String>),
+    DevicesChanged(Attached),
+    OpenRtxFound(Vec<RadioHW>),
+    CatalogSourceChanged(String),
+    LoadCatalogPressed,
+    CatalogLoaded(Result<Catalog, String>),
+    ReleaseSelected(Release),
+    ReleaseDownloaded(Result<PathBuf, String>),
+    ArchiveStockPressed,
+    SkipStockPressed,
+    RestoreStockPressed,
     Tick,
 }
 
 pub struct FlashTab {
     devices: Vec<rtxflash::target::DeviceInfo>,
+    // Serial ports of the last scan, to probe the newly attached ones
+    ports: Vec<SerialPort>,
     targets: Vec<rtxflash::target::Target>,
     selected_model: Option<RadioHW>,
     selected_device: Option<rtxflash::target::DeviceInfo>,
+    // The selected device has been unplugged
+    device_gone: bool,
     selected_target: Option<rtxflash::target::Target>,
     device_combo_state: combo_box::State<rtxflash::target::DeviceInfo>,
     target_combo_state: combo_box::State<rtxflash::target::Target>,
     firmware_path: Option<String>,
+    firmware_info: Option<FirmwareInfo>,
+    // Trusted key which signed the firmware, or why it is not trusted
+    signer: Option<Result<Signer, String>>,
+    catalog_source: String,
+    catalog: Option<Catalog>,
+    // Releases of the catalog for the selected target
+    releases: Vec<Release>,
+    selected_release: Option<Release>,
+    release_combo_state: combo_box::State<Release>,
+    // Latest factory data archive of the radio of the selected target
+    stock: Option<StockArchive>,
+    // Flashing waits for the user to confirm the vendor firmware is lost
+    stock_prompt: bool,
+    // Models seen running OpenRTX in this session, whose vendor firmware is
+    // already gone
+    openrtx_seen: Vec<RadioHW>,
+    stock_archive_job: Option<JobHandle<StockArchive, StockError>>,
+    stock_restore_job: Option<JobHandle<RadioHW, StockError>>,
     flash_in_progress: bool,
-    flash_progress: Option<Receiver<(usize, usize)>>,
-    flash_thread: Option<JoinHandle<Result<(), Error>>>,
+    flash_job: Option<JobHandle<FlashOutcome, FlashError>>,
+    verify: bool,
+    // Only flash images signed by a trusted key
+    strict: bool,
     progress: f32,
     status_text: String,
 }
@@ -79,23 +103,40 @@ async fn open_fw_file() -> Option<String> {
 
 impl Default for FlashTab {
     fn default() -> Self {
-        let devices = target::get_devices();
+        // Ports already attached at startup are known, only the ones
+        // attached later are probed
+        let Attached { ports, devices } = Attached::scan();
         let mut targets = vec![] as Vec<target::Target>;
         for t in target::get_targets() {
             targets.push(t);
         }
         Self {
             devices: devices.clone(),
+            ports,
             targets: targets.clone(),
             selected_model: None,
             selected_device: None,
+            device_gone: false,
             selected_target: None,
             device_combo_state: combo_box::State::new(devices),
             target_combo_state: combo_box::State::new(targets),
             firmware_path: None,
+            firmware_info: None,
+            signer: None,
+            catalog_source: default_source(),
+            catalog: None,
+            releases: vec![],
+            selected_release: None,
+            release_combo_state: combo_box::State::new(vec![]),
+            stock: None,
+            stock_prompt: false,
+            openrtx_seen: vec![],
+            stock_archive_job: None,
+            stock_restore_job: None,
             flash_in_progress: false,
-            flash_progress: None,
-            flash_thread: None,
+            flash_job: None,
+            verify: false,
+            strict: false,
             progress: 0.0,
             status_text: String::from("Select an action"),
         }
@@ -106,73 +147,300 @@ impl FlashTab {
     pub fn update(&mut self, message: FlashMessage) -> Task<Message> {
         match message {
             FlashMessage::DeviceSelected(device) => {
+                // Suggest the target matching the radio, if it can be identified
+                self.selected_model = detect_radio(&device);
+                if let Some(radio) = self.selected_model {
+                    match radio_target(radio) {
+                        Some(target) => {
+                            self.status_text =
+                                format!("Detected {radio}, selected target {target}");
+                            self.selected_target = Some(target);
+                            self.update_releases();
+                            self.update_stock();
+                        }
+                        None => self.status_text = format!("Detected {radio}, which has no target"),
+                    }
+                }
                 self.selected_device = Some(device);
+                self.device_gone = false;
+                self.stock_prompt = false;
                 Task::none()
             }
             FlashMessage::TargetSelected(target) => {
                 self.selected_target = Some(target);
+                self.stock_prompt = false;
+                self.update_releases();
+                self.update_stock();
                 Task::none()
             }
             FlashMessage::OpenFWPressed => {
                 Task::perform(open_fw_file(), move |f| Message::FilePath(f))
             }
             FlashMessage::FlashPressed => {
-                self.progress = 1.0;
-                self.flash_in_progress = true;
-                self.status_text = String::from("Flashing firmware...");
-                // rtxflash expects base path, not URI
-                let file_uri = self.firmware_path.clone().unwrap();
-                let bare_path = file_uri.strip_prefix("file:///").unwrap().to_string();
-                let target = self.selected_target.clone().unwrap();
-                let port = self.selected_device.clone().unwrap().port;
-
-                // Start flash in a separate thread
-                let (progress_tx, progress_rx) = channel();
-                self.flash_progress = Some(progress_rx);
-                self.flash_thread = Some(std::thread::spawn(move || {
-                    flash::flash(target, port, bare_path, Some(&progress_tx))
-                }));
+                // The radio may still run a vendor firmware which would be lost
+                if let Some(radio) = self
+                    .stock_radio()
+                    .filter(|r| self.stock.is_none() && !self.openrtx_seen.contains(r))
+                {
+                    self.stock_prompt = true;
+                    self.status_text = format!(
+                        "The {radio} was not seen running OpenRTX and may still run its \
+                         vendor firmware. {NOT_ARCHIVED} Its codeplug can be archived with \
+                         the radio turned on normally with its vendor firmware."
+                    );
+                    return Task::none();
+                }
+                self.start_flash();
+                Task::none()
+            }
+            FlashMessage::SkipStockPressed => {
+                self.stock_prompt = false;
+                self.start_flash();
+                Task::none()
+            }
+            FlashMessage::CancelPressed => {
+                let cancelled = self.flash_job.as_ref().is_some_and(|j| j.cancel())
+                    || self.stock_archive_job.as_ref().is_some_and(|j| j.cancel())
+                    || self.stock_restore_job.as_ref().is_some_and(|j| j.cancel());
+                if cancelled {
+                    self.status_text = String::from("Cancelling...");
+                }
+                Task::none()
+            }
+            FlashMessage::VerifyToggled(verify) => {
+                self.verify = verify;
+                Task::none()
+            }
+            FlashMessage::StrictToggled(strict) => {
+                self.strict = strict;
                 Task::none()
             }
             FlashMessage::FilePath(path) => {
                 self.firmware_path = path.clone();
+                self.firmware_info = None;
+                self.signer = None;
                 match path {
                     Some(p) => {
-                        self.status_text = format!("Loaded firmware: {p}");
+                        let bare_path = p.strip_prefix("file:///").unwrap_or(&p);
+                        match FirmwareInfo::read(bare_path) {
+                            Ok(info) => {
+                                self.status_text = format!("Loaded firmware: {p}");
+                                self.firmware_info = Some(info);
+                                self.signer = Some(
+                                    Keyring::load_default()
+                                        .and_then(|k| k.verify(bare_path.as_ref()))
+                                        .map_err(|e| e.to_string()),
+                                );
+                            }
+                            Err(e) => {
+                                self.status_text = format!("Error in reading firmware: {e}");
+                                self.firmware_path = None;
+                            }
+                        }
                     }
                     None => self.status_text = String::from("Error in reading firmware!"),
                 };
                 Task::none()
             }
+            FlashMessage::DevicesChanged(attached) => {
+                if !same_devices(&self.devices, &attached.devices) {
+                    self.devices = attached.devices.clone();
+                    self.device_combo_state = combo_box::State::new(attached.devices.clone());
+                }
+                if let Some(device) = &self.selected_device {
+                    self.device_gone = !attached.has_device(device);
+                }
+                // Radios attached while running OpenRTX no longer run
+                // their vendor firmware
+                let new: Vec<_> = attached
+                    .ports
+                    .iter()
+                    .filter(|p| !self.ports.contains(p))
+                    .cloned()
+                    .collect();
+                self.ports = attached.ports;
+                if new.is_empty() {
+                    return Task::none();
+                }
+                Task::perform(crate::blocking(move || find_openrtx(&new)), |radios| {
+                    Message::Flash(FlashMessage::OpenRtxFound(radios))
+                })
+            }
+            FlashMessage::OpenRtxFound(radios) => {
+                for radio in radios {
+                    if !self.openrtx_seen.contains(&radio) {
+                        self.openrtx_seen.push(radio);
+                    }
+                }
+                Task::none()
+            }
+            FlashMessage::CatalogSourceChanged(source) => {
+                self.catalog_source = source;
+                Task::none()
+            }
+            FlashMessage::LoadCatalogPressed => {
+                self.status_text = String::from("Loading firmware catalog...");
+                let source = self.catalog_source.clone();
+                Task::perform(
+                    crate::blocking(move || Catalog::load(&source).map_err(|e| e.to_string())),
+                    |res| Message::Flash(FlashMessage::CatalogLoaded(res)),
+                )
+            }
+            FlashMessage::CatalogLoaded(res) => {
+                match res {
+                    Ok(catalog) => {
+                        self.status_text =
+                            format!("Loaded {} firmware releases", catalog.releases.len());
+                        self.catalog = Some(catalog);
+                    }
+                    Err(e) => {
+                        self.status_text = format!("Error in loading catalog: {e}");
+                        self.catalog = None;
+                    }
+                }
+                self.update_releases();
+                Task::none()
+            }
+            FlashMessage::ReleaseSelected(release) => {
+                let Some(catalog) = self.catalog.clone() else {
+                    return Task::none();
+                };
+                self.status_text = format!("Downloading {release}...");
+                self.selected_release = Some(release.clone());
+                Task::perform(
+                    crate::blocking(move || {
+                        catalog
+                            .download(&release, &cache_dir())
+                            .map_err(|e| e.to_string())
+                    }),
+                    |res| Message::Flash(FlashMessage::ReleaseDownloaded(res)),
+                )
+            }
+            FlashMessage::ReleaseDownloaded(res) => match res {
+                Ok(path) => self.update(FlashMessage::FilePath(Some(format!(
+                    "file:///{}",
+                    path.display()
+                )))),
+                Err(e) => {
+                    self.status_text = format!("Error in downloading firmware: {e}");
+                    self.selected_release = None;
+                    Task::none()
+                }
+            },
+            FlashMessage::ArchiveStockPressed => {
+                let Some(radio) = self.stock_radio() else {
+                    return Task::none();
+                };
+                self.progress = 1.0;
+                self.status_text = String::from("Archiving the factory data...");
+                self.stock_archive_job = Some(StockArchiveJob::new(radio, archive_root()).start());
+                Task::none()
+            }
+            FlashMessage::RestoreStockPressed => {
+                let Some(archive) = &self.stock else {
+                    return Task::none();
+                };
+                self.progress = 1.0;
+                self.status_text = String::from("Restoring the factory data...");
+                self.stock_restore_job = Some(StockRestoreJob::new(archive.clone()).start());
+                Task::none()
+            }
             FlashMessage::Tick => {
-                if self.flash_in_progress {
-                    if let Some(flash_progress) = &self.flash_progress {
-                        for m in flash_progress.try_iter() {
-                            let (transferred_bytes, total_bytes) = m;
-                            if transferred_bytes == total_bytes {
-                                // Error condition
-                                if transferred_bytes == 0 {
-                                    if let Some(flash_thread) = self.flash_thread.take() {
-                                        let res = flash_thread
-                                            .join()
-                                            .expect("Failed to join flash thread");
-                                        match res {
-                                            Ok(_) => (),
-                                            Err(e) => self.status_text = e.to_string(),
-                                        }
-                                    }
-                                } else {
-                                    // Success
-                                    self.status_text = String::from("Flashing complete!");
-                                    self.progress = 100.0;
+                if let Some(flash_job) = &mut self.flash_job {
+                    match flash_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text =
+                                    format!("Flashed chunk {}/{}", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(outcome) => {
+                            self.progress = 100.0;
+                            let (status, openrtx) = match outcome {
+                                FlashOutcome::Flashed => (String::from("Flashing complete!"), None),
+                                FlashOutcome::NotVerified(reason) => (
+                                    format!(
+                                        "WARNING: the firmware was written \
+                                         but could not be verified: {reason}"
+                                    ),
+                                    None,
+                                ),
+                                FlashOutcome::Verified { info, version } => (
+                                    format!("Flashing complete! {info} runs firmware {version}"),
+                                    Some(info),
+                                ),
+                            };
+                            self.status_text = status;
+                            if let Some(radio) =
+                                openrtx.map(String::as_str).and_then(RadioHW::from_info)
+                            {
+                                if !self.openrtx_seen.contains(&radio) {
+                                    self.openrtx_seen.push(radio);
                                 }
-                            } else {
-                                self.progress =
-                                    transferred_bytes as f32 / total_bytes as f32 * 100.0;
+                            }
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Flashing failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Flashing cancelled")
+                        }
+                    }
+                    if !flash_job.is_running() {
+                        self.flash_in_progress = false;
+                        self.flash_job = None;
+                    }
+                }
+                if let Some(archive_job) = &mut self.stock_archive_job {
+                    match archive_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
                                 self.status_text =
-                                    format!("Flashed chunk {transferred_bytes}/{total_bytes}");
+                                    format!("Archived {}/{} bytes", p.transferred, p.total);
                             }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(archive) => {
+                            self.progress = 100.0;
+                            self.status_text = format!(
+                                "Archived {} in {}",
+                                archive.contents().join(", "),
+                                archive.dir.display()
+                            );
+                            self.stock = Some(archive);
                         }
+                        JobState::Failed(e) => self.status_text = format!("Archiving failed: {e}"),
+                        JobState::Cancelled => {
+                            self.status_text = String::from("Archiving cancelled")
+                        }
+                    }
+                    if !archive_job.is_running() {
+                        self.stock_archive_job = None;
+                    }
+                }
+                if let Some(restore_job) = &mut self.stock_restore_job {
+                    match restore_job.poll() {
+                        JobState::Running { phase, progress } => match (phase, progress) {
+                            (_, Some(p)) => {
+                                self.progress = p.percent();
+                                self.status_text =
+                                    format!("Restored {}/{} bytes", p.transferred, p.total);
+                            }
+                            (Some(phase), None) => self.status_text = phase.to_string(),
+                            (None, None) => (),
+                        },
+                        JobState::Completed(radio) => {
+                            self.progress = 100.0;
+                            self.status_text = format!("Restored the factory data of the {radio}");
+                        }
+                        JobState::Failed(e) => self.status_text = format!("Restore failed: {e}"),
+                        JobState::Cancelled => self.status_text = String::from("Restore cancelled"),
+                    }
+                    if !restore_job.is_running() {
+                        self.stock_restore_job = None;
                     }
                 }
                 Task::none()
@@ -180,6 +448,91 @@ impl FlashTab {
             _ => Task::none(),
         }
     }
+
+    fn start_flash(&mut self) {
+        self.progress = 1.0;
+        self.flash_in_progress = true;
+        self.status_text = String::from("Flashing firmware...");
+        // rtxflash expects base path, not URI
+        let file_uri = self.firmware_path.clone().unwrap();
+        let bare_path = file_uri.strip_prefix("file:///").unwrap().to_string();
+        let target = self.selected_target.clone().unwrap();
+        let port = self.selected_device.clone().unwrap().port;
+
+        // Start flash in a separate thread
+        self.flash_job = Some(
+            FlashJob::new(target, port, bare_path)
+                .verify(self.verify)
+                .strict(self.strict)
+                .start(),
+        );
+    }
+
+    /// Check whether there is a flash job in progress
+    pub fn is_busy(&self) -> bool {
+        self.flash_in_progress
+    }
+
+    // Show the catalog releases which can be flashed with the selected target
+    fn update_releases(&mut self) {
+        self.releases = match (&self.catalog, &self.selected_target) {
+            (Some(catalog), Some(target)) => catalog.for_target(target).cloned().collect(),
+            _ => vec![],
+        };
+        if self
+            .selected_release
+            .as_ref()
+            .is_some_and(|r| !self.releases.contains(r))
+        {
+            self.selected_release = None;
+        }
+        self.release_combo_state = combo_box::State::new(self.releases.clone());
+    }
+
+    // Radio of the selected target, if it has factory data to archive
+    fn stock_radio(&self) -> Option<RadioHW> {
+        let radio = self.selected_target.as_ref().and_then(target_radio)?;
+        is_supported(radio).then_some(radio)
+    }
+
+    fn update_stock(&mut self) {
+        self.stock = self
+            .stock_radio()
+            .and_then(|radio| StockArchive::latest(&archive_root(), radio).ok().flatten());
+    }
+
+    // A factory data archive or restore is in progress
+    fn stock_in_progress(&self) -> bool {
+        self.stock_archive_job.is_some() || self.stock_restore_job.is_some()
+    }
+
+    // Detected radio, when it does not match the selected target
+    fn target_mismatch(&self) -> Option<RadioHW> {
+        let radio = self.selected_model?;
+        let target = self.selected_target.as_ref()?;
+        (target_radio(target) != Some(radio)).then_some(radio)
+    }
+
+    // Problems of the firmware image with the radio of the selected target
+    fn image_issues(&self) -> Vec<ImageIssue> {
+        let radio = self.selected_target.as_ref().and_then(target_radio);
+        match (&self.firmware_info, radio) {
+            (Some(info), Some(radio)) => info.check(radio),
+            _ => vec![],
+        }
+    }
+
+    // Everything needed to flash has been selected
+    fn can_flash(&self) -> bool {
+        self.firmware_path.is_some()
+            && (!self.strict || matches!(self.signer, Some(Ok(_))))
+            && !self.image_issues().iter().any(ImageIssue::is_blocking)
+            && self.selected_target.is_some()
+            && self.selected_device.is_some()
+            && !self.device_gone
+            && !self.flash_in_progress
+            && !self.stock_in_progress()
+    }
 }
 
 impl Tab for FlashTab {
@@ -220,6 +573,8 @@ impl Tab for FlashTab {
                         Column::new().width(120).push(text("Device:").size(15)),
                         device_combo_box,
                     ]
+                    .push_maybe(self.device_gone.then(|| text("Unplugged").size(15)))
+                    .spacing(10)
                     .padding(10),
                 )
                 .push(
@@ -229,6 +584,75 @@ impl Tab for FlashTab {
                     ]
                     .padding(10),
                 )
+                .push(
+                    row![
+                        Column::new().width(120).push(text("Catalog:").size(15)),
+                        text_input("URL or folder of a release manifest", &self.catalog_source)
+                            .on_input(FlashMessage::CatalogSourceChanged)
+                            .on_submit(FlashMessage::LoadCatalogPressed)
+                            .size(15)
+                            .width(250),
+                        Button::new(Text::new("Load").size(15))
+                            .on_press(FlashMessage::LoadCatalogPressed),
+                    ]
+                    .spacing(10)
+                    .padding(10),
+                )
+                // Workaround: Iced crashes when rendering empty combo box
+                .push_maybe((!self.releases.is_empty()).then(|| {
+                    row![
+                        Column::new().width(120).push(text("Release:").size(15)),
+                        combo_box(
+                            &self.release_combo_state,
+                            "Select a release to download",
+                            self.selected_release.as_ref(),
+                            FlashMessage::ReleaseSelected,
+                        )
+                        .width(250),
+                    ]
+                    .padding(10)
+                }))
+                .push_maybe(self.stock_radio().map(|radio| {
+                    let label = match &self.stock {
+                        Some(archive) => text(format!(
+                            "Factory data ({}) archived on {}",
+                            archive.contents().join(", "),
+                            archive.created
+                        )),
+                        None => text(format!(
+                            "No factory data archived for the {radio}, turn it on normally \
+                             with its vendor firmware to archive it"
+                        ))
+                        .style(text::danger),
+                    };
+                    let ready = !self.flash_in_progress && !self.stock_in_progress();
+                    row![
+                        label.wrapping(text::Wrapping::Word).size(15).width(300),
+                        Button::new(Text::new("Archive factory data").size(15))
+                            .on_press_maybe(ready.then_some(FlashMessage::ArchiveStockPressed)),
+                    ]
+                    .push_maybe(self.stock.as_ref().map(|_| {
+                        Button::new(Text::new("Restore factory data").size(15))
+                            .on_press_maybe(ready.then_some(FlashMessage::RestoreStockPressed))
+                    }))
+                    .push_maybe(self.stock_prompt.then(|| {
+                        Button::new(Text::new("Flash anyway").size(15)).on_press_maybe(
+                            self.can_flash().then_some(FlashMessage::SkipStockPressed),
+                        )
+                    }))
+                    .spacing(10)
+                    .padding(10)
+                    .align_y(Alignment::Center)
+                }))
+                .push_maybe(self.target_mismatch().map(|radio| {
+                    text(format!(
+                        "WARNING: the selected target is not meant for the detected {radio}, \
+                         flashing it may brick the radio!"
+                    ))
+                    .style(text::danger)
+                    .wrapping(text::Wrapping::Word)
+                    .size(17)
+                }))
                 .push(row![Column::new()
                     .width(600)
                     .align_x(Alignment::Center)
@@ -237,7 +661,49 @@ impl Tab for FlashTab {
                             .wrapping(text::Wrapping::Word)
                             .size(20)
                     ),])
+                .push_maybe(self.firmware_info.as_ref().map(|info| {
+                    self.image_issues().into_iter().fold(
+                        Column::new()
+                            .width(600)
+                            .align_x(Alignment::Center)
+                            .push(text(info.to_string()).size(15))
+                            .push_maybe(self.signer.as_ref().map(|signer| {
+                                match signer {
+                                    Ok(signer) => {
+                                        text(format!("Signed by {signer}")).style(text::success)
+                                    }
+                                    Err(e) if self.strict => text(e).style(text::danger),
+                                    Err(e) => text(e),
+                                }
+                                .size(15)
+                            })),
+                        |column, issue| {
+                            let line = text(issue.to_string()).size(15);
+                            column.push(if issue.is_blocking() {
+                                line.style(text::danger)
+                            } else {
+                                line
+                            })
+                        },
+                    )
+                }))
                 .push(row![progress_bar(0.0..=100.0, self.progress),].padding(20))
+                .push(
+                    row![
+                        checkbox("Verify the firmware after flashing", self.verify)
+                            .on_toggle_maybe(
+                                (!self.flash_in_progress).then_some(FlashMessage::VerifyToggled)
+                            )
+                            .size(15),
+                        checkbox("Only flash signed images", self.strict)
+                            .on_toggle_maybe(
+                                (!self.flash_in_progress).then_some(FlashMessage::StrictToggled)
+                            )
+                            .size(15),
+                    ]
+                    .spacing(20)
+                    .padding([0, 20]),
+                )
                 .push(
                     Row::new()
                         .spacing(20)
@@ -247,9 +713,32 @@ impl Tab for FlashTab {
                                 .on_press(FlashMessage::OpenFWPressed),
                         )
                         .push(
-                            Button::new(Text::new("Flash").align_x(Horizontal::Center))
+                            Button::new(
+                                Text::new(if self.target_mismatch().is_some() {
+                                    "Flash anyway"
+                                } else {
+                                    "Flash"
+                                })
+                                .align_x(Horizontal::Center),
+                            )
+                            .width(Length::Fill)
+                            .on_press_maybe(self.can_flash().then_some(FlashMessage::FlashPressed)),
+                        )
+                        .push(
+                            Button::new(Text::new("Cancel").align_x(Horizontal::Center))
                                 .width(Length::Fill)
-                                .on_press(FlashMessage::FlashPressed),
+                                .on_press_maybe(
+                                    (self.flash_job.as_ref().is_some_and(|j| j.can_cancel())
+                                        || self
+                                            .stock_archive_job
+                                            .as_ref()
+                                            .is_some_and(|j| j.can_cancel())
+                                        || self
+                                            .stock_restore_job
+                                            .as_ref()
+                                            .is_some_and(|j| j.can_cancel()))
+                                    .then_some(FlashMessage::CancelPressed),
+                                ),
                         ),
                 ),
         )
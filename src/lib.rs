@@ -0,0 +1,13 @@
+//! OpenRTX Companion library
+//!
+//! The [`core`] module contains the radio workflows (flashing, backup and
+//! restore) independently from the graphical interface, so that they can be
+//! driven by the GUI, the command line interface or external tools.
+//!
+//! The `sim` module, built with the `sim` feature, provides a simulated
+//! radio to test them without hardware.
+
+pub mod core;
+
+#[cfg(all(unix, feature = "sim"))]
+pub mod sim;
@@ -11,6 +11,7 @@ use iced::{
 };
 use iced_aw::{TabBarPosition, TabLabel, Tabs};
 use image::{self, GenericImageView};
+use openrtx_companion::core::Attached;
 
 mod flash;
 use flash::{FlashMessage, FlashTab};
@@ -18,6 +19,29 @@ use flash::{FlashMessage, FlashTab};
 mod backup;
 use backup::{BackupMessage, BackupTab};
 
+mod calibration;
+use calibration::{CalibrationMessage, CalibrationTab};
+
+mod codeplug;
+use codeplug::{CodeplugMessage, CodeplugTab};
+
+mod contacts;
+use contacts::{ContactsMessage, ContactsTab};
+
+mod files;
+use files::{FilesMessage, FilesTab};
+
+mod rig;
+use rig::{RigMessage, RigTab};
+
+mod remote;
+use remote::{RemoteMessage, RemoteTab};
+
+mod telemetry;
+use telemetry::{TelemetryMessage, TelemetryTab};
+
+mod cli;
+
 const HEADER_SIZE: u16 = 32;
 const TAB_PADDING: u16 = 16;
 const ICON_BYTES: &[u8] = include_bytes!("../fonts/icons.ttf");
@@ -52,6 +76,11 @@ fn main() -> iced::Result {
     win_attach_terminal();
     // init_logging();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::is_command(&args) {
+        std::process::exit(cli::run(&args));
+    }
+
     let mut window_settings = window::Settings::default();
     window_settings.size = iced::Size {
         width: 600.0,
@@ -90,7 +119,13 @@ enum TabId {
     #[default]
     Flash,
     Backup,
-    // Files,
+    Calibration,
+    Codeplug,
+    Contacts,
+    Files,
+    Rig,
+    Remote,
+    Telemetry,
 }
 
 #[derive(Default)]
@@ -98,6 +133,15 @@ struct OpenRTXCompanion {
     active_tab: TabId,
     flash_tab: FlashTab,
     backup_tab: BackupTab,
+    calibration_tab: CalibrationTab,
+    codeplug_tab: CodeplugTab,
+    contacts_tab: ContactsTab,
+    files_tab: FilesTab,
+    rig_tab: RigTab,
+    remote_tab: RemoteTab,
+    telemetry_tab: TelemetryTab,
+    // A scan of the attached devices is in progress
+    scanning: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -105,11 +149,23 @@ enum Message {
     TabSelected(TabId),
     Flash(FlashMessage),
     Backup(BackupMessage),
+    Calibration(CalibrationMessage),
+    Codeplug(CodeplugMessage),
+    Contacts(ContactsMessage),
+    Files(FilesMessage),
+    Rig(RigMessage),
+    Remote(RemoteMessage),
+    Telemetry(TelemetryMessage),
     // These two messages are the result of asynchronous actions and need
     // to be propagated to the respective tabs
     FilePath(Option<String>),
     StartBackup(Option<String>),
     Tick,
+    // File dropped on the window from the desktop
+    FileDropped(std::path::PathBuf),
+    // Periodic rescan of the attached ports and devices
+    Rescan,
+    Attached(Attached),
     #[allow(dead_code)]
     Loaded(Result<(), String>),
     FontLoaded(Result<(), font::Error>),
@@ -120,6 +176,20 @@ async fn load() -> Result<(), String> {
     Ok(())
 }
 
+/// Run blocking work, such as talking to the radio, in a worker thread so
+/// that the UI thread is not held up
+pub(crate) async fn blocking<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = iced::futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        _ = tx.send(f());
+    });
+    rx.await.expect("Worker thread panicked")
+}
+
 impl OpenRTXCompanion {
     fn title(&self) -> String {
         String::from("OpenRTX Companion")
@@ -128,11 +198,20 @@ impl OpenRTXCompanion {
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::TabSelected(selected) => {
+                // The remote control keeps the serial port only while shown
+                let shown = selected == TabId::Remote;
                 self.active_tab = selected;
-                Task::none()
+                self.remote_tab.update(RemoteMessage::Shown(shown))
             }
             Message::Flash(message) => self.flash_tab.update(message),
             Message::Backup(message) => self.backup_tab.update(message),
+            Message::Calibration(message) => self.calibration_tab.update(message),
+            Message::Codeplug(message) => self.codeplug_tab.update(message),
+            Message::Contacts(message) => self.contacts_tab.update(message),
+            Message::Files(message) => self.files_tab.update(message),
+            Message::Rig(message) => self.rig_tab.update(message),
+            Message::Remote(message) => self.remote_tab.update(message),
+            Message::Telemetry(message) => self.telemetry_tab.update(message),
             Message::TabClosed(id) => {
                 println!("Tab {:?} event hit", id);
                 Task::none()
@@ -140,12 +219,74 @@ impl OpenRTXCompanion {
             Message::FilePath(path) => match &self.active_tab {
                 TabId::Flash => self.flash_tab.update(FlashMessage::FilePath(path)),
                 TabId::Backup => self.backup_tab.update(BackupMessage::FilePath(path)),
+                TabId::Calibration
+                | TabId::Codeplug
+                | TabId::Contacts
+                | TabId::Files
+                | TabId::Rig
+                | TabId::Remote
+                | TabId::Telemetry => Task::none(),
+            },
+            Message::FileDropped(path) => match &self.active_tab {
+                TabId::Files => self.files_tab.update(FilesMessage::Upload(vec![path])),
+                _ => Task::none(),
             },
             Message::StartBackup(path) => self.backup_tab.update(BackupMessage::StartBackup(path)),
             Message::Tick => {
                 _ = self.flash_tab.update(FlashMessage::Tick);
                 _ = self.backup_tab.update(BackupMessage::Tick);
-                Task::none()
+                _ = self.calibration_tab.update(CalibrationMessage::Tick);
+                _ = self.codeplug_tab.update(CodeplugMessage::Tick);
+                _ = self.contacts_tab.update(ContactsMessage::Tick);
+                _ = self.files_tab.update(FilesMessage::Tick);
+                _ = self.rig_tab.update(RigMessage::Tick);
+                _ = self.telemetry_tab.update(TelemetryMessage::Tick);
+                // The remote control polls the radio on each tick, only
+                // while it is shown
+                match self.active_tab {
+                    TabId::Remote => self.remote_tab.update(RemoteMessage::Tick),
+                    _ => Task::none(),
+                }
+            }
+            // Enumeration can be slow, keep it off the UI thread and skip
+            // the rescans requested while it runs
+            Message::Rescan => {
+                if self.scanning {
+                    return Task::none();
+                }
+                self.scanning = true;
+                Task::perform(blocking(Attached::scan), Message::Attached)
+            }
+            Message::Attached(attached) => {
+                self.scanning = false;
+                let flash = self
+                    .flash_tab
+                    .update(FlashMessage::DevicesChanged(attached.clone()));
+                _ = self
+                    .backup_tab
+                    .update(BackupMessage::PortsChanged(attached.clone()));
+                _ = self
+                    .calibration_tab
+                    .update(CalibrationMessage::PortsChanged(attached.clone()));
+                _ = self
+                    .codeplug_tab
+                    .update(CodeplugMessage::PortsChanged(attached.clone()));
+                _ = self
+                    .contacts_tab
+                    .update(ContactsMessage::PortsChanged(attached.clone()));
+                _ = self
+                    .files_tab
+                    .update(FilesMessage::PortsChanged(attached.clone()));
+                _ = self
+                    .rig_tab
+                    .update(RigMessage::PortsChanged(attached.clone()));
+                _ = self
+                    .remote_tab
+                    .update(RemoteMessage::PortsChanged(attached.clone()));
+                _ = self
+                    .telemetry_tab
+                    .update(TelemetryMessage::PortsChanged(attached));
+                flash
             }
             _ => Task::none(),
         }
@@ -164,6 +305,37 @@ impl OpenRTXCompanion {
                 self.backup_tab.tab_label(),
                 self.backup_tab.view(),
             )
+            .push(
+                TabId::Calibration,
+                self.calibration_tab.tab_label(),
+                self.calibration_tab.view(),
+            )
+            .push(
+                TabId::Codeplug,
+                self.codeplug_tab.tab_label(),
+                self.codeplug_tab.view(),
+            )
+            .push(
+                TabId::Contacts,
+                self.contacts_tab.tab_label(),
+                self.contacts_tab.view(),
+            )
+            .push(
+                TabId::Files,
+                self.files_tab.tab_label(),
+                self.files_tab.view(),
+            )
+            .push(TabId::Rig, self.rig_tab.tab_label(), self.rig_tab.view())
+            .push(
+                TabId::Remote,
+                self.remote_tab.tab_label(),
+                self.remote_tab.view(),
+            )
+            .push(
+                TabId::Telemetry,
+                self.telemetry_tab.tab_label(),
+                self.telemetry_tab.view(),
+            )
             .set_active_tab(&self.active_tab)
             .icon_font(ICON)
             .tab_bar_position(TabBarPosition::Top)
@@ -187,7 +359,22 @@ impl OpenRTXCompanion {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        iced::time::every(std::time::Duration::from_millis(500)).map(|_| Message::Tick)
+        let tick = iced::time::every(std::time::Duration::from_millis(500)).map(|_| Message::Tick);
+        let dropped = iced::event::listen_with(|event, _, _| match event {
+            iced::Event::Window(window::Event::FileDropped(path)) => {
+                Some(Message::FileDropped(path))
+            }
+            _ => None,
+        });
+        // Do not probe the USB devices while one of them is being flashed
+        if self.flash_tab.is_busy() {
+            return Subscription::batch([tick, dropped]);
+        }
+        Subscription::batch([
+            tick,
+            dropped,
+            iced::time::every(std::time::Duration::from_secs(1)).map(|_| Message::Rescan),
+        ])
     }
 }
 
@@ -0,0 +1,530 @@
+// show logs when debugging
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use crate::backup::port_options;
+use crate::Message;
+use crate::Tab;
+use iced::{
+    alignment::{Horizontal, Vertical},
+    mouse::ScrollDelta,
+    widget::{
+        checkbox, combo_box, mouse_area, pick_list, progress_bar, row, slider, text, text_input,
+        Button, Column, Container, Row, Text,
+    },
+    Alignment, Element, Length, Task,
+};
+use iced_aw::TabLabel;
+use openrtx_companion::core::{
+    codeplug::OpMode,
+    get_ports, is_port_busy,
+    remote::{in_band, step_frequency, PanelCommand, PanelState, RemoteSession, Setting},
+    Attached, LinkError, SerialPort,
+};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+
+const MODES: [OpMode; 2] = [OpMode::Fm, OpMode::M17];
+
+/// Tuning step in Hz
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step(u32);
+
+const STEPS: [Step; 6] = [
+    Step(5_000),
+    Step(6_250),
+    Step(12_500),
+    Step(25_000),
+    Step(100_000),
+    Step(1_000_000),
+];
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            s if s >= 1_000_000 => write!(f, "{} MHz", s as f64 / 1e6),
+            s => write!(f, "{} kHz", s as f64 / 1e3),
+        }
+    }
+}
+
+// Signal strength in S units, 6 dB each up to S9 at -73 dBm
+fn s_units(rssi: i16) -> String {
+    match rssi.saturating_add(73) {
+        over if over > 0 => format!("S9+{over}"),
+        under => format!("S{}", (9 + under / 6).max(0)),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum RemoteMessage {
+    PortSelected(SerialPort),
+    PortsChanged(Attached),
+    ConnectPressed,
+    DisconnectPressed,
+    Shown(bool),
+    Polled(Result<(PanelState, Vec<RangeInclusive<u32>>), String>),
+    StepSelected(Step),
+    StepPressed(i32),
+    Scrolled(ScrollDelta),
+    FrequencyChanged(String),
+    FrequencySubmitted,
+    TxFrequencyChanged(String),
+    TxFrequencySubmitted,
+    ModeSelected(OpMode),
+    SquelchChanged(u8),
+    VolumeChanged(u8),
+    SettingToggled(Setting, bool),
+    Tick,
+}
+
+pub struct RemoteTab {
+    serial_ports: Vec<SerialPort>,
+    serial_port: Option<SerialPort>,
+    // The selected port has been unplugged
+    port_gone: bool,
+    ports_combo_state: combo_box::State<SerialPort>,
+    connected: bool,
+    // The tab is shown, polling stops and the port is released otherwise
+    shown: bool,
+    // Link to the radio, kept open between polls
+    session: Arc<Mutex<Option<RemoteSession>>>,
+    // The session holds the serial port
+    linked: bool,
+    // A request to the radio is in progress
+    busy: bool,
+    state: Option<PanelState>,
+    // Bands the connected radio can be tuned in
+    bands: Vec<RangeInclusive<u32>>,
+    // Commands waiting for the request in progress
+    commands: Vec<PanelCommand>,
+    // Commands sent by the request in progress
+    in_flight: Vec<PanelCommand>,
+    step: Step,
+    frequency: String,
+    tx_frequency: String,
+    status_text: String,
+}
+
+impl Default for RemoteTab {
+    fn default() -> Self {
+        let ports = port_options(get_ports());
+        Self {
+            serial_ports: ports.clone(),
+            serial_port: None,
+            port_gone: false,
+            ports_combo_state: combo_box::State::new(ports),
+            connected: false,
+            shown: false,
+            session: Arc::new(Mutex::new(None)),
+            linked: false,
+            busy: false,
+            state: None,
+            bands: vec![],
+            commands: vec![],
+            in_flight: vec![],
+            step: Step(12_500),
+            frequency: String::new(),
+            tx_frequency: String::new(),
+            status_text: String::from("Connect to control the radio from the computer"),
+        }
+    }
+}
+
+// Send commands on the open session, opening it first if needed, and read
+// the state of the radio back. A failed session is closed, to be opened
+// again on the next poll.
+fn poll_radio(
+    session: &Mutex<Option<RemoteSession>>,
+    port: &str,
+    commands: &[PanelCommand],
+) -> Result<(PanelState, Vec<RangeInclusive<u32>>), LinkError> {
+    let mut session = session.lock().unwrap();
+    let mut open = match session.take() {
+        Some(open) => open,
+        None => RemoteSession::open(port)?,
+    };
+    let state = open.send(commands)?;
+    let bands = open.bands.clone();
+    *session = Some(open);
+    Ok((state, bands))
+}
+
+impl RemoteTab {
+    // Send the queued commands and read the state of the radio back
+    fn poll(&mut self) -> Task<Message> {
+        let Some(port) = self.serial_port.clone() else {
+            return Task::none();
+        };
+        if !self.connected || !self.shown || self.busy || self.port_gone {
+            return Task::none();
+        }
+        self.busy = true;
+        self.in_flight = std::mem::take(&mut self.commands);
+        let commands = self.in_flight.clone();
+        let session = self.session.clone();
+        Task::perform(
+            crate::blocking(move || {
+                poll_radio(&session, &port.name, &commands).map_err(|e| e.to_string())
+            }),
+            |res| Message::Remote(RemoteMessage::Polled(res)),
+        )
+    }
+
+    // Close the link to the radio, or leave it to be closed once the request
+    // in progress ends
+    fn release(&mut self) {
+        if !self.busy {
+            *self.session.lock().unwrap() = None;
+            self.linked = false;
+        }
+    }
+
+    fn send(&mut self, command: PanelCommand) -> Task<Message> {
+        if let Some(state) = &mut self.state {
+            state.apply(&command);
+        }
+        self.commands.retain(|queued| !command.replaces(queued));
+        self.commands.push(command);
+        self.poll()
+    }
+
+    fn tune(&mut self, steps: i32) -> Task<Message> {
+        let Some(state) = &self.state else {
+            return Task::none();
+        };
+        match step_frequency(&self.bands, state.rx_frequency, self.step.0, steps) {
+            Some(frequency) => self.send(PanelCommand::Tune(frequency)),
+            None => Task::none(),
+        }
+    }
+
+    // Frequency typed in MHz, if in the bands of the radio
+    fn parse_frequency(&self, mhz: &str) -> Option<u32> {
+        mhz.trim()
+            .parse::<f64>()
+            .ok()
+            .map(|mhz| (mhz * 1e6).round() as u32)
+            .filter(|f| in_band(&self.bands, *f))
+    }
+
+    pub fn update(&mut self, message: RemoteMessage) -> Task<Message> {
+        match message {
+            RemoteMessage::PortSelected(port) => {
+                self.serial_port = Some(port);
+                self.port_gone = false;
+                self.connected = false;
+                self.state = None;
+                self.commands.clear();
+                self.release();
+                Task::none()
+            }
+            RemoteMessage::PortsChanged(attached) => {
+                let ports = port_options(attached.ports.clone());
+                if ports != self.serial_ports {
+                    self.serial_ports = ports.clone();
+                    self.ports_combo_state = combo_box::State::new(ports);
+                }
+                if let Some(port) = &self.serial_port {
+                    self.port_gone = !attached.has_port(port);
+                }
+                if self.port_gone {
+                    self.release();
+                }
+                Task::none()
+            }
+            RemoteMessage::ConnectPressed => {
+                self.connected = true;
+                self.status_text = String::from("Connecting...");
+                self.poll()
+            }
+            RemoteMessage::DisconnectPressed => {
+                self.connected = false;
+                self.state = None;
+                self.commands.clear();
+                self.release();
+                self.status_text = String::from("Disconnected");
+                Task::none()
+            }
+            RemoteMessage::Shown(shown) => {
+                self.shown = shown;
+                if !shown {
+                    self.release();
+                }
+                Task::none()
+            }
+            RemoteMessage::Polled(res) => {
+                self.busy = false;
+                let sent = std::mem::take(&mut self.in_flight);
+                if !self.connected || !self.shown {
+                    self.release();
+                    return Task::none();
+                }
+                match res {
+                    Ok((mut state, bands)) => {
+                        self.linked = true;
+                        self.bands = bands;
+                        // Keep showing the commands not sent yet
+                        for command in &self.commands {
+                            state.apply(command);
+                        }
+                        self.state = Some(state);
+                        self.status_text = String::from("Connected");
+                        // Commands queued during the request are sent right
+                        // away
+                        match self.commands.is_empty() {
+                            true => Task::none(),
+                            false => self.poll(),
+                        }
+                    }
+                    Err(e) => {
+                        self.linked = false;
+                        // Send the commands again on the next poll, unless
+                        // newer ones replace them
+                        for command in sent.into_iter().rev() {
+                            if !self.commands.iter().any(|queued| queued.replaces(&command)) {
+                                self.commands.insert(0, command);
+                            }
+                        }
+                        self.status_text = format!("Error in reading the radio: {e}");
+                        Task::none()
+                    }
+                }
+            }
+            RemoteMessage::StepSelected(step) => {
+                self.step = step;
+                Task::none()
+            }
+            RemoteMessage::StepPressed(steps) => self.tune(steps),
+            RemoteMessage::Scrolled(delta) => {
+                let y = match delta {
+                    ScrollDelta::Lines { y, .. } | ScrollDelta::Pixels { y, .. } => y,
+                };
+                match y {
+                    y if y > 0.0 => self.tune(1),
+                    y if y < 0.0 => self.tune(-1),
+                    _ => Task::none(),
+                }
+            }
+            RemoteMessage::FrequencyChanged(frequency) => {
+                self.frequency = frequency;
+                Task::none()
+            }
+            RemoteMessage::FrequencySubmitted => match self.parse_frequency(&self.frequency) {
+                Some(frequency) => {
+                    self.frequency.clear();
+                    self.send(PanelCommand::Tune(frequency))
+                }
+                None => {
+                    self.status_text =
+                        format!("Frequency out of the radio bands: {} MHz", self.frequency);
+                    Task::none()
+                }
+            },
+            RemoteMessage::TxFrequencyChanged(frequency) => {
+                self.tx_frequency = frequency;
+                Task::none()
+            }
+            RemoteMessage::TxFrequencySubmitted => match self.parse_frequency(&self.tx_frequency) {
+                Some(frequency) => {
+                    self.tx_frequency.clear();
+                    self.send(PanelCommand::TuneTx(frequency))
+                }
+                None => {
+                    self.status_text = format!(
+                        "TX frequency out of the radio bands: {} MHz",
+                        self.tx_frequency
+                    );
+                    Task::none()
+                }
+            },
+            RemoteMessage::ModeSelected(mode) => self.send(PanelCommand::Mode(mode)),
+            RemoteMessage::SquelchChanged(level) => self.send(PanelCommand::Squelch(level)),
+            RemoteMessage::VolumeChanged(volume) => self.send(PanelCommand::Volume(volume)),
+            RemoteMessage::SettingToggled(setting, on) => self.send(PanelCommand::Set(setting, on)),
+            // Leave the radio to the job or server using it, the port being
+            // busy because of this tab only while its session is open
+            RemoteMessage::Tick => match &self.serial_port {
+                Some(port) if !self.linked && is_port_busy(&port.name) => Task::none(),
+                _ => self.poll(),
+            },
+        }
+    }
+
+    fn panel(&self, state: &PanelState) -> Element<'_, RemoteMessage> {
+        let label = |label: &'static str| Column::new().width(120).push(text(label).size(15));
+        let mhz = |f: u32| format!("{:.5}", f as f64 / 1e6);
+
+        let frequency = mouse_area(
+            Column::new()
+                .align_x(Alignment::Center)
+                .push(text(&state.info).size(15))
+                .push(text(format!("{} MHz", mhz(state.rx_frequency))).size(48))
+                .push(
+                    text(match state.tx_frequency {
+                        Some(f) if f == state.rx_frequency => String::from("TX simplex"),
+                        Some(f) => format!("TX {} MHz", mhz(f)),
+                        None => String::from("TX frequency not reported"),
+                    })
+                    .size(15),
+                ),
+        )
+        .on_scroll(RemoteMessage::Scrolled);
+        let tuning = row![
+            Button::new(Text::new("-")).on_press(RemoteMessage::StepPressed(-1)),
+            pick_list(STEPS, Some(self.step), RemoteMessage::StepSelected).width(120),
+            Button::new(Text::new("+")).on_press(RemoteMessage::StepPressed(1)),
+            text_input("Frequency in MHz", &self.frequency)
+                .on_input(RemoteMessage::FrequencyChanged)
+                .on_submit(RemoteMessage::FrequencySubmitted)
+                .width(200),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+        // Split operation, the TX frequency being reset by tuning
+        let split = text_input("TX frequency in MHz", &self.tx_frequency)
+            .on_input(RemoteMessage::TxFrequencyChanged)
+            .on_submit(RemoteMessage::TxFrequencySubmitted)
+            .width(200);
+
+        let mut panel = Column::new()
+            .spacing(10)
+            .align_x(Alignment::Center)
+            .push(frequency)
+            .push(tuning)
+            .push(split);
+        if let Some(mode) = state.mode {
+            panel = panel.push(
+                row![
+                    label("Mode:"),
+                    pick_list(MODES, Some(mode), RemoteMessage::ModeSelected).width(120),
+                ]
+                .spacing(10),
+            );
+        }
+        if let Some(rssi) = state.rssi {
+            // From S0 to S9+60 dB
+            let meter = progress_bar(-127.0..=-13.0, f32::from(rssi)).height(15);
+            panel = panel.push(
+                row![
+                    label("Signal:"),
+                    meter.width(250),
+                    text(format!("{} ({rssi} dBm)", s_units(rssi))).size(15),
+                ]
+                .spacing(10),
+            );
+        }
+        if let Some(level) = state.squelch {
+            let open = match state.squelch_open {
+                Some(true) => "Open",
+                Some(false) => "Closed",
+                None => "",
+            };
+            panel = panel.push(
+                row![
+                    label("Squelch:"),
+                    slider(0..=15, level, RemoteMessage::SquelchChanged).width(250),
+                    text(open).size(15),
+                ]
+                .spacing(10),
+            );
+        }
+        if let Some(volume) = state.volume {
+            panel = panel.push(
+                row![
+                    label("Volume:"),
+                    slider(0..=255, volume, RemoteMessage::VolumeChanged).width(250),
+                ]
+                .spacing(10),
+            );
+        }
+        if let Some(battery) = state.battery {
+            panel = panel.push(
+                row![
+                    label("Battery:"),
+                    text(format!("{:.2} V", battery as f32 / 1000.0)).size(15),
+                ]
+                .spacing(10),
+            );
+        }
+        let settings =
+            state
+                .settings
+                .iter()
+                .fold(Row::new().spacing(20), |settings, (setting, on)| {
+                    let setting = *setting;
+                    settings.push(
+                        checkbox(setting.to_string(), *on)
+                            .on_toggle(move |on| RemoteMessage::SettingToggled(setting, on)),
+                    )
+                });
+        panel.push(settings).into()
+    }
+}
+
+impl Tab for RemoteTab {
+    type Message = Message;
+
+    fn title(&self) -> String {
+        String::from("Remote")
+    }
+
+    fn tab_label(&self) -> TabLabel {
+        TabLabel::Text(self.title())
+    }
+
+    fn content(&self) -> Element<'_, Self::Message> {
+        let port_combo_box = combo_box(
+            &self.ports_combo_state,
+            "Select a serial port",
+            self.serial_port.as_ref(),
+            RemoteMessage::PortSelected,
+        )
+        .width(250);
+
+        let content: Element<'_, RemoteMessage> = Container::new(
+            Column::new()
+                .max_width(600)
+                .push(
+                    row![
+                        Column::new().width(120).push(text("Serial port:").size(15)),
+                        port_combo_box,
+                    ]
+                    .push_maybe(self.port_gone.then(|| text("Unplugged").size(15)))
+                    .spacing(10)
+                    .padding(20),
+                )
+                .push_maybe(self.state.as_ref().map(|state| self.panel(state)))
+                .push(row![Column::new()
+                    .width(600)
+                    .align_x(Alignment::Center)
+                    .push(text(&self.status_text).size(20)),])
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .padding(20)
+                        .push(
+                            Button::new(Text::new("Connect").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (!self.connected
+                                        && self.serial_port.is_some()
+                                        && !self.port_gone)
+                                        .then_some(RemoteMessage::ConnectPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Disconnect").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    self.connected.then_some(RemoteMessage::DisconnectPressed),
+                                ),
+                        ),
+                ),
+        )
+        .align_x(Horizontal::Center)
+        .align_y(Vertical::Center)
+        .into();
+
+        content.map(Message::Remote)
+    }
+}
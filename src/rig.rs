@@ -0,0 +1,262 @@
+// show logs when debugging
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use crate::backup::port_options;
+use crate::Message;
+use crate::Tab;
+use iced::{
+    alignment::{Horizontal, Vertical},
+    widget::{
+        combo_box, pick_list, row, scrollable, text, text_input, Button, Column, Container, Row,
+        Text,
+    },
+    Alignment, Element, Length, Task,
+};
+use iced_aw::TabLabel;
+use openrtx_companion::core::{
+    get_ports, rigctl::ServerStatus, Attached, RigProtocol, RigServer, SerialPort,
+};
+
+#[derive(Clone, Debug)]
+pub enum RigMessage {
+    PortSelected(SerialPort),
+    PortsChanged(Attached),
+    ProtocolSelected(RigProtocol),
+    TcpPortChanged(String),
+    StartPressed,
+    StopPressed,
+    // The server thread has ended, on the error if any
+    Stopped(Option<String>),
+    Tick,
+}
+
+pub struct RigTab {
+    serial_ports: Vec<SerialPort>,
+    serial_port: Option<SerialPort>,
+    // The selected port has been unplugged
+    port_gone: bool,
+    ports_combo_state: combo_box::State<SerialPort>,
+    protocol: RigProtocol,
+    tcp_port: String,
+    server: Option<RigServer>,
+    // The server is closing the connection to the radio
+    stopping: bool,
+    server_status: ServerStatus,
+    status_text: String,
+}
+
+impl Default for RigTab {
+    fn default() -> Self {
+        let ports = port_options(get_ports());
+        Self {
+            serial_ports: ports.clone(),
+            serial_port: None,
+            port_gone: false,
+            ports_combo_state: combo_box::State::new(ports),
+            protocol: RigProtocol::Rigctld,
+            tcp_port: RigProtocol::Rigctld.default_port().to_string(),
+            server: None,
+            stopping: false,
+            server_status: ServerStatus::default(),
+            status_text: String::from("Serve the radio to Hamlib or flrig applications"),
+        }
+    }
+}
+
+impl RigTab {
+    pub fn update(&mut self, message: RigMessage) -> Task<Message> {
+        match message {
+            RigMessage::PortSelected(port) => {
+                self.serial_port = Some(port);
+                self.port_gone = false;
+            }
+            RigMessage::PortsChanged(attached) => {
+                let ports = port_options(attached.ports.clone());
+                if ports != self.serial_ports {
+                    self.serial_ports = ports.clone();
+                    self.ports_combo_state = combo_box::State::new(ports);
+                }
+                if let Some(port) = &self.serial_port {
+                    self.port_gone = !attached.has_port(port);
+                }
+            }
+            RigMessage::ProtocolSelected(protocol) => {
+                // Follow the default port, unless it has been changed
+                if self.tcp_port == self.protocol.default_port().to_string() {
+                    self.tcp_port = protocol.default_port().to_string();
+                }
+                self.protocol = protocol;
+            }
+            RigMessage::TcpPortChanged(port) => self.tcp_port = port,
+            RigMessage::StartPressed => {
+                let (Some(port), Ok(tcp_port)) = (&self.serial_port, self.tcp_port.parse::<u16>())
+                else {
+                    return Task::none();
+                };
+                match RigServer::start(&port.name, self.protocol, ("127.0.0.1", tcp_port)) {
+                    Ok(server) => {
+                        self.status_text =
+                            format!("Serving {} on {}", self.protocol, server.address());
+                        self.server_status = ServerStatus::default();
+                        self.server = Some(server);
+                    }
+                    Err(e) => self.status_text = format!("Error in starting server: {e}"),
+                }
+            }
+            RigMessage::StopPressed => {
+                if let Some(server) = self.server.take() {
+                    self.stopping = true;
+                    self.status_text = String::from("Stopping server...");
+                    // Stopping waits for the request in progress
+                    return Task::perform(crate::blocking(move || server.stop()), |_| {
+                        Message::Rig(RigMessage::Stopped(None))
+                    });
+                }
+            }
+            RigMessage::Stopped(error) => {
+                self.stopping = false;
+                self.status_text = match error {
+                    Some(e) => format!("Server stopped on error: {e}"),
+                    None => String::from("Server stopped"),
+                };
+            }
+            RigMessage::Tick => {
+                if let Some(server) = &self.server {
+                    self.server_status = server.status();
+                }
+                // The server thread has ended, wait for it so that the port
+                // is free once the error is shown
+                if let Some(e) = self.server_status.error.take() {
+                    if let Some(server) = self.server.take() {
+                        self.stopping = true;
+                        let stop = move || {
+                            server.stop();
+                            Some(e)
+                        };
+                        return Task::perform(crate::blocking(stop), |error| {
+                            Message::Rig(RigMessage::Stopped(error))
+                        });
+                    }
+                }
+            }
+        }
+        Task::none()
+    }
+}
+
+impl Tab for RigTab {
+    type Message = Message;
+
+    fn title(&self) -> String {
+        String::from("Rig control")
+    }
+
+    fn tab_label(&self) -> TabLabel {
+        TabLabel::Text(self.title())
+    }
+
+    fn content(&self) -> Element<'_, Self::Message> {
+        let running = self.server.is_some();
+        let port_combo_box = combo_box(
+            &self.ports_combo_state,
+            "Select a serial port",
+            self.serial_port.as_ref(),
+            RigMessage::PortSelected,
+        )
+        .width(250);
+        let protocol = pick_list(
+            RigProtocol::ALL,
+            Some(self.protocol),
+            RigMessage::ProtocolSelected,
+        )
+        .width(250);
+        let protocol: Element<'_, RigMessage> = match running {
+            true => text(self.protocol.to_string()).size(15).into(),
+            false => protocol.into(),
+        };
+        let tcp_port = text_input("TCP port", &self.tcp_port)
+            .on_input_maybe((!running).then_some(RigMessage::TcpPortChanged))
+            .width(100);
+        let clients = match running {
+            true => format!(
+                "{} clients connected, {} requests",
+                self.server_status.clients, self.server_status.requests
+            ),
+            false => String::new(),
+        };
+
+        let content: Element<'_, RigMessage> = Container::new(
+            Column::new()
+                .max_width(600)
+                .push(
+                    row![
+                        Column::new().width(120).push(text("Serial port:").size(15)),
+                        port_combo_box,
+                    ]
+                    .push_maybe(self.port_gone.then(|| text("Unplugged").size(15)))
+                    .spacing(10)
+                    .padding(20),
+                )
+                .push(
+                    row![
+                        Column::new().width(120).push(text("Protocol:").size(15)),
+                        protocol,
+                    ]
+                    .spacing(10)
+                    .padding(20),
+                )
+                .push(
+                    row![
+                        Column::new().width(120).push(text("TCP port:").size(15)),
+                        tcp_port,
+                    ]
+                    .spacing(10)
+                    .padding(20),
+                )
+                .push(row![Column::new()
+                    .width(600)
+                    .align_x(Alignment::Center)
+                    .push(text(&self.status_text).size(20))
+                    .push(text(clients).size(15)),])
+                .push(
+                    scrollable(
+                        self.server_status
+                            .last_requests
+                            .iter()
+                            .rev()
+                            .fold(Column::new(), |column, request| {
+                                column.push(text(request).size(13))
+                            }),
+                    )
+                    .height(150),
+                )
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .padding(20)
+                        .push(
+                            Button::new(Text::new("Start").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (!running
+                                        && !self.stopping
+                                        && self.serial_port.is_some()
+                                        && !self.port_gone
+                                        && self.tcp_port.parse::<u16>().is_ok())
+                                    .then_some(RigMessage::StartPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Stop").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(running.then_some(RigMessage::StopPressed)),
+                        ),
+                ),
+        )
+        .align_x(Horizontal::Center)
+        .align_y(Vertical::Center)
+        .into();
+
+        content.map(Message::Rig)
+    }
+}
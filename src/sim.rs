@@ -0,0 +1,853 @@
+//! Simulated OpenRTX radio over a pseudo-terminal
+//!
+//! [`VirtualRadio`] opens a pty pair and answers rtxlink requests on the
+//! master side, so that the companion can connect to the slave side like to
+//! a real radio. Frames on the wire are SLIP encoded and made of a protocol
+//! byte, the payload and a big endian CRC-16/CCITT-FALSE of both. They are
+//! encoded and decoded here independently from the link of the companion,
+//! so that the tests catch a link which does not follow the protocol.
+//!
+//! The simulated radio answers CAT requests from a table of values and
+//! serves MEMINFO, DUMP and FLASH requests from its memory areas, and file
+//! requests from an in-memory file system, which makes it suitable to
+//! exercise the backup, restore and file management workflows without any
+//! hardware attached. Simulated radios are listed by
+//! [`crate::core::get_ports`] while they run.
+//!
+//! [`VirtualDfuRadio`] stands for a TYT radio running its vendor firmware,
+//! answering the DFU requests of [`crate::core::dfu`] from an image of its
+//! external flash. It is returned by [`crate::core::dfu::open`] while it
+//! runs.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serialport::{SerialPort as _, TTYPort};
+
+use crate::core::cat::{self, CatId};
+use crate::core::dfu::{self, DfuDevice, DfuError, DfuStatus, BLOCK_SIZE, SECTOR_SIZE};
+use crate::core::fmp::{self, EntryKind, FileEntry, FmpFrame, MemInfo, DAT_BLOCK_SIZE};
+use crate::core::SerialPort;
+
+// rtxlink protocol identifiers
+const PROTO_CAT: u8 = 0x01;
+const PROTO_FMP: u8 = 0x02;
+const PROTO_DAT: u8 = 0x03;
+
+// SLIP special characters
+const SLIP_END: u8 = 0xc0;
+const SLIP_ESC: u8 = 0xdb;
+const SLIP_ESC_END: u8 = 0xdc;
+const SLIP_ESC_ESC: u8 = 0xdd;
+
+// errno-like FMP statuses
+const ENOENT: u8 = 2;
+const EEXIST: u8 = 17;
+const EINVAL: u8 = 22;
+const EBUSY: u8 = 16;
+const ENOSYS: u8 = 38;
+const ENOTEMPTY: u8 = 39;
+
+// Ports of the simulated radios, which the operating system does not list
+static VIRTUAL_PORTS: Mutex<Vec<SerialPort>> = Mutex::new(Vec::new());
+
+/// Ports of the simulated radios currently running
+pub(crate) fn virtual_ports() -> Vec<SerialPort> {
+    VIRTUAL_PORTS.lock().unwrap().clone()
+}
+
+/// Named memory area of the simulated radio
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Memory {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl Memory {
+    pub fn new(name: &str, data: Vec<u8>) -> Self {
+        Memory {
+            name: String::from(name),
+            data,
+        }
+    }
+}
+
+// Memory area or file read or written by a bulk transfer
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Source {
+    Memory(usize),
+    File(String),
+}
+
+// Bulk transfer in progress over the DAT protocol
+enum Transfer {
+    Idle,
+    Sending {
+        source: Source,
+        offset: usize,
+        index: u8,
+    },
+    Receiving {
+        source: Source,
+        offset: usize,
+        size: usize,
+    },
+}
+
+struct RadioState {
+    cat: HashMap<[u8; 2], Vec<u8>>,
+    memories: Vec<Memory>,
+    files: BTreeMap<String, Vec<u8>>,
+    // Directories other than the root one
+    dirs: BTreeSet<String>,
+    // Whether the file requests are supported
+    file_system: bool,
+    transfer: Transfer,
+}
+
+// Parent directory and name of a path
+fn split_path(path: &str) -> Option<(&str, &str)> {
+    let (parent, name) = path.rsplit_once('/')?;
+    (!name.is_empty()).then_some((if parent.is_empty() { "/" } else { parent }, name))
+}
+
+impl RadioState {
+    fn data(&self, source: &Source) -> &[u8] {
+        match source {
+            Source::Memory(m) => &self.memories[*m].data,
+            Source::File(path) => self.files.get(path).map(Vec::as_slice).unwrap_or_default(),
+        }
+    }
+
+    fn data_mut(&mut self, source: &Source) -> &mut Vec<u8> {
+        match source {
+            Source::Memory(m) => &mut self.memories[*m].data,
+            Source::File(path) => self.files.entry(path.clone()).or_default(),
+        }
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        path == "/" || self.dirs.contains(path)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.is_dir(path) || self.files.contains_key(path)
+    }
+
+    fn list(&self, dir: &str) -> Vec<FileEntry> {
+        let dirs = self.dirs.iter().map(|d| (d, EntryKind::Directory, 0));
+        let files = self
+            .files
+            .iter()
+            .map(|(f, data)| (f, EntryKind::File, data.len()));
+        dirs.chain(files)
+            .filter_map(|(path, kind, size)| {
+                let (parent, name) = split_path(path)?;
+                (parent == dir).then(|| FileEntry {
+                    name: String::from(name),
+                    kind,
+                    size,
+                })
+            })
+            .collect()
+    }
+
+    // Create the directories leading to a path
+    fn add_parents(&mut self, path: &str) {
+        let mut parent = split_path(path).map(|(p, _)| p);
+        while let Some(dir) = parent.filter(|d| *d != "/") {
+            self.dirs.insert(String::from(dir));
+            parent = split_path(dir).map(|(p, _)| p);
+        }
+    }
+
+    fn file_request(&mut self, opcode: fmp::Opcode, params: &[String]) -> Result<Vec<Vec<u8>>, u8> {
+        let path = params.first().ok_or(EINVAL)?.as_str();
+        let parent_exists = split_path(path).is_some_and(|(p, _)| self.is_dir(p));
+        match opcode {
+            fmp::Opcode::List if self.is_dir(path) => {
+                Ok(self.list(path).iter().map(FileEntry::encode).collect())
+            }
+            fmp::Opcode::Mkdir if self.exists(path) => Err(EEXIST),
+            fmp::Opcode::Mkdir if parent_exists => {
+                self.dirs.insert(String::from(path));
+                Ok(vec![])
+            }
+            fmp::Opcode::Rm if self.files.remove(path).is_some() => Ok(vec![]),
+            fmp::Opcode::Rm if self.dirs.contains(path) => {
+                if !self.list(path).is_empty() {
+                    return Err(ENOTEMPTY);
+                }
+                self.dirs.remove(path);
+                Ok(vec![])
+            }
+            fmp::Opcode::Move => {
+                let to = params.get(1).ok_or(EINVAL)?;
+                if !self.exists(path) || path == "/" {
+                    return Err(ENOENT);
+                }
+                if self.exists(to) {
+                    return Err(EEXIST);
+                }
+                if !split_path(to).is_some_and(|(p, _)| self.is_dir(p)) {
+                    return Err(ENOENT);
+                }
+                // Move the entry and everything below it
+                let moved = |p: &String| p == path || p.starts_with(&format!("{path}/"));
+                let rename = |p: &String| format!("{to}{}", &p[path.len()..]);
+                let files: Vec<_> = self.files.keys().filter(|p| moved(p)).cloned().collect();
+                for f in files {
+                    let data = self.files.remove(&f).unwrap_or_default();
+                    self.files.insert(rename(&f), data);
+                }
+                let dirs: Vec<_> = self.dirs.iter().filter(|p| moved(p)).cloned().collect();
+                for d in dirs {
+                    self.dirs.remove(&d);
+                    self.dirs.insert(rename(&d));
+                }
+                Ok(vec![])
+            }
+            _ => Err(ENOENT),
+        }
+    }
+}
+
+/// Simulated radio answering rtxlink requests on a pseudo-terminal
+pub struct VirtualRadio {
+    port: String,
+    state: Arc<Mutex<RadioState>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    // Keep the slave side open, so that the master does not hang up
+    // between connections
+    _slave: TTYPort,
+}
+
+impl VirtualRadio {
+    /// Start a simulated radio reporting `info` as its hardware name
+    pub fn new(info: &str, memories: Vec<Memory>) -> std::io::Result<VirtualRadio> {
+        let (mut master, slave) = TTYPort::pair()?;
+        let port = slave
+            .name()
+            .ok_or(std::io::Error::other("Unnamed pseudo-terminal"))?;
+        master.set_timeout(Duration::from_millis(50))?;
+
+        let mut cat = HashMap::new();
+        cat.insert(CatId::Info.code(), info.as_bytes().to_vec());
+        let state = Arc::new(Mutex::new(RadioState {
+            cat,
+            memories,
+            files: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+            file_system: true,
+            transfer: Transfer::Idle,
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let state = state.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || serve(master, state, stop))
+        };
+        VIRTUAL_PORTS.lock().unwrap().push(SerialPort {
+            name: port.clone(),
+            vendor: String::from("OpenRTX"),
+            product: String::from("Virtual radio"),
+        });
+
+        Ok(VirtualRadio {
+            port,
+            state,
+            stop,
+            thread: Some(thread),
+            _slave: slave,
+        })
+    }
+
+    /// Path of the serial port to connect to
+    pub fn port(&self) -> &str {
+        &self.port
+    }
+
+    /// Set the value returned for a CAT GET request
+    pub fn set_cat(&self, id: CatId, value: Vec<u8>) {
+        self.state.lock().unwrap().cat.insert(id.code(), value);
+    }
+
+    /// Value last set for a CAT command, either by the test or over rtxlink
+    pub fn cat(&self, id: CatId) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().cat.get(&id.code()).cloned()
+    }
+
+    /// Create or replace a file, along with its parent directories
+    pub fn set_file(&self, path: &str, data: Vec<u8>) {
+        let mut state = self.state.lock().unwrap();
+        state.add_parents(path);
+        state.files.insert(String::from(path), data);
+    }
+
+    /// Answer the file requests as a firmware without file system would
+    pub fn disable_file_system(&self) {
+        self.state.lock().unwrap().file_system = false;
+    }
+
+    /// Current content of a file
+    pub fn file(&self, path: &str) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().files.get(path).cloned()
+    }
+
+    /// Current content of a memory area
+    pub fn memory(&self, name: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state
+            .memories
+            .iter()
+            .find(|m| m.name == name)
+            .map(|m| m.data.clone())
+    }
+}
+
+impl Drop for VirtualRadio {
+    fn drop(&mut self) {
+        VIRTUAL_PORTS
+            .lock()
+            .unwrap()
+            .retain(|p| p.name != self.port);
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xffff, neither
+// input nor output reflected
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// Accumulate received bytes until a whole SLIP frame is available
+#[derive(Default)]
+struct SlipDecoder {
+    frame: Vec<u8>,
+    escape: bool,
+}
+
+impl SlipDecoder {
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        match (self.escape, byte) {
+            (false, SLIP_END) => {
+                if !self.frame.is_empty() {
+                    return Some(std::mem::take(&mut self.frame));
+                }
+            }
+            (false, SLIP_ESC) => self.escape = true,
+            (true, SLIP_ESC_END) => {
+                self.frame.push(SLIP_END);
+                self.escape = false;
+            }
+            (true, SLIP_ESC_ESC) => {
+                self.frame.push(SLIP_ESC);
+                self.escape = false;
+            }
+            (_, b) => {
+                self.frame.push(b);
+                self.escape = false;
+            }
+        }
+        None
+    }
+}
+
+fn slip_encode(frame: &[u8]) -> Vec<u8> {
+    let mut data = vec![SLIP_END];
+    for b in frame {
+        match *b {
+            SLIP_END => data.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+            SLIP_ESC => data.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+            b => data.push(b),
+        }
+    }
+    data.push(SLIP_END);
+    data
+}
+
+fn send(port: &mut TTYPort, proto: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![proto];
+    frame.extend_from_slice(payload);
+    frame.extend_from_slice(&crc16(&frame).to_be_bytes());
+    port.write_all(&slip_encode(&frame))
+}
+
+// Serve rtxlink requests until the radio is dropped
+fn serve(mut port: TTYPort, state: Arc<Mutex<RadioState>>, stop: Arc<AtomicBool>) {
+    let mut decoder = SlipDecoder::default();
+    let mut buf = [0u8; 4096];
+    while !stop.load(Ordering::Relaxed) {
+        let len = match port.read(&mut buf) {
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::TimedOut => continue,
+            // No client connected to the slave side yet
+            Err(_) => {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+        };
+        for byte in &buf[..len] {
+            let Some(frame) = decoder.push(*byte) else {
+                continue;
+            };
+            // Drop frames which are too short or corrupted
+            if frame.len() < 3 {
+                continue;
+            }
+            let (frame, crc) = frame.split_at(frame.len() - 2);
+            if crc16(frame).to_be_bytes() != crc {
+                continue;
+            }
+            let mut state = state.lock().unwrap();
+            let replies = match frame[0] {
+                PROTO_CAT => vec![(PROTO_CAT, handle_cat(&mut state, &frame[1..]))],
+                PROTO_FMP => handle_fmp(&mut state, &frame[1..]),
+                PROTO_DAT => handle_dat(&mut state, &frame[1..]),
+                _ => vec![],
+            };
+            for (proto, payload) in replies {
+                _ = send(&mut port, proto, &payload);
+            }
+        }
+    }
+}
+
+fn handle_cat(state: &mut RadioState, request: &[u8]) -> Vec<u8> {
+    let ack = |status: u8| vec![cat::Opcode::Ack as u8, status];
+    let (Some(opcode), Some(id)) = (
+        request.first().and_then(|b| cat::Opcode::from_byte(*b)),
+        request
+            .get(1..3)
+            .and_then(|id| <[u8; 2]>::try_from(id).ok()),
+    ) else {
+        return ack(EINVAL);
+    };
+    match opcode {
+        cat::Opcode::Get => match state.cat.get(&id) {
+            Some(value) => {
+                let mut reply = vec![cat::Opcode::Data as u8];
+                reply.extend_from_slice(value);
+                reply
+            }
+            None => ack(cat::ENOTSUP),
+        },
+        cat::Opcode::Set => match state.cat.get_mut(&id) {
+            Some(value) => {
+                *value = request[3..].to_vec();
+                ack(0)
+            }
+            None => ack(cat::ENOTSUP),
+        },
+        _ => ack(EINVAL),
+    }
+}
+
+fn handle_fmp(state: &mut RadioState, request: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let Some(request) = FmpFrame::decode(request) else {
+        return vec![];
+    };
+    let mut reply = FmpFrame::new(request.opcode, vec![]);
+    let memory = request
+        .params
+        .first()
+        .and_then(|p| p.first())
+        .map(|m| *m as usize)
+        .filter(|m| *m < state.memories.len());
+    let busy = !matches!(state.transfer, Transfer::Idle);
+    let path = request
+        .params
+        .first()
+        .map(|p| String::from_utf8_lossy(p).to_string());
+    match (request.opcode, memory) {
+        (fmp::Opcode::MemInfo, _) => {
+            reply.params = state
+                .memories
+                .iter()
+                .map(|m| {
+                    MemInfo {
+                        name: m.name.clone(),
+                        size: m.data.len(),
+                    }
+                    .encode()
+                })
+                .collect();
+        }
+        (
+            fmp::Opcode::Read
+            | fmp::Opcode::Write
+            | fmp::Opcode::List
+            | fmp::Opcode::Mkdir
+            | fmp::Opcode::Rm
+            | fmp::Opcode::Move,
+            _,
+        ) if !state.file_system => reply.status = ENOSYS,
+        (fmp::Opcode::Dump | fmp::Opcode::Flash | fmp::Opcode::Read | fmp::Opcode::Write, _)
+            if busy =>
+        {
+            reply.status = EBUSY
+        }
+        (fmp::Opcode::Dump, Some(memory)) => {
+            return start_sending(state, reply, Source::Memory(memory));
+        }
+        (fmp::Opcode::Read, _) => match path {
+            Some(path) if state.files.contains_key(&path) => {
+                return start_sending(state, reply, Source::File(path));
+            }
+            _ => reply.status = ENOENT,
+        },
+        (fmp::Opcode::Write, _) => {
+            let size = request
+                .params
+                .get(1)
+                .and_then(|s| <[u8; 4]>::try_from(s.as_slice()).ok())
+                .map(|s| u32::from_le_bytes(s) as usize);
+            match (path, size) {
+                (Some(path), Some(size))
+                    if !state.is_dir(&path)
+                        && split_path(&path).is_some_and(|(p, _)| state.is_dir(p)) =>
+                {
+                    state.files.insert(path.clone(), vec![0; size]);
+                    state.transfer = Transfer::Receiving {
+                        source: Source::File(path),
+                        offset: 0,
+                        size,
+                    };
+                }
+                _ => reply.status = ENOENT,
+            }
+        }
+        (fmp::Opcode::List | fmp::Opcode::Mkdir | fmp::Opcode::Rm | fmp::Opcode::Move, _) => {
+            let params: Vec<String> = request
+                .params
+                .iter()
+                .map(|p| String::from_utf8_lossy(p).to_string())
+                .collect();
+            match state.file_request(request.opcode, &params) {
+                Ok(params) => reply.params = params,
+                Err(status) => reply.status = status,
+            }
+        }
+        (fmp::Opcode::Flash, Some(memory)) => {
+            let size = request
+                .params
+                .get(1)
+                .and_then(|s| <[u8; 4]>::try_from(s.as_slice()).ok())
+                .map(|s| u32::from_le_bytes(s) as usize);
+            match size {
+                Some(size) if size <= state.memories[memory].data.len() => {
+                    state.transfer = Transfer::Receiving {
+                        source: Source::Memory(memory),
+                        offset: 0,
+                        size,
+                    };
+                }
+                _ => reply.status = EINVAL,
+            }
+        }
+        (fmp::Opcode::Reset, _) => state.transfer = Transfer::Idle,
+        (_, _) => reply.status = EINVAL,
+    }
+    vec![(PROTO_FMP, encode_reply(reply))]
+}
+
+// Encode an FMP answer, as an EINVAL error if it does not fit in a frame
+fn encode_reply(mut reply: FmpFrame) -> Vec<u8> {
+    reply.encode().unwrap_or_else(|_| {
+        reply.status = EINVAL;
+        reply.params.clear();
+        reply.encode().unwrap_or_default()
+    })
+}
+
+// Answer a DUMP or READ request with the size of the data, followed by
+// its first block
+fn start_sending(
+    state: &mut RadioState,
+    mut reply: FmpFrame,
+    source: Source,
+) -> Vec<(u8, Vec<u8>)> {
+    let size = state.data(&source).len();
+    reply.params = vec![(size as u32).to_le_bytes().to_vec()];
+    state.transfer = Transfer::Sending {
+        source,
+        offset: 0,
+        index: 0,
+    };
+    let first_block = next_block(state);
+    vec![(PROTO_FMP, encode_reply(reply))]
+        .into_iter()
+        .chain(first_block.map(|b| (PROTO_DAT, b)))
+        .collect()
+}
+
+// Next DAT block of the data being sent, None when the transfer is over
+fn next_block(state: &mut RadioState) -> Option<Vec<u8>> {
+    let Transfer::Sending {
+        source,
+        offset,
+        index,
+    } = &state.transfer
+    else {
+        return None;
+    };
+    let (offset, index) = (*offset, *index);
+    let data = state.data(source);
+    if offset >= data.len() {
+        state.transfer = Transfer::Idle;
+        return None;
+    }
+    let end = (offset + DAT_BLOCK_SIZE).min(data.len());
+    let mut block = vec![index];
+    block.extend_from_slice(&data[offset..end]);
+    Some(block)
+}
+
+fn handle_dat(state: &mut RadioState, frame: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let Some((&index, data)) = frame.split_first() else {
+        return vec![];
+    };
+    match std::mem::replace(&mut state.transfer, Transfer::Idle) {
+        // Acknowledge of the last block sent
+        Transfer::Sending {
+            source,
+            offset,
+            index: sent,
+        } if index == sent => {
+            state.transfer = Transfer::Sending {
+                source,
+                offset: offset + DAT_BLOCK_SIZE,
+                index: sent.wrapping_add(1),
+            };
+            next_block(state)
+                .map(|b| vec![(PROTO_DAT, b)])
+                .unwrap_or_default()
+        }
+        Transfer::Receiving {
+            source,
+            offset,
+            size,
+        } => {
+            let len = data.len().min(size - offset);
+            state.data_mut(&source)[offset..offset + len].copy_from_slice(&data[..len]);
+            if offset + len < size {
+                state.transfer = Transfer::Receiving {
+                    source,
+                    offset: offset + len,
+                    size,
+                };
+            }
+            vec![(PROTO_DAT, vec![index])]
+        }
+        transfer => {
+            state.transfer = transfer;
+            vec![]
+        }
+    }
+}
+
+// State of the simulated radio in DFU mode, which the operating system does
+// not list
+static VIRTUAL_DFU: Mutex<Option<Arc<Mutex<DfuState>>>> = Mutex::new(None);
+
+/// Simulated radio in DFU mode, if one runs
+pub(crate) fn virtual_dfu() -> Option<Box<dyn DfuDevice>> {
+    let state = VIRTUAL_DFU.lock().unwrap().clone()?;
+    Some(Box::new(VirtualDfu(state)))
+}
+
+struct DfuState {
+    flash: Vec<u8>,
+    state: u8,
+    // DNLOAD request waiting for a GETSTATUS
+    pending: Option<(u16, Vec<u8>)>,
+    address: u32,
+    programming: bool,
+    rebooted: bool,
+}
+
+impl DfuState {
+    // Range of the external flash for a transfer of a block
+    fn block_range(&self, block: u16, len: usize) -> Option<std::ops::Range<usize>> {
+        let start = self.address as usize + (block as usize).checked_sub(2)? * BLOCK_SIZE;
+        (self.programming && len <= BLOCK_SIZE && start + len <= self.flash.len())
+            .then_some(start..start + len)
+    }
+
+    // Handle a TYT command, returning the next DFU state
+    fn command(&mut self, request: &[u8]) -> Option<u8> {
+        let address =
+            |request: &[u8]| Some(u32::from_le_bytes(request.get(1..5)?.try_into().ok()?));
+        match request {
+            [0x91, 0x01] => self.programming = true,
+            [0xa2, _] => (),
+            [0x91, 0x05] => {
+                // The radio restarts, idle in DFU mode again
+                self.programming = false;
+                self.rebooted = true;
+                return Some(dfu::STATE_IDLE);
+            }
+            [0x21, ..] if request.len() == 5 => self.address = address(request)?,
+            [0x41, ..] if request.len() == 5 && self.programming => {
+                let start = address(request)? as usize;
+                if !start.is_multiple_of(SECTOR_SIZE) || start + SECTOR_SIZE > self.flash.len() {
+                    return None;
+                }
+                self.flash[start..start + SECTOR_SIZE].fill(0xff);
+            }
+            _ => return None,
+        }
+        Some(dfu::STATE_DNBUSY)
+    }
+
+    // Handle a DNLOAD request, returning the next DFU state
+    fn dnload(&mut self, block: u16, data: &[u8]) -> Option<u8> {
+        if block == 0 {
+            return self.command(data);
+        }
+        // The flash is NOR: writing only clears bits
+        let range = self.block_range(block, data.len())?;
+        for (byte, new) in self.flash[range].iter_mut().zip(data) {
+            *byte &= new;
+        }
+        Some(dfu::STATE_DNBUSY)
+    }
+}
+
+// Request sent to the simulated radio, as seen over USB
+struct VirtualDfu(Arc<Mutex<DfuState>>);
+
+impl VirtualDfu {
+    fn fail(state: &mut DfuState) -> DfuError {
+        state.state = dfu::STATE_ERROR;
+        DfuError::Usb(String::from("Pipe error"))
+    }
+}
+
+impl DfuDevice for VirtualDfu {
+    fn dnload(&mut self, block: u16, data: &[u8]) -> Result<(), DfuError> {
+        let mut state = self.0.lock().unwrap();
+        if !matches!(state.state, dfu::STATE_IDLE | dfu::STATE_DNLOAD_IDLE) {
+            return Err(Self::fail(&mut state));
+        }
+        state.pending = Some((block, data.to_vec()));
+        state.state = dfu::STATE_DNLOAD_SYNC;
+        Ok(())
+    }
+
+    fn upload(&mut self, block: u16, length: usize) -> Result<Vec<u8>, DfuError> {
+        let mut state = self.0.lock().unwrap();
+        let range = state.block_range(block, length);
+        match range {
+            Some(range) if matches!(state.state, dfu::STATE_IDLE | dfu::STATE_UPLOAD_IDLE) => {
+                state.state = dfu::STATE_UPLOAD_IDLE;
+                Ok(state.flash[range].to_vec())
+            }
+            _ => Err(Self::fail(&mut state)),
+        }
+    }
+
+    fn get_status(&mut self) -> Result<DfuStatus, DfuError> {
+        let mut state = self.0.lock().unwrap();
+        match state.state {
+            dfu::STATE_DNLOAD_SYNC => {
+                let (block, data) = state.pending.take().unwrap_or_default();
+                state.state = state.dnload(block, &data).unwrap_or(dfu::STATE_ERROR);
+            }
+            dfu::STATE_DNBUSY => state.state = dfu::STATE_DNLOAD_IDLE,
+            _ => (),
+        }
+        Ok(DfuStatus {
+            // errUNKNOWN in the error state
+            status: if state.state == dfu::STATE_ERROR {
+                0x0e
+            } else {
+                0
+            },
+            poll_timeout: Duration::ZERO,
+            state: state.state,
+        })
+    }
+
+    fn get_state(&mut self) -> Result<u8, DfuError> {
+        Ok(self.0.lock().unwrap().state)
+    }
+
+    fn clear_status(&mut self) -> Result<(), DfuError> {
+        self.0.lock().unwrap().state = dfu::STATE_IDLE;
+        Ok(())
+    }
+
+    fn abort(&mut self) -> Result<(), DfuError> {
+        let mut state = self.0.lock().unwrap();
+        state.pending = None;
+        state.state = dfu::STATE_IDLE;
+        Ok(())
+    }
+}
+
+/// Simulated TYT radio running its vendor firmware, answering DFU requests
+///
+/// A single one can run at a time.
+pub struct VirtualDfuRadio {
+    state: Arc<Mutex<DfuState>>,
+}
+
+impl VirtualDfuRadio {
+    /// Start a simulated radio with an image of its external flash
+    pub fn new(flash: Vec<u8>) -> VirtualDfuRadio {
+        let state = Arc::new(Mutex::new(DfuState {
+            flash,
+            state: dfu::STATE_IDLE,
+            pending: None,
+            address: 0,
+            programming: false,
+            rebooted: false,
+        }));
+        *VIRTUAL_DFU.lock().unwrap() = Some(state.clone());
+        VirtualDfuRadio { state }
+    }
+
+    /// Current content of the external flash
+    pub fn flash(&self) -> Vec<u8> {
+        self.state.lock().unwrap().flash.clone()
+    }
+
+    /// Whether the radio was rebooted out of the programming mode
+    pub fn rebooted(&self) -> bool {
+        self.state.lock().unwrap().rebooted
+    }
+}
+
+impl Drop for VirtualDfuRadio {
+    fn drop(&mut self) {
+        let mut current = VIRTUAL_DFU.lock().unwrap();
+        if current
+            .as_ref()
+            .is_some_and(|s| Arc::ptr_eq(s, &self.state))
+        {
+            *current = None;
+        }
+    }
+}
@@ -0,0 +1,466 @@
+// show logs when debugging
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use crate::backup::port_options;
+use crate::Message;
+use crate::Tab;
+use iced::{
+    alignment::{Horizontal, Vertical},
+    mouse,
+    widget::{
+        canvas::{self, Frame, Geometry, Path, Stroke},
+        checkbox, combo_box, row, text, text_input, Button, Canvas, Column, Container, Row, Text,
+    },
+    Alignment, Color, Element, Length, Pixels, Point, Rectangle, Renderer, Size, Task, Theme,
+};
+use iced_aw::TabLabel;
+use openrtx_companion::core::{
+    get_ports,
+    telemetry::{self, Sample},
+    Attached, SerialPort, TelemetryRecorder,
+};
+use rfd::AsyncFileDialog;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Samples kept while recording, the oldest being dropped past it: ten hours
+// at the default interval, the recording itself keeping all of them
+const MAX_SAMPLES: usize = 36_000;
+
+#[derive(Clone, Debug)]
+pub enum TelemetryMessage {
+    PortSelected(SerialPort),
+    PortsChanged(Attached),
+    IntervalChanged(String),
+    RecordToggled(bool),
+    StartPressed,
+    RecordFileSelected(Option<PathBuf>),
+    StopPressed,
+    Stopped,
+    LoadPressed,
+    Loaded(Option<Result<(PathBuf, Vec<Sample>), String>>),
+    Tick,
+}
+
+// Line chart of one of the values of the samples
+struct Chart<'a> {
+    samples: &'a [Sample],
+    value: fn(&Sample) -> Option<f32>,
+    label: &'static str,
+    unit: &'static str,
+}
+
+impl canvas::Program<TelemetryMessage> for Chart<'_> {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let palette = theme.palette();
+        let mut frame = Frame::new(renderer, bounds.size());
+        let label = |frame: &mut Frame, content: String, position: Point| {
+            frame.fill_text(canvas::Text {
+                content,
+                position,
+                color: palette.text,
+                size: Pixels(12.0),
+                ..canvas::Text::default()
+            })
+        };
+        frame.stroke(
+            &Path::rectangle(Point::ORIGIN, bounds.size()),
+            Stroke::default().with_color(Color {
+                a: 0.3,
+                ..palette.text
+            }),
+        );
+
+        // Draw about one point per pixel of the chart width
+        let step = (self.samples.len() / (bounds.width as usize).max(1)).max(1);
+        let points: Vec<(f32, f32)> = self
+            .samples
+            .iter()
+            .step_by(step)
+            .chain(
+                self.samples
+                    .last()
+                    .filter(|_| !(self.samples.len() - 1).is_multiple_of(step)),
+            )
+            .filter_map(|sample| {
+                let seconds = (sample.time - self.samples[0].time).as_seconds_f32();
+                Some((seconds, (self.value)(sample)?))
+            })
+            .collect();
+        let latest = points
+            .last()
+            .map(|(_, value)| format!("{value:.3} {}", self.unit))
+            .unwrap_or_else(|| String::from("-"));
+        label(
+            &mut frame,
+            format!("{}: {latest}", self.label),
+            Point::new(5.0, 5.0),
+        );
+        if points.is_empty() {
+            return vec![frame.into_geometry()];
+        }
+
+        let span = self.samples[self.samples.len() - 1].time - self.samples[0].time;
+        let span = span.as_seconds_f32().max(1.0);
+        let (mut low, mut high) = points
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(low, high), (_, value)| {
+                (low.min(*value), high.max(*value))
+            });
+        // Keep flat lines in the middle of the chart
+        if high - low < 1.0 {
+            low -= 0.5;
+            high += 0.5;
+        }
+        label(&mut frame, format!("{high:.3}"), Point::new(5.0, 20.0));
+        label(
+            &mut frame,
+            format!("{low:.3}"),
+            Point::new(5.0, bounds.height - 17.0),
+        );
+        label(
+            &mut frame,
+            format!("{span:.0} s"),
+            Point::new(bounds.width - 50.0, bounds.height - 17.0),
+        );
+
+        // Plot area, leaving room for the labels
+        let area = Rectangle::new(
+            Point::new(50.0, 20.0),
+            Size::new(bounds.width - 60.0, bounds.height - 40.0),
+        );
+        let point = |(seconds, value): (f32, f32)| {
+            Point::new(
+                area.x + area.width * seconds / span,
+                area.y + area.height * (high - value) / (high - low),
+            )
+        };
+        let line = Path::new(|builder| {
+            builder.move_to(point(points[0]));
+            for p in &points[1..] {
+                builder.line_to(point(*p));
+            }
+        });
+        frame.stroke(
+            &line,
+            Stroke::default()
+                .with_color(palette.primary)
+                .with_width(2.0),
+        );
+        vec![frame.into_geometry()]
+    }
+}
+
+pub struct TelemetryTab {
+    serial_ports: Vec<SerialPort>,
+    serial_port: Option<SerialPort>,
+    // The selected port has been unplugged
+    port_gone: bool,
+    ports_combo_state: combo_box::State<SerialPort>,
+    // Sampling interval in seconds, as typed
+    interval: String,
+    record: bool,
+    recorder: Option<TelemetryRecorder>,
+    samples: Vec<Sample>,
+    // Samples taken or loaded, some of which may have been dropped
+    sample_count: usize,
+    status_text: String,
+}
+
+impl Default for TelemetryTab {
+    fn default() -> Self {
+        let ports = port_options(get_ports());
+        Self {
+            serial_ports: ports.clone(),
+            serial_port: None,
+            port_gone: false,
+            ports_combo_state: combo_box::State::new(ports),
+            interval: String::from("1"),
+            record: true,
+            recorder: None,
+            samples: vec![],
+            sample_count: 0,
+            status_text: String::from("Sample the radio or load a recording"),
+        }
+    }
+}
+
+fn recording_status(recorder: &TelemetryRecorder) -> String {
+    match recorder.output() {
+        Some(path) => format!("Recording to {}", path.display()),
+        None => String::from("Sampling without recording"),
+    }
+}
+
+impl TelemetryTab {
+    fn add_samples(&mut self, samples: Vec<Sample>) {
+        self.sample_count += samples.len();
+        self.samples.extend(samples);
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.drain(..self.samples.len() - MAX_SAMPLES);
+        }
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        let seconds = self.interval.trim().parse::<f64>().ok()?;
+        (seconds >= 0.1).then(|| Duration::from_secs_f64(seconds))
+    }
+
+    fn start(&mut self, output: Option<PathBuf>) {
+        let (Some(port), Some(interval)) = (&self.serial_port, self.interval()) else {
+            return;
+        };
+        match TelemetryRecorder::start(&port.name, interval, output.as_deref()) {
+            Ok(recorder) => {
+                self.status_text = recording_status(&recorder);
+                self.samples.clear();
+                self.sample_count = 0;
+                self.recorder = Some(recorder);
+            }
+            Err(e) => self.status_text = format!("Error in starting the recording: {e}"),
+        }
+    }
+
+    pub fn update(&mut self, message: TelemetryMessage) -> Task<Message> {
+        match message {
+            TelemetryMessage::PortSelected(port) => {
+                self.serial_port = Some(port);
+                self.port_gone = false;
+            }
+            TelemetryMessage::PortsChanged(attached) => {
+                let ports = port_options(attached.ports.clone());
+                if ports != self.serial_ports {
+                    self.serial_ports = ports.clone();
+                    self.ports_combo_state = combo_box::State::new(ports);
+                }
+                if let Some(port) = &self.serial_port {
+                    self.port_gone = !attached.has_port(port);
+                }
+            }
+            TelemetryMessage::IntervalChanged(interval) => self.interval = interval,
+            TelemetryMessage::RecordToggled(record) => self.record = record,
+            TelemetryMessage::StartPressed => {
+                if !self.record {
+                    self.start(None);
+                    return Task::none();
+                }
+                return Task::perform(
+                    async {
+                        let file = AsyncFileDialog::new()
+                            .set_title("Record telemetry")
+                            .add_filter("CSV", &["csv"])
+                            .set_file_name("telemetry.csv")
+                            .save_file()
+                            .await?;
+                        Some(file.path().to_path_buf())
+                    },
+                    |path| Message::Telemetry(TelemetryMessage::RecordFileSelected(path)),
+                );
+            }
+            TelemetryMessage::RecordFileSelected(path) => {
+                if let Some(path) = path {
+                    self.start(Some(path));
+                }
+            }
+            TelemetryMessage::StopPressed => {
+                if let Some(recorder) = self.recorder.take() {
+                    self.add_samples(recorder.take_samples());
+                    self.status_text = String::from("Stopping...");
+                    // The sample in progress may take until the link times out
+                    return Task::perform(crate::blocking(move || recorder.stop()), |_| {
+                        Message::Telemetry(TelemetryMessage::Stopped)
+                    });
+                }
+            }
+            TelemetryMessage::Stopped => {
+                self.status_text = format!("Stopped after {} samples", self.sample_count);
+            }
+            TelemetryMessage::LoadPressed => {
+                return Task::perform(
+                    async {
+                        let file = AsyncFileDialog::new()
+                            .set_title("Load telemetry")
+                            .add_filter("CSV", &["csv"])
+                            .pick_file()
+                            .await?;
+                        let path = file.path().to_path_buf();
+                        Some(
+                            telemetry::load_file(&path)
+                                .map(|samples| (path, samples))
+                                .map_err(|e| e.to_string()),
+                        )
+                    },
+                    |res| Message::Telemetry(TelemetryMessage::Loaded(res)),
+                );
+            }
+            TelemetryMessage::Loaded(res) => match res {
+                Some(Ok((path, samples))) => {
+                    self.status_text =
+                        format!("Loaded {} samples from {}", samples.len(), path.display());
+                    self.sample_count = samples.len();
+                    self.samples = samples;
+                }
+                Some(Err(e)) => self.status_text = format!("Error in loading recording: {e}"),
+                None => (),
+            },
+            TelemetryMessage::Tick => {
+                let Some(recorder) = &self.recorder else {
+                    return Task::none();
+                };
+                let samples = recorder.take_samples();
+                let error = recorder.error();
+                self.status_text = match &error {
+                    Some(e) => format!("Error in sampling the radio: {e}"),
+                    None => recording_status(recorder),
+                };
+                if !recorder.is_running() {
+                    self.recorder = None;
+                    self.status_text = format!(
+                        "Error in starting the recording: {}",
+                        error.unwrap_or_default()
+                    );
+                }
+                self.add_samples(samples);
+            }
+        }
+        Task::none()
+    }
+}
+
+impl Tab for TelemetryTab {
+    type Message = Message;
+
+    fn title(&self) -> String {
+        String::from("Telemetry")
+    }
+
+    fn tab_label(&self) -> TabLabel {
+        TabLabel::Text(self.title())
+    }
+
+    fn content(&self) -> Element<'_, Self::Message> {
+        let running = self.recorder.is_some();
+        let port_combo_box = combo_box(
+            &self.ports_combo_state,
+            "Select a serial port",
+            self.serial_port.as_ref(),
+            TelemetryMessage::PortSelected,
+        )
+        .width(250);
+        let interval = text_input("Seconds", &self.interval)
+            .on_input_maybe((!running).then_some(TelemetryMessage::IntervalChanged))
+            .width(100);
+        let record = checkbox("Record to CSV", self.record)
+            .on_toggle_maybe((!running).then_some(TelemetryMessage::RecordToggled));
+        let chart = |value: fn(&Sample) -> Option<f32>, label: &'static str, unit: &'static str| {
+            Canvas::new(Chart {
+                samples: &self.samples,
+                value,
+                label,
+                unit,
+            })
+            .width(Length::Fill)
+            .height(150)
+        };
+        let frequency = self
+            .samples
+            .last()
+            .map(|s| format!("{:.5} MHz", s.frequency as f64 / 1e6))
+            .unwrap_or_default();
+
+        let content: Element<'_, TelemetryMessage> = Container::new(
+            Column::new()
+                .max_width(800)
+                .push(
+                    row![
+                        Column::new().width(120).push(text("Serial port:").size(15)),
+                        port_combo_box,
+                    ]
+                    .push_maybe(self.port_gone.then(|| text("Unplugged").size(15)))
+                    .spacing(10)
+                    .padding(20),
+                )
+                .push(
+                    row![
+                        Column::new()
+                            .width(120)
+                            .push(text("Interval (s):").size(15)),
+                        interval,
+                        record,
+                    ]
+                    .spacing(20)
+                    .padding(20)
+                    .align_y(Alignment::Center),
+                )
+                .push(
+                    Column::new()
+                        .padding(20)
+                        .spacing(10)
+                        .push(chart(
+                            |s: &Sample| Some((f64::from(s.frequency) / 1e6) as f32),
+                            "Frequency",
+                            "MHz",
+                        ))
+                        .push(chart(
+                            |s: &Sample| s.rssi.map(f32::from),
+                            "Signal strength",
+                            "dBm",
+                        ))
+                        .push(chart(
+                            |s: &Sample| s.battery.map(|b| f32::from(b) / 1000.0),
+                            "Battery",
+                            "V",
+                        )),
+                )
+                .push(row![Column::new()
+                    .width(800)
+                    .align_x(Alignment::Center)
+                    .push(text(&self.status_text).size(20))
+                    .push(
+                        text(format!("{} samples {frequency}", self.sample_count)).size(15)
+                    ),])
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .padding(20)
+                        .push(
+                            Button::new(Text::new("Start").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (!running
+                                        && self.serial_port.is_some()
+                                        && !self.port_gone
+                                        && self.interval().is_some())
+                                    .then_some(TelemetryMessage::StartPressed),
+                                ),
+                        )
+                        .push(
+                            Button::new(Text::new("Stop").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(running.then_some(TelemetryMessage::StopPressed)),
+                        )
+                        .push(
+                            Button::new(Text::new("Load").align_x(Horizontal::Center))
+                                .width(Length::Fill)
+                                .on_press_maybe(
+                                    (!running).then_some(TelemetryMessage::LoadPressed),
+                                ),
+                        ),
+                ),
+        )
+        .align_x(Horizontal::Center)
+        .align_y(Vertical::Center)
+        .into();
+
+        content.map(Message::Telemetry)
+    }
+}
@@ -0,0 +1,83 @@
+//! Firmware catalog served from a local directory
+
+mod common;
+
+use std::path::PathBuf;
+
+use openrtx_companion::core::manifest::sha256_hex;
+use openrtx_companion::core::{Catalog, CatalogError};
+
+use common::test_dir;
+
+// Release folder with a good image and one whose hash is wrong
+fn release_dir(name: &str) -> (PathBuf, Vec<u8>) {
+    let dir = test_dir(name);
+    let image = b"OpenRTX test image".to_vec();
+    std::fs::write(dir.join("openrtx_md3x0.bin"), &image).unwrap();
+    std::fs::write(dir.join("openrtx_mduv3x0.bin"), b"tampered").unwrap();
+    let manifest = format!(
+        r#"{{
+  "releases": [
+    {{ "version": "v1.0", "target": "MD3x0", "url": "openrtx_md3x0.bin", "sha256": "{}" }},
+    {{ "version": "v1.0", "target": "MD-UV3x0", "url": "openrtx_mduv3x0.bin", "sha256": "{}" }}
+  ]
+}}"#,
+        sha256_hex(&image),
+        sha256_hex(b"original"),
+    );
+    std::fs::write(dir.join("manifest.json"), manifest).unwrap();
+    (dir, image)
+}
+
+#[test]
+fn download_from_local_folder() {
+    let (dir, image) = release_dir("catalog");
+    let cache = test_dir("catalog-cache");
+
+    let catalog = Catalog::load(dir.to_str().unwrap()).unwrap();
+    assert_eq!(catalog.releases.len(), 2);
+    let path = catalog.download(&catalog.releases[0], &cache).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), image);
+
+    // Cached images are used once the source is gone
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        catalog.download(&catalog.releases[0], &cache).unwrap(),
+        path
+    );
+}
+
+#[test]
+fn download_rejects_wrong_hash() {
+    let (dir, _) = release_dir("catalog-hash");
+    let cache = test_dir("catalog-hash-cache");
+
+    let catalog = Catalog::load(dir.join("manifest.json").to_str().unwrap()).unwrap();
+    let res = catalog.download(&catalog.releases[1], &cache);
+    assert!(matches!(res, Err(CatalogError::HashMismatch { .. })));
+    assert_eq!(std::fs::read_dir(&cache).unwrap().count(), 0);
+}
+
+#[test]
+fn releases_without_a_valid_sha256_are_refused() {
+    let dir = test_dir("catalog-sha256");
+    // Non-ASCII digits of the right length in bytes included
+    let invalid = [
+        String::new(),
+        "abcd".repeat(4),
+        "é".repeat(32),
+        "g".repeat(64),
+    ];
+    for sha256 in &invalid {
+        let manifest = format!(
+            r#"{{
+  "releases": [
+    {{ "version": "v1.0", "target": "MD3x0", "url": "openrtx_md3x0.bin", "sha256": "{sha256}" }}
+  ]
+}}"#
+        );
+        std::fs::write(dir.join("manifest.json"), manifest).unwrap();
+        let res = Catalog::load(dir.to_str().unwrap());
+        assert!(matches!(res, Err(CatalogError::Parse(_))), "{sha256}");
+    }
+}
@@ -0,0 +1,78 @@
+//! Conversion of CHIRP channel lists to OpenRTX channels and back
+
+use openrtx_companion::core::chirp::{self, RowReport, Unmapped};
+use openrtx_companion::core::codeplug::{Bandwidth, OpMode};
+
+const CHIRP_CSV: &str = "\
+Location,Name,Frequency,Duplex,Offset,Tone,rToneFreq,cToneFreq,DtcsCode,DtcsPolarity,RxDtcsCode,CrossMode,Mode,TStep,Skip,Power,Comment,URCALL,RPT1CALL,RPT2CALL,DVCODE
+1,Repeater,145.600000,-,0.600000,Tone,88.5,88.5,023,NN,023,Tone->Tone,FM,12.50,,5.0W,Club,,,,
+2,Simplex,145.500000,,0.000000,TSQL,88.5,123.0,023,NN,023,Tone->Tone,NFM,12.50,,1.0W,,,,,
+3,Satellite,145.800000,split,435.300000,,88.5,88.5,023,NN,023,Tone->Tone,NFM,5.00,,,,,,,
+4,DCS repeater,438.500000,-,7.600000,DTCS,88.5,88.5,023,NN,023,Tone->Tone,NFM,12.50,,,,,,,
+5,Airband,118.100000,,0.000000,,88.5,88.5,023,NN,023,Tone->Tone,AM,8.33,,,,,,,
+6,Listen,433.000000,off,0.000000,Cross,88.5,67.0,023,NN,023,->Tone,NFM,12.50,,,,,,,
+";
+
+#[test]
+fn import_converts_duplex_and_reports_unmapped_rows() {
+    let import = chirp::import(CHIRP_CSV.as_bytes()).unwrap();
+    let names: Vec<&str> = import.channels.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        ["Repeater", "Simplex", "Satellite", "DCS repeater", "Listen"]
+    );
+
+    let repeater = &import.channels[0];
+    assert_eq!(repeater.mode, OpMode::Fm);
+    assert_eq!(repeater.bandwidth, Bandwidth::B25);
+    assert_eq!(repeater.rx_frequency, 145_600_000);
+    assert_eq!(repeater.tx_frequency, 145_000_000);
+    assert_eq!((repeater.rx_tone, repeater.tx_tone), (None, Some(885)));
+    assert_eq!(repeater.description, "Club");
+    let simplex = &import.channels[1];
+    assert_eq!((simplex.rx_tone, simplex.tx_tone), (Some(1230), Some(1230)));
+    assert_eq!(import.channels[2].tx_frequency, 435_300_000);
+    let listen = &import.channels[4];
+    assert!(listen.rx_only);
+    assert_eq!((listen.rx_tone, listen.tx_tone), (Some(670), None));
+
+    assert_eq!(
+        import.reports,
+        vec![
+            RowReport {
+                location: String::from("4"),
+                name: String::from("DCS repeater"),
+                skipped: false,
+                unmapped: vec![Unmapped::Dcs(String::from("DTCS"))],
+            },
+            RowReport {
+                location: String::from("5"),
+                name: String::from("Airband"),
+                skipped: true,
+                unmapped: vec![Unmapped::Mode(String::from("AM"))],
+            },
+        ]
+    );
+}
+
+#[test]
+fn export_restores_duplex_offsets() {
+    let mut channels = chirp::import(CHIRP_CSV.as_bytes()).unwrap().channels;
+    channels[3].mode = OpMode::M17;
+
+    let mut csv = vec![];
+    let reports = chirp::export(&channels, &mut csv).unwrap();
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].skipped);
+    let csv = String::from_utf8(csv).unwrap();
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(rows.len(), 5);
+    assert!(rows[1].starts_with("0,Repeater,145.600000,-,0.600000,Tone,88.5,88.5,"));
+    assert!(rows[3].starts_with("2,Satellite,145.800000,split,435.300000,,"));
+    assert!(rows[4].starts_with("3,Listen,433.000000,off,"));
+
+    let import = chirp::import(csv.as_bytes()).unwrap();
+    assert!(import.reports.is_empty());
+    channels.remove(3);
+    assert_eq!(import.channels, channels);
+}
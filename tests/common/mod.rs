@@ -0,0 +1,11 @@
+//! Helpers shared by the integration tests
+
+use std::path::PathBuf;
+
+/// Empty folder for a test, unique to the test process
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("openrtx-companion-{name}-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
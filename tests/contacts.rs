@@ -0,0 +1,105 @@
+//! M17 callsign encoding and contact list import and export
+
+use openrtx_companion::core::codeplug::{CallType, Contact};
+use openrtx_companion::core::contacts::{
+    self, check, decode_callsign, encode_callsign, CallsignError, ContactIssue, Format,
+};
+
+#[test]
+fn callsigns_are_encoded_in_base_40() {
+    // Example of the M17 specification
+    let address = encode_callsign("AB1CD").unwrap();
+    assert_eq!(address, [0x00, 0x00, 0x00, 0x9f, 0xdd, 0x51]);
+    assert_eq!(decode_callsign(&address).as_deref(), Some("AB1CD"));
+    assert_eq!(encode_callsign(" ab1cd ").unwrap(), address);
+
+    let address = encode_callsign("IU2KWO/P").unwrap();
+    assert_eq!(decode_callsign(&address).as_deref(), Some("IU2KWO/P"));
+    assert_eq!(
+        decode_callsign(&encode_callsign("@ALL").unwrap()).as_deref(),
+        Some("@ALL")
+    );
+    assert_eq!(decode_callsign(&[0; 6]), None);
+    assert_eq!(decode_callsign(&[0xee, 0x6b, 0x28, 0, 0, 0]), None);
+
+    assert_eq!(encode_callsign(""), Err(CallsignError::Empty));
+    assert_eq!(
+        encode_callsign("IU2KWO-123"),
+        Err(CallsignError::TooLong(10))
+    );
+    assert_eq!(
+        encode_callsign("IU2 KWO"),
+        Err(CallsignError::Character(' '))
+    );
+    assert_eq!(
+        encode_callsign("N0CALL_"),
+        Err(CallsignError::Character('_'))
+    );
+}
+
+#[test]
+fn contacts_are_checked() {
+    let contacts = vec![
+        Contact::m17(String::from("Club"), "IR2UFV").unwrap(),
+        Contact::m17(String::new(), "N0CALL").unwrap(),
+        Contact::dmr(String::from("Worldwide"), 91, CallType::Group),
+    ];
+    assert_eq!(check(&contacts), vec![ContactIssue::Name { contact: 1 }]);
+}
+
+#[test]
+fn csv_contacts_survive_export_and_import() {
+    let contacts = vec![
+        Contact::m17(String::from("Club, repeater"), "IR2UFV").unwrap(),
+        Contact::dmr(String::from("Worldwide"), 91, CallType::Group),
+        Contact::m17(String::from("Portable"), "IU2KWO/P").unwrap(),
+    ];
+    let mut csv = vec![];
+    let skipped = contacts::export(&contacts, &mut csv, Format::Csv).unwrap();
+    assert_eq!(skipped, ["Worldwide"]);
+    let text = String::from_utf8(csv.clone()).unwrap();
+    assert!(text.starts_with("Name,Callsign"));
+    assert!(text.contains("\"Club, repeater\",IR2UFV"));
+
+    let import = contacts::import(csv.as_slice(), Format::Csv).unwrap();
+    assert_eq!(
+        import.contacts,
+        vec![contacts[0].clone(), contacts[2].clone()]
+    );
+    assert!(import.skipped.is_empty());
+}
+
+#[test]
+fn invalid_callsigns_are_skipped_on_import() {
+    let csv = "Callsign,Name\nn0call,Lowercase\nTOO-LONG-CALL,Long\n";
+    let import = contacts::import(csv.as_bytes(), Format::Csv).unwrap();
+    assert_eq!(import.contacts.len(), 1);
+    assert_eq!(import.contacts[0].m17_callsign().as_deref(), Some("N0CALL"));
+    assert_eq!(import.skipped.len(), 1);
+    assert_eq!(import.skipped[0].name, "Long");
+    assert_eq!(import.skipped[0].error, CallsignError::TooLong(13));
+
+    assert!(contacts::import("Name,Call\n".as_bytes(), Format::Csv).is_err());
+}
+
+#[test]
+fn vcard_contacts_survive_export_and_import() {
+    let contacts = vec![
+        Contact::m17(String::from("Club; repeater"), "IR2UFV").unwrap(),
+        Contact::m17(String::from("Portable"), "IU2KWO/P").unwrap(),
+    ];
+    let mut vcard = vec![];
+    contacts::export(&contacts, &mut vcard, Format::VCard).unwrap();
+    let import = contacts::import(vcard.as_slice(), Format::VCard).unwrap();
+    assert_eq!(import.contacts, contacts);
+
+    // Folded lines and property parameters from other applications
+    let vcard = "BEGIN:VCARD\r\nVERSION:4.0\r\nFN;CHARSET=UTF-8:Mario\r\n  Rossi\r\n\
+                 item1.X-M17-CALLSIGN:IK2ABC\r\nEND:VCARD\r\n\
+                 BEGIN:VCARD\r\nFN:No callsign\r\nEND:VCARD\r\n";
+    let import = contacts::import(vcard.as_bytes(), Format::VCard).unwrap();
+    assert_eq!(import.contacts.len(), 1);
+    assert_eq!(import.contacts[0].name, "Mario Rossi");
+    assert_eq!(import.contacts[0].m17_callsign().as_deref(), Some("IK2ABC"));
+    assert_eq!(import.skipped[0].error, CallsignError::Empty);
+}
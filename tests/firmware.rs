@@ -0,0 +1,151 @@
+//! Firmware image inspection
+
+use openrtx_companion::core::firmware::ImageFormat;
+use openrtx_companion::core::{FirmwareInfo, ImageIssue, RadioHW};
+
+// Plain STM32 image with the given reset handler
+fn stm32_image(reset: u32, size: usize, openrtx: bool) -> Vec<u8> {
+    let mut data = 0x2002_0000u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&reset.to_le_bytes());
+    if openrtx {
+        data.extend_from_slice(b"OpenRTX v0.3.6");
+    }
+    data.resize(size, 0xff);
+    data
+}
+
+// ESP-IDF image with a single segment of `len` bytes
+fn esp_image(chip_id: u16, len: u32) -> Vec<u8> {
+    let mut data = vec![0xe9, 1, 0x02, 0x4f];
+    data.extend_from_slice(&0x4037_5a2cu32.to_le_bytes());
+    data.extend_from_slice(&[0xee, 0, 0, 0]);
+    data.extend_from_slice(&chip_id.to_le_bytes());
+    data.resize(24, 0);
+    data.extend_from_slice(&0x3c00_0020u32.to_le_bytes());
+    data.extend_from_slice(&len.to_le_bytes());
+    let mut segment = b"OpenRTX".to_vec();
+    segment.resize(len as usize, 0);
+    data.extend_from_slice(&segment);
+    data
+}
+
+fn tyt_image(payload: usize) -> Vec<u8> {
+    let mut data = b"OutSecurityBin".to_vec();
+    data.resize(0x100 + payload, 0x5a);
+    data
+}
+
+#[test]
+fn stm32_images_are_checked() {
+    let info = FirmwareInfo::parse(&stm32_image(0x0800_c1a1, 64 * 1024, true));
+    assert_eq!(
+        info.format,
+        ImageFormat::Stm32 {
+            stack: 0x2002_0000,
+            reset: 0x0800_c1a1
+        }
+    );
+    assert!(info.openrtx && !info.truncated);
+    assert!(info.check(RadioHW::Md3x0).is_empty());
+    assert!(info.check(RadioHW::Mduv3x0).is_empty());
+    assert_eq!(
+        info.check(RadioHW::Twrplus),
+        [ImageIssue::WrongFormat(RadioHW::Twrplus)]
+    );
+}
+
+#[test]
+fn stm32_images_overwriting_the_bootloader_are_blocked() {
+    let info = FirmwareInfo::parse(&stm32_image(0x0800_0101, 64 * 1024, true));
+    let issues = info.check(RadioHW::Md3x0);
+    assert_eq!(issues, [ImageIssue::WrongLoadAddress(0x0800_0101)]);
+    assert!(issues[0].is_blocking());
+}
+
+#[test]
+fn oversized_images_are_blocked() {
+    let info = FirmwareInfo::parse(&stm32_image(0x0800_c1a1, 1024 * 1024, true));
+    assert_eq!(
+        info.check(RadioHW::Md3x0),
+        [ImageIssue::TooLarge {
+            size: 1024 * 1024,
+            max: 976 * 1024
+        }]
+    );
+}
+
+#[test]
+fn vendor_images_are_reported_without_blocking() {
+    let info = FirmwareInfo::parse(&stm32_image(0x0800_c1a1, 64 * 1024, false));
+    assert!(!info.openrtx);
+    let issues = info.check(RadioHW::Md3x0);
+    assert_eq!(issues, [ImageIssue::VendorImage]);
+    assert!(!issues[0].is_blocking());
+}
+
+#[test]
+fn tyt_wrapped_images_are_checked() {
+    let info = FirmwareInfo::parse(&tyt_image(0x1000));
+    assert_eq!(info.format, ImageFormat::TytWrapped);
+    // The encrypted content can not tell whether it is OpenRTX
+    assert!(info.check(RadioHW::Md3x0).is_empty());
+    assert_eq!(
+        info.check(RadioHW::Twrplus),
+        [ImageIssue::WrongFormat(RadioHW::Twrplus)]
+    );
+
+    let truncated = FirmwareInfo::parse(&tyt_image(0));
+    assert!(truncated.truncated);
+    assert_eq!(truncated.check(RadioHW::Md3x0), [ImageIssue::Truncated]);
+}
+
+#[test]
+fn esp32_images_are_checked() {
+    let info = FirmwareInfo::parse(&esp_image(0x0009, 0x100));
+    assert_eq!(
+        info.format,
+        ImageFormat::Esp32 {
+            chip_id: 0x0009,
+            segments: 1
+        }
+    );
+    assert!(info.openrtx && !info.truncated);
+    assert!(info.check(RadioHW::Twrplus).is_empty());
+    assert_eq!(
+        info.check(RadioHW::Md3x0),
+        [ImageIssue::WrongFormat(RadioHW::Md3x0)]
+    );
+
+    let other_chip = FirmwareInfo::parse(&esp_image(0x0000, 0x100));
+    assert_eq!(
+        other_chip.check(RadioHW::Twrplus),
+        [ImageIssue::WrongChip(0x0000)]
+    );
+}
+
+#[test]
+fn truncated_esp32_images_are_blocked() {
+    let mut data = esp_image(0x0009, 0x100);
+    data.truncate(data.len() - 1);
+    let info = FirmwareInfo::parse(&data);
+    assert!(info.truncated);
+    let issues = info.check(RadioHW::Twrplus);
+    assert_eq!(issues, [ImageIssue::Truncated]);
+    assert!(issues[0].is_blocking());
+
+    // Cut in the middle of the segment header
+    let info = FirmwareInfo::parse(&esp_image(0x0009, 0x100)[..28]);
+    assert!(info.truncated);
+}
+
+#[test]
+fn unknown_images_are_reported() {
+    for data in [&b"\x7fELF\x02\x01\x01"[..], b"", b"\x00\x00\x02\x20\xa1"] {
+        let info = FirmwareInfo::parse(data);
+        assert_eq!(info.format, ImageFormat::Unknown);
+        assert_eq!(
+            info.check(RadioHW::Md3x0),
+            [ImageIssue::UnknownFormat, ImageIssue::VendorImage]
+        );
+    }
+}
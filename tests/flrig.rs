@@ -0,0 +1,65 @@
+//! flrig XML-RPC call parsing and responses
+
+use openrtx_companion::core::flrig::{parse_call, response, smeter, Call, Value};
+
+#[test]
+fn calls_are_parsed() {
+    let xml = "<?xml version=\"1.0\"?>\r\n<methodCall>\
+               <methodName>rig.set_vfo</methodName>\
+               <params><param><value><double>145500000.5</double></value></param></params>\
+               </methodCall>";
+    assert_eq!(
+        parse_call(xml),
+        Some(Call {
+            method: String::from("rig.set_vfo"),
+            params: vec![Value::Double(145_500_000.5)],
+        })
+    );
+
+    let xml = "<methodCall><methodName>rig.set_mode</methodName><params>\
+               <param><value>A &amp; B</value></param>\
+               <param><value><int>1</int></value></param>\
+               <param><value><string/></value></param>\
+               </params></methodCall>";
+    let call = parse_call(xml).unwrap();
+    assert_eq!(
+        call.params,
+        vec![
+            Value::String(String::from("A & B")),
+            Value::Int(1),
+            Value::String(String::new()),
+        ]
+    );
+    assert_eq!(call.to_string(), "rig.set_mode(\"A & B\", 1, \"\")");
+
+    let call = parse_call("<methodCall><methodName>rig.get_vfo</methodName></methodCall>");
+    assert_eq!(call.unwrap().params, vec![]);
+    assert_eq!(parse_call("<methodCall></methodCall>"), None);
+}
+
+#[test]
+fn responses_carry_values_or_faults() {
+    let xml = response(&Ok(Value::Array(vec![
+        Value::String(String::from("FM")),
+        Value::Int(1),
+    ])));
+    assert!(xml.contains(
+        "<params><param><value><array><data>\
+         <value>FM</value><value><i4>1</i4></value>\
+         </data></array></value></param></params>"
+    ));
+
+    let xml = response(&Err(String::from("Unknown method <rig.tune>")));
+    assert!(xml.contains("<name>faultCode</name><value><i4>-1</i4></value>"));
+    assert!(xml.contains("<value>Unknown method &lt;rig.tune&gt;</value>"));
+}
+
+#[test]
+fn smeter_has_s9_at_half_scale() {
+    assert_eq!(smeter(-140), 0);
+    assert_eq!(smeter(-127), 0);
+    assert_eq!(smeter(-73), 50);
+    assert_eq!(smeter(-43), 75);
+    assert_eq!(smeter(-13), 100);
+    assert_eq!(smeter(0), 100);
+}
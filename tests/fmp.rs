@@ -0,0 +1,25 @@
+//! FMP frame encoding
+
+use openrtx_companion::core::fmp::{FmpFrame, Opcode};
+use openrtx_companion::core::LinkError;
+
+#[test]
+fn frames_are_encoded_and_decoded() {
+    let frame = FmpFrame::new(Opcode::Move, vec![b"/a".to_vec(), b"/b/c".to_vec()]);
+    let data = frame.encode().unwrap();
+    assert_eq!(data, b"\x07\x00\x02\x02/a\x04/b/c");
+    assert_eq!(FmpFrame::decode(&data), Some(frame));
+
+    // Parameters of the largest size still fit
+    let frame = FmpFrame::new(Opcode::Write, vec![vec![0x55; 255]; 255]);
+    assert_eq!(FmpFrame::decode(&frame.encode().unwrap()), Some(frame));
+}
+
+#[test]
+fn oversized_frames_are_rejected() {
+    let long_path = FmpFrame::new(Opcode::Read, vec![vec![b'a'; 256]]);
+    assert!(matches!(long_path.encode(), Err(LinkError::Protocol(_))));
+
+    let many_params = FmpFrame::new(Opcode::List, vec![vec![]; 256]);
+    assert!(matches!(many_params.encode(), Err(LinkError::Protocol(_))));
+}
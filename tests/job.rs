@@ -0,0 +1,64 @@
+//! Job cancellation and state tracking
+
+use openrtx_companion::core::{BackupJob, CancelToken, JobEvent, JobState};
+
+#[test]
+fn cancel_token_stops_the_next_step() {
+    let cancel = CancelToken::new();
+    assert!(cancel.is_interruptible());
+    assert!(!cancel.is_cancelled());
+
+    cancel.cancel();
+    assert!(cancel.is_cancelled());
+    assert!(!cancel.is_interruptible());
+    // Steps are not started once cancelled
+    assert_eq!(cancel.uninterruptible(|| 1), None);
+}
+
+#[test]
+fn cancel_is_deferred_during_uninterruptible_steps() {
+    let cancel = CancelToken::new();
+    let res = cancel.uninterruptible(|| {
+        assert!(!cancel.is_interruptible());
+        cancel.cancel();
+        // The step goes on until it completes
+        assert!(!cancel.is_cancelled());
+        2
+    });
+    assert_eq!(res, Some(2));
+    assert!(cancel.is_cancelled());
+}
+
+#[test]
+fn uninterruptible_steps_can_follow_each_other() {
+    let cancel = CancelToken::new();
+    assert_eq!(cancel.uninterruptible(|| 1), Some(1));
+    assert!(cancel.is_interruptible());
+    assert_eq!(cancel.uninterruptible(|| 2), Some(2));
+    assert!(!cancel.is_cancelled());
+}
+
+#[test]
+fn failed_job_reports_its_error() {
+    let dest = std::env::temp_dir().join("openrtx-companion-missing-backup-folder");
+    let mut job = BackupJob::new(String::from("/dev/null"), dest).start();
+    assert_eq!(
+        *job.state(),
+        JobState::Running {
+            phase: None,
+            progress: None
+        }
+    );
+
+    let mut events = vec![];
+    while let Some(event) = job.recv() {
+        events.push(event);
+    }
+    assert!(matches!(events.first(), Some(JobEvent::Started)));
+    assert!(events.last().is_some_and(JobEvent::is_terminal));
+    assert!(matches!(job.state(), JobState::Failed(e) if e.ends_with("is not a folder")));
+    assert!(!job.is_running());
+    // Ended jobs can not be cancelled anymore
+    assert!(!job.cancel());
+    assert!(!job.can_cancel());
+}
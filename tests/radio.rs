@@ -0,0 +1,24 @@
+//! Identification of the radio models
+
+use openrtx_companion::core::RadioHW;
+
+#[test]
+fn radios_are_identified_by_their_name() {
+    assert_eq!(RadioHW::from_info("MD-3x0"), Some(RadioHW::Md3x0));
+    assert_eq!(RadioHW::from_info("TYT MD-380"), Some(RadioHW::Md3x0));
+    assert_eq!(RadioHW::from_info("MD-UV3x0"), Some(RadioHW::Mduv3x0));
+    assert_eq!(RadioHW::from_info("md-uv380"), Some(RadioHW::Mduv3x0));
+    assert_eq!(
+        RadioHW::from_info("LILYGO T-TWR Plus"),
+        Some(RadioHW::Twrplus)
+    );
+    assert_eq!(RadioHW::from_info("GD-77"), None);
+}
+
+#[test]
+fn radios_are_identified_by_a_unique_usb_id() {
+    assert_eq!(RadioHW::from_usb_id(0x303a, 0x1001), Some(RadioHW::Twrplus));
+    // The STM32 bootloader of the MD-3x0 family does not tell the model
+    assert_eq!(RadioHW::from_usb_id(0x0483, 0xdf11), None);
+    assert_eq!(RadioHW::from_usb_id(0x1234, 0x5678), None);
+}
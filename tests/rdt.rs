@@ -0,0 +1,139 @@
+//! Conversion of stock TYT codeplugs built from scratch
+
+use openrtx_companion::core::codeplug::{Bandwidth, Bank};
+use openrtx_companion::core::rdt::{self, RdtError, RdtWarning, RDT_HEADER};
+use openrtx_companion::core::RadioHW;
+
+fn utf16(name: &str) -> Vec<u8> {
+    name.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+}
+
+fn bcd(value: u32, digits: usize) -> Vec<u8> {
+    let mut bcd = 0u32;
+    for i in 0..digits {
+        bcd |= ((value / 10u32.pow(i as u32)) % 10) << (i * 4);
+    }
+    bcd.to_le_bytes()[..digits / 2].to_vec()
+}
+
+// Stock channel entry, tones in 0.1 Hz
+fn channel(name: &str, analog: bool, rx: u32, tx: u32, tone: Option<u16>) -> Vec<u8> {
+    let mut entry = vec![0u8; 64];
+    entry[0] = if analog { 0x09 } else { 0x02 };
+    entry[4] = 0x20;
+    entry[16..20].copy_from_slice(&bcd(rx / 10, 8));
+    entry[20..24].copy_from_slice(&bcd(tx / 10, 8));
+    entry[24..26].copy_from_slice(&[0xff, 0xff]);
+    let tone = tone.map(|t| bcd(t as u32, 4)).unwrap_or(vec![0xff, 0xff]);
+    entry[26..28].copy_from_slice(&tone);
+    let name = utf16(name);
+    entry[32..32 + name.len()].copy_from_slice(&name);
+    entry
+}
+
+// MD-380 codeplug with two analog channels, a digital one, a zone and a
+// contact
+fn md380_image() -> Vec<u8> {
+    let mut image = vec![0xff; 0x40000];
+    let channels = [
+        channel("Repeater", true, 438_500_000, 431_100_000, Some(885)),
+        channel("DMR TG 91", false, 439_000_000, 431_400_000, None),
+        channel("Simplex", true, 433_500_000, 433_500_000, None),
+    ];
+    for (i, entry) in channels.iter().enumerate() {
+        image[0x1ee00 + i * 64..0x1ee00 + (i + 1) * 64].copy_from_slice(entry);
+    }
+    let mut zone = vec![0u8; 64];
+    zone[..8].copy_from_slice(&utf16("Home"));
+    zone[32..38].copy_from_slice(&[3, 0, 2, 0, 1, 0]);
+    image[0x149e0..0x149e0 + 64].copy_from_slice(&zone);
+    let mut contact = vec![0u8; 36];
+    contact[..3].copy_from_slice(&[91, 0, 0]);
+    contact[3] = 0xc1;
+    contact[4..22].copy_from_slice(&utf16("Worldwide"));
+    image[0x5f80..0x5f80 + 36].copy_from_slice(&contact);
+    image
+}
+
+#[test]
+fn rdt_analog_channels_zones_and_contacts_are_converted() {
+    let mut rdt = vec![0u8; RDT_HEADER];
+    rdt.extend(md380_image());
+    rdt.extend([0u8; 16]);
+
+    let conversion = rdt::convert(&rdt, None).unwrap();
+    assert_eq!(conversion.radio, RadioHW::Md3x0);
+    let codeplug = conversion.codeplug;
+    assert_eq!(codeplug.channels.len(), 2);
+    let repeater = &codeplug.channels[0];
+    assert_eq!(repeater.name, "Repeater");
+    assert_eq!(repeater.rx_frequency, 438_500_000);
+    assert_eq!(repeater.tx_frequency, 431_100_000);
+    assert_eq!(repeater.tx_tone, Some(885));
+    assert_eq!(repeater.bandwidth, Bandwidth::B25);
+    assert_eq!(
+        codeplug.banks,
+        vec![Bank {
+            name: String::from("Home"),
+            channels: vec![1, 0],
+        }]
+    );
+    assert_eq!(codeplug.contacts.len(), 1);
+    assert_eq!(codeplug.contacts[0].name, "Worldwide");
+    assert_eq!(codeplug.contacts[0].dmr_id(), Some(91));
+    assert_eq!(
+        conversion.warnings,
+        vec![
+            RdtWarning::DigitalChannel {
+                number: 2,
+                name: String::from("DMR TG 91")
+            },
+            RdtWarning::ZoneMember {
+                zone: String::from("Home"),
+                number: 2
+            },
+        ]
+    );
+    assert!(codeplug.check().is_empty());
+}
+
+#[test]
+fn flash_images_need_their_radio() {
+    let mut image = md380_image();
+    image.resize(0x200000, 0xff);
+
+    assert!(matches!(
+        rdt::convert(&image, None),
+        Err(RdtError::UnknownImage(0x200000))
+    ));
+    let conversion = rdt::convert(&image, Some(RadioHW::Md3x0)).unwrap();
+    assert_eq!(conversion.codeplug.channels.len(), 2);
+    // The MD-UV380 keeps its channels in another area of the flash
+    let conversion = rdt::convert(&image, Some(RadioHW::Mduv3x0)).unwrap();
+    assert!(conversion.codeplug.channels.is_empty());
+    assert!(matches!(
+        rdt::convert(&image[..0x1000], Some(RadioHW::Md3x0)),
+        Err(RdtError::Truncated { .. })
+    ));
+}
+
+#[test]
+fn long_names_are_cut_on_a_character_boundary() {
+    // 16 UTF-16 characters, 48 bytes of UTF-8
+    let name = "中継局".repeat(5) + "中";
+    let mut image = md380_image();
+    let entry = channel(&name, true, 433_500_000, 433_500_000, None);
+    image[0x1ee00..0x1ee00 + 64].copy_from_slice(&entry);
+    let mut rdt = vec![0u8; RDT_HEADER];
+    rdt.extend(image);
+    rdt.extend([0u8; 16]);
+
+    let conversion = rdt::convert(&rdt, None).unwrap();
+    let short = "中継局".repeat(3) + "中";
+    assert_eq!(conversion.codeplug.channels[0].name, short);
+    assert!(conversion.warnings.contains(&RdtWarning::LongName {
+        name,
+        short: short.clone()
+    }));
+    assert!(conversion.codeplug.check().is_empty());
+}
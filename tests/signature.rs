@@ -0,0 +1,153 @@
+//! Minisign signatures of firmware images
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+
+use openrtx_companion::core::signature::signature_path;
+use openrtx_companion::core::{CancelToken, FlashError, FlashJob, Keyring, SignatureError, Signer};
+use rtxflash::target;
+
+use common::test_dir;
+
+const IMAGE: &[u8] = b"OpenRTX test firmware image\n";
+
+const TRUSTED_KEY: &str = "untrusted comment: minisign public key 8877665544332211\n\
+    RWQRIjNEVWZ3iMQz12r3udL3xWrux5dseXvHN99eN/Pz+quODcco/ZhF\n";
+
+const OTHER_KEY: &str = "untrusted comment: minisign public key 1122334455667788\n\
+    RWSId2ZVRDMiERIGydwaHjsgjlRTsVngtyQkhpQoCqFKAvoSwgX0N4Bt\n";
+
+// Signatures of IMAGE by the two keys
+const TRUSTED_SIGNATURE: &str = "untrusted comment: signature from minisign secret key\n\
+    RUQRIjNEVWZ3iK/hmdIQQFhKbr3JVJ5FNamJRgSGT8ZMzIVPh7lWAZ8XngqkRrDgJy0vgq3tA/srZz+MO1CEFGSOzWh/bLwiHAc=\n\
+    trusted comment: timestamp:1700000000\tfile:openrtx.bin\n\
+    VZsWK1LbLRADLlmruIqyZfxmZRW1qfFeNFJ/EgolVQMcAD11cPIKNG4JaRXfLOgAyWweUgCMqh4nwdfl0zITCg==\n";
+
+const OTHER_SIGNATURE: &str = "untrusted comment: signature from minisign secret key\n\
+    RUSId2ZVRDMiEcvg+vjflvO93dApjZDXm1wX/+1MAglY/mUQs0M1X7TnvAwX/fa9n1jSC8mjqK28IHjiBskGLT+aDT4oZZR0LQA=\n\
+    trusted comment: timestamp:1700000000\tfile:openrtx.bin\n\
+    7FzX2TkQhC6Ps6PvKxfK7Cxy+n2s3VBrNZ4TIdrs2LJ0Clf9EyDkhUv/2FTz49OiGrYanfkm/yuRWK1bbTWfBw==\n";
+
+// Keyring folder trusting a single key, named "openrtx"
+fn keyring_dir(name: &str) -> PathBuf {
+    let dir = test_dir(name).join("keys");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("openrtx.pub"), TRUSTED_KEY).unwrap();
+    std::fs::write(dir.join("README.txt"), "Not a key").unwrap();
+    dir
+}
+
+fn signed_image(dir: &Path, data: &[u8], signature: Option<&str>) -> PathBuf {
+    let image = dir.join("openrtx.bin");
+    std::fs::write(&image, data).unwrap();
+    if let Some(signature) = signature {
+        std::fs::write(signature_path(&image), signature).unwrap();
+    }
+    image
+}
+
+#[test]
+fn keyring_loads_public_keys() {
+    assert!(!Keyring::load(&keyring_dir("keyring")).unwrap().is_empty());
+    // A missing folder trusts no key
+    let missing = test_dir("keyring-missing").join("keys");
+    assert!(Keyring::load(&missing).unwrap().is_empty());
+
+    let invalid = keyring_dir("keyring-invalid");
+    std::fs::write(invalid.join("broken.pub"), "not a key").unwrap();
+    assert!(matches!(
+        Keyring::load(&invalid),
+        Err(SignatureError::Invalid(_))
+    ));
+}
+
+#[test]
+fn valid_signature_names_the_signer() {
+    let dir = keyring_dir("signature-valid");
+    let keyring = Keyring::load(&dir).unwrap();
+    let image = signed_image(dir.parent().unwrap(), IMAGE, Some(TRUSTED_SIGNATURE));
+    assert_eq!(
+        keyring.verify(&image).unwrap(),
+        Signer {
+            key: String::from("openrtx"),
+            comment: String::from("timestamp:1700000000\tfile:openrtx.bin"),
+        }
+    );
+}
+
+#[test]
+fn modified_image_is_rejected() {
+    let dir = keyring_dir("signature-modified");
+    let keyring = Keyring::load(&dir).unwrap();
+    let image = signed_image(
+        dir.parent().unwrap(),
+        b"OpenRTX test firmware imagE\n",
+        Some(TRUSTED_SIGNATURE),
+    );
+    assert!(matches!(
+        keyring.verify(&image),
+        Err(SignatureError::Untrusted)
+    ));
+}
+
+#[test]
+fn malformed_signature_is_rejected() {
+    let dir = keyring_dir("signature-malformed");
+    let keyring = Keyring::load(&dir).unwrap();
+    let image = signed_image(dir.parent().unwrap(), IMAGE, Some("not a signature"));
+    assert!(matches!(
+        keyring.verify(&image),
+        Err(SignatureError::Invalid(_))
+    ));
+}
+
+#[test]
+fn signature_by_unknown_key_is_untrusted() {
+    let dir = keyring_dir("signature-unknown");
+    let keyring = Keyring::load(&dir).unwrap();
+    let image = signed_image(dir.parent().unwrap(), IMAGE, Some(OTHER_SIGNATURE));
+    assert!(matches!(
+        keyring.verify(&image),
+        Err(SignatureError::Untrusted)
+    ));
+
+    // The same signature is accepted once its key is trusted
+    std::fs::write(dir.join("other.pub"), OTHER_KEY).unwrap();
+    let keyring = Keyring::load(&dir).unwrap();
+    assert_eq!(keyring.verify(&image).unwrap().key, "other");
+}
+
+#[test]
+fn missing_signature_is_reported() {
+    let dir = keyring_dir("signature-missing");
+    let keyring = Keyring::load(&dir).unwrap();
+    let image = signed_image(dir.parent().unwrap(), IMAGE, None);
+    assert!(matches!(
+        keyring.verify(&image),
+        Err(SignatureError::Unsigned)
+    ));
+}
+
+#[test]
+fn strict_flash_refuses_unsigned_and_untrusted_images() {
+    let dir = keyring_dir("signature-strict");
+    std::env::set_var("OPENRTX_TRUSTED_KEYS", &dir);
+    let flash = |signature: Option<&str>| {
+        let image = signed_image(dir.parent().unwrap(), IMAGE, signature);
+        let target = target::get_targets().into_iter().next().unwrap();
+        let (events, _events_rx) = channel();
+        FlashJob::new(target, String::from("/dev/null"), image)
+            .strict(true)
+            .run(&events, &CancelToken::new())
+    };
+    assert!(matches!(
+        flash(None),
+        Err(FlashError::Signature(SignatureError::Unsigned))
+    ));
+    assert!(matches!(
+        flash(Some(OTHER_SIGNATURE)),
+        Err(FlashError::Signature(SignatureError::Untrusted))
+    ));
+}
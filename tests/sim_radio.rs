@@ -0,0 +1,1055 @@
+//! End-to-end tests of the backup and restore workflows against a
+//! simulated radio
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::channel;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use openrtx_companion::core::calibration::{CalibrationIssue, MEMORY_NAME as CALIBRATION};
+use openrtx_companion::core::cat::CatId;
+use openrtx_companion::core::codeplug::{
+    Bank, Channel, ChannelIssue, Contact, OpMode, CODEPLUG_PATH,
+};
+use openrtx_companion::core::contacts::ContactIssue;
+use openrtx_companion::core::flash::verify_firmware;
+use openrtx_companion::core::fmp::{self, DAT_BLOCK_SIZE};
+use openrtx_companion::core::remote::{
+    in_band, radio_bands, step_frequency, PanelCommand, PanelState, RemoteSession, Setting,
+};
+use openrtx_companion::core::rigctl::MAX_LINE_SIZE;
+use openrtx_companion::core::telemetry;
+use openrtx_companion::core::{
+    files::{self, Entry},
+    get_ports, is_port_busy, BackupError, BackupJob, BackupManifest, Calibration, CalibrationError,
+    CalibrationReadJob, CalibrationWriteJob, CancelToken, Codeplug, CodeplugError, CodeplugReadJob,
+    CodeplugWriteJob, ContactsWriteJob, FileError, FlashError, JobEvent, JobState, LinkError,
+    Phase, RadioHW, RadioLink, RadioStatus, RestoreJob, RigProtocol, RigServer, TelemetryRecorder,
+    Transfer, TransferJob,
+};
+use openrtx_companion::sim::{Memory, VirtualRadio};
+
+use common::test_dir;
+
+const MEMORY_NAME: &str = "External flash";
+
+// rtxlink keeps a single link open, tests using it can not run in parallel
+static LINK: Mutex<()> = Mutex::new(());
+
+fn test_image(seed: u8) -> Vec<u8> {
+    (0..256 * 1024).map(|i| (i % 251) as u8 ^ seed).collect()
+}
+
+// Calibration memory of an MD-3x0 with sane values
+fn test_calibration() -> Calibration {
+    let mut calibration = Calibration::decode(RadioHW::Md3x0, &[0xff; 0x200]).unwrap();
+    let band = &mut calibration.bands[0];
+    band.rx_frequencies = (0..9).map(|i| 400_000_000 + i * 10_000_000).collect();
+    band.tx_frequencies = band.rx_frequencies.clone();
+    for table in &mut band.tables {
+        table.values = vec![0x80; 9];
+    }
+    Calibration::decode(RadioHW::Md3x0, &calibration.encode()).unwrap()
+}
+
+fn test_codeplug() -> Codeplug {
+    let mut codeplug = Codeplug::default();
+    for (name, mode, frequency) in [
+        ("Repeater", OpMode::Fm, 145_600_000),
+        ("M17 simplex", OpMode::M17, 433_475_000),
+        ("Calling", OpMode::Fm, 145_500_000),
+    ] {
+        let mut channel = Channel::new(String::from(name));
+        channel.mode = mode;
+        channel.rx_frequency = frequency;
+        channel.tx_frequency = frequency;
+        codeplug.channels.push(channel);
+    }
+    codeplug.channels[0].tx_frequency = 145_000_000;
+    codeplug.channels[0].tx_tone = Some(885);
+    codeplug.channels[1].rx_can = 5;
+    codeplug.channels[1].tx_can = 5;
+    codeplug.banks.push(Bank {
+        name: String::from("VHF"),
+        channels: vec![0, 2],
+    });
+    Codeplug::decode(&codeplug.encode()).unwrap()
+}
+
+#[test]
+fn virtual_radio_is_listed() {
+    let radio = VirtualRadio::new("MD-3x0", vec![]).unwrap();
+    let port = String::from(radio.port());
+    assert!(get_ports().iter().any(|p| p.name == port));
+    drop(radio);
+    assert!(!get_ports().iter().any(|p| p.name == port));
+}
+
+#[test]
+fn virtual_radio_frames_follow_rtxlink() {
+    let _guard = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-3x0", vec![]).unwrap();
+    // The value needs SLIP escaping
+    radio.set_cat(CatId::RxFreq, vec![0xc0, 0xdb, 0x12, 0x34]);
+    let mut port = serialport::new(radio.port(), 115_200)
+        .timeout(Duration::from_secs(5))
+        .open()
+        .unwrap();
+    // CAT GET "RF", CRC-16/CCITT-FALSE 0x2a96
+    port.write_all(&[0xc0, 0x01, 0x47, 0x52, 0x46, 0x2a, 0x96, 0xc0])
+        .unwrap();
+    // CAT DATA c0 db 12 34, CRC-16/CCITT-FALSE 0xe6d9
+    let expected = [
+        0xc0, 0x01, 0x44, 0xdb, 0xdc, 0xdb, 0xdd, 0x12, 0x34, 0xe6, 0xd9, 0xc0,
+    ];
+    let mut reply = vec![0; expected.len()];
+    port.read_exact(&mut reply).unwrap();
+    assert_eq!(reply, expected);
+    // A corrupted frame is dropped without an answer
+    port.write_all(&[0xc0, 0x01, 0x47, 0x52, 0x46, 0x2a, 0x97, 0xc0])
+        .unwrap();
+    port.set_timeout(Duration::from_millis(200)).unwrap();
+    let mut byte = [0];
+    assert!(port.read(&mut byte).is_err());
+}
+
+#[test]
+fn backup_reads_radio_memory() {
+    let _link = LINK.lock().unwrap();
+    let image = test_image(0x00);
+    let radio = VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, image.clone())]).unwrap();
+    let dir = test_dir("backup");
+
+    let (events, _events_rx) = channel();
+    BackupJob::new(String::from(radio.port()), &dir)
+        .run(&events, &CancelToken::new())
+        .unwrap();
+
+    let saved = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|f| std::fs::read(f.unwrap().path()).unwrap())
+        .any(|data| data == image);
+    assert!(saved);
+}
+
+#[test]
+fn backup_manifest_records_the_firmware_version() {
+    let _link = LINK.lock().unwrap();
+    let radio =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, test_image(0x00))]).unwrap();
+    radio.set_cat(CatId::Version, b"v0.3.6-12-g1a2b3c4\0".to_vec());
+    let (events, _events_rx) = channel();
+    let files = BackupJob::new(String::from(radio.port()), test_dir("backup-version"))
+        .run(&events, &CancelToken::new())
+        .unwrap();
+
+    let manifest = BackupManifest::read(&files[0]).unwrap().unwrap();
+    assert_eq!(manifest.info, "MD-3x0");
+    assert_eq!(
+        manifest.firmware_version.as_deref(),
+        Some("v0.3.6-12-g1a2b3c4")
+    );
+}
+
+#[test]
+fn restore_writes_radio_memory() {
+    let _link = LINK.lock().unwrap();
+    let radio =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, test_image(0x00))]).unwrap();
+    let image = test_image(0x5a);
+    let path = test_dir("restore").join("backup.bin");
+    std::fs::write(&path, &image).unwrap();
+
+    let (events, _events_rx) = channel();
+    let radio_hw = RestoreJob::new(String::from(radio.port()), &path)
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    assert_eq!(radio_hw, RadioHW::Md3x0);
+    assert_eq!(radio.memory(MEMORY_NAME).unwrap(), image);
+}
+
+#[test]
+fn backup_job_state_follows_its_events() {
+    let _link = LINK.lock().unwrap();
+    let image = test_image(0x00);
+    let radio = VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, image.clone())]).unwrap();
+    let dir = test_dir("backup-state");
+
+    let mut job = BackupJob::new(String::from(radio.port()), &dir).start();
+    let mut phases = vec![];
+    let mut transferred = 0;
+    while job.recv().is_some() {
+        match job.state() {
+            JobState::Running {
+                phase: Some(phase),
+                progress: None,
+            } => phases.push(*phase),
+            JobState::Running {
+                progress: Some(p), ..
+            } => {
+                assert!(p.transferred > transferred && p.total == image.len());
+                transferred = p.transferred;
+            }
+            _ => (),
+        }
+    }
+    assert_eq!(phases, [Phase::Connecting, Phase::Reading]);
+    assert_eq!(transferred, image.len());
+    assert!(matches!(job.state(), JobState::Completed(files) if files.len() == 1));
+}
+
+#[test]
+fn backup_job_is_cancelled_during_transfer() {
+    let _link = LINK.lock().unwrap();
+    let radio =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, test_image(0x00))]).unwrap();
+    let dir = test_dir("backup-cancel");
+
+    let mut job = BackupJob::new(String::from(radio.port()), &dir).start();
+    while let Some(event) = job.recv() {
+        if matches!(event, JobEvent::Progress(_)) && job.can_cancel() {
+            assert!(job.cancel());
+        }
+    }
+    assert_eq!(*job.state(), JobState::Cancelled);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+}
+
+#[test]
+fn transfers_abort_when_cancelled() {
+    let _link = LINK.lock().unwrap();
+    let image = test_image(0x00);
+    let radio = VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, image.clone())]).unwrap();
+    let mut link = RadioLink::open(radio.port()).unwrap();
+
+    let cancel = CancelToken::new();
+    let res = fmp::dump(&mut link, 0, &cancel, |p| {
+        if p.transferred >= image.len() / 2 {
+            cancel.cancel();
+        }
+    });
+    assert!(matches!(res, Err(LinkError::Cancelled)));
+    // The radio stopped sending and accepts new transfers
+    let data = fmp::dump(&mut link, 0, &CancelToken::new(), |_| ()).unwrap();
+    assert_eq!(data, image);
+}
+
+#[test]
+fn transfers_number_blocks_past_255() {
+    let _link = LINK.lock().unwrap();
+    let image: Vec<u8> = (0..300 * DAT_BLOCK_SIZE).map(|i| (i % 241) as u8).collect();
+    let radio = VirtualRadio::new(
+        "MD-3x0",
+        vec![Memory::new(MEMORY_NAME, vec![0; image.len()])],
+    )
+    .unwrap();
+    let mut link = RadioLink::open(radio.port()).unwrap();
+
+    fmp::flash(&mut link, 0, &image, |_| ()).unwrap();
+    let data = fmp::dump(&mut link, 0, &CancelToken::new(), |_| ()).unwrap();
+    assert_eq!(data, image);
+}
+
+#[test]
+fn uninterruptible_transfers_complete_before_cancelling() {
+    let _link = LINK.lock().unwrap();
+    let radio =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, test_image(0x00))]).unwrap();
+    let image = test_image(0x5a);
+    let mut link = RadioLink::open(radio.port()).unwrap();
+
+    let cancel = CancelToken::new();
+    let res = cancel.uninterruptible(|| {
+        fmp::flash(&mut link, 0, &image, |p| {
+            if p.transferred == DAT_BLOCK_SIZE {
+                cancel.cancel();
+            }
+        })
+    });
+    assert!(matches!(res, Some(Ok(()))));
+    assert_eq!(radio.memory(MEMORY_NAME).unwrap(), image);
+    // The deferred cancellation stops the next step
+    assert!(cancel.is_cancelled());
+    let res = fmp::dump(&mut link, 0, &cancel, |_| ());
+    assert!(matches!(res, Err(LinkError::Cancelled)));
+}
+
+#[test]
+fn flashed_firmware_version_is_checked() {
+    let _link = LINK.lock().unwrap();
+    let mut image = test_image(0x00)[..100_000].to_vec();
+    image[4096..4096 + 18].copy_from_slice(b"v0.3.6-12-g1a2b3c4");
+    let radio = VirtualRadio::new("MD-3x0", vec![]).unwrap();
+    let mut link = RadioLink::open(radio.port()).unwrap();
+
+    // Firmwares which do not report their version are not verified
+    assert_eq!(verify_firmware(&mut link, &image).unwrap(), None);
+
+    radio.set_cat(CatId::Version, b"v0.3.6-12-g1a2b3c4\0".to_vec());
+    assert_eq!(
+        verify_firmware(&mut link, &image).unwrap().as_deref(),
+        Some("v0.3.6-12-g1a2b3c4")
+    );
+
+    radio.set_cat(CatId::Version, b"v0.3.5\0".to_vec());
+    let res = verify_firmware(&mut link, &image);
+    assert!(matches!(res, Err(FlashError::VerifyFailed(v)) if v == "v0.3.5"));
+}
+
+#[test]
+fn restore_rejects_mismatched_image() {
+    let _link = LINK.lock().unwrap();
+    let original = test_image(0x00);
+    let radio =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, original.clone())]).unwrap();
+    let path = test_dir("restore-mismatch").join("backup.bin");
+    std::fs::write(&path, [0u8; 1024]).unwrap();
+
+    let (events, _events_rx) = channel();
+    let res = RestoreJob::new(String::from(radio.port()), &path).run(&events, &CancelToken::new());
+    assert!(matches!(res, Err(BackupError::ImageSizeMismatch { .. })));
+    assert_eq!(radio.memory(MEMORY_NAME).unwrap(), original);
+}
+
+#[test]
+fn restore_writes_the_memory_named_in_the_manifest() {
+    let _link = LINK.lock().unwrap();
+    let dir = test_dir("restore-named-memory");
+    let radio = VirtualRadio::new(
+        "MD-3x0",
+        vec![
+            Memory::new(MEMORY_NAME, test_image(0x00)),
+            Memory::new("Internal flash", test_image(0x11)),
+        ],
+    )
+    .unwrap();
+    let (events, _events_rx) = channel();
+    let files = BackupJob::new(String::from(radio.port()), &dir)
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    drop(radio);
+
+    // Both memories have the size of the image, only the named one is written
+    let radio = VirtualRadio::new(
+        "MD-3x0",
+        vec![
+            Memory::new(MEMORY_NAME, test_image(0x22)),
+            Memory::new("Internal flash", test_image(0x33)),
+        ],
+    )
+    .unwrap();
+    let (events, _events_rx) = channel();
+    RestoreJob::new(String::from(radio.port()), &files[1])
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    assert_eq!(radio.memory(MEMORY_NAME).unwrap(), test_image(0x22));
+    assert_eq!(radio.memory("Internal flash").unwrap(), test_image(0x11));
+    drop(radio);
+
+    let radio =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, test_image(0x22))]).unwrap();
+    let (events, _events_rx) = channel();
+    let res =
+        RestoreJob::new(String::from(radio.port()), &files[1]).run(&events, &CancelToken::new());
+    assert!(matches!(res, Err(BackupError::MissingMemory { .. })));
+    assert_eq!(radio.memory(MEMORY_NAME).unwrap(), test_image(0x22));
+}
+
+#[test]
+fn restore_rejects_image_from_other_radio() {
+    let _link = LINK.lock().unwrap();
+    let dir = test_dir("restore-other-radio");
+    let md3x0 =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, test_image(0x00))]).unwrap();
+    let (events, _events_rx) = channel();
+    let files = BackupJob::new(String::from(md3x0.port()), &dir)
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    drop(md3x0);
+
+    let original = test_image(0x5a);
+    let mduv3x0 =
+        VirtualRadio::new("MD-UV3x0", vec![Memory::new(MEMORY_NAME, original.clone())]).unwrap();
+    let (events, _events_rx) = channel();
+    let res =
+        RestoreJob::new(String::from(mduv3x0.port()), &files[0]).run(&events, &CancelToken::new());
+    assert!(matches!(
+        res,
+        Err(BackupError::RadioMismatch {
+            image: RadioHW::Md3x0,
+            radio: RadioHW::Mduv3x0
+        })
+    ));
+    assert_eq!(mduv3x0.memory(MEMORY_NAME).unwrap(), original);
+}
+
+#[test]
+fn calibration_survives_file_round_trip() {
+    let _link = LINK.lock().unwrap();
+    let original = test_calibration();
+    let radio =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(CALIBRATION, original.encode())]).unwrap();
+    let path = test_dir("calibration").join("calibration.toml");
+
+    let (events, _events_rx) = channel();
+    let calibration = CalibrationReadJob::new(String::from(radio.port()))
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    assert_eq!(calibration, original);
+    calibration.write(&path).unwrap();
+
+    // Tables edited in the file are written to the radio
+    let mut edited = Calibration::read(&path).unwrap();
+    assert_eq!(edited, original);
+    edited.bands[0].tables[0].values[3] = 0x90;
+    edited.write(&path).unwrap();
+    let edited = Calibration::read(&path).unwrap();
+    let (events, _events_rx) = channel();
+    CalibrationWriteJob::new(String::from(radio.port()), edited.clone())
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    assert_eq!(radio.memory(CALIBRATION).unwrap(), edited.encode());
+    assert_ne!(edited.encode(), original.encode());
+}
+
+#[test]
+fn calibration_write_rejects_insane_values() {
+    let _link = LINK.lock().unwrap();
+    let original = test_calibration();
+    let radio =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(CALIBRATION, original.encode())]).unwrap();
+
+    let mut erased = original.clone();
+    erased.bands[0].tables[0].values[0] = 0xff;
+    let (events, _events_rx) = channel();
+    let res = CalibrationWriteJob::new(String::from(radio.port()), erased)
+        .run(&events, &CancelToken::new());
+    assert!(matches!(res, Err(CalibrationError::OutOfRange(issues)) if issues.len() == 1));
+    assert_eq!(radio.memory(CALIBRATION).unwrap(), original.encode());
+}
+
+#[test]
+fn calibration_tables_have_their_own_ranges() {
+    let mut calibration = test_calibration();
+    assert!(calibration.check().is_empty());
+    // A muted CTCSS tone and a squelch which never closes
+    calibration.bands[0].tables[7].values[2] = 0x00;
+    calibration.bands[0].tables[3].values[4] = 0x00;
+    // High power well above the tone deviation ranges, but valid for its table
+    calibration.bands[0].tables[0].values[0] = 0xf0;
+    let tables: Vec<_> = calibration
+        .check()
+        .into_iter()
+        .map(|issue| match issue {
+            CalibrationIssue::OutOfRange { table, value, .. } => (table, value),
+            issue => panic!("unexpected {issue}"),
+        })
+        .collect();
+    assert_eq!(tables, [("Open squelch 9", 0), ("CTCSS 67 Hz", 0)]);
+}
+
+#[test]
+fn calibration_write_refuses_another_unit() {
+    let _link = LINK.lock().unwrap();
+    let original = test_calibration();
+    let mut other = test_calibration();
+    other.bands[0].tables[2].values = vec![0x70; 9];
+    let other = Calibration::decode(RadioHW::Md3x0, &other.encode()).unwrap();
+    let radio =
+        VirtualRadio::new("MD-3x0", vec![Memory::new(CALIBRATION, other.encode())]).unwrap();
+
+    let (events, _events_rx) = channel();
+    let res = CalibrationWriteJob::new(String::from(radio.port()), original.clone())
+        .run(&events, &CancelToken::new());
+    assert!(matches!(res, Err(CalibrationError::OtherUnit)));
+    assert_eq!(radio.memory(CALIBRATION).unwrap(), other.encode());
+
+    // Written once explicitly allowed
+    let (events, _events_rx) = channel();
+    CalibrationWriteJob::new(String::from(radio.port()), original.clone())
+        .other_unit(true)
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    assert_eq!(radio.memory(CALIBRATION).unwrap(), original.encode());
+}
+
+#[test]
+fn radio_status_skips_unsupported_values() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    radio.set_cat(CatId::Version, b"v0.3.6-12-g1a2b3c4\0".to_vec());
+    radio.set_cat(CatId::RxFreq, 433_475_000u32.to_le_bytes().to_vec());
+    radio.set_cat(CatId::OpMode, vec![3]);
+    radio.set_cat(CatId::Battery, 7400u16.to_le_bytes().to_vec());
+
+    let status = RadioStatus::read(radio.port()).unwrap();
+    assert_eq!(status.radio(), Some(RadioHW::Mduv3x0));
+    assert_eq!(status.firmware.as_deref(), Some("v0.3.6-12-g1a2b3c4"));
+    assert_eq!(status.commit.as_deref(), Some("1a2b3c4"));
+    assert_eq!(status.rx_frequency, Some(433_475_000));
+    assert_eq!(status.mode, Some(OpMode::M17));
+    assert_eq!(status.battery, Some(7400));
+    assert_eq!(status.callsign, None);
+    assert_eq!(status.tx_frequency, None);
+}
+
+#[test]
+fn files_survive_upload_rename_and_download() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    let port = String::from(radio.port());
+    radio.set_file("/m17/settings.bin", vec![1, 2, 3]);
+    let dir = test_dir("files");
+    let local = dir.join("codeplug.rtxc");
+    let data = test_image(0x33);
+    std::fs::write(&local, &data).unwrap();
+    let (events, _) = channel();
+
+    let upload = Transfer::upload(&local, "/m17");
+    TransferJob::new(port.clone(), upload)
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    assert_eq!(radio.file("/m17/codeplug.rtxc"), Some(data.clone()));
+
+    files::rename(&port, "/m17/codeplug.rtxc", "/m17/backup.rtxc").unwrap();
+    files::remove(&port, "/m17/settings.bin").unwrap();
+    assert_eq!(
+        files::browse(&port, "/").unwrap(),
+        vec![Entry::Directory {
+            name: String::from("m17")
+        }]
+    );
+    assert_eq!(
+        files::browse(&port, "/m17").unwrap(),
+        vec![Entry::File {
+            name: String::from("backup.rtxc"),
+            size: data.len()
+        }]
+    );
+
+    let download = Transfer::Download {
+        remote: String::from("/m17/backup.rtxc"),
+        local: dir.join("downloaded.rtxc"),
+    };
+    TransferJob::new(port.clone(), download)
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    assert_eq!(std::fs::read(dir.join("downloaded.rtxc")).unwrap(), data);
+
+    let missing = Transfer::Download {
+        remote: String::from("/m17/settings.bin"),
+        local: dir.join("settings.bin"),
+    };
+    let res = TransferJob::new(port, missing).run(&events, &CancelToken::new());
+    assert!(matches!(res, Err(FileError::Link(_))));
+}
+
+#[test]
+fn files_fall_back_to_memory_areas() {
+    let _link = LINK.lock().unwrap();
+    let image = test_image(0x44);
+    let radio = VirtualRadio::new("MD-3x0", vec![Memory::new(MEMORY_NAME, image.clone())]).unwrap();
+    radio.disable_file_system();
+    let port = String::from(radio.port());
+
+    assert_eq!(
+        files::browse(&port, "/").unwrap(),
+        vec![Entry::Memory {
+            index: 0,
+            name: String::from(MEMORY_NAME),
+            size: image.len()
+        }]
+    );
+    let local = test_dir("memory").join("memory.bin");
+    let (events, _) = channel();
+    let dump = Transfer::DumpMemory {
+        memory: 0,
+        local: local.clone(),
+    };
+    TransferJob::new(port, dump)
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    assert_eq!(std::fs::read(local).unwrap(), image);
+}
+
+#[test]
+fn codeplug_edit_is_written_back() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    let port = String::from(radio.port());
+    radio.set_file(CODEPLUG_PATH, test_codeplug().encode());
+    let (events, _) = channel();
+
+    let mut codeplug = CodeplugReadJob::new(port.clone())
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    assert_eq!(codeplug, test_codeplug());
+    codeplug.remove_channel(1);
+    codeplug.channels[1].name = String::from("Simplex");
+    assert_eq!(codeplug.banks[0].channels, vec![0, 1]);
+
+    let (events, _) = channel();
+    CodeplugWriteJob::new(port, codeplug.clone())
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    let written = Codeplug::decode(&radio.file(CODEPLUG_PATH).unwrap()).unwrap();
+    assert_eq!(written.channels, codeplug.channels);
+    assert_eq!(written.banks, codeplug.banks);
+    assert_eq!(radio.file(&format!("{CODEPLUG_PATH}.new")), None);
+}
+
+#[test]
+fn codeplug_write_rejects_invalid_channels() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    let original = test_codeplug().encode();
+    radio.set_file(CODEPLUG_PATH, original.clone());
+    let mut codeplug = test_codeplug();
+    codeplug.channels[2].rx_frequency = 300_000_000;
+    codeplug.channels[1].tx_can = 16;
+
+    let (events, _) = channel();
+    let res = CodeplugWriteJob::new(String::from(radio.port()), codeplug)
+        .run(&events, &CancelToken::new());
+    let Err(CodeplugError::Invalid(issues)) = res else {
+        panic!("invalid codeplug written");
+    };
+    assert_eq!(
+        issues,
+        vec![
+            ChannelIssue::Can {
+                channel: 1,
+                can: 16
+            },
+            ChannelIssue::RxFrequency {
+                channel: 2,
+                frequency: 300_000_000
+            },
+        ]
+    );
+    assert_eq!(radio.file(CODEPLUG_PATH), Some(original));
+}
+
+#[test]
+fn contacts_are_written_without_changing_channels() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    let port = String::from(radio.port());
+    radio.set_file(CODEPLUG_PATH, test_codeplug().encode());
+    let contacts = vec![
+        Contact::m17(String::from("Club"), "IR2UFV").unwrap(),
+        Contact::m17(String::from("Portable"), "IU2KWO/P").unwrap(),
+    ];
+
+    let (events, _) = channel();
+    ContactsWriteJob::new(port.clone(), contacts.clone())
+        .run(&events, &CancelToken::new())
+        .unwrap();
+    let written = Codeplug::decode(&radio.file(CODEPLUG_PATH).unwrap()).unwrap();
+    assert_eq!(written.contacts, contacts);
+    assert_eq!(written.channels, test_codeplug().channels);
+
+    let (events, _) = channel();
+    let invalid = vec![Contact::m17(String::new(), "N0CALL").unwrap()];
+    let res = ContactsWriteJob::new(port, invalid).run(&events, &CancelToken::new());
+    let Err(CodeplugError::InvalidContacts(issues)) = res else {
+        panic!("invalid contacts written");
+    };
+    assert_eq!(issues, vec![ContactIssue::Name { contact: 0 }]);
+    let unchanged = Codeplug::decode(&radio.file(CODEPLUG_PATH).unwrap()).unwrap();
+    assert_eq!(unchanged.contacts, contacts);
+}
+
+#[test]
+fn codeplug_needs_file_access() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    let port = String::from(radio.port());
+
+    // A missing codeplug is not mistaken for missing file access
+    let (events, _) = channel();
+    let res = CodeplugReadJob::new(port.clone()).run(&events, &CancelToken::new());
+    assert!(matches!(
+        res,
+        Err(CodeplugError::Link(LinkError::Status(_)))
+    ));
+
+    radio.set_file(CODEPLUG_PATH, test_codeplug().encode());
+    radio.disable_file_system();
+    let (events, _) = channel();
+    let res = CodeplugReadJob::new(port.clone()).run(&events, &CancelToken::new());
+    assert!(matches!(res, Err(CodeplugError::NoFileAccess)));
+    let (events, _) = channel();
+    let res =
+        CodeplugWriteJob::new(port.clone(), test_codeplug()).run(&events, &CancelToken::new());
+    assert!(matches!(res, Err(CodeplugError::NoFileAccess)));
+    let (events, _) = channel();
+    let contacts = vec![Contact::m17(String::from("Club"), "IR2UFV").unwrap()];
+    let res = ContactsWriteJob::new(port, contacts).run(&events, &CancelToken::new());
+    assert!(matches!(res, Err(CodeplugError::NoFileAccess)));
+}
+
+#[test]
+fn rigctld_clients_control_the_radio() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    radio.set_cat(CatId::RxFreq, 145_500_000u32.to_le_bytes().to_vec());
+    radio.set_cat(CatId::TxFreq, 145_500_000u32.to_le_bytes().to_vec());
+    radio.set_cat(CatId::OpMode, vec![OpMode::Fm.into()]);
+    radio.set_cat(CatId::Ptt, vec![0]);
+    radio.set_cat(CatId::Rssi, (-93i16).to_le_bytes().to_vec());
+
+    let server = RigServer::start(radio.port(), RigProtocol::Rigctld, "127.0.0.1:0").unwrap();
+    let mut stream = TcpStream::connect(server.address()).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request = |command: &str, lines: usize| {
+        writeln!(stream, "{command}").unwrap();
+        (0..lines)
+            .map(|_| {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                String::from(line.trim_end())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(request("f", 1), ["145500000"]);
+    assert_eq!(request("F 433475000", 1), ["RPRT 0"]);
+    assert_eq!(request("\\get_freq", 1), ["433475000"]);
+    assert_eq!(
+        radio.cat(CatId::TxFreq),
+        Some(433_475_000u32.to_le_bytes().to_vec())
+    );
+    assert_eq!(request("m", 2), ["FM", "0"]);
+    // Hamlib has no M17 mode
+    assert_eq!(request("M PKTFM 0", 1), ["RPRT 0"]);
+    assert_eq!(radio.cat(CatId::OpMode), Some(vec![OpMode::M17.into()]));
+    assert_eq!(request("\\get_mode", 2), ["PKTFM", "0"]);
+    assert_eq!(request("M USB 0", 1), ["RPRT -1"]);
+    assert_eq!(request("t", 1), ["0"]);
+    assert_eq!(request("T 1", 1), ["RPRT 0"]);
+    assert_eq!(radio.cat(CatId::Ptt), Some(vec![1]));
+    assert_eq!(request("\\get_ptt", 1), ["1"]);
+    assert_eq!(request("T 0", 1), ["RPRT 0"]);
+    assert_eq!(radio.cat(CatId::Ptt), Some(vec![0]));
+    // Signal strength relative to S9, in dB
+    assert_eq!(request("l STRENGTH", 1), ["-20"]);
+    assert_eq!(request("l RFPOWER", 1), ["RPRT -1"]);
+    assert_eq!(request("_", 1), ["MD-UV3x0"]);
+    assert_eq!(request("\\set_powerstat 1", 1), ["RPRT -4"]);
+    let state = request("\\dump_state", 4);
+    assert_eq!(state[0], "1");
+    // FM and PKTFM are advertised on the RX ranges
+    assert_eq!(state[3].split_whitespace().nth(2), Some("0x1020"));
+
+    let status = server.status();
+    assert_eq!(status.clients, 1);
+    assert_eq!(status.requests, 16);
+    assert_eq!(
+        status
+            .last_requests
+            .last()
+            .map(|r| r.ends_with("\\dump_state -> 1")),
+        Some(true)
+    );
+
+    // Clients sending endless lines are dropped
+    let mut flood = TcpStream::connect(server.address()).unwrap();
+    flood.write_all(&[b'f'; MAX_LINE_SIZE]).unwrap();
+    let mut response = String::new();
+    // The server may reset the connection on unread data
+    _ = flood.read_to_string(&mut response);
+    assert_eq!(response, "RPRT -1\n");
+    server.stop();
+}
+
+#[test]
+fn flrig_clients_control_the_radio() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    radio.set_cat(CatId::RxFreq, 145_500_000u32.to_le_bytes().to_vec());
+    radio.set_cat(CatId::TxFreq, 145_500_000u32.to_le_bytes().to_vec());
+    radio.set_cat(CatId::OpMode, vec![OpMode::Fm.into()]);
+    radio.set_cat(CatId::Ptt, vec![0]);
+    radio.set_cat(CatId::Rssi, (-73i16).to_le_bytes().to_vec());
+
+    let server = RigServer::start(radio.port(), RigProtocol::Flrig, "127.0.0.1:0").unwrap();
+    let mut stream = TcpStream::connect(server.address()).unwrap();
+    let mut call = |method: &str, params: &str| {
+        let body = format!(
+            "<?xml version=\"1.0\"?><methodCall><methodName>{method}</methodName>\
+             <params>{params}</params></methodCall>"
+        );
+        write!(
+            stream,
+            "POST /RPC2 HTTP/1.1\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        // Read the header, then the body of the response
+        let mut response = vec![];
+        let mut byte = [0];
+        while !response.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+        let header = String::from_utf8(response).unwrap();
+        assert!(header.starts_with("HTTP/1.1 200 OK"));
+        let length = header
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).unwrap();
+        String::from_utf8(body).unwrap()
+    };
+
+    assert!(call("rig.get_vfo", "").contains("<value>145500000</value>"));
+    call(
+        "rig.set_vfo",
+        "<param><value><double>433475000.0</double></value></param>",
+    );
+    assert_eq!(
+        radio.cat(CatId::TxFreq),
+        Some(433_475_000u32.to_le_bytes().to_vec())
+    );
+    assert!(call("rig.get_xcvr", "").contains("<value>MD-UV3x0</value>"));
+    assert!(call("rig.get_mode", "").contains("<value>FM</value>"));
+    call("rig.set_mode", "<param><value>M17</value></param>");
+    assert_eq!(radio.cat(CatId::OpMode), Some(vec![OpMode::M17.into()]));
+    assert!(call("rig.set_mode", "<param><value>USB</value></param>").contains("<fault>"));
+    assert!(call("rig.get_modes", "").contains("<value>FM</value><value>M17</value>"));
+    call("rig.set_ptt", "<param><value><i4>1</i4></value></param>");
+    assert_eq!(radio.cat(CatId::Ptt), Some(vec![1]));
+    assert!(call("rig.get_ptt", "").contains("<value><i4>1</i4></value>"));
+    // S9 is at half scale
+    assert!(call("rig.get_smeter", "").contains("<value><i4>50</i4></value>"));
+
+    let status = server.status();
+    assert_eq!(status.requests, 10);
+    assert!(status.last_requests[1].ends_with(": rig.set_vfo(433475000) -> ok"));
+    server.stop();
+}
+
+#[test]
+fn flrig_rejects_invalid_requests() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    let server = RigServer::start(radio.port(), RigProtocol::Flrig, "127.0.0.1:0").unwrap();
+    // Send a request and read the response until the server closes the
+    // connection
+    let reject = |request: &[u8]| {
+        let mut stream = TcpStream::connect(server.address()).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = String::new();
+        // The server may reset the connection on unread data
+        _ = stream.read_to_string(&mut response);
+        response
+    };
+
+    assert!(reject(b"GET / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405"));
+    assert!(reject(b"garbage\r\n\r\n").starts_with("HTTP/1.1 400"));
+    assert!(reject(b"POST /RPC2 HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 411"));
+    assert!(
+        reject(b"POST /RPC2 HTTP/1.1\r\nContent-Length: 100000000\r\n\r\n")
+            .starts_with("HTTP/1.1 413")
+    );
+    assert!(reject(&[b'a'; 16 * 1024]).starts_with("HTTP/1.1 431"));
+    assert!(
+        reject(b"POST /RPC2 HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello")
+            .starts_with("HTTP/1.1 400")
+    );
+    server.stop();
+}
+
+#[test]
+fn rig_server_owns_the_serial_port() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    radio.set_cat(CatId::RxFreq, 145_500_000u32.to_le_bytes().to_vec());
+
+    let server = RigServer::start(radio.port(), RigProtocol::Rigctld, "127.0.0.1:0").unwrap();
+    let mut stream = TcpStream::connect(server.address()).unwrap();
+    writeln!(stream, "f").unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    assert_eq!(line, "145500000\n");
+    assert!(is_port_busy(radio.port()));
+    assert!(matches!(
+        RadioLink::open(radio.port()),
+        Err(LinkError::Busy(_))
+    ));
+    server.stop();
+    // The port is released once stop returns
+    assert!(!is_port_busy(radio.port()));
+}
+
+#[test]
+fn rig_server_reports_unreachable_radio() {
+    let dir = test_dir("rig-unreachable");
+    let port = dir.join("ttyACM0");
+    let server =
+        RigServer::start(port.to_str().unwrap(), RigProtocol::Rigctld, "127.0.0.1:0").unwrap();
+    let mut status = server.status();
+    for _ in 0..50 {
+        if status.error.is_some() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        status = server.status();
+    }
+    assert!(status.error.is_some());
+}
+
+#[test]
+fn remote_panel_follows_the_radio() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    radio.set_cat(CatId::RxFreq, 433_475_000u32.to_le_bytes().to_vec());
+    radio.set_cat(CatId::TxFreq, 433_475_000u32.to_le_bytes().to_vec());
+    radio.set_cat(CatId::OpMode, vec![OpMode::Fm.into()]);
+    radio.set_cat(CatId::Rssi, (-93i16).to_le_bytes().to_vec());
+    radio.set_cat(CatId::Squelch, vec![4]);
+    radio.set_cat(CatId::SquelchOpen, vec![0]);
+    radio.set_cat(CatId::Battery, 7_400u16.to_le_bytes().to_vec());
+    radio.set_cat(CatId::Volume, vec![128]);
+    radio.set_cat(CatId::Monitor, vec![0]);
+
+    let state = PanelState::read(radio.port()).unwrap();
+    assert_eq!(state.info, "MD-UV3x0");
+    assert_eq!(state.rx_frequency, 433_475_000);
+    assert_eq!(state.tx_frequency, Some(433_475_000));
+    assert_eq!(state.mode, Some(OpMode::Fm));
+    assert_eq!(state.rssi, Some(-93));
+    assert_eq!(state.squelch, Some(4));
+    assert_eq!(state.squelch_open, Some(false));
+    assert_eq!(state.battery, Some(7_400));
+    assert_eq!(state.volume, Some(128));
+    // Settings the radio does not support are left out
+    assert_eq!(state.setting(Setting::Monitor), Some(false));
+    assert_eq!(state.setting(Setting::Gps), None);
+
+    let mut session = RemoteSession::open(radio.port()).unwrap();
+    assert_eq!(session.radio, Some(RadioHW::Mduv3x0));
+    // The session keeps the port between requests
+    assert!(is_port_busy(radio.port()));
+    let frequency = step_frequency(&session.bands, state.rx_frequency, 12_500, -3).unwrap();
+    let state = session.send(&[PanelCommand::Tune(frequency)]).unwrap();
+    assert_eq!(state.rx_frequency, 433_437_500);
+    assert_eq!(state.tx_frequency, Some(433_437_500));
+    assert_eq!(session.send(&[]).unwrap(), state);
+    // Split operation sets the TX frequency only
+    let state = session.send(&[PanelCommand::TuneTx(438_437_500)]).unwrap();
+    assert_eq!(state.rx_frequency, 433_437_500);
+    assert_eq!(state.tx_frequency, Some(438_437_500));
+    let state = session
+        .send(&[
+            PanelCommand::Mode(OpMode::M17),
+            PanelCommand::Squelch(2),
+            PanelCommand::Volume(200),
+            PanelCommand::Set(Setting::Monitor, true),
+        ])
+        .unwrap();
+    assert_eq!(state.mode, Some(OpMode::M17));
+    assert_eq!(state.squelch, Some(2));
+    assert_eq!(state.volume, Some(200));
+    assert_eq!(state.setting(Setting::Monitor), Some(true));
+    assert_eq!(radio.cat(CatId::Monitor), Some(vec![1]));
+    drop(session);
+    assert!(!is_port_busy(radio.port()));
+
+    assert_eq!(
+        step_frequency(&radio_bands(None, 0), 479_990_000, 25_000, 1),
+        None
+    );
+    assert!(PanelCommand::Tune(1).replaces(&PanelCommand::Tune(2)));
+    assert!(PanelCommand::Tune(1).replaces(&PanelCommand::TuneTx(2)));
+    assert!(!PanelCommand::TuneTx(1).replaces(&PanelCommand::Tune(2)));
+    assert!(PanelCommand::Set(Setting::Gps, true).replaces(&PanelCommand::Set(Setting::Gps, false)));
+    assert!(
+        !PanelCommand::Set(Setting::Gps, true).replaces(&PanelCommand::Set(Setting::Monitor, true))
+    );
+    assert!(!PanelCommand::Mode(OpMode::Fm).replaces(&PanelCommand::Volume(1)));
+
+    // Values the firmware does not support are left empty
+    let bare = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    bare.set_cat(CatId::RxFreq, 145_500_000u32.to_le_bytes().to_vec());
+    let state = PanelState::read(bare.port()).unwrap();
+    assert_eq!((state.mode, state.rssi, state.battery), (None, None, None));
+    assert!(state.settings.is_empty());
+}
+
+#[test]
+fn remote_panel_keeps_single_band_radios_in_their_band() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-3x0", vec![]).unwrap();
+    radio.set_cat(CatId::RxFreq, 145_500_000u32.to_le_bytes().to_vec());
+
+    let session = RemoteSession::open(radio.port()).unwrap();
+    assert_eq!(session.bands, [136_000_000..=174_000_000]);
+    assert!(!in_band(&session.bands, 433_475_000));
+    assert_eq!(
+        radio_bands(Some(RadioHW::Md3x0), 433_475_000),
+        [400_000_000..=480_000_000]
+    );
+    assert_eq!(radio_bands(Some(RadioHW::Mduv3x0), 145_500_000).len(), 2);
+    // Tuned out of the known bands, the radio can be tuned back in any
+    assert_eq!(radio_bands(Some(RadioHW::Md3x0), 220_000_000).len(), 2);
+}
+
+#[test]
+fn telemetry_is_recorded_to_csv() {
+    let _link = LINK.lock().unwrap();
+    let radio = VirtualRadio::new("MD-UV3x0", vec![]).unwrap();
+    radio.set_cat(CatId::RxFreq, 433_475_000u32.to_le_bytes().to_vec());
+    radio.set_cat(CatId::Rssi, (-97i16).to_le_bytes().to_vec());
+    let dir = test_dir("telemetry");
+    let output = dir.join("telemetry.csv");
+
+    let recorder = TelemetryRecorder::start(
+        radio.port(),
+        Duration::from_millis(100),
+        Some(output.as_path()),
+    )
+    .unwrap();
+    let mut samples = vec![];
+    for _ in 0..100 {
+        std::thread::sleep(Duration::from_millis(50));
+        samples.extend(recorder.take_samples());
+        if samples.len() >= 3 {
+            break;
+        }
+    }
+    assert_eq!(recorder.error(), None);
+    recorder.stop();
+
+    assert_eq!(samples[0].frequency, 433_475_000);
+    assert_eq!(samples[0].rssi, Some(-97));
+    // Values the radio does not report are left empty
+    assert_eq!(samples[0].battery, None);
+    assert!(samples[1].time > samples[0].time);
+    // The recording holds at least the samples seen while running
+    let recorded = telemetry::load_file(&output).unwrap();
+    assert_eq!(recorded[..samples.len()], samples[..]);
+}
+
+#[test]
+fn telemetry_reports_unreachable_radio() {
+    let port = test_dir("telemetry-unreachable").join("ttyACM0");
+    let recorder =
+        TelemetryRecorder::start(port.to_str().unwrap(), Duration::from_secs(1), None).unwrap();
+    for _ in 0..50 {
+        if !recorder.is_running() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    assert!(!recorder.is_running());
+    assert!(recorder.error().is_some());
+}
@@ -0,0 +1,165 @@
+//! Archives of the factory data of TYT radios, read from a simulated radio
+#![cfg(unix)]
+
+mod common;
+
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::sync::Mutex;
+
+use openrtx_companion::core::dfu::{self, DfuError};
+use openrtx_companion::core::stock::{needs_archive, CODEPLUG_REGION};
+use openrtx_companion::core::{
+    CancelToken, RadioHW, StockArchive, StockArchiveJob, StockError, StockRestoreJob,
+};
+use openrtx_companion::sim::VirtualDfuRadio;
+
+use common::test_dir;
+
+// A single simulated radio can be in DFU mode, tests using it can not run in
+// parallel
+static DFU: Mutex<()> = Mutex::new(());
+
+// Image of a 1 MiB external flash
+fn test_flash(seed: u8) -> Vec<u8> {
+    (0..1024 * 1024).map(|i| (i % 251) as u8 ^ seed).collect()
+}
+
+fn archive(radio: RadioHW, root: &Path) -> Result<StockArchive, StockError> {
+    let (events, _events_rx) = channel();
+    StockArchiveJob::new(radio, root).run(&events, &CancelToken::new())
+}
+
+fn restore(archive: &StockArchive) -> Result<RadioHW, StockError> {
+    let (events, _events_rx) = channel();
+    StockRestoreJob::new(archive.clone()).run(&events, &CancelToken::new())
+}
+
+#[test]
+fn archives_are_read_from_the_radio() {
+    let _dfu = DFU.lock().unwrap();
+    let root = test_dir("stock-read").join("stock");
+    let radio = VirtualDfuRadio::new(test_flash(0x00));
+    assert!(needs_archive(&root, RadioHW::Md3x0));
+
+    let archive = archive(RadioHW::Md3x0, &root).unwrap();
+    assert_eq!(archive.radio, RadioHW::Md3x0);
+    assert_eq!(archive.contents(), [CODEPLUG_REGION]);
+    let (address, size) = dfu::codeplug_region(RadioHW::Md3x0).unwrap();
+    assert_eq!(archive.regions[0].address, address);
+    assert_eq!(
+        std::fs::read(archive.region(&archive.regions[0])).unwrap(),
+        test_flash(0x00)[..size]
+    );
+    assert!(radio.rebooted());
+    assert_eq!(radio.flash(), test_flash(0x00));
+    assert_eq!(StockArchive::load(&archive.dir).unwrap(), archive);
+    archive.verify().unwrap();
+    assert!(!needs_archive(&root, RadioHW::Md3x0));
+    // Archives are kept per radio model
+    assert!(needs_archive(&root, RadioHW::Mduv3x0));
+    // Radios without a TYT bootloader have nothing to archive
+    assert!(!needs_archive(&root, RadioHW::Twrplus));
+}
+
+#[test]
+fn larger_codeplugs_are_read_in_segments() {
+    let _dfu = DFU.lock().unwrap();
+    let root = test_dir("stock-segments").join("stock");
+    let _radio = VirtualDfuRadio::new(test_flash(0x33));
+
+    let archive = archive(RadioHW::Mduv3x0, &root).unwrap();
+    let (_, size) = dfu::codeplug_region(RadioHW::Mduv3x0).unwrap();
+    assert_eq!(
+        std::fs::read(archive.region(&archive.regions[0])).unwrap(),
+        test_flash(0x33)[..size]
+    );
+}
+
+#[test]
+fn archives_created_in_the_same_second_are_kept_apart() {
+    let _dfu = DFU.lock().unwrap();
+    let root = test_dir("stock-unique").join("stock");
+    let _radio = VirtualDfuRadio::new(test_flash(0x00));
+    let archives: Vec<_> = (0..3)
+        .map(|_| archive(RadioHW::Md3x0, &root).unwrap())
+        .collect();
+    assert_ne!(archives[0].dir, archives[1].dir);
+    assert_ne!(archives[1].dir, archives[2].dir);
+    assert_eq!(std::fs::read_dir(&root).unwrap().count(), 3);
+
+    let latest = StockArchive::latest(&root, RadioHW::Md3x0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(latest.dir, archives[2].dir);
+}
+
+#[test]
+fn only_tyt_radios_are_archived() {
+    let _dfu = DFU.lock().unwrap();
+    let root = test_dir("stock-unsupported").join("stock");
+    let radio = VirtualDfuRadio::new(test_flash(0x00));
+    assert!(matches!(
+        archive(RadioHW::Twrplus, &root),
+        Err(StockError::Unsupported(RadioHW::Twrplus))
+    ));
+    assert!(!radio.rebooted());
+    assert_eq!(StockArchive::latest(&root, RadioHW::Twrplus).unwrap(), None);
+}
+
+#[test]
+fn no_radio_in_dfu_mode_is_reported() {
+    let _dfu = DFU.lock().unwrap();
+    let root = test_dir("stock-missing").join("stock");
+    // Without USB access, no radio is found either
+    assert!(matches!(
+        archive(RadioHW::Md3x0, &root),
+        Err(StockError::Dfu(DfuError::NotFound | DfuError::Usb(_)))
+    ));
+    assert_eq!(StockArchive::latest(&root, RadioHW::Md3x0).unwrap(), None);
+}
+
+#[test]
+fn only_the_archived_region_is_restored() {
+    let _dfu = DFU.lock().unwrap();
+    let root = test_dir("stock-restore").join("stock");
+    let archive = {
+        let _radio = VirtualDfuRadio::new(test_flash(0x00));
+        archive(RadioHW::Md3x0, &root).unwrap()
+    };
+
+    // The codeplug is written back, the rest of the flash is left as is
+    let radio = VirtualDfuRadio::new(test_flash(0x5a));
+    assert_eq!(restore(&archive).unwrap(), RadioHW::Md3x0);
+    let (_, size) = dfu::codeplug_region(RadioHW::Md3x0).unwrap();
+    let flash = radio.flash();
+    assert_eq!(flash[..size], test_flash(0x00)[..size]);
+    assert_eq!(flash[size..], test_flash(0x5a)[size..]);
+    assert!(radio.rebooted());
+
+    // Regions other than the codeplug are not written
+    let manifest = archive.dir.join("archive.toml");
+    let text = std::fs::read_to_string(&manifest).unwrap();
+    std::fs::write(&manifest, text.replace("address = 0", "address = 65536")).unwrap();
+    let moved = StockArchive::load(&archive.dir).unwrap();
+    assert_eq!(moved.regions[0].address, 0x10000);
+    let radio = VirtualDfuRadio::new(test_flash(0x5a));
+    assert!(matches!(
+        restore(&moved),
+        Err(StockError::InvalidRegion(name)) if name == CODEPLUG_REGION
+    ));
+    assert_eq!(radio.flash(), test_flash(0x5a));
+}
+
+#[test]
+fn corrupted_archives_are_not_restored() {
+    let _dfu = DFU.lock().unwrap();
+    let root = test_dir("stock-corrupted").join("stock");
+    let radio = VirtualDfuRadio::new(test_flash(0x00));
+    let archive = archive(RadioHW::Md3x0, &root).unwrap();
+
+    std::fs::write(archive.region(&archive.regions[0]), vec![0x5a; 1024]).unwrap();
+    assert!(matches!(archive.verify(), Err(StockError::Corrupted(_))));
+    assert!(matches!(restore(&archive), Err(StockError::Corrupted(_))));
+    assert_eq!(radio.flash(), test_flash(0x00));
+}
@@ -0,0 +1,86 @@
+//! Telemetry recordings in CSV
+
+use openrtx_companion::core::telemetry::{self, format_time, parse_time, Sample, TelemetryError};
+use time::{Date, Month, OffsetDateTime};
+
+// Time on 2024-03-09 at 14:05, in UTC
+fn utc(second: u8, millisecond: u16) -> OffsetDateTime {
+    Date::from_calendar_date(2024, Month::March, 9)
+        .unwrap()
+        .with_hms_milli(14, 5, second, millisecond)
+        .unwrap()
+        .assume_utc()
+}
+
+#[test]
+fn times_have_milliseconds() {
+    let time = utc(7, 250);
+    assert_eq!(format_time(time), "2024-03-09T14:05:07.250Z");
+    assert_eq!(parse_time("2024-03-09T14:05:07.250Z"), Some(time));
+    assert_eq!(parse_time("2024-03-09T14:05:07Z"), Some(utc(7, 0)));
+    // Fractions of any precision are read as milliseconds
+    assert_eq!(parse_time("2024-03-09T14:05:07.25Z"), Some(time));
+    assert_eq!(parse_time("2024-03-09T14:05:07.2500Z"), Some(time));
+    assert_eq!(parse_time("2024-03-09T14:05:07.2Z"), Some(utc(7, 200)));
+    assert_eq!(parse_time("2024-03-09T14:05:07.Z"), None);
+    assert_eq!(parse_time("2024-03-09T14:05:07.+25Z"), None);
+    assert_eq!(parse_time("2024-03-09 14:05:07"), None);
+    assert_eq!(parse_time("2024-13-09T14:05:07Z"), None);
+}
+
+#[test]
+fn recordings_survive_save_and_load() {
+    let samples = vec![
+        Sample {
+            time: utc(7, 250),
+            frequency: 433_475_000,
+            rssi: Some(-97),
+            battery: Some(7420),
+        },
+        Sample {
+            time: utc(8, 250),
+            frequency: 145_500_000,
+            rssi: None,
+            battery: Some(7400),
+        },
+    ];
+    let mut csv = vec![];
+    telemetry::save(&samples, &mut csv).unwrap();
+    let text = String::from_utf8(csv.clone()).unwrap();
+    assert!(text.starts_with("Time,Frequency,RSSI,Battery\n"));
+    assert!(text.contains("2024-03-09T14:05:07.250Z,433475000,-97,7420\n"));
+    assert!(text.contains("2024-03-09T14:05:08.250Z,145500000,,7400\n"));
+    assert_eq!(telemetry::load(csv.as_slice()).unwrap(), samples);
+
+    // Older recordings only have the frequency
+    let csv = "Time,Frequency\n2024-03-09T14:05:07.250Z,433475000\n";
+    assert_eq!(
+        telemetry::load(csv.as_bytes()).unwrap(),
+        [Sample {
+            rssi: None,
+            battery: None,
+            ..samples[0]
+        }]
+    );
+}
+
+#[test]
+fn invalid_recordings_are_refused() {
+    let csv = "Time,RSSI\n";
+    assert!(matches!(
+        telemetry::load(csv.as_bytes()),
+        Err(TelemetryError::MissingColumn("Frequency"))
+    ));
+    let csv = "Time,Frequency\n\
+               2024-03-09T14:05:07Z,433475000\n\
+               2024-03-09T14:05:08Z,UHF\n";
+    assert!(matches!(
+        telemetry::load(csv.as_bytes()),
+        Err(TelemetryError::InvalidRow(3))
+    ));
+    let csv = "Time,Frequency,RSSI\n2024-03-09T14:05:07Z,433475000,strong\n";
+    assert!(matches!(
+        telemetry::load(csv.as_bytes()),
+        Err(TelemetryError::InvalidRow(2))
+    ));
+}